@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+
+use anyhow::anyhow;
+
+use crate::JoseError;
+
+/// The alg and enc header claim values that are FIPS-approved.
+const FIPS_ALGORITHMS: &[&str] = &[
+    // JWS
+    "HS256",
+    "HS384",
+    "HS512",
+    "josekit:HS3-256",
+    "josekit:HS3-384",
+    "josekit:HS3-512",
+    "PS256",
+    "PS384",
+    "PS512",
+    "ES256",
+    "ES384",
+    "ES512",
+    // JWE alg
+    "dir",
+    "RSA-OAEP",
+    "RSA-OAEP-256",
+    "RSA-OAEP-384",
+    "RSA-OAEP-512",
+    "A128KW",
+    "A192KW",
+    "A256KW",
+    "A128KWP",
+    "A192KWP",
+    "A256KWP",
+    "A128GCMKW",
+    "A192GCMKW",
+    "A256GCMKW",
+    "ECDH-ES",
+    "ECDH-ES+A128KW",
+    "ECDH-ES+A192KW",
+    "ECDH-ES+A256KW",
+    "PBES2-HS256+A128KW",
+    "PBES2-HS384+A192KW",
+    "PBES2-HS512+A256KW",
+    "josekit:PBES2-HS256+A128GCMKW",
+    "josekit:PBES2-HS384+A192GCMKW",
+    "josekit:PBES2-HS512+A256GCMKW",
+    // JWE enc
+    "A128CBC-HS256",
+    "A192CBC-HS384",
+    "A256CBC-HS512",
+    "A128GCM",
+    "A192GCM",
+    "A256GCM",
+];
+
+/// Represent a restriction of the alg and enc header claim values that a context accepts,
+/// for both producing and consuming JWS and JWE.
+///
+/// This is a policy over the existing algorithms and doesn't change how they work.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct AlgorithmPolicy {
+    allowed: Option<BTreeSet<String>>,
+    denied: BTreeSet<String>,
+}
+
+impl AlgorithmPolicy {
+    /// Return a policy that allows every algorithm.
+    pub fn new() -> Self {
+        Self {
+            allowed: None,
+            denied: BTreeSet::new(),
+        }
+    }
+
+    /// Return a policy that allows only the specified algorithms.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - alg or enc header claim values
+    pub fn allow_only(names: &[&str]) -> Self {
+        Self {
+            allowed: Some(names.iter().map(|val| val.to_string()).collect()),
+            denied: BTreeSet::new(),
+        }
+    }
+
+    /// Return a policy that allows only FIPS-approved algorithms.
+    ///
+    /// EdDSA, ES256K, the RSASSA-PKCS1-v1_5 signatures (RS256, RS384 and RS512),
+    /// RSA1_5, ChaCha20-Poly1305 (C20P and XC20P), AES-GCM-SIV and ECDH-1PU are
+    /// not allowed. Note that the curve of ECDH-ES is not restricted by this policy.
+    pub fn fips() -> Self {
+        Self::allow_only(FIPS_ALGORITHMS)
+    }
+
+    /// Deny an algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a alg or enc header claim value
+    pub fn deny(&mut self, name: &str) {
+        self.denied.insert(name.to_string());
+    }
+
+    /// Test an algorithm is allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a alg or enc header claim value
+    pub fn is_allowed(&self, name: &str) -> bool {
+        if self.denied.contains(name) {
+            return false;
+        }
+
+        match &self.allowed {
+            Some(allowed) => allowed.contains(name),
+            None => true,
+        }
+    }
+
+    pub(crate) fn check(&self, name: &str) -> Result<(), JoseError> {
+        if self.is_allowed(name) {
+            Ok(())
+        } else {
+            Err(JoseError::UnsupportedAlgorithm(anyhow!(
+                "The algorithm is not allowed by the policy: {}",
+                name
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlgorithmPolicy;
+    use crate::JoseError;
+
+    #[test]
+    fn test_algorithm_policy() {
+        let mut policy = AlgorithmPolicy::new();
+        assert!(policy.is_allowed("EdDSA"));
+        policy.deny("EdDSA");
+        assert!(!policy.is_allowed("EdDSA"));
+        assert!(policy.is_allowed("ES256"));
+
+        let policy = AlgorithmPolicy::fips();
+        for name in &[
+            "HS256",
+            "PS256",
+            "ES256",
+            "RSA-OAEP-256",
+            "A128KW",
+            "A256GCM",
+        ] {
+            assert!(policy.is_allowed(name), "{}", name);
+        }
+        for name in &[
+            "EdDSA", "ES256K", "RS256", "RSA1_5", "C20P", "XC20P", "none", "",
+        ] {
+            assert!(!policy.is_allowed(name), "{}", name);
+        }
+        assert!(matches!(
+            policy.check("EdDSA"),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,6 +7,9 @@ pub enum JoseError {
     #[error("Unsupported signature algorithm: {0}")]
     UnsupportedSignatureAlgorithm(#[source] anyhow::Error),
 
+    #[error("Unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(#[source] anyhow::Error),
+
     #[error("Invalid JWT format: {0}")]
     InvalidJwtFormat(#[source] anyhow::Error),
 
@@ -29,3 +34,80 @@ pub enum JoseError {
     #[error("Invalid signature: {0}")]
     InvalidSignature(#[source] anyhow::Error),
 }
+
+impl JoseError {
+    /// Return the kind of the failure, if it is one of the distinguished kinds.
+    ///
+    /// The Display output is not affected by the kind.
+    pub fn kind(&self) -> Option<JoseErrorKind> {
+        let err = match self {
+            Self::UnsupportedSignatureAlgorithm(err)
+            | Self::UnsupportedAlgorithm(err)
+            | Self::InvalidJwtFormat(err)
+            | Self::InvalidJwkFormat(err)
+            | Self::InvalidJwsFormat(err)
+            | Self::InvalidJweFormat(err)
+            | Self::InvalidKeyFormat(err)
+            | Self::InvalidJson(err)
+            | Self::InvalidClaim(err)
+            | Self::InvalidSignature(err) => err,
+        };
+
+        err.chain()
+            .find_map(|val| val.downcast_ref::<JoseErrorDetail>())
+            .map(|val| val.kind)
+    }
+}
+
+/// A kind of failure that can be matched without inspecting the error message.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum JoseErrorKind {
+    /// The signature of JWS doesn't match.
+    SignatureMismatch,
+
+    /// The authentication tag of JWE or key wrapping doesn't match.
+    TagMismatch,
+
+    /// The length of a key is invalid.
+    InvalidKeyLength,
+
+    /// The length of an initialization vector is invalid.
+    InvalidIvLength,
+
+    /// The length of an authentication tag is invalid.
+    InvalidTagLength,
+
+    /// The use, key_ops or alg parameter of a JWK doesn't allow the operation.
+    InvalidKeyUsage,
+
+    /// The cty header claim doesn't indicate the expected content.
+    InvalidContentType,
+
+    /// The typ header claim doesn't indicate the expected type.
+    InvalidType,
+}
+
+/// An error message with a kind, carried as the source of a `JoseError`.
+#[derive(Debug)]
+pub(crate) struct JoseErrorDetail {
+    kind: JoseErrorKind,
+    message: String,
+}
+
+impl JoseErrorDetail {
+    pub fn new(kind: JoseErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for JoseErrorDetail {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for JoseErrorDetail {}
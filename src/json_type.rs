@@ -0,0 +1,63 @@
+use crate::Value;
+
+/// Represent the type of a JSON value, e.g. the expected type of a private header parameter.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum JsonType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "boolean",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Array => "array",
+            Self::Object => "object",
+        }
+    }
+
+    /// Test a JSON value is of this type.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a JSON value
+    pub fn is_type_of(&self, value: &Value) -> bool {
+        match self {
+            Self::Null => value.is_null(),
+            Self::Bool => value.is_boolean(),
+            Self::Number => value.is_number(),
+            Self::String => value.is_string(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::JsonType;
+
+    #[test]
+    fn test_is_type_of() {
+        assert!(JsonType::Null.is_type_of(&json!(null)));
+        assert!(JsonType::Bool.is_type_of(&json!(true)));
+        assert!(JsonType::Number.is_type_of(&json!(1)));
+        assert!(JsonType::Number.is_type_of(&json!(1.5)));
+        assert!(JsonType::String.is_type_of(&json!("1")));
+        assert!(JsonType::Array.is_type_of(&json!([1])));
+        assert!(JsonType::Object.is_type_of(&json!({ "a": 1 })));
+
+        assert!(!JsonType::Number.is_type_of(&json!("1")));
+        assert!(!JsonType::String.is_type_of(&json!(null)));
+        assert!(!JsonType::Array.is_type_of(&json!({ "a": 1 })));
+    }
+}
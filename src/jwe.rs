@@ -1,6 +1,7 @@
 //! JSON Web Encryption (JWE) support.
 
 pub mod alg;
+mod cek_material;
 pub mod enc;
 mod jwe_algorithm;
 mod jwe_compression;
@@ -12,29 +13,45 @@ pub mod zip;
 
 use once_cell::sync::Lazy;
 
+use crate::jwk::JwkSet;
 use crate::JoseError;
 
+pub use crate::jwe::cek_material::CekMaterial;
 pub use crate::jwe::jwe_algorithm::JweAlgorithm;
 pub use crate::jwe::jwe_algorithm::JweDecrypter;
 pub use crate::jwe::jwe_algorithm::JweEncrypter;
 pub use crate::jwe::jwe_compression::JweCompression;
 pub use crate::jwe::jwe_content_encryption::JweContentEncryption;
+pub use crate::jwe::jwe_content_encryption::JweEncryptStream;
 pub use crate::jwe::jwe_context::JweContext;
 pub use crate::jwe::jwe_header::JweHeader;
 pub use crate::jwe::jwe_header_set::JweHeaderSet;
 
 pub use crate::jwe::alg::direct::DirectJweAlgorithm::Dir;
 
+use crate::jwe::alg::ecdh_1pu::Ecdh1puJweAlgorithm;
+pub use Ecdh1puJweAlgorithm::Ecdh1pu as ECDH_1PU;
+
 use crate::jwe::alg::ecdh_es::EcdhEsJweAlgorithm;
 pub use EcdhEsJweAlgorithm::EcdhEs as ECDH_ES;
 pub use EcdhEsJweAlgorithm::EcdhEsA128kw as ECDH_ES_A128KW;
 pub use EcdhEsJweAlgorithm::EcdhEsA192kw as ECDH_ES_A192KW;
 pub use EcdhEsJweAlgorithm::EcdhEsA256kw as ECDH_ES_A256KW;
 
+#[cfg(feature = "hpke")]
+use crate::jwe::alg::hpke::HpkeJweAlgorithm;
+#[cfg(feature = "hpke")]
+pub use HpkeJweAlgorithm::Hpke0 as HPKE_0;
+#[cfg(feature = "hpke")]
+pub use HpkeJweAlgorithm::Hpke3 as HPKE_3;
+
 use crate::jwe::alg::aeskw::AeskwJweAlgorithm;
 pub use AeskwJweAlgorithm::A128kw as A128KW;
+pub use AeskwJweAlgorithm::A128kwp as A128KWP;
 pub use AeskwJweAlgorithm::A192kw as A192KW;
+pub use AeskwJweAlgorithm::A192kwp as A192KWP;
 pub use AeskwJweAlgorithm::A256kw as A256KW;
+pub use AeskwJweAlgorithm::A256kwp as A256KWP;
 
 use crate::jwe::alg::aesgcmkw::AesgcmkwJweAlgorithm;
 pub use AesgcmkwJweAlgorithm::A128gcmkw as A128GCMKW;
@@ -46,6 +63,11 @@ pub use Pbes2HmacAeskwJweAlgorithm::Pbes2Hs256A128kw as PBES2_HS256_A128KW;
 pub use Pbes2HmacAeskwJweAlgorithm::Pbes2Hs384A192kw as PBES2_HS384_A192KW;
 pub use Pbes2HmacAeskwJweAlgorithm::Pbes2Hs512A256kw as PBES2_HS512_A256KW;
 
+use crate::jwe::alg::pbes2_hmac_aesgcmkw::Pbes2HmacAesgcmkwJweAlgorithm;
+pub use Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs256A128gcmkw as PBES2_HS256_A128GCMKW;
+pub use Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs384A192gcmkw as PBES2_HS384_A192GCMKW;
+pub use Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs512A256gcmkw as PBES2_HS512_A256GCMKW;
+
 use crate::jwe::alg::rsaes::RsaesJweAlgorithm;
 #[allow(deprecated)]
 pub use RsaesJweAlgorithm::Rsa1_5 as RSA1_5;
@@ -71,6 +93,45 @@ pub fn serialize_compact(
     DEFAULT_CONTEXT.serialize_compact(payload, header, encrypter)
 }
 
+/// Return a representation of the data that is formatted by compact serialization,
+/// using the specified initialization vector instead of a random one.
+///
+/// This is intended for producing reproducible test vectors. Never reuse an IV
+/// with the same key in production.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWE header claims.
+/// * `encrypter` - The JWE encrypter.
+/// * `iv` - The initialization vector. Its length must match the content encryption.
+pub fn serialize_compact_with_iv(
+    payload: &[u8],
+    header: &JweHeader,
+    encrypter: &dyn JweEncrypter,
+    iv: &[u8],
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_compact_with_iv(payload, header, encrypter, iv)
+}
+
+/// Return a representation of the data that is formatted by compact serialization,
+/// encrypted for a recipient with a key in the JWK set.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWE header claims.
+/// * `jwk_set` - The JWK set of the recipient.
+/// * `algorithms` - The preferred key management algorithms.
+pub fn serialize_compact_to_jwk_set(
+    payload: &[u8],
+    header: &JweHeader,
+    jwk_set: &JwkSet,
+    algorithms: &[&dyn JweAlgorithm],
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_compact_to_jwk_set(payload, header, jwk_set, algorithms)
+}
+
 /// Return a representation of the data that is formatted by compact serialization.
 ///
 /// # Arguments
@@ -181,6 +242,20 @@ where
     )
 }
 
+/// Return the protected header of the input that is formatted by compact
+/// serialization, without decrypting it.
+///
+/// The returned header is untrusted: it is not authenticated until the
+/// input has been decrypted. Use it only to decide how to decrypt the
+/// input (e.g. to look up a key by `kid`).
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn extract_header(input: impl AsRef<[u8]>) -> Result<JweHeader, JoseError> {
+    DEFAULT_CONTEXT.extract_header(input)
+}
+
 /// Deserialize the input that is formatted by compact serialization.
 ///
 /// # Arguments
@@ -210,6 +285,34 @@ where
     DEFAULT_CONTEXT.deserialize_compact_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by compact serialization,
+/// trying the decrypters in order.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypters` - The JWE decrypters.
+pub fn deserialize_compact_with_decrypters(
+    input: &str,
+    decrypters: &[&dyn JweDecrypter],
+) -> Result<(Vec<u8>, JweHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_decrypters(input, decrypters)
+}
+
+/// Deserialize the input that is formatted by compact serialization,
+/// and return the content encryption key (CEK) together with the payload.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypter` - The JWE decrypter.
+pub fn deserialize_compact_with_cek(
+    input: &str,
+    decrypter: &dyn JweDecrypter,
+) -> Result<(Vec<u8>, CekMaterial, JweHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_cek(input, decrypter)
+}
+
 /// Deserialize the input that is formatted by flattened json serialization.
 ///
 /// # Arguments
@@ -240,6 +343,77 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by flattened json serialization,
+/// keeping the shared protected, shared unprotected and per-recipient header claims apart.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypter` - The JWE decrypter.
+pub fn deserialize_json_header_set<'a>(
+    input: &str,
+    decrypter: &'a dyn JweDecrypter,
+) -> Result<(Vec<u8>, JweHeaderSet, JweHeader), JoseError> {
+    DEFAULT_CONTEXT.deserialize_json_header_set(input, decrypter)
+}
+
+/// Deserialize the input that is formatted by flattened json serialization,
+/// keeping the shared protected, shared unprotected and per-recipient header claims apart.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `selector` - a function for selecting the decrypting algorithm.
+pub fn deserialize_json_header_set_with_selector<'a, F>(
+    input: &str,
+    selector: F,
+) -> Result<(Vec<u8>, JweHeaderSet, JweHeader), JoseError>
+where
+    F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_json_header_set_with_selector(input, selector)
+}
+
+/// The content encryption algorithms in order of strength, used by `negotiate_enc`.
+const ENC_STRENGTH_ORDER: &[&str] = &[
+    "A256GCM",
+    "XC20P",
+    "C20P",
+    "A256GCMSIV",
+    "A256CBC-HS512",
+    "A192GCM",
+    "A192CBC-HS384",
+    "A128GCM",
+    "A128GCMSIV",
+    "A128CBC-HS256",
+];
+
+/// Return the content encryption algorithm (enc) to use with a peer.
+///
+/// This returns the server's most preferred value that the client also supports.
+/// If the server preference is empty, the strongest value that the client supports
+/// is chosen by a fixed order. None is returned if there is no such value.
+///
+/// # Arguments
+///
+/// * `client_supported` - The enc values the client supports.
+/// * `server_preference` - The enc values the server accepts, most preferred first.
+pub fn negotiate_enc<'a>(
+    client_supported: &[&'a str],
+    server_preference: &[&'a str],
+) -> Option<&'a str> {
+    let preference = if server_preference.is_empty() {
+        ENC_STRENGTH_ORDER
+    } else {
+        server_preference
+    };
+
+    preference
+        .iter()
+        .find(|val| client_supported.contains(*val))
+        .map(|val| *val)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -247,13 +421,14 @@ mod tests {
 
     use anyhow::Result;
 
+    use crate::jwe::enc::{A256GCM, C20P};
     use crate::jwe::{
-        self, Dir, JweAlgorithm, JweHeader, JweHeaderSet, ECDH_ES_A128KW, PBES2_HS256_A128KW,
-        RSA_OAEP,
+        self, Dir, JweAlgorithm, JweContentEncryption, JweContext, JweHeader, JweHeaderSet, A128KW,
+        ECDH_ES_A128KW, PBES2_HS256_A128KW, RSA_OAEP,
     };
-    use crate::jwk::Jwk;
-    use crate::util;
-    use crate::Value;
+    use crate::jwk::{Jwk, JwkSet, KeyPair};
+    use crate::util::{self, RandomSource};
+    use crate::{AlgorithmPolicy, JoseError, JoseErrorKind, JsonType, Map, Value};
 
     #[test]
     fn test_jwe_compact_serialization() -> Result<()> {
@@ -296,6 +471,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwe_compact_serialization_with_cek() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let key = util::random_bytes(16);
+        let encrypter = A128KW.encrypter_from_bytes(&key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let decrypter = A128KW.decrypter_from_bytes(&key)?;
+        let (payload, cek, _) = jwe::deserialize_compact_with_cek(&jwe, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(cek.len(), A256GCM.key_len());
+
+        let parts: Vec<&str> = jwe.split('.').collect();
+        let iv = base64::decode_config(parts[2], base64::URL_SAFE_NO_PAD)?;
+        let ciphertext = base64::decode_config(parts[3], base64::URL_SAFE_NO_PAD)?;
+        let tag = base64::decode_config(parts[4], base64::URL_SAFE_NO_PAD)?;
+        let content = A256GCM.decrypt(
+            &cek,
+            Some(&iv),
+            &ciphertext,
+            parts[0].as_bytes(),
+            Some(&tag),
+        )?;
+        assert_eq!(content, b"test payload!");
+
+        let decrypter = A128KW.decrypter_from_bytes(util::random_bytes(16))?;
+        assert!(jwe::deserialize_compact_with_cek(&jwe, &decrypter).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwe_json_serialization() -> Result<()> {
         let alg = RSA_OAEP;
@@ -334,6 +542,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwe_flattened_json_deserialization_rfc7516() -> Result<()> {
+        // RFC 7516 A.5. Example JWE Using Flattened JWE JSON Serialization
+        let json = r#"{
+            "protected": "eyJlbmMiOiJBMTI4Q0JDLUhTMjU2In0",
+            "unprotected": {"jku":"https://server.example.com/keys.jwks"},
+            "header": {"alg":"A128KW","kid":"7"},
+            "encrypted_key": "6KB707dM9YTIgHtLvtgWQ8mKwboJW3of9locizkDTHzBC2IlrT1oOQ",
+            "iv": "AxY8DCtDaGlsbGljb3RoZQ",
+            "ciphertext": "KDlTtXchhZTGufMYmOYGS4HffxPSUrfmqCHXaI9wOGY",
+            "tag": "Mz-VPPyU4RlcuYv1IwIvzw"
+        }"#;
+
+        let jwk = Jwk::from_bytes(br#"{"kty":"oct","k":"GawgguFyGrWKav7AX4VKUg"}"#)?;
+        let decrypter = A128KW.decrypter_from_jwk(&jwk)?;
+        let (payload, header) = jwe::deserialize_json(json, &decrypter)?;
+        assert_eq!(payload, b"Live long and prosper.");
+        assert_eq!(header.algorithm(), Some("A128KW"));
+        assert_eq!(header.content_encryption(), Some("A128CBC-HS256"));
+        assert_eq!(header.key_id(), Some("7"));
+        assert_eq!(
+            header.jwk_set_url(),
+            Some("https://server.example.com/keys.jwks")
+        );
+
+        let decrypter = A128KW.decrypter_from_bytes(util::random_bytes(16))?;
+        assert!(jwe::deserialize_json(json, &decrypter).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwe_general_json_serialization() -> Result<()> {
         let public_key_1 = load_file("pem/RSA_2048bit_public.pem")?;
@@ -383,6 +622,697 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwe_general_json_serialization_with_same_algorithm() -> Result<()> {
+        let key_pair_1 = RSA_OAEP.generate_key_pair(2048)?;
+        let key_pair_2 = RSA_OAEP.generate_key_pair(2048)?;
+
+        let src_payload = b"test payload!";
+
+        let mut src_header = JweHeaderSet::new();
+        src_header.set_content_encryption("A128GCM", true);
+
+        let encrypter_1 = RSA_OAEP.encrypter_from_der(key_pair_1.to_der_public_key())?;
+        let encrypter_2 = RSA_OAEP.encrypter_from_der(key_pair_2.to_der_public_key())?;
+
+        let json = jwe::serialize_general_json(
+            src_payload,
+            Some(&src_header),
+            &[(None, &encrypter_1), (None, &encrypter_2)],
+            None,
+        )?;
+
+        for key_pair in vec![&key_pair_1, &key_pair_2] {
+            let decrypter = RSA_OAEP.decrypter_from_der(key_pair.to_der_private_key())?;
+            let (dst_payload, dst_header) = jwe::deserialize_json(&json, &decrypter)?;
+
+            assert_eq!(dst_header.algorithm(), Some("RSA-OAEP"));
+            assert_eq!(src_payload.to_vec(), dst_payload);
+        }
+
+        let key_pair_3 = RSA_OAEP.generate_key_pair(2048)?;
+        let decrypter = RSA_OAEP.decrypter_from_der(key_pair_3.to_der_private_key())?;
+        assert!(jwe::deserialize_json(&json, &decrypter).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_general_json_serialization_with_multiple_direct_recipients() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+
+        let mut src_header = JweHeaderSet::new();
+        src_header.set_content_encryption("A128GCM", true);
+
+        assert!(jwe::serialize_general_json(
+            b"test payload!",
+            Some(&src_header),
+            &[(None, &encrypter), (None, &encrypter)],
+            None,
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_general_json_serialization_with_incompatible_algorithm() -> Result<()> {
+        let mut src_header = JweHeaderSet::new();
+        src_header.set_content_encryption("A128GCM", true);
+
+        let encrypter_1 = A128KW.encrypter_from_bytes(&util::random_bytes(16))?;
+        let encrypter_2 = A128KW.encrypter_from_bytes(&util::random_bytes(16))?;
+        jwe::serialize_general_json(
+            b"test payload!",
+            Some(&src_header),
+            &[(None, &encrypter_1), (None, &encrypter_2)],
+            None,
+        )?;
+
+        let dir_encrypter = Dir.encrypter_from_bytes(&util::random_bytes(16))?;
+        assert!(matches!(
+            jwe::serialize_general_json(
+                b"test payload!",
+                Some(&src_header),
+                &[(None, &*encrypter_1), (None, &*dir_encrypter)],
+                None,
+            ),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let ecdh_encrypter = jwe::ECDH_ES.encrypter_from_pem(&public_key)?;
+        assert!(matches!(
+            jwe::serialize_general_json(
+                b"test payload!",
+                Some(&src_header),
+                &[(None, &*ecdh_encrypter), (None, &*encrypter_2)],
+                None,
+            ),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        jwe::serialize_general_json(
+            b"test payload!",
+            Some(&src_header),
+            &[(None, &ecdh_encrypter)],
+            None,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_flattened_json_serialization_with_unprotected_header() -> Result<()> {
+        let public_key = load_file("der/EC_P-256_spki_public.der")?;
+        let private_key = load_file("der/EC_P-256_pkcs8_private.der")?;
+
+        let src_payload = b"test payload!";
+        let mut src_header = JweHeaderSet::new();
+        src_header.set_content_encryption("A128GCM", true);
+        src_header.set_key_id("xxx", false);
+        let mut src_rheader = JweHeader::new();
+        src_rheader.set_token_type("JWT");
+
+        let encrypter = ECDH_ES_A128KW.encrypter_from_der(&public_key)?;
+        let json = jwe::serialize_flattened_json(
+            src_payload,
+            Some(&src_header),
+            Some(&src_rheader),
+            None,
+            &encrypter,
+        )?;
+
+        let decrypter = ECDH_ES_A128KW.decrypter_from_der(&private_key)?;
+        let (dst_payload, dst_header, dst_rheader) =
+            jwe::deserialize_json_header_set_with_selector(&json, |header| {
+                assert_eq!(header.key_id(), Some("xxx"));
+                Ok(Some(&decrypter))
+            })?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+        assert_eq!(dst_header.key_id(), Some("xxx"));
+        assert!(dst_header.claims_set(false).contains_key("kid"));
+        assert!(!dst_header.claims_set(true).contains_key("kid"));
+        assert!(dst_header.claims_set(true).contains_key("enc"));
+        assert_eq!(dst_rheader.token_type(), Some("JWT"));
+
+        // The unprotected header is not part of the additional authenticated data.
+        let mut map: Map<String, Value> = serde_json::from_str(&json)?;
+        map.insert(
+            "unprotected".to_string(),
+            serde_json::json!({ "kid": "yyy" }),
+        );
+        let json = serde_json::to_string(&map)?;
+
+        let (dst_payload, dst_header) = jwe::deserialize_json(&json, &decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+        assert_eq!(dst_header.key_id(), Some("yyy"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_critical_header_claims() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let src_payload = b"test payload!";
+        let mut context = JweContext::new();
+        context.add_acceptable_critical("exp1");
+        context.add_acceptable_critical("enc");
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_critical(&vec!["exp1"]);
+        src_header.set_claim("exp1", Some(Value::String("value".to_string())))?;
+        let jwe = context.serialize_compact(src_payload, &src_header, &encrypter)?;
+        let (dst_payload, _) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("A128GCM", true);
+        header_set.set_critical(&vec!["exp1"]);
+        header_set.set_claim("exp1", Some(Value::String("value".to_string())), true)?;
+        let json = context.serialize_flattened_json(
+            src_payload,
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+        let (dst_payload, _) = context.deserialize_json(&json, &decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_critical(&vec!["exp2"]);
+        src_header.set_claim("exp2", Some(Value::String("value".to_string())))?;
+        let jwe = context.serialize_compact(src_payload, &src_header, &encrypter)?;
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_critical(&vec!["enc"]);
+        let jwe = context.serialize_compact(src_payload, &src_header, &encrypter)?;
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("A128GCM", true);
+        header_set.set_claim("exp1", Some(Value::String("value".to_string())), true)?;
+        header_set.set_claim("crit", Some(serde_json::json!(["exp1"])), false)?;
+        let json = context.serialize_flattened_json(
+            src_payload,
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+        assert!(matches!(
+            context.deserialize_json(&json, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_serialization_with_padded_base64() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let src_payload = b"test payload!";
+        let jwe = jwe::serialize_compact(src_payload, &src_header, &encrypter)?;
+
+        // The protected header is kept as is because it is the additional authenticated data.
+        let jwe = jwe
+            .split('.')
+            .enumerate()
+            .map(|(i, part)| match i {
+                0 => part.to_string(),
+                _ => format!("{}{}", part, "=".repeat((4 - part.len() % 4) % 4)),
+            })
+            .collect::<Vec<String>>()
+            .join(".");
+        assert!(jwe.contains('='));
+
+        let mut context = JweContext::new();
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        context.set_lenient_base64(true);
+        let (dst_payload, _) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compression() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let src_payload = vec![b'a'; 10000];
+        let mut context = JweContext::new();
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let plain_jwe = context.serialize_compact(&src_payload, &src_header, &encrypter)?;
+
+        src_header.set_compression("DEF");
+        let jwe = context.serialize_compact(&src_payload, &src_header, &encrypter)?;
+        assert!(jwe.len() < plain_jwe.len());
+
+        let (dst_payload, dst_header) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_header.compression(), Some("DEF"));
+        assert_eq!(src_payload, dst_payload);
+
+        context.set_max_decompressed_len(9999);
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("A128GCM", true);
+        header_set.set_compression("DEF");
+        let json = context.serialize_flattened_json(
+            &src_payload,
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+        assert!(matches!(
+            context.deserialize_json(&json, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        context.set_max_decompressed_len(10000);
+        let (dst_payload, _) = context.deserialize_json(&json, &decrypter)?;
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_max_token_size() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let src_payload = vec![b'a'; 10000];
+        let mut context = JweContext::new();
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let jwe = context.serialize_compact(&src_payload, &src_header, &encrypter)?;
+
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("A128GCM", true);
+        let json = context.serialize_flattened_json(
+            &src_payload,
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+
+        context.set_max_token_size(jwe.len() - 1);
+        assert!(matches!(
+            context.deserialize_compact_with_selector(&jwe, |_header| {
+                panic!("The header must not be decoded.")
+            }),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            context.extract_header(&jwe),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        context.set_max_token_size(json.len());
+        let (dst_payload, _) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(src_payload, dst_payload);
+        let (dst_payload, _) = context.deserialize_json(&json, &decrypter)?;
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_serialization_with_decrypters() -> Result<()> {
+        let old_key = util::random_bytes(16);
+        let new_key = util::random_bytes(16);
+        let other_key = util::random_bytes(16);
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let encrypter = A128KW.encrypter_from_bytes(&new_key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &src_header, &encrypter)?;
+
+        let old_decrypter = A128KW.decrypter_from_bytes(&old_key)?;
+        let new_decrypter = A128KW.decrypter_from_bytes(&new_key)?;
+        let (payload, header) =
+            jwe::deserialize_compact_with_decrypters(&jwe, &[&old_decrypter, &new_decrypter])?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(header.algorithm(), Some("A128KW"));
+
+        let other_decrypter = A128KW.decrypter_from_bytes(&other_key)?;
+        assert!(matches!(
+            jwe::deserialize_compact_with_decrypters(&jwe, &[&old_decrypter, &other_decrypter]),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            jwe::deserialize_compact_with_decrypters(&jwe, &[]),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_parameters() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = A128KW.encrypter_from_bytes(&key)?;
+        let decrypter = A128KW.decrypter_from_bytes(&key)?;
+
+        let mut context = JweContext::new();
+        context.register_header_parameter("exp1", JsonType::String);
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_critical(&vec!["exp1"]);
+        src_header.set_claim("exp1", Some(serde_json::json!("value")))?;
+        let jwe = context.serialize_compact(b"test payload!", &src_header, &encrypter)?;
+        context.set_strict_header_parameters(true);
+        let (_, dst_header) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_header.claim("exp1"), Some(&serde_json::json!("value")));
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_claim("exp1", Some(serde_json::json!(true)))?;
+        let jwe = context.serialize_compact(b"test payload!", &src_header, &encrypter)?;
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("A128GCM", true);
+        header_set.set_claim("exp1", Some(serde_json::json!(true)), false)?;
+        let json = context.serialize_flattened_json(
+            b"test payload!",
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &decrypter),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        context.set_strict_header_parameters(false);
+        let (_, dst_header) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_header.claim("exp1"), Some(&serde_json::json!(true)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_algorithm_policy() -> Result<()> {
+        let key = util::random_bytes(C20P.key_len());
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let mut context = JweContext::new();
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("C20P");
+        let jwe = context.serialize_compact(b"test payload!", &src_header, &encrypter)?;
+
+        let mut header_set = JweHeaderSet::new();
+        header_set.set_content_encryption("C20P", true);
+        let json = context.serialize_flattened_json(
+            b"test payload!",
+            Some(&header_set),
+            None,
+            None,
+            &encrypter,
+        )?;
+        context.deserialize_compact(&jwe, &decrypter)?;
+        context.deserialize_json(&json, &decrypter)?;
+
+        context.set_algorithm_policy(AlgorithmPolicy::fips());
+        assert!(matches!(
+            context.serialize_compact(b"test payload!", &src_header, &encrypter),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.serialize_flattened_json(
+                b"test payload!",
+                Some(&header_set),
+                None,
+                None,
+                &encrypter,
+            ),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.deserialize_compact(&jwe, &decrypter),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &decrypter),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+
+        let mut policy = AlgorithmPolicy::fips();
+        policy.deny("dir");
+        context.set_algorithm_policy(policy);
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        assert!(matches!(
+            context.serialize_compact(b"test payload!", &src_header, &encrypter),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+
+        context.remove_algorithm_policy();
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+        let jwe = context.serialize_compact(b"test payload!", &src_header, &encrypter)?;
+        let (payload, _) = context.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_error_kind() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let jwe = jwe::serialize_compact(b"test payload!", &src_header, &encrypter)?;
+
+        let mut parts: Vec<String> = jwe.split('.').map(|val| val.to_string()).collect();
+        let mut tag = base64::decode_config(&parts[4], base64::URL_SAFE_NO_PAD)?;
+        tag[0] ^= 0x01;
+        parts[4] = base64::encode_config(&tag, base64::URL_SAFE_NO_PAD);
+        let err = jwe::deserialize_compact(&parts.join("."), &decrypter).unwrap_err();
+        assert_eq!(err.kind(), Some(JoseErrorKind::TagMismatch));
+
+        let decrypter = Dir.decrypter_from_bytes(util::random_bytes(32))?;
+        let err = jwe::deserialize_compact(&jwe, &decrypter).unwrap_err();
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone)]
+    struct SequentialRandomSource;
+
+    impl RandomSource for SequentialRandomSource {
+        fn fill_bytes(&self, buf: &mut [u8]) {
+            for (i, val) in buf.iter_mut().enumerate() {
+                *val = i as u8;
+            }
+        }
+
+        fn box_clone(&self) -> Box<dyn RandomSource> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_jwe_with_random_source() -> Result<()> {
+        let key: Vec<u8> = (0x10..0x20).collect();
+        let encrypter = A128KW.encrypter_from_bytes(&key)?;
+        let decrypter = A128KW.decrypter_from_bytes(&key)?;
+
+        let src_payload = b"test payload!";
+        let mut context = JweContext::new();
+        context.set_random_source(Box::new(SequentialRandomSource));
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let jwe = context.serialize_compact(src_payload, &src_header, &encrypter)?;
+        assert_eq!(
+            jwe,
+            concat!(
+                "eyJlbmMiOiJBMTI4R0NNIiwiYWxnIjoiQTEyOEtXIn0.",
+                "Ogu9AxwToenv9SHshBF8S5PKe5Pwh_YY.",
+                "AAECAwQFBgcICQoL.",
+                "5wnUukZrli0nvQDuFw.",
+                "-LNkQjNygNIygupvHbDaeQ"
+            )
+        );
+
+        let (dst_payload, _) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_extract_header() -> Result<()> {
+        let key = util::random_bytes(16);
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_key_id("key-1");
+        let jwe = jwe::serialize_compact(b"test payload!", &src_header, &encrypter)?;
+
+        let dst_header = jwe::extract_header(&jwe)?;
+        assert_eq!(dst_header.key_id(), Some("key-1"));
+        assert_eq!(dst_header.algorithm(), Some("dir"));
+        assert_eq!(dst_header.content_encryption(), Some("A128GCM"));
+
+        let header_b64 = base64::encode_config(br#"{"alg":"dir""#, base64::URL_SAFE_NO_PAD);
+        for input in vec![
+            "".to_string(),
+            "e30.e30.e30".to_string(),
+            "e30.e30.e30.e30.e30.e30".to_string(),
+            "*.e30.e30.e30.e30".to_string(),
+            format!("{}.e30.e30.e30.e30", header_b64),
+        ] {
+            assert!(matches!(
+                jwe::extract_header(&input),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_serialization_to_jwk_set() -> Result<()> {
+        let mut ec_public_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_public.jwk")?)?;
+        ec_public_key.set_key_id("ec-1");
+        let mut rsa_public_key = Jwk::from_bytes(&load_file("jwk/RSA_public.jwk")?)?;
+        rsa_public_key.set_key_id("rsa-1");
+        rsa_public_key.set_key_use("enc");
+
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(ec_public_key);
+        jwk_set.push_key(rsa_public_key);
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let src_payload = b"test payload!";
+
+        let jwe = jwe::serialize_compact_to_jwk_set(
+            src_payload,
+            &src_header,
+            &jwk_set,
+            &[&RSA_OAEP, &ECDH_ES_A128KW],
+        )?;
+        let rsa_private_key = Jwk::from_bytes(&load_file("jwk/RSA_private.jwk")?)?;
+        let decrypter = RSA_OAEP.decrypter_from_jwk(&rsa_private_key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(dst_header.algorithm(), Some("RSA-OAEP"));
+        assert_eq!(dst_header.key_id(), Some("rsa-1"));
+
+        let jwe = jwe::serialize_compact_to_jwk_set(
+            src_payload,
+            &src_header,
+            &jwk_set,
+            &[&ECDH_ES_A128KW, &RSA_OAEP],
+        )?;
+        let ec_private_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_private.jwk")?)?;
+        let decrypter = ECDH_ES_A128KW.decrypter_from_jwk(&ec_private_key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(dst_header.algorithm(), Some("ECDH-ES+A128KW"));
+        assert_eq!(dst_header.key_id(), Some("ec-1"));
+
+        let mut kid_header = src_header.clone();
+        kid_header.set_key_id("ec-1");
+        assert!(matches!(
+            jwe::serialize_compact_to_jwk_set(src_payload, &kid_header, &jwk_set, &[&RSA_OAEP]),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            jwe::serialize_compact_to_jwk_set(src_payload, &src_header, &jwk_set, &[&A128KW]),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            jwe::serialize_compact_to_jwk_set(
+                src_payload,
+                &src_header,
+                &JwkSet::new(),
+                &[&RSA_OAEP]
+            ),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiate_enc() {
+        assert_eq!(
+            jwe::negotiate_enc(&["A128GCM", "A256GCM"], &["A256GCM", "A128GCM"]),
+            Some("A256GCM")
+        );
+        assert_eq!(
+            jwe::negotiate_enc(&["A128GCM", "A256GCM"], &["A128GCM", "A256GCM"]),
+            Some("A128GCM")
+        );
+        assert_eq!(
+            jwe::negotiate_enc(&["A128CBC-HS256", "A256GCM"], &["A128CBC-HS256"]),
+            Some("A128CBC-HS256")
+        );
+        assert_eq!(jwe::negotiate_enc(&["A128GCM"], &["A256GCM", "C20P"]), None);
+        assert_eq!(jwe::negotiate_enc(&[], &["A256GCM"]), None);
+
+        assert_eq!(
+            jwe::negotiate_enc(&["A128CBC-HS256", "A256CBC-HS512", "A128GCM"], &[]),
+            Some("A256CBC-HS512")
+        );
+        assert_eq!(jwe::negotiate_enc(&["X-UNKNOWN"], &[]), None);
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -1,6 +1,10 @@
 pub mod aesgcmkw;
 pub mod aeskw;
 pub mod direct;
+pub mod ecdh_1pu;
 pub mod ecdh_es;
+#[cfg(feature = "hpke")]
+pub mod hpke;
+pub mod pbes2_hmac_aesgcmkw;
 pub mod pbes2_hmac_aeskw;
 pub mod rsaes;
@@ -1,14 +1,15 @@
 use std::borrow::Cow;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::symm::{self, Cipher};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
 use crate::util;
-use crate::{JoseError, JoseHeader, Value};
+use crate::{JoseError, JoseErrorKind, JoseHeader, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AesgcmkwJweAlgorithm {
@@ -29,11 +30,14 @@ impl AesgcmkwJweAlgorithm {
             let private_key = input.as_ref().to_vec();
 
             if private_key.len() != self.key_len() {
-                bail!(
-                    "The key size must be {}: {}",
-                    self.key_len(),
-                    private_key.len()
-                );
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The key size must be {}: {}",
+                        self.key_len(),
+                        private_key.len()
+                    )
+                ));
             }
 
             Ok(AesgcmkwJweEncrypter {
@@ -51,19 +55,7 @@ impl AesgcmkwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("encrypt") {
-                bail!("A parameter key_ops must contains encrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "encrypt", self.name())?;
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(val) => bail!("A parameter k must be string type but {:?}", val),
@@ -71,7 +63,10 @@ impl AesgcmkwJweAlgorithm {
             };
 
             if k.len() != self.key_len() {
-                bail!("The key size must be {}: {}", self.key_len(), k.len());
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!("The key size must be {}: {}", self.key_len(), k.len())
+                ));
             }
 
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -93,11 +88,14 @@ impl AesgcmkwJweAlgorithm {
             let private_key = input.as_ref().to_vec();
 
             if private_key.len() != self.key_len() {
-                bail!(
-                    "The key size must be {}: {}",
-                    self.key_len(),
-                    private_key.len()
-                );
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The key size must be {}: {}",
+                        self.key_len(),
+                        private_key.len()
+                    )
+                ));
             }
 
             Ok(AesgcmkwJweDecrypter {
@@ -115,19 +113,7 @@ impl AesgcmkwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("decrypt") {
-                bail!("A parameter key_ops must contains decrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "decrypt", self.name())?;
 
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -136,7 +122,10 @@ impl AesgcmkwJweAlgorithm {
             };
 
             if k.len() != self.key_len() {
-                bail!("The key size must be {}: {}", self.key_len(), k.len());
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!("The key size must be {}: {}", self.key_len(), k.len())
+                ));
             }
 
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -195,13 +184,23 @@ impl Deref for AesgcmkwJweAlgorithm {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AesgcmkwJweEncrypter {
     algorithm: AesgcmkwJweAlgorithm,
     private_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for AesgcmkwJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("AesgcmkwJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl AesgcmkwJweEncrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -240,7 +239,7 @@ impl JweEncrypter for AesgcmkwJweEncrypter {
         out_header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
         (|| -> anyhow::Result<Option<Vec<u8>>> {
-            let iv = util::random_bytes(32);
+            let iv = util::random_bytes(12);
 
             let cipher = self.algorithm.cipher();
             let mut tag = [0; 16];
@@ -274,13 +273,23 @@ impl Deref for AesgcmkwJweEncrypter {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AesgcmkwJweDecrypter {
     algorithm: AesgcmkwJweAlgorithm,
     private_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for AesgcmkwJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("AesgcmkwJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl AesgcmkwJweDecrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -320,12 +329,29 @@ impl JweDecrypter for AesgcmkwJweDecrypter {
                 Some(_) => bail!("The iv header claim must be string."),
                 None => bail!("The iv header claim is required."),
             };
+            if iv.len() != 12 {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidIvLength,
+                    format!("The length of the iv header claim must be 12: {}", iv.len())
+                ));
+            }
 
+            // OpenSSL accepts a truncated GCM tag, so the length must be checked
+            // before the tag authenticates the wrapped key.
             let tag = match header.claim("tag") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(_) => bail!("The tag header claim must be string."),
                 None => bail!("The tag header claim is required."),
             };
+            if tag.len() != 16 {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidTagLength,
+                    format!(
+                        "The length of the tag header claim must be 16: {}",
+                        tag.len()
+                    )
+                ));
+            }
 
             let cipher = self.algorithm.cipher();
             let key = symm::decrypt_aead(
@@ -335,7 +361,8 @@ impl JweDecrypter for AesgcmkwJweDecrypter {
                 b"",
                 encrypted_key,
                 &tag,
-            )?;
+            )
+            .map_err(|err| JoseErrorDetail::new(JoseErrorKind::TagMismatch, err.to_string()))?;
 
             Ok(Cow::Owned(key))
         })()
@@ -363,9 +390,10 @@ mod tests {
 
     use super::AesgcmkwJweAlgorithm;
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{self, JweHeader};
     use crate::jwk::Jwk;
     use crate::util;
+    use crate::{JoseError, JoseErrorKind, Value};
 
     #[test]
     fn encrypt_and_decrypt_aes_gcm() -> Result<()> {
@@ -402,4 +430,99 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn reject_aes_gcm_tampered_key_wrap() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let alg = AesgcmkwJweAlgorithm::A128gcmkw;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let key = util::random_bytes(alg.key_len());
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+
+        let src_key = util::random_bytes(enc.key_len());
+        let mut out_header = header.clone();
+        let encrypted_key = encrypter
+            .encrypt(&src_key, &header, &mut out_header)?
+            .unwrap();
+        let iv = match out_header.claim("iv") {
+            Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+            _ => unreachable!(),
+        };
+        let tag = match out_header.claim("tag") {
+            Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+            _ => unreachable!(),
+        };
+        assert_eq!(iv.len(), 12);
+        assert_eq!(tag.len(), 16);
+
+        let mut tampered_key = encrypted_key.clone();
+        tampered_key[0] ^= 1;
+        let err = decrypter
+            .decrypt(Some(&tampered_key), &enc, &out_header)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::TagMismatch));
+
+        let mut tampered_tag = tag.clone();
+        tampered_tag[0] ^= 1;
+        let mut tampered_header = out_header.clone();
+        tampered_header.set_claim("tag", Some(json!(encode(&tampered_tag))))?;
+        let err = decrypter
+            .decrypt(Some(&encrypted_key), &enc, &tampered_header)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::TagMismatch));
+
+        let mut tampered_header = out_header.clone();
+        tampered_header.set_claim("tag", Some(json!(encode(&tag[..12]))))?;
+        let err = decrypter
+            .decrypt(Some(&encrypted_key), &enc, &tampered_header)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidTagLength));
+
+        let mut tampered_header = out_header.clone();
+        tampered_header.set_claim("iv", Some(json!(encode(&util::random_bytes(16)))))?;
+        let err = decrypter
+            .decrypt(Some(&encrypted_key), &enc, &tampered_header)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidIvLength));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_aes_gcm_tampered_encrypted_key_in_jwe() -> Result<()> {
+        let alg = AesgcmkwJweAlgorithm::A256gcmkw;
+        let key = util::random_bytes(alg.key_len());
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+        let encrypter = alg.encrypter_from_bytes(&key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let mut parts: Vec<String> = jwe.split('.').map(|val| val.to_string()).collect();
+        let mut encrypted_key = base64::decode_config(&parts[1], base64::URL_SAFE_NO_PAD)?;
+        encrypted_key[0] ^= 1;
+        parts[1] = encode(&encrypted_key);
+        let tampered = parts.join(".");
+
+        let decrypter = alg.decrypter_from_bytes(&key)?;
+        let err = jwe::deserialize_compact(&tampered, &decrypter).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+
+        let (payload, _) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        Ok(())
+    }
+
+    fn encode(input: &[u8]) -> String {
+        base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+    }
 }
@@ -1,13 +1,16 @@
 use std::borrow::Cow;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::aes::{self, AesKey};
+use openssl::symm::{Cipher, Crypter, Mode};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
-use crate::{JoseError, Value};
+use crate::util;
+use crate::{JoseError, JoseErrorKind, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AeskwJweAlgorithm {
@@ -17,6 +20,18 @@ pub enum AeskwJweAlgorithm {
     A192kw,
     /// AES Key Wrap with default initial value using 256-bit key
     A256kw,
+    /// AES Key Wrap with Padding (RFC 5649) using 128-bit key
+    ///
+    /// This algorithm is not registered in the IANA JOSE registry.
+    A128kwp,
+    /// AES Key Wrap with Padding (RFC 5649) using 192-bit key
+    ///
+    /// This algorithm is not registered in the IANA JOSE registry.
+    A192kwp,
+    /// AES Key Wrap with Padding (RFC 5649) using 256-bit key
+    ///
+    /// This algorithm is not registered in the IANA JOSE registry.
+    A256kwp,
 }
 
 impl AeskwJweAlgorithm {
@@ -28,11 +43,14 @@ impl AeskwJweAlgorithm {
             let private_key = input.as_ref().to_vec();
 
             if private_key.len() != self.key_len() {
-                bail!(
-                    "The key size must be {}: {}",
-                    self.key_len(),
-                    private_key.len()
-                );
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The key size must be {}: {}",
+                        self.key_len(),
+                        private_key.len()
+                    )
+                ));
             }
 
             Ok(AeskwJweEncrypter {
@@ -50,19 +68,7 @@ impl AeskwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("wrapKey") {
-                bail!("A parameter key_ops must contains wrapKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "wrapKey", self.name())?;
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(val) => bail!("A parameter k must be string type but {:?}", val),
@@ -70,7 +76,10 @@ impl AeskwJweAlgorithm {
             };
 
             if k.len() != self.key_len() {
-                bail!("The key size must be {}: {}", self.key_len(), k.len());
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!("The key size must be {}: {}", self.key_len(), k.len())
+                ));
             }
 
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -92,11 +101,14 @@ impl AeskwJweAlgorithm {
             let private_key = input.as_ref().to_vec();
 
             if private_key.len() != self.key_len() {
-                bail!(
-                    "The key size must be {}: {}",
-                    self.key_len(),
-                    private_key.len()
-                );
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The key size must be {}: {}",
+                        self.key_len(),
+                        private_key.len()
+                    )
+                ));
             }
 
             Ok(AeskwJweDecrypter {
@@ -114,19 +126,7 @@ impl AeskwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("unwrapKey") {
-                bail!("A parameter key_ops must contains unwrapKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "unwrapKey", self.name())?;
 
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -135,7 +135,10 @@ impl AeskwJweAlgorithm {
             };
 
             if k.len() != self.key_len() {
-                bail!("The key size must be {}: {}", self.key_len(), k.len());
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!("The key size must be {}: {}", self.key_len(), k.len())
+                ));
             }
 
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -151,19 +154,147 @@ impl AeskwJweAlgorithm {
 
     fn key_len(&self) -> usize {
         match self {
-            Self::A128kw => 16,
-            Self::A192kw => 24,
-            Self::A256kw => 32,
+            Self::A128kw | Self::A128kwp => 16,
+            Self::A192kw | Self::A192kwp => 24,
+            Self::A256kw | Self::A256kwp => 32,
+        }
+    }
+
+    fn is_padded(&self) -> bool {
+        matches!(self, Self::A128kwp | Self::A192kwp | Self::A256kwp)
+    }
+
+    fn ecb_cipher(&self) -> Cipher {
+        match self {
+            Self::A128kw | Self::A128kwp => Cipher::aes_128_ecb(),
+            Self::A192kw | Self::A192kwp => Cipher::aes_192_ecb(),
+            Self::A256kw | Self::A256kwp => Cipher::aes_256_ecb(),
         }
     }
+
+    /// Wrap a key by AES Key Wrap with Padding (RFC 5649).
+    fn wrap_key_with_padding(&self, kek: &[u8], key: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if key.is_empty() || key.len() > u32::MAX as usize {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!("The length of key is invalid: {}", key.len())
+            ));
+        }
+
+        let mut aiv = [0; 8];
+        aiv[0..4].copy_from_slice(&AKW_PAD_ICV);
+        aiv[4..8].copy_from_slice(&(key.len() as u32).to_be_bytes());
+
+        let mut padded_key = key.to_vec();
+        padded_key.resize((key.len() + 7) & !7, 0);
+
+        let mut crypter = Crypter::new(self.ecb_cipher(), Mode::Encrypt, kek, None)?;
+        crypter.pad(false);
+        let mut buf = [0; 32];
+
+        if padded_key.len() == 8 {
+            let mut block = aiv.to_vec();
+            block.extend_from_slice(&padded_key);
+            crypter.update(&block, &mut buf)?;
+            return Ok(buf[..16].to_vec());
+        }
+
+        let n = padded_key.len() / 8;
+        let mut a = aiv;
+        let mut r = padded_key;
+        let mut block = [0; 16];
+        for j in 0..6 {
+            for i in 0..n {
+                block[0..8].copy_from_slice(&a);
+                block[8..16].copy_from_slice(&r[(i * 8)..(i * 8 + 8)]);
+                crypter.update(&block, &mut buf)?;
+
+                let t = ((n * j) + i + 1) as u64;
+                a.copy_from_slice(&buf[0..8]);
+                for (k, b) in t.to_be_bytes().iter().enumerate() {
+                    a[k] ^= b;
+                }
+                r[(i * 8)..(i * 8 + 8)].copy_from_slice(&buf[8..16]);
+            }
+        }
+
+        let mut encrypted_key = a.to_vec();
+        encrypted_key.extend_from_slice(&r);
+        Ok(encrypted_key)
+    }
+
+    /// Unwrap a key by AES Key Wrap with Padding (RFC 5649).
+    fn unwrap_key_with_padding(&self, kek: &[u8], encrypted_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if encrypted_key.len() < 16 || encrypted_key.len() & 7 != 0 {
+            bail!(
+                "The length of encrypted key is invalid: {}",
+                encrypted_key.len()
+            );
+        }
+
+        let mut crypter = Crypter::new(self.ecb_cipher(), Mode::Decrypt, kek, None)?;
+        crypter.pad(false);
+        let mut buf = [0; 32];
+
+        let n = encrypted_key.len() / 8 - 1;
+        let mut a = [0; 8];
+        let mut r;
+        if n == 1 {
+            crypter.update(encrypted_key, &mut buf)?;
+            a.copy_from_slice(&buf[0..8]);
+            r = buf[8..16].to_vec();
+        } else {
+            a.copy_from_slice(&encrypted_key[0..8]);
+            r = encrypted_key[8..].to_vec();
+            let mut block = [0; 16];
+            for j in (0..6).rev() {
+                for i in (0..n).rev() {
+                    let t = ((n * j) + i + 1) as u64;
+                    for (k, b) in t.to_be_bytes().iter().enumerate() {
+                        a[k] ^= b;
+                    }
+                    block[0..8].copy_from_slice(&a);
+                    block[8..16].copy_from_slice(&r[(i * 8)..(i * 8 + 8)]);
+                    crypter.update(&block, &mut buf)?;
+
+                    a.copy_from_slice(&buf[0..8]);
+                    r[(i * 8)..(i * 8 + 8)].copy_from_slice(&buf[8..16]);
+                }
+            }
+        }
+
+        if !util::constant_time_eq(&a[0..4], &AKW_PAD_ICV) {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::TagMismatch,
+                "The alternative initial value is mismatched."
+            ));
+        }
+
+        let mli = u32::from_be_bytes([a[4], a[5], a[6], a[7]]) as usize;
+        if mli <= 8 * (n - 1) || mli > 8 * n {
+            bail!("The message length indicator is invalid: {}", mli);
+        }
+        if !util::constant_time_eq(&r[mli..], &[0; 8][..(r.len() - mli)]) {
+            bail!("The padding is invalid.");
+        }
+
+        r.truncate(mli);
+        Ok(r)
+    }
 }
 
+/// The high-order half of the alternative initial value of RFC 5649.
+const AKW_PAD_ICV: [u8; 4] = [0xA6, 0x59, 0x59, 0xA6];
+
 impl JweAlgorithm for AeskwJweAlgorithm {
     fn name(&self) -> &str {
         match self {
             Self::A128kw => "A128KW",
             Self::A192kw => "A192KW",
             Self::A256kw => "A256KW",
+            Self::A128kwp => "A128KWP",
+            Self::A192kwp => "A192KWP",
+            Self::A256kwp => "A256KWP",
         }
     }
 
@@ -186,13 +317,23 @@ impl Deref for AeskwJweAlgorithm {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AeskwJweEncrypter {
     algorithm: AeskwJweAlgorithm,
     private_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for AeskwJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("AeskwJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl AeskwJweEncrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -230,7 +371,26 @@ impl JweEncrypter for AeskwJweEncrypter {
         _in_header: &JweHeader,
         _out_header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
+        if self.algorithm.is_padded() {
+            return self
+                .algorithm
+                .wrap_key_with_padding(&self.private_key, key)
+                .map(Some)
+                .map_err(|err| JoseError::InvalidKeyFormat(err));
+        }
+
         (|| -> anyhow::Result<Option<Vec<u8>>> {
+            // RFC 3394 wraps two or more 64-bit blocks.
+            if key.len() < 16 || key.len() % 8 != 0 {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The length of key must be a multiple of 8 and 16 or more: {}",
+                        key.len()
+                    )
+                ));
+            }
+
             let aes = match AesKey::new_encrypt(&self.private_key) {
                 Ok(val) => val,
                 Err(_) => bail!("Failed to set encrypt key."),
@@ -264,13 +424,23 @@ impl Deref for AeskwJweEncrypter {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AeskwJweDecrypter {
     algorithm: AeskwJweAlgorithm,
     private_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for AeskwJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("AeskwJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl AeskwJweDecrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -299,12 +469,36 @@ impl JweDecrypter for AeskwJweDecrypter {
         _cencryption: &dyn JweContentEncryption,
         _header: &JweHeader,
     ) -> Result<Cow<[u8]>, JoseError> {
+        if self.algorithm.is_padded() {
+            let encrypted_key = match encrypted_key {
+                Some(val) => val,
+                None => {
+                    return Err(JoseError::InvalidJweFormat(anyhow::anyhow!(
+                        "A encrypted_key is required."
+                    )))
+                }
+            };
+
+            return self
+                .algorithm
+                .unwrap_key_with_padding(&self.private_key, encrypted_key)
+                .map(Cow::Owned)
+                .map_err(|err| JoseError::InvalidKeyFormat(err));
+        }
+
         (|| -> anyhow::Result<Cow<[u8]>> {
             let encrypted_key = match encrypted_key {
                 Some(val) => val,
                 None => bail!("A encrypted_key is required."),
             };
 
+            if encrypted_key.len() < 24 || encrypted_key.len() % 8 != 0 {
+                bail!(
+                    "The length of encrypted key is invalid: {}",
+                    encrypted_key.len()
+                );
+            }
+
             let aes = match AesKey::new_decrypt(&self.private_key) {
                 Ok(val) => val,
                 Err(_) => bail!("Failed to set decrypt key."),
@@ -346,9 +540,10 @@ mod tests {
 
     use super::AeskwJweAlgorithm;
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{JweDecrypter, JweEncrypter, JweHeader};
     use crate::jwk::Jwk;
-    use crate::util;
+    use crate::util::{self, decode_hex};
+    use crate::{JoseError, JoseErrorKind};
 
     #[test]
     fn encrypt_and_decrypt_aes() -> Result<()> {
@@ -358,6 +553,9 @@ mod tests {
             AeskwJweAlgorithm::A128kw,
             AeskwJweAlgorithm::A192kw,
             AeskwJweAlgorithm::A256kw,
+            AeskwJweAlgorithm::A128kwp,
+            AeskwJweAlgorithm::A192kwp,
+            AeskwJweAlgorithm::A256kwp,
         ] {
             let mut header = JweHeader::new();
             header.set_content_encryption(enc.name());
@@ -385,4 +583,139 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn aes_with_wrong_length_kek() -> Result<()> {
+        for alg in vec![
+            AeskwJweAlgorithm::A128kw,
+            AeskwJweAlgorithm::A192kw,
+            AeskwJweAlgorithm::A256kw,
+            AeskwJweAlgorithm::A128kwp,
+            AeskwJweAlgorithm::A192kwp,
+            AeskwJweAlgorithm::A256kwp,
+        ] {
+            for len in vec![alg.key_len() - 8, alg.key_len() - 1, alg.key_len() + 8] {
+                let key = util::random_bytes(len);
+                let jwk = {
+                    let mut jwk = Jwk::new("oct");
+                    jwk.set_parameter(
+                        "k",
+                        Some(json!(base64::encode_config(&key, base64::URL_SAFE_NO_PAD))),
+                    )?;
+                    jwk
+                };
+
+                for err in vec![
+                    alg.encrypter_from_bytes(&key).unwrap_err(),
+                    alg.encrypter_from_jwk(&jwk).unwrap_err(),
+                    alg.decrypter_from_bytes(&key).unwrap_err(),
+                    alg.decrypter_from_jwk(&jwk).unwrap_err(),
+                ] {
+                    assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn aes_with_invalid_content_encryption_key_length() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let header = JweHeader::new();
+
+        for alg in vec![
+            AeskwJweAlgorithm::A128kw,
+            AeskwJweAlgorithm::A192kw,
+            AeskwJweAlgorithm::A256kw,
+        ] {
+            let key = util::random_bytes(alg.key_len());
+            let encrypter = alg.encrypter_from_bytes(&key)?;
+            let decrypter = alg.decrypter_from_bytes(&key)?;
+
+            for len in vec![0, 8, 20] {
+                let mut out_header = header.clone();
+                let err = encrypter
+                    .encrypt(&util::random_bytes(len), &header, &mut out_header)
+                    .unwrap_err();
+                assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+            }
+
+            for len in vec![0, 4, 16, 28] {
+                assert!(matches!(
+                    decrypter.decrypt(Some(&util::random_bytes(len)), &enc, &header),
+                    Err(JoseError::InvalidJweFormat(_))
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_with_padding_20_byte_key() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let header = JweHeader::new();
+
+        for alg in vec![
+            AeskwJweAlgorithm::A128kwp,
+            AeskwJweAlgorithm::A192kwp,
+            AeskwJweAlgorithm::A256kwp,
+        ] {
+            let key = util::random_bytes(alg.key_len());
+            let encrypter = alg.encrypter_from_bytes(&key)?;
+            let decrypter = alg.decrypter_from_bytes(&key)?;
+
+            for len in vec![1, 7, 8, 20, 64] {
+                let src_key = util::random_bytes(len);
+                let mut out_header = header.clone();
+                let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+                let encrypted_key = encrypted_key.unwrap();
+                assert_eq!(encrypted_key.len(), ((len + 7) & !7) + 8);
+
+                let dst_key = decrypter.decrypt(Some(&encrypted_key), &enc, &out_header)?;
+                assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_with_padding_test_vectors() -> Result<()> {
+        // RFC 5649 Section 6. Padded Key Wrap Examples
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let alg = AeskwJweAlgorithm::A192kwp;
+        let kek = decode_hex("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8");
+        let header = JweHeader::new();
+
+        for (key, expected_encrypted_key) in vec![
+            (
+                "c37b7e6492584340bed12207808941155068f738",
+                "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a",
+            ),
+            ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f"),
+        ] {
+            let key = decode_hex(key);
+            let expected_encrypted_key = decode_hex(expected_encrypted_key);
+
+            let encrypter = alg.encrypter_from_bytes(&kek)?;
+            let mut out_header = header.clone();
+            let encrypted_key = encrypter.encrypt(&key, &header, &mut out_header)?;
+            assert_eq!(encrypted_key, Some(expected_encrypted_key.clone()));
+
+            let decrypter = alg.decrypter_from_bytes(&kek)?;
+            let dst_key = decrypter.decrypt(Some(&expected_encrypted_key), &enc, &header)?;
+            assert_eq!(&key as &[u8], &dst_key as &[u8]);
+
+            let mut modified_encrypted_key = expected_encrypted_key.clone();
+            modified_encrypted_key[0] ^= 0x01;
+            assert!(matches!(
+                decrypter.decrypt(Some(&modified_encrypted_key), &enc, &header),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+        }
+
+        Ok(())
+    }
 }
@@ -1,13 +1,18 @@
 use std::borrow::Cow;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
 use anyhow::bail;
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
+use crate::util::{self, hkdf, SHA_256};
 use crate::{JoseError, Value};
 
+/// The header claim that carries the salt of a CEK derived from a passphrase.
+/// This is an application-defined header claim, not a standard JOSE one.
+pub const HKDF_SALT_HEADER_CLAIM: &str = "josekit:hkdf_salt";
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum DirectJweAlgorithm {
     /// Direct use of a shared symmetric key as the CEK
@@ -34,19 +39,7 @@ impl DirectJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("encrypt") {
-                bail!("A parameter key_ops must contains encrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "encrypt", self.name())?;
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(val) => bail!("A parameter k must be string type but {:?}", val),
@@ -83,19 +76,7 @@ impl DirectJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("decrypt") {
-                bail!("A parameter key_ops must contains decrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "decrypt", self.name())?;
 
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -113,6 +94,64 @@ impl DirectJweAlgorithm {
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
+
+    /// Return a encrypter that uses a CEK derived from a passphrase by HKDF-SHA256.
+    ///
+    /// The CEK has the key length of the content encryption and the salt is
+    /// emitted in the josekit:hkdf_salt header claim. This is an extension of
+    /// this library, not standard JOSE, so the peer must also be josekit.
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - A passphrase.
+    /// * `salt` - A salt for HKDF.
+    pub fn encrypter_from_passphrase(
+        &self,
+        passphrase: impl AsRef<[u8]>,
+        salt: impl AsRef<[u8]>,
+    ) -> Result<DirectHkdfJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<DirectHkdfJweEncrypter> {
+            let passphrase = passphrase.as_ref();
+            if passphrase.len() == 0 {
+                bail!("A passphrase must not be empty.");
+            }
+
+            Ok(DirectHkdfJweEncrypter {
+                algorithm: self.clone(),
+                passphrase: passphrase.to_vec(),
+                salt: salt.as_ref().to_vec(),
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    /// Return a decrypter that uses a CEK derived from a passphrase by HKDF-SHA256.
+    ///
+    /// The salt is read from the josekit:hkdf_salt header claim.
+    /// This is an extension of this library, not standard JOSE.
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - A passphrase.
+    pub fn decrypter_from_passphrase(
+        &self,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<DirectHkdfJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<DirectHkdfJweDecrypter> {
+            let passphrase = passphrase.as_ref();
+            if passphrase.len() == 0 {
+                bail!("A passphrase must not be empty.");
+            }
+
+            Ok(DirectHkdfJweDecrypter {
+                algorithm: self.clone(),
+                passphrase: passphrase.to_vec(),
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
 }
 
 impl JweAlgorithm for DirectJweAlgorithm {
@@ -141,13 +180,23 @@ impl Deref for DirectJweAlgorithm {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct DirectJweEncrypter {
     algorithm: DirectJweAlgorithm,
     cencryption_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for DirectJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("DirectJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("cencryption_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl DirectJweEncrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -213,13 +262,23 @@ impl Deref for DirectJweEncrypter {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct DirectJweDecrypter {
     algorithm: DirectJweAlgorithm,
     cencryption_key: Vec<u8>,
     key_id: Option<String>,
 }
 
+impl Debug for DirectJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("DirectJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("cencryption_key", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl DirectJweDecrypter {
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
@@ -271,14 +330,185 @@ impl Deref for DirectJweDecrypter {
     }
 }
 
+#[derive(Clone)]
+pub struct DirectHkdfJweEncrypter {
+    algorithm: DirectJweAlgorithm,
+    passphrase: Vec<u8>,
+    salt: Vec<u8>,
+    key_id: Option<String>,
+}
+
+impl Debug for DirectHkdfJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("DirectHkdfJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("passphrase", &util::REDACTED)
+            .field("salt", &self.salt)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl DirectHkdfJweEncrypter {
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweEncrypter for DirectHkdfJweEncrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn compute_content_encryption_key(
+        &self,
+        cencryption: &dyn JweContentEncryption,
+        _in_header: &JweHeader,
+        out_header: &mut JweHeader,
+    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+        (|| -> anyhow::Result<Option<Cow<[u8]>>> {
+            let key = hkdf::derive(
+                &self.salt,
+                &self.passphrase,
+                cencryption.name().as_bytes(),
+                cencryption.key_len(),
+                SHA_256,
+            )?;
+
+            let salt_b64 = base64::encode_config(&self.salt, base64::URL_SAFE_NO_PAD);
+            out_header.set_claim(HKDF_SALT_HEADER_CLAIM, Some(Value::String(salt_b64)))?;
+
+            Ok(Some(Cow::Owned(key)))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn encrypt(
+        &self,
+        _key: &[u8],
+        _in_header: &JweHeader,
+        _out_header: &mut JweHeader,
+    ) -> Result<Option<Vec<u8>>, JoseError> {
+        Ok(None)
+    }
+
+    fn box_clone(&self) -> Box<dyn JweEncrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for DirectHkdfJweEncrypter {
+    type Target = dyn JweEncrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct DirectHkdfJweDecrypter {
+    algorithm: DirectJweAlgorithm,
+    passphrase: Vec<u8>,
+    key_id: Option<String>,
+}
+
+impl Debug for DirectHkdfJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("DirectHkdfJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("passphrase", &util::REDACTED)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl DirectHkdfJweDecrypter {
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweDecrypter for DirectHkdfJweDecrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+    ) -> Result<Cow<[u8]>, JoseError> {
+        (|| -> anyhow::Result<Cow<[u8]>> {
+            if let Some(_) = encrypted_key {
+                bail!("The encrypted_key must not exist.");
+            }
+
+            let salt = match header.claim(HKDF_SALT_HEADER_CLAIM) {
+                Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+                Some(_) => bail!(
+                    "The {} header claim must be string.",
+                    HKDF_SALT_HEADER_CLAIM
+                ),
+                None => bail!("The {} header claim is required.", HKDF_SALT_HEADER_CLAIM),
+            };
+
+            let key = hkdf::derive(
+                &salt,
+                &self.passphrase,
+                cencryption.name().as_bytes(),
+                cencryption.key_len(),
+                SHA_256,
+            )?;
+
+            Ok(Cow::Owned(key))
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
+
+    fn box_clone(&self) -> Box<dyn JweDecrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for DirectHkdfJweDecrypter {
+    type Target = dyn JweDecrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
     use serde_json::json;
 
-    use super::DirectJweAlgorithm;
+    use super::{DirectJweAlgorithm, HKDF_SALT_HEADER_CLAIM};
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{self, JweHeader};
     use crate::jwk::Jwk;
 
     #[test]
@@ -314,4 +544,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_and_decrypt_direct_with_passphrase() -> Result<()> {
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let encrypter = alg.encrypter_from_passphrase(b"correct horse battery staple", b"salt")?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let decrypter = alg.decrypter_from_passphrase(b"correct horse battery staple")?;
+        let (payload, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        let salt_b64 = base64::encode_config(b"salt", base64::URL_SAFE_NO_PAD);
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(header.claim(HKDF_SALT_HEADER_CLAIM), Some(&json!(salt_b64)));
+
+        let decrypter = alg.decrypter_from_passphrase(b"wrong passphrase")?;
+        assert!(jwe::deserialize_compact(&jwe, &decrypter).is_err());
+
+        Ok(())
+    }
 }
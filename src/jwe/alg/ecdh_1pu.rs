@@ -0,0 +1,517 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::ops::Deref;
+
+use anyhow::bail;
+use openssl::derive::Deriver;
+use openssl::pkey::{PKey, Private, Public};
+
+use crate::jwe::alg::ecdh_es::{EcdhEsJweAlgorithm, EcdhEsKeyType};
+use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
+use crate::jwk::Jwk;
+use crate::util;
+use crate::{JoseError, JoseHeader, Value};
+
+/// ECDH One-Pass Unified Model key agreement (draft-madden-jose-ecdh-1pu-04).
+///
+/// The content encryption key is derived by Concat KDF from Ze || Zs, where Ze is the
+/// ephemeral-static shared secret and Zs is the static-static shared secret between the
+/// sender and the recipient, so the recipient also authenticates the sender.
+///
+/// Only the direct key agreement mode is supported. ECDH-1PU+A128KW, ECDH-1PU+A192KW and
+/// ECDH-1PU+A256KW are not implemented, so DIDComm authenticated encryption, which uses
+/// ECDH-1PU+A256KW, is not available. These modes bind the authentication tag of the
+/// content into the key derivation, which requires the content to be encrypted before
+/// the key.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Ecdh1puJweAlgorithm {
+    /// ECDH One-Pass Unified Model key agreement using Concat KDF
+    Ecdh1pu,
+}
+
+impl Ecdh1puJweAlgorithm {
+    /// Return a encrypter from the private key of the sender and the public key of the recipient
+    /// that are formatted by a JWK of EC or OKP type.
+    ///
+    /// # Arguments
+    /// * `sender_private_key` - A private key of the sender.
+    /// * `recipient_public_key` - A public key of the recipient.
+    pub fn encrypter_from_jwk(
+        &self,
+        sender_private_key: &Jwk,
+        recipient_public_key: &Jwk,
+    ) -> Result<Ecdh1puJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<Ecdh1puJweEncrypter> {
+            self.check_jwk(sender_private_key)?;
+            self.check_jwk(recipient_public_key)?;
+
+            let (private_key, key_type) = EcdhEsKeyType::private_key_from_jwk(sender_private_key)?;
+            let (public_key, recipient_key_type) =
+                EcdhEsKeyType::public_key_from_jwk(recipient_public_key)?;
+            if key_type != recipient_key_type {
+                bail!(
+                    "The key types of the sender and the recipient are mismatched: {} and {}",
+                    key_type,
+                    recipient_key_type
+                );
+            }
+            let key_id = recipient_public_key.key_id().map(|val| val.to_string());
+
+            Ok(Ecdh1puJweEncrypter {
+                algorithm: self.clone(),
+                key_type,
+                private_key,
+                public_key,
+                key_id,
+                agreement_partyuinfo: None,
+                agreement_partyvinfo: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    /// Return a decrypter from the private key of the recipient and the public key of the sender
+    /// that are formatted by a JWK of EC or OKP type.
+    ///
+    /// # Arguments
+    /// * `recipient_private_key` - A private key of the recipient.
+    /// * `sender_public_key` - A public key of the sender.
+    pub fn decrypter_from_jwk(
+        &self,
+        recipient_private_key: &Jwk,
+        sender_public_key: &Jwk,
+    ) -> Result<Ecdh1puJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<Ecdh1puJweDecrypter> {
+            self.check_jwk(recipient_private_key)?;
+            self.check_jwk(sender_public_key)?;
+
+            let (private_key, key_type) =
+                EcdhEsKeyType::private_key_from_jwk(recipient_private_key)?;
+            let (public_key, sender_key_type) =
+                EcdhEsKeyType::public_key_from_jwk(sender_public_key)?;
+            if key_type != sender_key_type {
+                bail!(
+                    "The key types of the sender and the recipient are mismatched: {} and {}",
+                    sender_key_type,
+                    key_type
+                );
+            }
+            let key_id = recipient_private_key.key_id().map(|val| val.to_string());
+
+            Ok(Ecdh1puJweDecrypter {
+                algorithm: self.clone(),
+                key_type,
+                private_key,
+                public_key,
+                key_id,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn check_jwk(&self, jwk: &Jwk) -> anyhow::Result<()> {
+        match jwk.key_type() {
+            val if val == "EC" || val == "OKP" => {}
+            val => bail!("A parameter kty must be EC or OKP: {}", val),
+        }
+        jwk.check_key_usage("enc", "deriveKey", self.name())?;
+        Ok(())
+    }
+}
+
+impl JweAlgorithm for Ecdh1puJweAlgorithm {
+    fn name(&self) -> &str {
+        match self {
+            Self::Ecdh1pu => "ECDH-1PU",
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JweAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for Ecdh1puJweAlgorithm {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for Ecdh1puJweAlgorithm {
+    type Target = dyn JweAlgorithm;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ecdh1puJweEncrypter {
+    algorithm: Ecdh1puJweAlgorithm,
+    key_type: EcdhEsKeyType,
+    private_key: PKey<Private>,
+    public_key: PKey<Public>,
+    agreement_partyuinfo: Option<Vec<u8>>,
+    agreement_partyvinfo: Option<Vec<u8>>,
+    key_id: Option<String>,
+}
+
+impl Ecdh1puJweEncrypter {
+    pub fn set_agreement_partyuinfo(&mut self, value: impl Into<Vec<u8>>) {
+        self.agreement_partyuinfo = Some(value.into());
+    }
+
+    pub fn remove_agreement_partyuinfo(&mut self) {
+        self.agreement_partyuinfo = None;
+    }
+
+    pub fn set_agreement_partyvinfo(&mut self, value: impl Into<Vec<u8>>) {
+        self.agreement_partyvinfo = Some(value.into());
+    }
+
+    pub fn remove_agreement_partyvinfo(&mut self) {
+        self.agreement_partyvinfo = None;
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweEncrypter for Ecdh1puJweEncrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn compute_content_encryption_key(
+        &self,
+        cencryption: &dyn JweContentEncryption,
+        _merged: &JweHeader,
+        header: &mut JweHeader,
+    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+        (|| -> anyhow::Result<Option<Cow<[u8]>>> {
+            let apu_vec;
+            let apu = match header.claim("apu") {
+                Some(Value::String(val)) => {
+                    apu_vec = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    Some(apu_vec.as_slice())
+                }
+                Some(_) => bail!("The apu header claim must be string."),
+                None => match &self.agreement_partyuinfo {
+                    Some(val) => {
+                        let apu_b64 = base64::encode_config(val, base64::URL_SAFE_NO_PAD);
+                        header.set_claim("apu", Some(Value::String(apu_b64)))?;
+                        Some(val.as_slice())
+                    }
+                    None => None,
+                },
+            };
+            let apv_vec;
+            let apv = match header.claim("apv") {
+                Some(Value::String(val)) => {
+                    apv_vec = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    Some(apv_vec.as_slice())
+                }
+                Some(_) => bail!("The apv header claim must be string."),
+                None => match &self.agreement_partyvinfo {
+                    Some(val) => {
+                        let apv_b64 = base64::encode_config(val, base64::URL_SAFE_NO_PAD);
+                        header.set_claim("apv", Some(Value::String(apv_b64)))?;
+                        Some(val.as_slice())
+                    }
+                    None => None,
+                },
+            };
+
+            let (ephemeral_private_key, epk) = self.key_type.generate_ephemeral_key()?;
+            header.set_claim("epk", Some(Value::Object(epk)))?;
+
+            // Z = Ze || Zs
+            let mut deriver = Deriver::new(&ephemeral_private_key)?;
+            deriver.set_peer(&self.public_key)?;
+            let mut derived_key = deriver.derive_to_vec()?;
+
+            let mut deriver = Deriver::new(&self.private_key)?;
+            deriver.set_peer(&self.public_key)?;
+            derived_key.extend_from_slice(&deriver.derive_to_vec()?);
+
+            let shared_key = EcdhEsJweAlgorithm::concat_kdf(
+                cencryption.name(),
+                cencryption.key_len(),
+                &derived_key,
+                apu,
+                apv,
+            );
+            util::zeroize(&mut derived_key);
+
+            Ok(Some(Cow::Owned(shared_key?)))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn encrypt(
+        &self,
+        _key: &[u8],
+        _merged: &JweHeader,
+        _header: &mut JweHeader,
+    ) -> Result<Option<Vec<u8>>, JoseError> {
+        Ok(None)
+    }
+
+    fn box_clone(&self) -> Box<dyn JweEncrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for Ecdh1puJweEncrypter {
+    type Target = dyn JweEncrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ecdh1puJweDecrypter {
+    algorithm: Ecdh1puJweAlgorithm,
+    key_type: EcdhEsKeyType,
+    private_key: PKey<Private>,
+    public_key: PKey<Public>,
+    key_id: Option<String>,
+}
+
+impl Ecdh1puJweDecrypter {
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweDecrypter for Ecdh1puJweDecrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+    ) -> Result<Cow<[u8]>, JoseError> {
+        (|| -> anyhow::Result<Cow<[u8]>> {
+            if encrypted_key.is_some() {
+                bail!("The encrypted_key must be empty.");
+            }
+
+            let apu = match header.claim("apu") {
+                Some(Value::String(val)) => {
+                    let apu = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    Some(apu)
+                }
+                Some(_) => bail!("The apu header claim must be string."),
+                None => None,
+            };
+            let apv = match header.claim("apv") {
+                Some(Value::String(val)) => {
+                    let apv = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    Some(apv)
+                }
+                Some(_) => bail!("The apv header claim must be string."),
+                None => None,
+            };
+
+            let ephemeral_public_key = self.key_type.ephemeral_public_key(header)?;
+
+            // Z = Ze || Zs
+            let mut deriver = Deriver::new(&self.private_key)?;
+            deriver.set_peer(&ephemeral_public_key)?;
+            let mut derived_key = deriver.derive_to_vec()?;
+
+            let mut deriver = Deriver::new(&self.private_key)?;
+            deriver.set_peer(&self.public_key)?;
+            derived_key.extend_from_slice(&deriver.derive_to_vec()?);
+
+            let shared_key = EcdhEsJweAlgorithm::concat_kdf(
+                cencryption.name(),
+                cencryption.key_len(),
+                &derived_key,
+                apu.as_deref(),
+                apv.as_deref(),
+            );
+            util::zeroize(&mut derived_key);
+
+            Ok(Cow::Owned(shared_key?))
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
+
+    fn box_clone(&self) -> Box<dyn JweDecrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for Ecdh1puJweDecrypter {
+    type Target = dyn JweDecrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::Ecdh1puJweAlgorithm;
+    use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
+    use crate::jwe::{self, JweDecrypter, JweHeader};
+    use crate::jwk::alg::{ec::EcCurve, ec::EcKeyPair, ecx::EcxCurve, ecx::EcxKeyPair};
+    use crate::jwk::{Jwk, KeyPair};
+    use crate::JoseError;
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_1pu() -> Result<()> {
+        let alg = Ecdh1puJweAlgorithm::Ecdh1pu;
+
+        let mut key_pairs: Vec<(Jwk, Jwk)> = Vec::new();
+        for curve in vec![
+            EcCurve::P256,
+            EcCurve::P384,
+            EcCurve::P521,
+            EcCurve::Secp256k1,
+        ] {
+            key_pairs.push((
+                EcKeyPair::generate(curve)?.to_jwk_key_pair(),
+                EcKeyPair::generate(curve)?.to_jwk_key_pair(),
+            ));
+        }
+        for curve in vec![EcxCurve::X25519, EcxCurve::X448] {
+            key_pairs.push((
+                EcxKeyPair::generate(curve)?.to_jwk_key_pair(),
+                EcxKeyPair::generate(curve)?.to_jwk_key_pair(),
+            ));
+        }
+
+        for (sender, recipient) in key_pairs {
+            let sender_public = sender.to_public_key()?;
+            let recipient_public = recipient.to_public_key()?;
+
+            let mut encrypter = alg.encrypter_from_jwk(&sender, &recipient_public)?;
+            encrypter.set_agreement_partyuinfo(b"Alice".to_vec());
+            encrypter.set_agreement_partyvinfo(b"Bob".to_vec());
+            let decrypter = alg.decrypter_from_jwk(&recipient, &sender_public)?;
+
+            let mut src_header = JweHeader::new();
+            src_header.set_content_encryption("A256GCM");
+            let src_payload = b"test payload!";
+            let token = jwe::serialize_compact(src_payload, &src_header, &encrypter)?;
+
+            let (dst_payload, dst_header) = jwe::deserialize_compact(&token, &decrypter)?;
+            assert_eq!(dst_header.algorithm(), Some("ECDH-1PU"));
+            assert_eq!(dst_header.agreement_partyuinfo(), Some(b"Alice".to_vec()));
+            assert_eq!(dst_header.agreement_partyvinfo(), Some(b"Bob".to_vec()));
+            assert_eq!(src_payload.to_vec(), dst_payload);
+
+            let encrypter = alg.encrypter_from_jwk(&sender, &recipient_public)?;
+            let token = jwe::serialize_compact(src_payload, &src_header, &encrypter)?;
+            let (dst_payload, _) = jwe::deserialize_compact(&token, &decrypter)?;
+            assert_eq!(src_payload.to_vec(), dst_payload);
+
+            // A recipient that expects another sender cannot decrypt.
+            let decrypter = alg.decrypter_from_jwk(&recipient, &recipient_public)?;
+            assert!(jwe::deserialize_compact(&token, &decrypter).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_ecdh_1pu_with_mismatched_curves() -> Result<()> {
+        let alg = Ecdh1puJweAlgorithm::Ecdh1pu;
+        let sender = EcKeyPair::generate(EcCurve::P256)?.to_jwk_key_pair();
+        let recipient = EcKeyPair::generate(EcCurve::P384)?.to_jwk_key_pair();
+
+        assert!(matches!(
+            alg.encrypter_from_jwk(&sender, &recipient.to_public_key()?),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            alg.decrypter_from_jwk(&recipient, &sender.to_public_key()?),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_ecdh_1pu_with_test_vector() -> Result<()> {
+        // draft-madden-jose-ecdh-1pu-04 Appendix A. Example ECDH-1PU Key Agreement Computation with A256GCM
+        let alg = Ecdh1puJweAlgorithm::Ecdh1pu;
+        let enc = AesgcmJweEncryption::A256gcm;
+
+        let alice_public_key = Jwk::from_bytes(
+            br#"{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "WKn-ZIGevcwGIyyrzFoZNBdaq9_TsqzGl96oc0CWuis",
+                "y": "y77t-RvAHRKTsSGdIYUfweuOvwrvDD-Q3Hv5J0fSKbE"
+            }"#,
+        )?;
+        let bob_private_key = Jwk::from_bytes(
+            br#"{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+                "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+                "d": "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"
+            }"#,
+        )?;
+        let header = JweHeader::from_bytes(
+            br#"{
+                "alg": "ECDH-1PU",
+                "enc": "A256GCM",
+                "apu": "QWxpY2U",
+                "apv": "Qm9i",
+                "epk": {
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "x": "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+                    "y": "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps"
+                }
+            }"#,
+        )?;
+
+        let decrypter = alg.decrypter_from_jwk(&bob_private_key, &alice_public_key)?;
+        let key = decrypter.decrypt(None, &enc, &header)?;
+        assert_eq!(
+            base64::encode_config(&key, base64::URL_SAFE_NO_PAD),
+            "bK8Tcj0UhQrUtCzW3ek1v_0v_wCpunDeBcIDpeFyLKc"
+        );
+
+        Ok(())
+    }
+}
@@ -5,7 +5,6 @@ use std::ops::Deref;
 use anyhow::bail;
 use openssl::aes::{self, AesKey};
 use openssl::derive::Deriver;
-use openssl::hash::{Hasher, MessageDigest};
 use openssl::pkey::{PKey, Private, Public};
 
 use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
@@ -14,22 +13,22 @@ use crate::jwk::alg::{
     ecx::{EcxCurve, EcxKeyPair},
 };
 use crate::jwk::Jwk;
-use crate::util;
 use crate::util::der::{DerReader, DerType};
 use crate::util::oid::{
     OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SECP256K1, OID_SECP384R1, OID_SECP521R1, OID_X25519,
     OID_X448,
 };
+use crate::util::{self, concat_kdf, SHA_256};
 use crate::{JoseError, JoseHeader, Map, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
-enum EcdhEsKeyType {
+pub(crate) enum EcdhEsKeyType {
     Ec(EcCurve),
     Ecx(EcxCurve),
 }
 
 impl EcdhEsKeyType {
-    fn key_type(&self) -> &str {
+    pub(crate) fn key_type(&self) -> &str {
         match self {
             Self::Ec(_) => "EC",
             Self::Ecx(_) => "OKP",
@@ -42,6 +41,216 @@ impl EcdhEsKeyType {
             Self::Ecx(val) => val.name(),
         }
     }
+
+    pub(crate) fn public_key_from_jwk(jwk: &Jwk) -> anyhow::Result<(PKey<Public>, Self)> {
+        match jwk.parameter("crv") {
+            Some(Value::String(val)) => match jwk.key_type() {
+                "EC" => {
+                    let curve = match val.as_str() {
+                        "P-256" => EcCurve::P256,
+                        "P-384" => EcCurve::P384,
+                        "P-521" => EcCurve::P521,
+                        "secp256k1" => EcCurve::Secp256k1,
+                        val => bail!("EC key doesn't support the curve algorithm: {}", val),
+                    };
+                    let x = match jwk.parameter("x") {
+                        Some(Value::String(val)) => {
+                            base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
+                        }
+                        Some(_) => bail!("A parameter x must be a string."),
+                        None => bail!("A parameter x is required."),
+                    };
+                    let y = match jwk.parameter("y") {
+                        Some(Value::String(val)) => {
+                            base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
+                        }
+                        Some(_) => bail!("A parameter y must be a string."),
+                        None => bail!("A parameter y is required."),
+                    };
+
+                    let mut vec = Vec::with_capacity(1 + x.len() + y.len());
+                    vec.push(0x04);
+                    vec.extend_from_slice(&x);
+                    vec.extend_from_slice(&y);
+
+                    let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, curve);
+                    let public_key = PKey::public_key_from_der(&pkcs8)?;
+
+                    Ok((public_key, Self::Ec(curve)))
+                }
+                "OKP" => {
+                    let curve = match val.as_str() {
+                        "X25519" => EcxCurve::X25519,
+                        "X448" => EcxCurve::X448,
+                        val => bail!("OKP key doesn't support the curve algorithm: {}", val),
+                    };
+                    let x = match jwk.parameter("x") {
+                        Some(Value::String(val)) => {
+                            base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
+                        }
+                        Some(_) => bail!("A parameter x must be a string."),
+                        None => bail!("A parameter x is required."),
+                    };
+
+                    let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, curve);
+                    let public_key = PKey::public_key_from_der(&pkcs8)?;
+
+                    Ok((public_key, Self::Ecx(curve)))
+                }
+                val => bail!("A parameter kty must be EC or OKP: {}", val),
+            },
+            Some(_) => bail!("A parameter crv must be a string."),
+            None => bail!("A parameter crv is required."),
+        }
+    }
+
+    pub(crate) fn private_key_from_jwk(jwk: &Jwk) -> anyhow::Result<(PKey<Private>, Self)> {
+        match jwk.parameter("crv") {
+            Some(Value::String(val)) => match jwk.key_type() {
+                "EC" => {
+                    let curve = match val.as_str() {
+                        "P-256" => EcCurve::P256,
+                        "P-384" => EcCurve::P384,
+                        "P-521" => EcCurve::P521,
+                        "secp256k1" => EcCurve::Secp256k1,
+                        val => bail!("EC key doesn't support the curve algorithm: {}", val),
+                    };
+                    let key_pair = EcKeyPair::from_jwk(jwk)?;
+                    let private_key = key_pair.into_private_key();
+
+                    Ok((private_key, Self::Ec(curve)))
+                }
+                "OKP" => {
+                    let curve = match val.as_str() {
+                        "X25519" => EcxCurve::X25519,
+                        "X448" => EcxCurve::X448,
+                        val => bail!("OKP key doesn't support the curve algorithm: {}", val),
+                    };
+                    let key_pair = EcxKeyPair::from_jwk(jwk)?;
+                    let private_key = key_pair.into_private_key();
+
+                    Ok((private_key, Self::Ecx(curve)))
+                }
+                val => bail!("A parameter kty must be EC or OKP: {}", val),
+            },
+            Some(_) => bail!("A parameter crv must be a string."),
+            None => bail!("A parameter crv is required."),
+        }
+    }
+
+    /// Generate a ephemeral key pair and return the private key and the value of epk header claim.
+    pub(crate) fn generate_ephemeral_key(
+        &self,
+    ) -> anyhow::Result<(PKey<Private>, Map<String, Value>)> {
+        let mut map = Map::new();
+        map.insert(
+            "kty".to_string(),
+            Value::String(self.key_type().to_string()),
+        );
+        map.insert(
+            "crv".to_string(),
+            Value::String(self.curve_name().to_string()),
+        );
+        let private_key = match self {
+            Self::Ec(curve) => {
+                let key_pair = EcKeyPair::generate(*curve)?;
+                let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
+
+                match jwk.remove("x") {
+                    Some(val) => {
+                        map.insert("x".to_string(), val);
+                    }
+                    None => unreachable!(),
+                }
+                match jwk.remove("y") {
+                    Some(val) => {
+                        map.insert("y".to_string(), val);
+                    }
+                    None => unreachable!(),
+                }
+
+                key_pair.into_private_key()
+            }
+            Self::Ecx(curve) => {
+                let key_pair = EcxKeyPair::generate(*curve)?;
+                let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
+
+                match jwk.remove("x") {
+                    Some(val) => {
+                        map.insert("x".to_string(), val);
+                    }
+                    None => unreachable!(),
+                }
+
+                key_pair.into_private_key()
+            }
+        };
+
+        Ok((private_key, map))
+    }
+
+    /// Return the ephemeral public key of epk header claim.
+    pub(crate) fn ephemeral_public_key(&self, header: &JweHeader) -> anyhow::Result<PKey<Public>> {
+        match header.claim("epk") {
+            Some(Value::Object(map)) => {
+                match map.get("kty") {
+                    Some(Value::String(val)) => {
+                        if val != self.key_type() {
+                            bail!("The kty parameter in epk header claim is invalid: {}", val);
+                        }
+                    }
+                    Some(_) => bail!("The kty parameter in epk header claim must be a string."),
+                    None => bail!("The kty parameter in epk header claim is required."),
+                }
+
+                match map.get("crv") {
+                    Some(Value::String(val)) => {
+                        if val != self.curve_name() {
+                            bail!("The crv parameter in epk header claim is invalid: {}", val);
+                        }
+                    }
+                    Some(_) => bail!("The crv parameter in epk header claim must be a string."),
+                    None => bail!("The crv parameter in epk header claim is required."),
+                }
+
+                let x = match map.get("x") {
+                    Some(Value::String(val)) => {
+                        base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
+                    }
+                    Some(_) => bail!("The x parameter in epk header claim must be a string."),
+                    None => bail!("The x parameter in epk header claim is required."),
+                };
+
+                match self {
+                    Self::Ec(curve) => {
+                        let y = match map.get("y") {
+                            Some(Value::String(val)) => {
+                                base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
+                            }
+                            Some(_) => {
+                                bail!("The y parameter in epk header claim must be a string.")
+                            }
+                            None => bail!("The y parameter in epk header claim is required."),
+                        };
+
+                        let mut vec = Vec::with_capacity(1 + x.len() + y.len());
+                        vec.push(0x04);
+                        vec.extend_from_slice(&x);
+                        vec.extend_from_slice(&y);
+
+                        let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, *curve);
+                        Ok(PKey::public_key_from_der(&pkcs8)?)
+                    }
+                    Self::Ecx(curve) => {
+                        let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, *curve);
+                        Ok(PKey::public_key_from_der(&pkcs8)?)
+                    }
+                }
+            }
+            Some(_) => bail!("The epk header claim must be object."),
+            None => bail!("This algorithm must have epk header claim."),
+        }
+    }
 }
 
 impl Display for EcdhEsKeyType {
@@ -188,82 +397,12 @@ impl EcdhEsJweAlgorithm {
 
     pub fn encrypter_from_jwk(&self, jwk: &Jwk) -> Result<EcdhEsJweEncrypter, JoseError> {
         (|| -> anyhow::Result<EcdhEsJweEncrypter> {
-            let key_type = match jwk.key_type() {
-                val if val == "EC" || val == "OKP" => val,
+            match jwk.key_type() {
+                val if val == "EC" || val == "OKP" => {}
                 val => bail!("A parameter kty must be EC or OKP: {}", val),
-            };
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
-            let (public_key, key_type) = match jwk.parameter("crv") {
-                Some(Value::String(val)) => match key_type {
-                    "EC" => {
-                        let curve = match val.as_str() {
-                            "P-256" => EcCurve::P256,
-                            "P-384" => EcCurve::P384,
-                            "P-521" => EcCurve::P521,
-                            "secp256k1" => EcCurve::Secp256k1,
-                            val => bail!("EC key doesn't support the curve algorithm: {}", val),
-                        };
-                        let x = match jwk.parameter("x") {
-                            Some(Value::String(val)) => {
-                                base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                            }
-                            Some(_) => bail!("A parameter x must be a string."),
-                            None => bail!("A parameter x is required."),
-                        };
-                        let y = match jwk.parameter("y") {
-                            Some(Value::String(val)) => {
-                                base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                            }
-                            Some(_) => bail!("A parameter y must be a string."),
-                            None => bail!("A parameter y is required."),
-                        };
-
-                        let mut vec = Vec::with_capacity(1 + x.len() + y.len());
-                        vec.push(0x04);
-                        vec.extend_from_slice(&x);
-                        vec.extend_from_slice(&y);
-
-                        let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, curve);
-                        let public_key = PKey::public_key_from_der(&pkcs8)?;
-
-                        (public_key, EcdhEsKeyType::Ec(curve))
-                    }
-                    "OKP" => {
-                        let curve = match val.as_str() {
-                            "X25519" => EcxCurve::X25519,
-                            "X448" => EcxCurve::X448,
-                            val => bail!("OKP key doesn't support the curve algorithm: {}", val),
-                        };
-                        let x = match jwk.parameter("x") {
-                            Some(Value::String(val)) => {
-                                base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                            }
-                            Some(_) => bail!("A parameter x must be a string."),
-                            None => bail!("A parameter x is required."),
-                        };
-
-                        let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, curve);
-                        let public_key = PKey::public_key_from_der(&pkcs8)?;
-
-                        (public_key, EcdhEsKeyType::Ecx(curve))
-                    }
-                    _ => unreachable!(),
-                },
-                Some(_) => bail!("A parameter crv must be a string."),
-                None => bail!("A parameter crv is required."),
-            };
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
+            let (public_key, key_type) = EcdhEsKeyType::public_key_from_jwk(jwk)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(EcdhEsJweEncrypter {
@@ -354,68 +493,12 @@ impl EcdhEsJweAlgorithm {
 
     pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<EcdhEsJweDecrypter, JoseError> {
         (|| -> anyhow::Result<EcdhEsJweDecrypter> {
-            let key_type = match jwk.key_type() {
-                val if val == "EC" || val == "OKP" => val,
+            match jwk.key_type() {
+                val if val == "EC" || val == "OKP" => {}
                 val => bail!("A parameter kty must be EC or OKP: {}", val),
-            };
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
-            let (private_key, key_type) = match jwk.parameter("crv") {
-                Some(Value::String(val)) => match key_type {
-                    "EC" => {
-                        let curve = match val.as_str() {
-                            "P-256" => EcCurve::P256,
-                            "P-384" => EcCurve::P384,
-                            "P-521" => EcCurve::P521,
-                            "secp256k1" => EcCurve::Secp256k1,
-                            val => bail!("EC key doesn't support the curve algorithm: {}", val),
-                        };
-                        match jwk.curve() {
-                            Some(val) if val == curve.name() => {}
-                            Some(val) => {
-                                bail!("A parameter crv must be {} but {}", self.name(), val)
-                            }
-                            None => bail!("A parameter crv is required."),
-                        }
-                        let key_pair = EcKeyPair::from_jwk(&jwk)?;
-                        let private_key = key_pair.into_private_key();
-
-                        (private_key, EcdhEsKeyType::Ec(curve))
-                    }
-                    "OKP" => {
-                        let curve = match val.as_str() {
-                            "X25519" => EcxCurve::X25519,
-                            "X448" => EcxCurve::X448,
-                            val => bail!("OKP key doesn't support the curve algorithm: {}", val),
-                        };
-                        match jwk.curve() {
-                            Some(val) if val == curve.name() => {}
-                            Some(val) => {
-                                bail!("A parameter crv must be {} but {}", self.name(), val)
-                            }
-                            None => bail!("A parameter crv is required."),
-                        }
-                        let key_pair = EcxKeyPair::from_jwk(&jwk)?;
-                        let private_key = key_pair.into_private_key();
-
-                        (private_key, EcdhEsKeyType::Ecx(curve))
-                    }
-                    _ => unreachable!(),
-                },
-                Some(_) => bail!("A parameter crv must be a string."),
-                None => bail!("A parameter crv is required."),
-            };
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
+            let (private_key, key_type) = EcdhEsKeyType::private_key_from_jwk(jwk)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(EcdhEsJweDecrypter {
@@ -500,56 +583,21 @@ impl EcdhEsJweAlgorithm {
         Some(key_type)
     }
 
-    fn concat_kdf(
-        &self,
+    pub(crate) fn concat_kdf(
         alg: &str,
         shared_key_len: usize,
         derived_key: &[u8],
         apu: Option<&[u8]>,
         apv: Option<&[u8]>,
     ) -> anyhow::Result<Vec<u8>> {
-        let shared_key_len_bytes = ((shared_key_len * 8) as u32).to_be_bytes();
-        let alg_len_bytes = (alg.len() as u32).to_be_bytes();
-        let apu_len_bytes = (match apu {
-            Some(val) => val.len(),
-            None => 0,
-        } as u32)
-            .to_be_bytes();
-        let apv_len_bytes = (match apv {
-            Some(val) => val.len(),
-            None => 0,
-        } as u32)
-            .to_be_bytes();
-
-        let mut shared_key = Vec::new();
-        let md = MessageDigest::sha256();
-        let count = util::ceiling(shared_key_len, md.size());
-        for i in 0..count {
-            let mut hasher = Hasher::new(md)?;
-            hasher.update(&((i + 1) as u32).to_be_bytes())?;
-            hasher.update(&derived_key)?;
-            hasher.update(&alg_len_bytes)?;
-            hasher.update(alg.as_bytes())?;
-            hasher.update(&apu_len_bytes)?;
-            if let Some(val) = apu {
-                hasher.update(val)?;
-            }
-            hasher.update(&apv_len_bytes)?;
-            if let Some(val) = apv {
-                hasher.update(val)?;
-            }
-            hasher.update(&shared_key_len_bytes)?;
-
-            let digest = hasher.finish()?;
-            shared_key.extend(digest.to_vec());
-        }
-
-        if shared_key.len() > shared_key_len {
-            shared_key.truncate(shared_key_len);
-        } else if shared_key.len() < shared_key_len {
-            unreachable!();
-        }
-
+        let shared_key = concat_kdf::derive(
+            derived_key,
+            alg.as_bytes(),
+            apu.unwrap_or_default(),
+            apv.unwrap_or_default(),
+            shared_key_len,
+            SHA_256,
+        )?;
         Ok(shared_key)
     }
 }
@@ -658,57 +706,14 @@ impl EcdhEsJweEncrypter {
                 },
             };
 
-            let mut map = Map::new();
-            map.insert(
-                "kty".to_string(),
-                Value::String(self.key_type.key_type().to_string()),
-            );
-            map.insert(
-                "crv".to_string(),
-                Value::String(self.key_type.curve_name().to_string()),
-            );
-            let private_key = match self.key_type {
-                EcdhEsKeyType::Ec(curve) => {
-                    let key_pair = EcKeyPair::generate(curve)?;
-                    let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
-
-                    match jwk.remove("x") {
-                        Some(val) => {
-                            map.insert("x".to_string(), val);
-                        }
-                        None => unreachable!(),
-                    }
-                    match jwk.remove("y") {
-                        Some(val) => {
-                            map.insert("y".to_string(), val);
-                        }
-                        None => unreachable!(),
-                    }
-
-                    key_pair.into_private_key()
-                }
-                EcdhEsKeyType::Ecx(curve) => {
-                    let key_pair = EcxKeyPair::generate(curve)?;
-                    let mut jwk: Map<String, Value> = key_pair.to_jwk_public_key().into();
-
-                    match jwk.remove("x") {
-                        Some(val) => {
-                            map.insert("x".to_string(), val);
-                        }
-                        None => unreachable!(),
-                    }
-
-                    key_pair.into_private_key()
-                }
-            };
-
-            header.set_claim("epk", Some(Value::Object(map)))?;
+            let (private_key, epk) = self.key_type.generate_ephemeral_key()?;
+            header.set_claim("epk", Some(Value::Object(epk)))?;
 
             let mut deriver = Deriver::new(&private_key)?;
             deriver.set_peer(&self.public_key)?;
             let derived_key = deriver.derive_to_vec()?;
 
-            let shared_key = self.algorithm.concat_kdf(
+            let shared_key = EcdhEsJweAlgorithm::concat_kdf(
                 alg,
                 key_len,
                 &derived_key,
@@ -871,76 +876,7 @@ impl JweDecrypter for EcdhEsJweDecrypter {
                 None => None,
             };
 
-            let public_key = match header.claim("epk") {
-                Some(Value::Object(map)) => {
-                    match map.get("kty") {
-                        Some(Value::String(val)) => {
-                            if val != self.key_type.key_type() {
-                                bail!("The kty parameter in epk header claim is invalid: {}", val);
-                            }
-                        }
-                        Some(_) => bail!("The kty parameter in epk header claim must be a string."),
-                        None => bail!("The kty parameter in epk header claim is required."),
-                    }
-
-                    match map.get("crv") {
-                        Some(Value::String(val)) => {
-                            if val != self.key_type.curve_name() {
-                                bail!("The crv parameter in epk header claim is invalid: {}", val);
-                            }
-                        }
-                        Some(_) => bail!("The crv parameter in epk header claim must be a string."),
-                        None => bail!("The crv parameter in epk header claim is required."),
-                    }
-
-                    match &self.key_type {
-                        EcdhEsKeyType::Ec(curve) => {
-                            let x = match map.get("x") {
-                                Some(Value::String(val)) => {
-                                    base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-                            let y = match map.get("y") {
-                                Some(Value::String(val)) => {
-                                    base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-
-                            let mut vec = Vec::with_capacity(1 + x.len() + y.len());
-                            vec.push(0x04);
-                            vec.extend_from_slice(&x);
-                            vec.extend_from_slice(&y);
-
-                            let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, *curve);
-                            PKey::public_key_from_der(&pkcs8)?
-                        }
-                        EcdhEsKeyType::Ecx(curve) => {
-                            let x = match map.get("x") {
-                                Some(Value::String(val)) => {
-                                    base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                                }
-                                Some(_) => {
-                                    bail!("The x parameter in epk header claim must be a string.")
-                                }
-                                None => bail!("The x parameter in epk header claim is required."),
-                            };
-
-                            let pkcs8 = EcxKeyPair::to_pkcs8(&x, true, *curve);
-                            PKey::public_key_from_der(&pkcs8)?
-                        }
-                    }
-                }
-                Some(_) => bail!("The epk header claim must be object."),
-                None => bail!("This algorithm must have epk header claim."),
-            };
+            let public_key = self.key_type.ephemeral_public_key(header)?;
 
             let mut deriver = Deriver::new(&self.private_key)?;
             deriver.set_peer(&public_key)?;
@@ -948,7 +884,7 @@ impl JweDecrypter for EcdhEsJweDecrypter {
 
             // concat KDF
             if let EcdhEsJweAlgorithm::EcdhEs = self.algorithm {
-                let shared_key = self.algorithm.concat_kdf(
+                let shared_key = EcdhEsJweAlgorithm::concat_kdf(
                     cencryption.name(),
                     cencryption.key_len(),
                     &derived_key,
@@ -957,7 +893,7 @@ impl JweDecrypter for EcdhEsJweDecrypter {
                 )?;
                 Ok(Cow::Owned(shared_key))
             } else {
-                let shared_key = self.algorithm.concat_kdf(
+                let shared_key = EcdhEsJweAlgorithm::concat_kdf(
                     self.algorithm.name(),
                     self.algorithm.key_len(),
                     &derived_key,
@@ -1014,7 +950,7 @@ mod tests {
     use super::{EcdhEsJweAlgorithm, EcdhEsKeyType};
     use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
     use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
     use crate::jwk::alg::{ec::EcCurve, ecx::EcxCurve};
     use crate::jwk::Jwk;
     use crate::util;
@@ -1271,6 +1207,95 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decrypt_ecdh_es_with_agreement_party_info_test_vector() -> Result<()> {
+        // RFC 7518 Appendix C. Example ECDH-ES Key Agreement Computation
+        let enc = AesgcmJweEncryption::A128gcm;
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+
+        let private_key = Jwk::from_bytes(
+            br#"{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+                "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+                "d": "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"
+            }"#,
+        )?;
+        let header = JweHeader::from_bytes(
+            br#"{
+                "alg": "ECDH-ES",
+                "enc": "A128GCM",
+                "apu": "QWxpY2U",
+                "apv": "Qm9i",
+                "epk": {
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "x": "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+                    "y": "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps"
+                }
+            }"#,
+        )?;
+
+        let decrypter = alg.decrypter_from_jwk(&private_key)?;
+        let key = decrypter.decrypt(None, &enc, &header)?;
+        assert_eq!(
+            base64::encode_config(&key, base64::URL_SAFE_NO_PAD),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecdh_es_with_agreement_party_info() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+        let private_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_public.jwk")?)?;
+
+        for alg in vec![EcdhEsJweAlgorithm::EcdhEs, EcdhEsJweAlgorithm::EcdhEsA128kw] {
+            for from_header in vec![true, false] {
+                let mut header = JweHeader::new();
+                header.set_content_encryption(enc.name());
+
+                let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+                if from_header {
+                    header.set_agreement_partyuinfo(b"Alice");
+                    header.set_agreement_partyvinfo(b"Bob");
+                } else {
+                    encrypter.set_agreement_partyuinfo(b"Alice".to_vec());
+                    encrypter.set_agreement_partyvinfo(b"Bob".to_vec());
+                }
+
+                let mut out_header = header.clone();
+                let src_key = match encrypter.compute_content_encryption_key(
+                    &enc,
+                    &header,
+                    &mut out_header,
+                )? {
+                    Some(val) => val,
+                    None => Cow::Owned(util::random_bytes(enc.key_len())),
+                };
+                let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+                out_header.set_algorithm(alg.name());
+                assert_eq!(out_header.agreement_partyuinfo(), Some(b"Alice".to_vec()));
+                assert_eq!(out_header.agreement_partyvinfo(), Some(b"Bob".to_vec()));
+
+                let decrypter = alg.decrypter_from_jwk(&private_key)?;
+                let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+                assert_eq!(&src_key, &dst_key);
+
+                out_header.set_agreement_partyvinfo(b"Eve");
+                let result = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header);
+                if let Ok(dst_key) = result {
+                    assert_ne!(&src_key, &dst_key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
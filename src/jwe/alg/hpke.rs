@@ -0,0 +1,735 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::ops::Deref;
+
+use anyhow::bail;
+use openssl::bn::BigNumContext;
+use openssl::derive::Deriver;
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Private, Public};
+use openssl::symm::{self, Cipher};
+
+use crate::jose_error::JoseErrorDetail;
+use crate::jwe::alg::ecdh_es::EcdhEsKeyType;
+use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
+use crate::jwk::alg::{
+    ec::{EcCurve, EcKeyPair},
+    ecx::{EcxCurve, EcxKeyPair},
+};
+use crate::jwk::Jwk;
+use crate::util::{self, hkdf, SHA_256};
+use crate::{JoseError, JoseErrorKind, JoseHeader, Value};
+
+/// The prefix of the labels of HPKE key derivation (RFC 9180 Section 4).
+const HPKE_VERSION_LABEL: &[u8] = b"HPKE-v1";
+
+/// The KDF ID of HKDF-SHA256 (RFC 9180 Section 7.2).
+const KDF_HKDF_SHA256: u16 = 0x0001;
+
+/// The AEAD ID of AES-128-GCM (RFC 9180 Section 7.3).
+const AEAD_AES_128_GCM: u16 = 0x0001;
+
+/// The length of the KEM shared secret of DHKEM with HKDF-SHA256.
+const SHARED_SECRET_LEN: usize = 32;
+
+/// The key length of AES-128-GCM.
+const AEAD_KEY_LEN: usize = 16;
+
+/// The nonce length of AES-128-GCM.
+const AEAD_NONCE_LEN: usize = 12;
+
+/// The tag length of AES-128-GCM.
+const AEAD_TAG_LEN: usize = 16;
+
+/// HPKE key encryption algorithms of the JOSE HPKE draft (draft-ietf-jose-hpke-encrypt).
+///
+/// The content encryption key is encrypted to the recipient public key with the single-shot
+/// Base mode of HPKE (RFC 9180). The encapsulated key is carried in the "ek" header claim
+/// and the HPKE ciphertext is the JWE encrypted key. The HPKE info and aad inputs are empty.
+///
+/// The algorithm names follow the "Key Encryption" section of the draft and may change
+/// before it's published. These algorithms are available only when the `hpke` feature
+/// is enabled.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum HpkeJweAlgorithm {
+    /// HPKE using DHKEM(P-256, HKDF-SHA256), HKDF-SHA256 and AES-128-GCM
+    Hpke0,
+    /// HPKE using DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 and AES-128-GCM
+    Hpke3,
+}
+
+impl HpkeJweAlgorithm {
+    pub fn encrypter_from_der(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HpkeJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweEncrypter> {
+            let public_key = match PKey::public_key_from_der(input.as_ref()) {
+                Ok(val) => val,
+                Err(_) => bail!("The public key must be wrapped by SubjectPublicKeyInfo."),
+            };
+            self.check_key(&public_key)?;
+
+            Ok(HpkeJweEncrypter {
+                algorithm: self.clone(),
+                public_key,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    pub fn encrypter_from_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HpkeJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweEncrypter> {
+            let (alg, data) = util::parse_pem(input.as_ref())?;
+            match alg.as_str() {
+                "PUBLIC KEY" => {}
+                alg => bail!("Inappropriate algorithm: {}", alg),
+            }
+
+            Ok(self.encrypter_from_der(&data)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    pub fn encrypter_from_jwk(&self, jwk: &Jwk) -> Result<HpkeJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweEncrypter> {
+            match jwk.key_type() {
+                val if val == self.key_type().key_type() => {}
+                val => bail!(
+                    "A parameter kty must be {}: {}",
+                    self.key_type().key_type(),
+                    val
+                ),
+            }
+            jwk.check_key_usage("enc", "encrypt", self.name())?;
+            let (public_key, key_type) = EcdhEsKeyType::public_key_from_jwk(jwk)?;
+            if key_type != self.key_type() {
+                bail!("The key type must be {}: {}", self.key_type(), key_type);
+            }
+            let key_id = jwk.key_id().map(|val| val.to_string());
+
+            Ok(HpkeJweEncrypter {
+                algorithm: self.clone(),
+                public_key,
+                key_id,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    pub fn decrypter_from_der(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HpkeJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweDecrypter> {
+            let private_key = match PKey::private_key_from_pkcs8(input.as_ref()) {
+                Ok(val) => val,
+                Err(_) => bail!("The private key must be wrapped by PKCS#8 PrivateKeyInfo."),
+            };
+            self.check_key(&private_key)?;
+
+            Ok(HpkeJweDecrypter {
+                algorithm: self.clone(),
+                private_key,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    pub fn decrypter_from_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HpkeJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweDecrypter> {
+            let (alg, data) = util::parse_pem(input.as_ref())?;
+            match alg.as_str() {
+                "PRIVATE KEY" => {}
+                alg => bail!("Inappropriate algorithm: {}", alg),
+            }
+
+            Ok(self.decrypter_from_der(&data)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<HpkeJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<HpkeJweDecrypter> {
+            match jwk.key_type() {
+                val if val == self.key_type().key_type() => {}
+                val => bail!(
+                    "A parameter kty must be {}: {}",
+                    self.key_type().key_type(),
+                    val
+                ),
+            }
+            jwk.check_key_usage("enc", "decrypt", self.name())?;
+            let (private_key, key_type) = EcdhEsKeyType::private_key_from_jwk(jwk)?;
+            if key_type != self.key_type() {
+                bail!("The key type must be {}: {}", self.key_type(), key_type);
+            }
+            let key_id = jwk.key_id().map(|val| val.to_string());
+
+            Ok(HpkeJweDecrypter {
+                algorithm: self.clone(),
+                private_key,
+                key_id,
+            })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn key_type(&self) -> EcdhEsKeyType {
+        match self {
+            Self::Hpke0 => EcdhEsKeyType::Ec(EcCurve::P256),
+            Self::Hpke3 => EcdhEsKeyType::Ecx(EcxCurve::X25519),
+        }
+    }
+
+    fn kem_id(&self) -> u16 {
+        match self {
+            Self::Hpke0 => 0x0010,
+            Self::Hpke3 => 0x0020,
+        }
+    }
+
+    fn check_key<T: HasPublic>(&self, key: &PKeyRef<T>) -> anyhow::Result<()> {
+        let is_valid = match self {
+            Self::Hpke0 => {
+                key.id() == Id::EC
+                    && key.ec_key()?.group().curve_name() == Some(Nid::X9_62_PRIME256V1)
+            }
+            Self::Hpke3 => key.id() == Id::X25519,
+        };
+        if !is_valid {
+            bail!("The key type must be {}.", self.key_type());
+        }
+        Ok(())
+    }
+
+    /// Return the serialized public key (RFC 9180 Section 7.1.1).
+    fn serialize_public_key<T: HasPublic>(&self, key: &PKeyRef<T>) -> anyhow::Result<Vec<u8>> {
+        let vec = match self {
+            Self::Hpke0 => {
+                let ec_key = key.ec_key()?;
+                let mut ctx = BigNumContext::new()?;
+                ec_key.public_key().to_bytes(
+                    ec_key.group(),
+                    PointConversionForm::UNCOMPRESSED,
+                    &mut ctx,
+                )?
+            }
+            Self::Hpke3 => key.raw_public_key()?,
+        };
+        Ok(vec)
+    }
+
+    /// Return the public key of a serialized public key (RFC 9180 Section 7.1.1).
+    fn deserialize_public_key(&self, input: &[u8]) -> anyhow::Result<PKey<Public>> {
+        let public_key = match self {
+            Self::Hpke0 => {
+                if input.len() != 65 || input[0] != 0x04 {
+                    bail!("The encapsulated key must be a uncompressed P-256 point.");
+                }
+                let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+                let mut ctx = BigNumContext::new()?;
+                let point = EcPoint::from_bytes(&group, input, &mut ctx)?;
+                let ec_key = EcKey::from_public_key(&group, &point)?;
+                ec_key.check_key()?;
+                PKey::from_ec_key(ec_key)?
+            }
+            Self::Hpke3 => {
+                if input.len() != 32 {
+                    bail!("The encapsulated key must be 32 bytes: {}", input.len());
+                }
+                PKey::public_key_from_raw_bytes(input, Id::X25519)?
+            }
+        };
+        Ok(public_key)
+    }
+
+    fn generate_ephemeral_key(&self) -> anyhow::Result<PKey<Private>> {
+        let private_key = match self {
+            Self::Hpke0 => EcKeyPair::generate(EcCurve::P256)?.into_private_key(),
+            Self::Hpke3 => EcxKeyPair::generate(EcxCurve::X25519)?.into_private_key(),
+        };
+        Ok(private_key)
+    }
+
+    fn labeled_extract(
+        suite_id: &[u8],
+        salt: &[u8],
+        label: &[u8],
+        ikm: &[u8],
+    ) -> Result<Vec<u8>, JoseError> {
+        let mut labeled_ikm = Vec::new();
+        labeled_ikm.extend_from_slice(HPKE_VERSION_LABEL);
+        labeled_ikm.extend_from_slice(suite_id);
+        labeled_ikm.extend_from_slice(label);
+        labeled_ikm.extend_from_slice(ikm);
+        hkdf::extract(salt, &labeled_ikm, SHA_256)
+    }
+
+    fn labeled_expand(
+        suite_id: &[u8],
+        prk: &[u8],
+        label: &[u8],
+        info: &[u8],
+        len: usize,
+    ) -> Result<Vec<u8>, JoseError> {
+        let mut labeled_info = Vec::new();
+        labeled_info.extend_from_slice(&(len as u16).to_be_bytes());
+        labeled_info.extend_from_slice(HPKE_VERSION_LABEL);
+        labeled_info.extend_from_slice(suite_id);
+        labeled_info.extend_from_slice(label);
+        labeled_info.extend_from_slice(info);
+        hkdf::expand(prk, &labeled_info, len, SHA_256)
+    }
+
+    /// Compute the KEM shared secret from the Diffie-Hellman result (RFC 9180 Section 4.1).
+    fn extract_and_expand(
+        &self,
+        dh: &[u8],
+        enc: &[u8],
+        public_key: &[u8],
+    ) -> Result<Vec<u8>, JoseError> {
+        let mut suite_id = b"KEM".to_vec();
+        suite_id.extend_from_slice(&self.kem_id().to_be_bytes());
+
+        let mut kem_context = enc.to_vec();
+        kem_context.extend_from_slice(public_key);
+
+        let eae_prk = Self::labeled_extract(&suite_id, b"", b"eae_prk", dh)?;
+        Self::labeled_expand(
+            &suite_id,
+            &eae_prk,
+            b"shared_secret",
+            &kem_context,
+            SHARED_SECRET_LEN,
+        )
+    }
+
+    /// Compute the AEAD key and the nonce of Base mode with a empty info (RFC 9180 Section 5.1).
+    fn key_schedule(&self, shared_secret: &[u8]) -> Result<(Vec<u8>, Vec<u8>), JoseError> {
+        let mut suite_id = b"HPKE".to_vec();
+        suite_id.extend_from_slice(&self.kem_id().to_be_bytes());
+        suite_id.extend_from_slice(&KDF_HKDF_SHA256.to_be_bytes());
+        suite_id.extend_from_slice(&AEAD_AES_128_GCM.to_be_bytes());
+
+        let psk_id_hash = Self::labeled_extract(&suite_id, b"", b"psk_id_hash", b"")?;
+        let info_hash = Self::labeled_extract(&suite_id, b"", b"info_hash", b"")?;
+        let mut key_schedule_context = vec![0x00];
+        key_schedule_context.extend_from_slice(&psk_id_hash);
+        key_schedule_context.extend_from_slice(&info_hash);
+
+        let secret = Self::labeled_extract(&suite_id, shared_secret, b"secret", b"")?;
+        let key = Self::labeled_expand(
+            &suite_id,
+            &secret,
+            b"key",
+            &key_schedule_context,
+            AEAD_KEY_LEN,
+        )?;
+        let base_nonce = Self::labeled_expand(
+            &suite_id,
+            &secret,
+            b"base_nonce",
+            &key_schedule_context,
+            AEAD_NONCE_LEN,
+        )?;
+        Ok((key, base_nonce))
+    }
+}
+
+impl JweAlgorithm for HpkeJweAlgorithm {
+    fn name(&self) -> &str {
+        match self {
+            Self::Hpke0 => "HPKE-0",
+            Self::Hpke3 => "HPKE-3",
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JweAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for HpkeJweAlgorithm {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for HpkeJweAlgorithm {
+    type Target = dyn JweAlgorithm;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HpkeJweEncrypter {
+    algorithm: HpkeJweAlgorithm,
+    public_key: PKey<Public>,
+    key_id: Option<String>,
+}
+
+impl HpkeJweEncrypter {
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweEncrypter for HpkeJweEncrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn compute_content_encryption_key(
+        &self,
+        _cencryption: &dyn JweContentEncryption,
+        _merged: &JweHeader,
+        _header: &mut JweHeader,
+    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+        Ok(None)
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        _merged: &JweHeader,
+        header: &mut JweHeader,
+    ) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
+            let private_key = self.algorithm.generate_ephemeral_key()?;
+
+            let mut deriver = Deriver::new(&private_key)?;
+            deriver.set_peer(&self.public_key)?;
+            let dh = deriver.derive_to_vec()?;
+
+            let enc = self.algorithm.serialize_public_key(&private_key)?;
+            let public_key = self.algorithm.serialize_public_key(&self.public_key)?;
+            let shared_secret = self.algorithm.extract_and_expand(&dh, &enc, &public_key)?;
+            let (aead_key, base_nonce) = self.algorithm.key_schedule(&shared_secret)?;
+
+            let mut tag = [0; AEAD_TAG_LEN];
+            let mut encrypted_key = symm::encrypt_aead(
+                Cipher::aes_128_gcm(),
+                &aead_key,
+                Some(&base_nonce),
+                b"",
+                key,
+                &mut tag,
+            )?;
+            encrypted_key.extend_from_slice(&tag);
+
+            let enc_b64 = base64::encode_config(&enc, base64::URL_SAFE_NO_PAD);
+            header.set_claim("ek", Some(Value::String(enc_b64)))?;
+
+            Ok(Some(encrypted_key))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn JweEncrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for HpkeJweEncrypter {
+    type Target = dyn JweEncrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HpkeJweDecrypter {
+    algorithm: HpkeJweAlgorithm,
+    private_key: PKey<Private>,
+    key_id: Option<String>,
+}
+
+impl HpkeJweDecrypter {
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweDecrypter for HpkeJweDecrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        _cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+    ) -> Result<Cow<[u8]>, JoseError> {
+        (|| -> anyhow::Result<Cow<[u8]>> {
+            let encrypted_key = match encrypted_key {
+                Some(val) => val,
+                None => bail!("A encrypted_key is required."),
+            };
+            if encrypted_key.len() < AEAD_TAG_LEN {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidTagLength,
+                    format!(
+                        "The length of encrypted_key must be {} or more: {}",
+                        AEAD_TAG_LEN,
+                        encrypted_key.len()
+                    )
+                ));
+            }
+
+            let enc = match header.claim("ek") {
+                Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+                Some(_) => bail!("The ek header claim must be string."),
+                None => bail!("This algorithm must have ek header claim."),
+            };
+            let ephemeral_public_key = self.algorithm.deserialize_public_key(&enc)?;
+
+            let mut deriver = Deriver::new(&self.private_key)?;
+            deriver.set_peer(&ephemeral_public_key)?;
+            let dh = deriver.derive_to_vec()?;
+
+            let public_key = self.algorithm.serialize_public_key(&self.private_key)?;
+            let shared_secret = self.algorithm.extract_and_expand(&dh, &enc, &public_key)?;
+            let (aead_key, base_nonce) = self.algorithm.key_schedule(&shared_secret)?;
+
+            let (ciphertext, tag) = encrypted_key.split_at(encrypted_key.len() - AEAD_TAG_LEN);
+            let key = match symm::decrypt_aead(
+                Cipher::aes_128_gcm(),
+                &aead_key,
+                Some(&base_nonce),
+                b"",
+                ciphertext,
+                tag,
+            ) {
+                Ok(val) => val,
+                Err(_) => bail!("Failed to decrypt the encrypted_key."),
+            };
+
+            Ok(Cow::Owned(key))
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
+
+    fn box_clone(&self) -> Box<dyn JweDecrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for HpkeJweDecrypter {
+    type Target = dyn JweDecrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::HpkeJweAlgorithm;
+    use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
+    use crate::jwe::{
+        self, JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader,
+    };
+    use crate::jwk::Jwk;
+    use crate::util;
+    use crate::JoseError;
+
+    #[test]
+    fn encrypt_and_decrypt_hpke_with_jwk() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+
+        for alg in vec![HpkeJweAlgorithm::Hpke0, HpkeJweAlgorithm::Hpke3] {
+            let (private_key, public_key) = match alg {
+                HpkeJweAlgorithm::Hpke0 => ("jwk/EC_P-256_private.jwk", "jwk/EC_P-256_public.jwk"),
+                HpkeJweAlgorithm::Hpke3 => {
+                    ("jwk/OKP_X25519_private.jwk", "jwk/OKP_X25519_public.jwk")
+                }
+            };
+            let private_key = Jwk::from_bytes(&load_file(private_key)?)?;
+            let public_key = Jwk::from_bytes(&load_file(public_key)?)?;
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_jwk(&public_key)?;
+            let mut out_header = header.clone();
+            assert!(encrypter
+                .compute_content_encryption_key(&enc, &header, &mut out_header)?
+                .is_none());
+            let src_key = util::random_bytes(enc.key_len());
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+            assert!(out_header.claim("ek").is_some());
+
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+
+            assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_hpke_with_pem() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+
+        for alg in vec![HpkeJweAlgorithm::Hpke0, HpkeJweAlgorithm::Hpke3] {
+            let (private_key, public_key) = match alg {
+                HpkeJweAlgorithm::Hpke0 => ("pem/EC_P-256_private.pem", "pem/EC_P-256_public.pem"),
+                HpkeJweAlgorithm::Hpke3 => ("pem/X25519_private.pem", "pem/X25519_public.pem"),
+            };
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_pem(&load_file(public_key)?)?;
+            let mut out_header = header.clone();
+            let src_key = util::random_bytes(enc.key_len());
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+
+            let decrypter = alg.decrypter_from_pem(&load_file(private_key)?)?;
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+
+            assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_hpke_interoperability_vectors() -> Result<()> {
+        // These are self-generated vectors, not test vectors from the draft.
+        // They were produced by the HPKE implementation of pyca/cryptography with the
+        // CEK 000102...0f encrypted to the test keys, and A128GCM content encryption.
+        // The script is in data/memo.md.
+        for (alg, private_key, input) in vec![
+            (
+                HpkeJweAlgorithm::Hpke0,
+                "jwk/EC_P-256_private.jwk",
+                "eyJhbGciOiJIUEtFLTAiLCJlbmMiOiJBMTI4R0NNIiwiZWsiOiJCS012QzZoN0dLMHVFUGxzb0NkSXBLQlo0dHFtRGhyZ3F2N0RRc3BsNlRyR09EcC16RjJ5YVQxVFJxTnU2d2RDN3JRQTY5WkJTVDk3T1JsSnliZmROMEUifQ.Iv5hWkBvUJ5JdNbj4TI6-5qOv6KYfn0mayoqeuSpNr0.ZGVmZ2hpamtsbW5v.UjIF4Pt23YnvHidx_6ZEjVQ.tHkSxRlxgo0l53suJA9JKA",
+            ),
+            (
+                HpkeJweAlgorithm::Hpke3,
+                "jwk/OKP_X25519_private.jwk",
+                "eyJhbGciOiJIUEtFLTMiLCJlbmMiOiJBMTI4R0NNIiwiZWsiOiI4OFkyZWtqSXhqZ0J0djhhckNZb2dKNXJjZ1ZkY2JBVDdkZVRXZjJaZUZJIn0.eu22WAi8KIymdVDoeenuevkgd6WwgvMEmH9iWQN3kmI.ZGVmZ2hpamtsbW5v.UjIF4Pt23YnvHidx_6ZEjVQ.jQYPwiydcT3dHG4rYwLhLg",
+            ),
+        ] {
+            let private_key = Jwk::from_bytes(&load_file(private_key)?)?;
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+
+            let (payload, header) = jwe::deserialize_compact(input, &decrypter)?;
+            assert_eq!(header.algorithm(), Some(alg.name()));
+            assert_eq!(payload, b"HPKE test payload");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_hpke_with_invalid_input() -> Result<()> {
+        let enc = AesgcmJweEncryption::A128gcm;
+        let alg = HpkeJweAlgorithm::Hpke3;
+
+        let private_key = Jwk::from_bytes(&load_file("jwk/OKP_X25519_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/OKP_X25519_public.jwk")?)?;
+        let encrypter = alg.encrypter_from_jwk(&public_key)?;
+        let decrypter = alg.decrypter_from_jwk(&private_key)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        let mut out_header = header.clone();
+        let src_key = util::random_bytes(enc.key_len());
+        let encrypted_key = encrypter
+            .encrypt(&src_key, &header, &mut out_header)?
+            .unwrap();
+
+        let mut tampered_key = encrypted_key.clone();
+        tampered_key[0] ^= 0x01;
+        assert!(decrypter
+            .decrypt(Some(&tampered_key), &enc, &out_header)
+            .is_err());
+        assert!(decrypter
+            .decrypt(Some(&encrypted_key[..8]), &enc, &out_header)
+            .is_err());
+        assert!(decrypter
+            .decrypt(Some(&encrypted_key), &enc, &header)
+            .is_err());
+
+        let ec_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_public.jwk")?)?;
+        assert!(matches!(
+            alg.encrypter_from_jwk(&ec_key),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
@@ -0,0 +1,561 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::ops::Deref;
+
+use anyhow::bail;
+use openssl::pkcs5;
+
+use crate::jwe::alg::aesgcmkw::AesgcmkwJweAlgorithm;
+use crate::jwe::{JweAlgorithm, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader};
+use crate::jwk::Jwk;
+use crate::util::{self, HashAlgorithm};
+use crate::{JoseError, JoseHeader, Number, Value};
+
+const DEFAULT_MAX_ITER_COUNT: usize = 1000000;
+
+/// PBES2 key derivation followed by AES GCM key wrapping (non-standard).
+///
+/// The key derived by PBKDF2 wraps the CEK as A128GCMKW, A192GCMKW or A256GCMKW does,
+/// so the header carries the iv and tag header claims besides p2s and p2c.
+/// The alg names are prefixed with "josekit:" because they are not registered.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Pbes2HmacAesgcmkwJweAlgorithm {
+    /// PBES2 with HMAC SHA-256 and "A128GCMKW" wrapping
+    Pbes2Hs256A128gcmkw,
+    /// PBES2 with HMAC SHA-384 and "A192GCMKW" wrapping
+    Pbes2Hs384A192gcmkw,
+    /// PBES2 with HMAC SHA-512 and "A256GCMKW" wrapping
+    Pbes2Hs512A256gcmkw,
+}
+
+impl Pbes2HmacAesgcmkwJweAlgorithm {
+    pub fn encrypter_from_bytes(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<Pbes2HmacAesgcmkwJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<Pbes2HmacAesgcmkwJweEncrypter> {
+            let private_key = input.as_ref().to_vec();
+
+            if private_key.len() == 0 {
+                bail!("The key size must not be empty.");
+            }
+
+            Ok(Pbes2HmacAesgcmkwJweEncrypter {
+                algorithm: self.clone(),
+                private_key,
+                salt_len: 8,
+                iter_count: 1000,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    pub fn encrypter_from_jwk(
+        &self,
+        jwk: &Jwk,
+    ) -> Result<Pbes2HmacAesgcmkwJweEncrypter, JoseError> {
+        (|| -> anyhow::Result<Pbes2HmacAesgcmkwJweEncrypter> {
+            match jwk.key_type() {
+                val if val == "oct" => {}
+                val => bail!("A parameter kty must be oct: {}", val),
+            }
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
+            let k = match jwk.parameter("k") {
+                Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+                Some(val) => bail!("A parameter k must be string type but {:?}", val),
+                None => bail!("A parameter k is required."),
+            };
+
+            if k.len() == 0 {
+                bail!("The key size must not be empty.");
+            }
+
+            let key_id = jwk.key_id().map(|val| val.to_string());
+
+            Ok(Pbes2HmacAesgcmkwJweEncrypter {
+                algorithm: self.clone(),
+                private_key: k,
+                salt_len: 8,
+                iter_count: 1000,
+                key_id,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    pub fn decrypter_from_bytes(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<Pbes2HmacAesgcmkwJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<Pbes2HmacAesgcmkwJweDecrypter> {
+            let private_key = input.as_ref().to_vec();
+
+            if private_key.len() == 0 {
+                bail!("The key size must not be empty.");
+            }
+
+            Ok(Pbes2HmacAesgcmkwJweDecrypter {
+                algorithm: self.clone(),
+                private_key,
+                max_iter_count: Some(DEFAULT_MAX_ITER_COUNT),
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    pub fn decrypter_from_jwk(
+        &self,
+        jwk: &Jwk,
+    ) -> Result<Pbes2HmacAesgcmkwJweDecrypter, JoseError> {
+        (|| -> anyhow::Result<Pbes2HmacAesgcmkwJweDecrypter> {
+            match jwk.key_type() {
+                val if val == "oct" => {}
+                val => bail!("A parameter kty must be oct: {}", val),
+            }
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
+
+            let k = match jwk.parameter("k") {
+                Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+                Some(val) => bail!("A parameter k must be string type but {:?}", val),
+                None => bail!("A parameter k is required."),
+            };
+
+            if k.len() == 0 {
+                bail!("The key size must not be empty.");
+            }
+
+            let key_id = jwk.key_id().map(|val| val.to_string());
+
+            Ok(Pbes2HmacAesgcmkwJweDecrypter {
+                algorithm: self.clone(),
+                private_key: k,
+                max_iter_count: Some(DEFAULT_MAX_ITER_COUNT),
+                key_id,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        match self {
+            Self::Pbes2Hs256A128gcmkw => HashAlgorithm::Sha256,
+            Self::Pbes2Hs384A192gcmkw => HashAlgorithm::Sha384,
+            Self::Pbes2Hs512A256gcmkw => HashAlgorithm::Sha512,
+        }
+    }
+
+    fn derived_key_len(&self) -> usize {
+        match self {
+            Self::Pbes2Hs256A128gcmkw => 16,
+            Self::Pbes2Hs384A192gcmkw => 24,
+            Self::Pbes2Hs512A256gcmkw => 32,
+        }
+    }
+
+    fn key_wrapping(&self) -> AesgcmkwJweAlgorithm {
+        match self {
+            Self::Pbes2Hs256A128gcmkw => AesgcmkwJweAlgorithm::A128gcmkw,
+            Self::Pbes2Hs384A192gcmkw => AesgcmkwJweAlgorithm::A192gcmkw,
+            Self::Pbes2Hs512A256gcmkw => AesgcmkwJweAlgorithm::A256gcmkw,
+        }
+    }
+}
+
+impl JweAlgorithm for Pbes2HmacAesgcmkwJweAlgorithm {
+    fn name(&self) -> &str {
+        match self {
+            Self::Pbes2Hs256A128gcmkw => "josekit:PBES2-HS256+A128GCMKW",
+            Self::Pbes2Hs384A192gcmkw => "josekit:PBES2-HS384+A192GCMKW",
+            Self::Pbes2Hs512A256gcmkw => "josekit:PBES2-HS512+A256GCMKW",
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JweAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for Pbes2HmacAesgcmkwJweAlgorithm {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for Pbes2HmacAesgcmkwJweAlgorithm {
+    type Target = dyn JweAlgorithm;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct Pbes2HmacAesgcmkwJweEncrypter {
+    algorithm: Pbes2HmacAesgcmkwJweAlgorithm,
+    private_key: Vec<u8>,
+    salt_len: usize,
+    iter_count: usize,
+    key_id: Option<String>,
+}
+
+impl Debug for Pbes2HmacAesgcmkwJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("Pbes2HmacAesgcmkwJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("salt_len", &self.salt_len)
+            .field("iter_count", &self.iter_count)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl Pbes2HmacAesgcmkwJweEncrypter {
+    /// Set the length of a random salt that is used as p2s header claim. The default is 8.
+    ///
+    /// # Arguments
+    ///
+    /// * `salt_len` - a salt length (8 or more)
+    pub fn set_salt_len(&mut self, salt_len: usize) {
+        if salt_len < 8 {
+            panic!("salt_len must be 8 or more: {}", salt_len);
+        }
+        self.salt_len = salt_len;
+    }
+
+    /// Set the PBKDF2 iteration count that is used as p2c header claim. The default is 1000.
+    ///
+    /// If the header for encryption has a p2c header claim, it takes precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter_count` - a iteration count (1000 or more)
+    pub fn set_iter_count(&mut self, iter_count: usize) {
+        if iter_count < 1000 {
+            panic!("iter_count must be 1000 or more: {}", iter_count);
+        }
+        self.iter_count = iter_count;
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweEncrypter for Pbes2HmacAesgcmkwJweEncrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn compute_content_encryption_key(
+        &self,
+        _cencryption: &dyn JweContentEncryption,
+        _in_header: &JweHeader,
+        _out_header: &mut JweHeader,
+    ) -> Result<Option<Cow<[u8]>>, JoseError> {
+        Ok(None)
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        in_header: &JweHeader,
+        out_header: &mut JweHeader,
+    ) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
+            let p2s = match in_header.claim("p2s") {
+                Some(Value::String(val)) => {
+                    let p2s = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    if p2s.len() < 8 {
+                        bail!("The decoded value of p2s header claim must be 8 or more.");
+                    }
+                    p2s
+                }
+                Some(_) => bail!("The p2s header claim must be string."),
+                None => {
+                    let p2s = util::random_bytes(self.salt_len);
+                    let p2s_b64 = base64::encode_config(&p2s, base64::URL_SAFE_NO_PAD);
+                    out_header.set_claim("p2s", Some(Value::String(p2s_b64)))?;
+                    p2s
+                }
+            };
+            let p2c = match in_header.claim("p2c") {
+                Some(Value::Number(val)) => match val.as_u64() {
+                    Some(val) => usize::try_from(val)?,
+                    None => bail!("Overflow u64 value: {}", val),
+                },
+                Some(_) => bail!("The p2c header claim must be a positive number."),
+                None => {
+                    let p2c = self.iter_count;
+                    out_header.set_claim("p2c", Some(Value::Number(Number::from(p2c))))?;
+                    p2c
+                }
+            };
+
+            let mut salt = Vec::with_capacity(self.algorithm().name().len() + 1 + p2s.len());
+            salt.extend_from_slice(self.algorithm().name().as_bytes());
+            salt.push(0);
+            salt.extend_from_slice(&p2s);
+
+            let md = self.algorithm.hash_algorithm().message_digest();
+            let mut derived_key = vec![0; self.algorithm.derived_key_len()];
+            pkcs5::pbkdf2_hmac(&self.private_key, &salt, p2c, md, &mut derived_key)?;
+
+            let encrypter = self
+                .algorithm
+                .key_wrapping()
+                .encrypter_from_bytes(&derived_key)?;
+            let encrypted_key = encrypter.encrypt(key, in_header, out_header)?;
+
+            Ok(encrypted_key)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn JweEncrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for Pbes2HmacAesgcmkwJweEncrypter {
+    type Target = dyn JweEncrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct Pbes2HmacAesgcmkwJweDecrypter {
+    algorithm: Pbes2HmacAesgcmkwJweAlgorithm,
+    private_key: Vec<u8>,
+    max_iter_count: Option<usize>,
+    key_id: Option<String>,
+}
+
+impl Debug for Pbes2HmacAesgcmkwJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("Pbes2HmacAesgcmkwJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("max_iter_count", &self.max_iter_count)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl Pbes2HmacAesgcmkwJweDecrypter {
+    /// Set the maximum PBKDF2 iteration count that is accepted as p2c header claim.
+    /// The default is 1000000.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iter_count` - a maximum iteration count
+    pub fn set_max_iter_count(&mut self, max_iter_count: usize) {
+        self.max_iter_count = Some(max_iter_count);
+    }
+
+    /// Accept any PBKDF2 iteration count as p2c header claim.
+    pub fn remove_max_iter_count(&mut self) {
+        self.max_iter_count = None;
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JweDecrypter for Pbes2HmacAesgcmkwJweDecrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_key: Option<&[u8]>,
+        cencryption: &dyn JweContentEncryption,
+        header: &JweHeader,
+    ) -> Result<Cow<[u8]>, JoseError> {
+        (|| -> anyhow::Result<Cow<[u8]>> {
+            let encrypted_key = match encrypted_key {
+                Some(val) => val,
+                None => bail!("A encrypted_key value is required."),
+            };
+
+            let p2s = match header.claim("p2s") {
+                Some(Value::String(val)) => {
+                    let p2s = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                    if p2s.len() < 8 {
+                        bail!("The decoded value of p2s header claim must be 8 or more.");
+                    }
+                    p2s
+                }
+                Some(_) => bail!("The p2s header claim must be string."),
+                None => bail!("The p2s header claim is required."),
+            };
+            let p2c = match header.claim("p2c") {
+                Some(Value::Number(val)) => match val.as_u64() {
+                    Some(val) => usize::try_from(val)?,
+                    None => bail!("Overflow u64 value: {}", val),
+                },
+                Some(_) => bail!("The p2c header claim must be a positive number."),
+                None => bail!("The p2c header claim is required."),
+            };
+            if p2c == 0 {
+                bail!("The p2c header claim must be a positive number.");
+            }
+            if let Some(max_iter_count) = self.max_iter_count {
+                if p2c > max_iter_count {
+                    bail!(
+                        "The p2c header claim must be {} or less: {}",
+                        max_iter_count,
+                        p2c
+                    );
+                }
+            }
+
+            let mut salt = Vec::with_capacity(self.algorithm().name().len() + 1 + p2s.len());
+            salt.extend_from_slice(self.algorithm().name().as_bytes());
+            salt.push(0);
+            salt.extend_from_slice(&p2s);
+
+            let md = self.algorithm.hash_algorithm().message_digest();
+            let mut derived_key = vec![0; self.algorithm.derived_key_len()];
+            pkcs5::pbkdf2_hmac(&self.private_key, &salt, p2c, md, &mut derived_key)?;
+
+            let decrypter = self
+                .algorithm
+                .key_wrapping()
+                .decrypter_from_bytes(&derived_key)?;
+            let key = decrypter.decrypt(Some(encrypted_key), cencryption, header)?;
+
+            Ok(Cow::Owned(key.into_owned()))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn JweDecrypter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Deref for Pbes2HmacAesgcmkwJweDecrypter {
+    type Target = dyn JweDecrypter;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use base64;
+    use serde_json::json;
+
+    use super::Pbes2HmacAesgcmkwJweAlgorithm;
+    use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
+    use crate::jwe::{self, JweHeader};
+    use crate::jwk::Jwk;
+    use crate::util;
+
+    #[test]
+    fn encrypt_and_decrypt_pbes2_hmac_aesgcmkw() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+
+        for alg in vec![
+            Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs256A128gcmkw,
+            Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs384A192gcmkw,
+            Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs512A256gcmkw,
+        ] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let jwk = {
+                let key = util::random_bytes(8);
+                let key = base64::encode_config(&key, base64::URL_SAFE_NO_PAD);
+
+                let mut jwk = Jwk::new("oct");
+                jwk.set_key_use("enc");
+                jwk.set_parameter("k", Some(json!(key)))?;
+                jwk
+            };
+
+            let encrypter = alg.encrypter_from_jwk(&jwk)?;
+            let mut out_header = header.clone();
+            let src_key = util::random_bytes(enc.key_len());
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+            for name in vec!["p2s", "p2c", "iv", "tag"] {
+                assert!(out_header.claim(name).is_some());
+            }
+
+            let decrypter = alg.decrypter_from_jwk(&jwk)?;
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+            assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+
+            let mut tampered_header = out_header.clone();
+            tampered_header.set_claim("tag", Some(json!("AAAAAAAAAAAAAAAAAAAAAA")))?;
+            assert!(decrypter
+                .decrypt(encrypted_key.as_deref(), &enc, &tampered_header)
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_jwe_with_pbes2_hmac_aesgcmkw() -> Result<()> {
+        let alg = Pbes2HmacAesgcmkwJweAlgorithm::Pbes2Hs256A128gcmkw;
+        let password = b"correct horse battery staple";
+
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        let src_payload = b"test payload!";
+        let encrypter = alg.encrypter_from_bytes(password)?;
+        let jwe = jwe::serialize_compact(src_payload, &src_header, &encrypter)?;
+
+        let decrypter = alg.decrypter_from_bytes(password)?;
+        let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(
+            dst_header.algorithm(),
+            Some("josekit:PBES2-HS256+A128GCMKW")
+        );
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let decrypter = alg.decrypter_from_bytes(b"wrong password")?;
+        assert!(jwe::deserialize_compact(&jwe, &decrypter).is_err());
+
+        Ok(())
+    }
+}
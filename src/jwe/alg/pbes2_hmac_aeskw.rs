@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 use std::convert::TryFrom;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
 use anyhow::bail;
@@ -12,6 +12,8 @@ use crate::jwk::Jwk;
 use crate::util::{self, HashAlgorithm};
 use crate::{JoseError, JoseHeader, Number, Value};
 
+const DEFAULT_MAX_ITER_COUNT: usize = 1000000;
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Pbes2HmacAeskwJweAlgorithm {
     /// PBES2 with HMAC SHA-256 and "A128KW" wrapping
@@ -51,19 +53,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(val) => bail!("A parameter k must be string type but {:?}", val),
@@ -101,6 +91,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
             Ok(Pbes2HmacAeskwJweDecrypter {
                 algorithm: self.clone(),
                 private_key,
+                max_iter_count: Some(DEFAULT_MAX_ITER_COUNT),
                 key_id: None,
             })
         })()
@@ -113,19 +104,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("deriveKey") {
-                bail!("A parameter key_ops must contains deriveKey.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "deriveKey", self.name())?;
 
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -142,6 +121,7 @@ impl Pbes2HmacAeskwJweAlgorithm {
             Ok(Pbes2HmacAeskwJweDecrypter {
                 algorithm: self.clone(),
                 private_key: k,
+                max_iter_count: Some(DEFAULT_MAX_ITER_COUNT),
                 key_id,
             })
         })()
@@ -193,7 +173,7 @@ impl Deref for Pbes2HmacAeskwJweAlgorithm {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Pbes2HmacAeskwJweEncrypter {
     algorithm: Pbes2HmacAeskwJweAlgorithm,
     private_key: Vec<u8>,
@@ -202,7 +182,24 @@ pub struct Pbes2HmacAeskwJweEncrypter {
     key_id: Option<String>,
 }
 
+impl Debug for Pbes2HmacAeskwJweEncrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("Pbes2HmacAeskwJweEncrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("salt_len", &self.salt_len)
+            .field("iter_count", &self.iter_count)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl Pbes2HmacAeskwJweEncrypter {
+    /// Set the length of a random salt that is used as p2s header claim. The default is 8.
+    ///
+    /// # Arguments
+    ///
+    /// * `salt_len` - a salt length (8 or more)
     pub fn set_salt_len(&mut self, salt_len: usize) {
         if salt_len < 8 {
             panic!("salt_len must be 8 or more: {}", salt_len);
@@ -210,6 +207,13 @@ impl Pbes2HmacAeskwJweEncrypter {
         self.salt_len = salt_len;
     }
 
+    /// Set the PBKDF2 iteration count that is used as p2c header claim. The default is 1000.
+    ///
+    /// If the header for encryption has a p2c header claim, it takes precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter_count` - a iteration count (1000 or more)
     pub fn set_iter_count(&mut self, iter_count: usize) {
         if iter_count < 1000 {
             panic!("iter_count must be 1000 or more: {}", iter_count);
@@ -275,7 +279,7 @@ impl JweEncrypter for Pbes2HmacAeskwJweEncrypter {
                     Some(val) => usize::try_from(val)?,
                     None => bail!("Overflow u64 value: {}", val),
                 },
-                Some(_) => bail!("The apv header claim must be string."),
+                Some(_) => bail!("The p2c header claim must be a positive number."),
                 None => {
                     let p2c = self.iter_count;
                     out_header.set_claim("p2c", Some(Value::Number(Number::from(p2c))))?;
@@ -325,14 +329,41 @@ impl Deref for Pbes2HmacAeskwJweEncrypter {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Pbes2HmacAeskwJweDecrypter {
     algorithm: Pbes2HmacAeskwJweAlgorithm,
     private_key: Vec<u8>,
+    max_iter_count: Option<usize>,
     key_id: Option<String>,
 }
 
+impl Debug for Pbes2HmacAeskwJweDecrypter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("Pbes2HmacAeskwJweDecrypter")
+            .field("algorithm", &self.algorithm)
+            .field("private_key", &util::REDACTED)
+            .field("max_iter_count", &self.max_iter_count)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
 impl Pbes2HmacAeskwJweDecrypter {
+    /// Set the maximum PBKDF2 iteration count that is accepted as p2c header claim.
+    /// The default is 1000000.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_iter_count` - a maximum iteration count
+    pub fn set_max_iter_count(&mut self, max_iter_count: usize) {
+        self.max_iter_count = Some(max_iter_count);
+    }
+
+    /// Accept any PBKDF2 iteration count as p2c header claim.
+    pub fn remove_max_iter_count(&mut self) {
+        self.max_iter_count = None;
+    }
+
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
     }
@@ -382,9 +413,21 @@ impl JweDecrypter for Pbes2HmacAeskwJweDecrypter {
                     Some(val) => usize::try_from(val)?,
                     None => bail!("Overflow u64 value: {}", val),
                 },
-                Some(_) => bail!("The p2s header claim must be string."),
+                Some(_) => bail!("The p2c header claim must be a positive number."),
                 None => bail!("The p2c header claim is required."),
             };
+            if p2c == 0 {
+                bail!("The p2c header claim must be a positive number.");
+            }
+            if let Some(max_iter_count) = self.max_iter_count {
+                if p2c > max_iter_count {
+                    bail!(
+                        "The p2c header claim must be {} or less: {}",
+                        max_iter_count,
+                        p2c
+                    );
+                }
+            }
 
             let mut salt = Vec::with_capacity(self.algorithm().name().len() + 1 + p2s.len());
             salt.extend_from_slice(self.algorithm().name().as_bytes());
@@ -476,4 +519,63 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_and_decrypt_pbes2_hmac_with_iter_count() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+
+        for alg in vec![
+            Pbes2HmacAeskwJweAlgorithm::Pbes2Hs256A128kw,
+            Pbes2HmacAeskwJweAlgorithm::Pbes2Hs384A192kw,
+            Pbes2HmacAeskwJweAlgorithm::Pbes2Hs512A256kw,
+        ] {
+            for iter_count in vec![1000, 100000] {
+                let mut header = JweHeader::new();
+                header.set_content_encryption(enc.name());
+
+                let key = util::random_bytes(8);
+
+                let mut encrypter = alg.encrypter_from_bytes(&key)?;
+                encrypter.set_iter_count(iter_count);
+                let mut out_header = header.clone();
+                let src_key = util::random_bytes(enc.key_len());
+                let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+                assert_eq!(out_header.claim("p2c"), Some(&json!(iter_count)));
+
+                let mut decrypter = alg.decrypter_from_bytes(&key)?;
+                let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+                assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+
+                decrypter.set_max_iter_count(iter_count - 1);
+                assert!(decrypter
+                    .decrypt(encrypted_key.as_deref(), &enc, &out_header)
+                    .is_err());
+
+                decrypter.remove_max_iter_count();
+                let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+                assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_pbes2_hmac_with_too_large_iter_count() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let alg = Pbes2HmacAeskwJweAlgorithm::Pbes2Hs256A128kw;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        header.set_claim("p2s", Some(json!("AAAAAAAAAAA")))?;
+        header.set_claim("p2c", Some(json!(2147483648u64)))?;
+
+        let decrypter = alg.decrypter_from_bytes(util::random_bytes(8))?;
+        let encrypted_key = util::random_bytes(enc.key_len() + 8);
+        assert!(decrypter
+            .decrypt(Some(&encrypted_key), &enc, &header)
+            .is_err());
+
+        Ok(())
+    }
 }
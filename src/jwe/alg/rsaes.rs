@@ -23,8 +23,12 @@ pub enum RsaesJweAlgorithm {
     /// RSAES OAEP using SHA-256 and MGF1 with SHA-256
     RsaOaep256,
     /// RSAES OAEP using SHA-384 and MGF1 with SHA-384
+    ///
+    /// This algorithm is registered in the IANA JOSE registry, but not defined in RFC 7518.
     RsaOaep384,
     /// RSAES OAEP using SHA-512 and MGF1 with SHA-512
+    ///
+    /// This algorithm is registered in the IANA JOSE registry, but not defined in RFC 7518.
     RsaOaep512,
 }
 
@@ -122,6 +126,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -155,6 +160,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -166,19 +172,7 @@ impl RsaesJweAlgorithm {
                 val if val == "RSA" => {}
                 val => bail!("A parameter kty must be RSA: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("encrypt") {
-                bail!("A parameter key_ops must contains encrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "encrypt", self.name())?;
 
             let n = match jwk.parameter("n") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -213,6 +207,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -227,6 +222,7 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            oaep_label: None,
         })
     }
 
@@ -239,24 +235,13 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            oaep_label: None,
         })
     }
 
     pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<RsaesJweDecrypter, JoseError> {
         (|| -> anyhow::Result<RsaesJweDecrypter> {
-            match jwk.key_use() {
-                Some(val) if val == "enc" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be enc: {}", val),
-            }
-            if !jwk.is_for_key_operation("decrypt") {
-                bail!("A parameter key_ops must contains decrypt.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("enc", "decrypt", self.name())?;
 
             let key_pair = RsaKeyPair::from_jwk(&jwk)?;
             if key_pair.key_len() * 8 < 2048 {
@@ -270,6 +255,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -312,6 +298,7 @@ pub struct RsaesJweEncrypter {
     algorithm: RsaesJweAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    oaep_label: Option<Vec<u8>>,
 }
 
 impl RsaesJweEncrypter {
@@ -322,6 +309,27 @@ impl RsaesJweEncrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a label of RSAES-OAEP. The default label is empty.
+    ///
+    /// This is a non-standard extension: no header claim carries the label, so
+    /// the decrypter must be given the same label out of band.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a label
+    pub fn set_oaep_label(&mut self, value: impl Into<Vec<u8>>) {
+        self.oaep_label = Some(value.into());
+    }
+
+    /// Return the label of RSAES-OAEP.
+    pub fn oaep_label(&self) -> Option<&[u8]> {
+        self.oaep_label.as_deref()
+    }
+
+    pub fn remove_oaep_label(&mut self) {
+        self.oaep_label = None;
+    }
 }
 
 impl JweEncrypter for RsaesJweEncrypter {
@@ -353,8 +361,20 @@ impl JweEncrypter for RsaesJweEncrypter {
         _out_header: &mut JweHeader,
     ) -> Result<Option<Vec<u8>>, JoseError> {
         (|| -> anyhow::Result<Option<Vec<u8>>> {
+            let label = self.oaep_label.as_deref();
             let rsa = self.public_key.rsa()?;
             let encrypted_key = match self.algorithm {
+                RsaesJweAlgorithm::Rsa1_5 if label.is_some() => {
+                    bail!("A label cannot be used with RSA1_5.");
+                }
+                RsaesJweAlgorithm::RsaOaep if label.is_some() => {
+                    openssl_rsa_oaep::pkey_public_encrypt(
+                        &self.public_key,
+                        &key,
+                        MessageDigest::sha1(),
+                        label,
+                    )?
+                }
                 RsaesJweAlgorithm::Rsa1_5 => {
                     let mut encrypted_key = vec![0; rsa.size() as usize];
                     let len = rsa.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1)?;
@@ -371,16 +391,19 @@ impl JweEncrypter for RsaesJweEncrypter {
                     &self.public_key,
                     &key,
                     MessageDigest::sha256(),
+                    label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep384 => openssl_rsa_oaep::pkey_public_encrypt(
                     &self.public_key,
                     &key,
                     MessageDigest::sha384(),
+                    label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep512 => openssl_rsa_oaep::pkey_public_encrypt(
                     &self.public_key,
                     &key,
                     MessageDigest::sha512(),
+                    label,
                 )?,
             };
 
@@ -407,6 +430,7 @@ pub struct RsaesJweDecrypter {
     algorithm: RsaesJweAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    oaep_label: Option<Vec<u8>>,
 }
 
 impl RsaesJweDecrypter {
@@ -417,6 +441,27 @@ impl RsaesJweDecrypter {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set a label of RSAES-OAEP. The default label is empty.
+    ///
+    /// This is a non-standard extension: it must be the label that the
+    /// encrypter used, as agreed out of band.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a label
+    pub fn set_oaep_label(&mut self, value: impl Into<Vec<u8>>) {
+        self.oaep_label = Some(value.into());
+    }
+
+    /// Return the label of RSAES-OAEP.
+    pub fn oaep_label(&self) -> Option<&[u8]> {
+        self.oaep_label.as_deref()
+    }
+
+    pub fn remove_oaep_label(&mut self) {
+        self.oaep_label = None;
+    }
 }
 
 impl JweDecrypter for RsaesJweDecrypter {
@@ -444,8 +489,20 @@ impl JweDecrypter for RsaesJweDecrypter {
                 None => bail!("A encrypted_key is required."),
             };
 
+            let label = self.oaep_label.as_deref();
             let rsa = self.private_key.rsa()?;
             let key = match self.algorithm {
+                RsaesJweAlgorithm::Rsa1_5 if label.is_some() => {
+                    bail!("A label cannot be used with RSA1_5.");
+                }
+                RsaesJweAlgorithm::RsaOaep if label.is_some() => {
+                    openssl_rsa_oaep::pkey_private_decrypt(
+                        &self.private_key,
+                        &encrypted_key,
+                        MessageDigest::sha1(),
+                        label,
+                    )?
+                }
                 RsaesJweAlgorithm::Rsa1_5 => {
                     let mut key = vec![0; rsa.size() as usize];
                     let len = rsa.private_decrypt(&encrypted_key, &mut key, Padding::PKCS1)?;
@@ -462,16 +519,19 @@ impl JweDecrypter for RsaesJweDecrypter {
                     &self.private_key,
                     &encrypted_key,
                     MessageDigest::sha256(),
+                    label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep384 => openssl_rsa_oaep::pkey_private_decrypt(
                     &self.private_key,
                     &encrypted_key,
                     MessageDigest::sha384(),
+                    label,
                 )?,
                 RsaesJweAlgorithm::RsaOaep512 => openssl_rsa_oaep::pkey_private_decrypt(
                     &self.private_key,
                     &encrypted_key,
                     MessageDigest::sha512(),
+                    label,
                 )?,
             };
 
@@ -504,6 +564,7 @@ mod tests {
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
     use crate::util;
+    use crate::JoseError;
 
     #[test]
     #[allow(deprecated)]
@@ -522,6 +583,8 @@ mod tests {
             RsaesJweAlgorithm::Rsa1_5,
             RsaesJweAlgorithm::RsaOaep,
             RsaesJweAlgorithm::RsaOaep256,
+            RsaesJweAlgorithm::RsaOaep384,
+            RsaesJweAlgorithm::RsaOaep512,
         ] {
             let mut header = JweHeader::new();
             header.set_content_encryption(enc.name());
@@ -540,6 +603,62 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn encrypt_and_decrypt_rsaes_with_oaep_label() -> Result<()> {
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+
+        let private_key = load_file("jwk/RSA_private.jwk")?;
+        let private_key = Jwk::from_bytes(&private_key)?;
+
+        let public_key = load_file("jwk/RSA_public.jwk")?;
+        let public_key = Jwk::from_bytes(&public_key)?;
+
+        for alg in vec![
+            RsaesJweAlgorithm::RsaOaep,
+            RsaesJweAlgorithm::RsaOaep256,
+            RsaesJweAlgorithm::RsaOaep384,
+            RsaesJweAlgorithm::RsaOaep512,
+        ] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+            encrypter.set_oaep_label(b"context".to_vec());
+            let mut out_header = header.clone();
+            let src_key = util::random_bytes(enc.key_len());
+            let encrypted_key = encrypter.encrypt(&src_key, &header, &mut out_header)?;
+
+            let mut decrypter = alg.decrypter_from_jwk(&private_key)?;
+            decrypter.set_oaep_label(b"context".to_vec());
+            let dst_key = decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header)?;
+            assert_eq!(&src_key as &[u8], &dst_key as &[u8]);
+
+            decrypter.set_oaep_label(b"another context".to_vec());
+            assert!(matches!(
+                decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+
+            decrypter.remove_oaep_label();
+            assert!(matches!(
+                decrypter.decrypt(encrypted_key.as_deref(), &enc, &out_header),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+        }
+
+        let mut encrypter = RsaesJweAlgorithm::Rsa1_5.encrypter_from_jwk(&public_key)?;
+        encrypter.set_oaep_label(b"context".to_vec());
+        let header = JweHeader::new();
+        let mut out_header = header.clone();
+        let src_key = util::random_bytes(enc.key_len());
+        assert!(encrypter
+            .encrypt(&src_key, &header, &mut out_header)
+            .is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -563,11 +682,15 @@ mod openssl_rsa_oaep {
         pkey: &PKey<Public>,
         input: &[u8],
         md: MessageDigest,
+        label: Option<&[u8]>,
     ) -> Result<Vec<u8>, ErrorStack> {
         let mut encrypter = Encrypter::new(pkey)?;
         encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
         encrypter.set_rsa_oaep_md(md)?;
         encrypter.set_rsa_mgf1_md(md)?;
+        if let Some(label) = label {
+            encrypter.set_rsa_oaep_label(label)?;
+        }
 
         let outlen = encrypter.encrypt_len(input)?;
         let mut output = vec![0; outlen];
@@ -580,11 +703,15 @@ mod openssl_rsa_oaep {
         pkey: &PKey<Private>,
         input: &[u8],
         md: MessageDigest,
+        label: Option<&[u8]>,
     ) -> Result<Vec<u8>, ErrorStack> {
         let mut decrypter = Decrypter::new(pkey)?;
         decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
         decrypter.set_rsa_oaep_md(md)?;
         decrypter.set_rsa_mgf1_md(md)?;
+        if let Some(label) = label {
+            decrypter.set_rsa_oaep_label(label)?;
+        }
 
         let outlen = decrypter.decrypt_len(input)?;
         let mut output = vec![0; outlen];
@@ -0,0 +1,40 @@
+use std::fmt::Debug;
+use std::ops::Deref;
+
+use zeroize::Zeroizing;
+
+/// Represents a content encryption key (CEK) that is returned after a JWE decryption.
+///
+/// The key is always overwritten with zeros when it is dropped.
+#[derive(Clone)]
+pub struct CekMaterial {
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl CekMaterial {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        Self {
+            key: Zeroizing::new(key.to_vec()),
+        }
+    }
+}
+
+impl Deref for CekMaterial {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.key
+    }
+}
+
+impl AsRef<[u8]> for CekMaterial {
+    fn as_ref(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl Debug for CekMaterial {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str("CekMaterial { .. }")
+    }
+}
@@ -1,5 +1,8 @@
 pub mod aescbc_hmac;
 pub mod aesgcm;
+#[cfg(feature = "aes-gcm-siv")]
+pub mod aesgcmsiv;
+pub mod chacha20_poly1305;
 
 use crate::jwe::enc::aescbc_hmac::AescbcHmacJweEncryption;
 pub use AescbcHmacJweEncryption::A128cbcHs256 as A128CBC_HS256;
@@ -10,3 +13,14 @@ use crate::jwe::enc::aesgcm::AesgcmJweEncryption;
 pub use AesgcmJweEncryption::A128gcm as A128GCM;
 pub use AesgcmJweEncryption::A192gcm as A192GCM;
 pub use AesgcmJweEncryption::A256gcm as A256GCM;
+
+#[cfg(feature = "aes-gcm-siv")]
+use crate::jwe::enc::aesgcmsiv::AesgcmsivJweEncryption;
+#[cfg(feature = "aes-gcm-siv")]
+pub use AesgcmsivJweEncryption::A128gcmsiv as A128GCMSIV;
+#[cfg(feature = "aes-gcm-siv")]
+pub use AesgcmsivJweEncryption::A256gcmsiv as A256GCMSIV;
+
+use crate::jwe::enc::chacha20_poly1305::ChaCha20Poly1305JweEncryption;
+pub use ChaCha20Poly1305JweEncryption::C20p as C20P;
+pub use ChaCha20Poly1305JweEncryption::Xc20p as XC20P;
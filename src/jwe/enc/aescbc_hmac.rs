@@ -1,6 +1,6 @@
 use std::{fmt::Display, ops::Deref};
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use openssl::{
     hash::MessageDigest,
     pkey::{PKey, Private},
@@ -8,7 +8,21 @@ use openssl::{
     symm::{self, Cipher},
 };
 
-use crate::{jwe::JweContentEncryption, JoseError};
+use crate::jose_error::JoseErrorDetail;
+use crate::util;
+use crate::{jwe::JweContentEncryption, JoseError, JoseErrorKind};
+
+/// The plaintext (P) of the test vectors in RFC 7518 Appendix B.
+const TEST_VECTOR_MESSAGE: &[u8] = b"A cipher system must not be required to be secret, \
+and it must be able to fall into the hands of the enemy without inconvenience";
+
+/// The initialization vector (IV) of the test vectors in RFC 7518 Appendix B.
+const TEST_VECTOR_IV: [u8; 16] = [
+    0x1a, 0xf3, 0x8c, 0x2d, 0xc2, 0xb9, 0x6f, 0xfd, 0xd8, 0x66, 0x94, 0x09, 0x23, 0x41, 0xbc, 0x04,
+];
+
+/// The associated data (A) of the test vectors in RFC 7518 Appendix B.
+const TEST_VECTOR_AAD: &[u8] = b"The second principle of Auguste Kerckhoffs";
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AescbcHmacJweEncryption {
@@ -21,6 +35,91 @@ pub enum AescbcHmacJweEncryption {
 }
 
 impl AescbcHmacJweEncryption {
+    /// Run the test vectors of RFC 7518 Appendix B through encrypt and decrypt
+    /// for all variants, and return an error if any of them fails.
+    ///
+    /// This can be called at startup to check the behavior with the linked OpenSSL.
+    pub fn self_test() -> Result<(), JoseError> {
+        for enc in &[Self::A128cbcHs256, Self::A192cbcHs384, Self::A256cbcHs512] {
+            let key: Vec<u8> = (0..enc.key_len() as u8).collect();
+            let (expected_message, expected_tag) = enc.test_vector();
+            let expected_message = decode_hex(expected_message);
+            let expected_tag = decode_hex(expected_tag);
+
+            let (encrypted_message, tag) = enc.encrypt(
+                &key,
+                Some(&TEST_VECTOR_IV),
+                TEST_VECTOR_MESSAGE,
+                TEST_VECTOR_AAD,
+            )?;
+            if encrypted_message != expected_message {
+                return Err(JoseError::UnsupportedAlgorithm(anyhow!(
+                    "The {} self-test failed: the ciphertext doesn't match.",
+                    enc.name()
+                )));
+            }
+            if tag.as_deref() != Some(&expected_tag[..]) {
+                return Err(JoseError::UnsupportedAlgorithm(anyhow!(
+                    "The {} self-test failed: the tag doesn't match.",
+                    enc.name()
+                )));
+            }
+
+            let message = enc.decrypt(
+                &key,
+                Some(&TEST_VECTOR_IV),
+                &expected_message,
+                TEST_VECTOR_AAD,
+                Some(&expected_tag),
+            )?;
+            if message != TEST_VECTOR_MESSAGE {
+                return Err(JoseError::UnsupportedAlgorithm(anyhow!(
+                    "The {} self-test failed: the plaintext doesn't match.",
+                    enc.name()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return the ciphertext (E) and the authentication tag (T) in hex
+    /// of the test vector in RFC 7518 Appendix B.
+    fn test_vector(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::A128cbcHs256 => (
+                concat!(
+                    "c80edfa32ddf39d5ef00c0b468834279a2e46a1b8049f792f76bfe54b903a9c9",
+                    "a94ac9b47ad2655c5f10f9aef71427e2fc6f9b3f399a221489f16362c7032336",
+                    "09d45ac69864e3321cf82935ac4096c86e133314c54019e8ca7980dfa4b9cf1b",
+                    "384c486f3a54c51078158ee5d79de59fbd34d848b3d69550a67646344427ade5",
+                    "4b8851ffb598f7f80074b9473c82e2db",
+                ),
+                "652c3fa36b0a7c5b3219fab3a30bc1c4",
+            ),
+            Self::A192cbcHs384 => (
+                concat!(
+                    "ea65da6b59e61edb419be62d19712ae5d303eeb50052d0dfd6697f77224c8edb",
+                    "000d279bdc14c1072654bd30944230c657bed4ca0c9f4a8466f22b226d174621",
+                    "4bf8cfc2400add9f5126e479663fc90b3bed787a2f0ffcbf3904be2a641d5c21",
+                    "05bfe591bae23b1d7449e532eef60a9ac8bb6c6b01d35d49787bcd57ef484927",
+                    "f280adc91ac0c4e79c7b11efc60054e3",
+                ),
+                "8490ac0e58949bfe51875d733f93ac2075168039ccc733d7",
+            ),
+            Self::A256cbcHs512 => (
+                concat!(
+                    "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd",
+                    "822c301dd67c373bccb584ad3e9279c2e6d12a1374b77f077553df829410446b",
+                    "36ebd97066296ae6427ea75c2e0846a11a09ccf5370dc80bfecbad28c73f09b3",
+                    "a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950b",
+                    "be2638d09dd7a4930930806d0703b1f6",
+                ),
+                "4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5",
+            ),
+        }
+    }
+
     fn cipher(&self) -> Cipher {
         match self {
             Self::A128cbcHs256 => Cipher::aes_128_cbc(),
@@ -29,6 +128,39 @@ impl AescbcHmacJweEncryption {
         }
     }
 
+    fn check_key_and_iv(&self, key: &[u8], iv: Option<&[u8]>) -> anyhow::Result<()> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of content encryption key must be {}: {}",
+                    expected_len,
+                    key.len()
+                )
+            ));
+        }
+
+        let iv = match iv {
+            Some(val) => val,
+            None => bail!("An initialization vector is required."),
+        };
+
+        let expected_len = self.iv_len();
+        if iv.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidIvLength,
+                format!(
+                    "The length of initialization vector must be {}: {}",
+                    expected_len,
+                    iv.len()
+                )
+            ));
+        }
+
+        Ok(())
+    }
+
     fn calcurate_tag(
         &self,
         aad: &[u8],
@@ -49,7 +181,8 @@ impl AescbcHmacJweEncryption {
         .map_err(|err| JoseError::InvalidKeyFormat(err))?;
 
         let signature = (|| -> anyhow::Result<Vec<u8>> {
-            let aad_bits = ((aad.len() * 8) as u64).to_be_bytes();
+            // AL is always 64 bits, and the multiplication must not overflow a 32-bit usize.
+            let aad_bits = (aad.len() as u64 * 8).to_be_bytes();
 
             let mut signer = Signer::new(message_digest, &pkey)?;
             signer.update(aad)?;
@@ -68,6 +201,13 @@ impl AescbcHmacJweEncryption {
     }
 }
 
+fn decode_hex(input: &str) -> Vec<u8> {
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).unwrap())
+        .collect()
+}
+
 impl JweContentEncryption for AescbcHmacJweEncryption {
     fn name(&self) -> &str {
         match self {
@@ -97,14 +237,9 @@ impl JweContentEncryption for AescbcHmacJweEncryption {
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
         let (encrypted_message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
+            self.check_key_and_iv(key, iv)?;
+
             let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
 
             let mac_key_len = expected_len / 2;
             let mac_key = &key[0..mac_key_len];
@@ -129,42 +264,44 @@ impl JweContentEncryption for AescbcHmacJweEncryption {
         aad: &[u8],
         tag: Option<&[u8]>,
     ) -> Result<Vec<u8>, JoseError> {
-        let (message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
+        let (mac_key, enc_key) = (|| -> anyhow::Result<(&[u8], &[u8])> {
+            self.check_key_and_iv(key, iv)?;
+
             let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
 
             let mac_key_len = expected_len / 2;
-            let mac_key = &key[0..mac_key_len];
-            let enc_key = &key[mac_key_len..];
-
-            let cipher = self.cipher();
-            let message = symm::decrypt(cipher, enc_key, iv, encrypted_message)?;
-            Ok((message, mac_key))
+            Ok((&key[0..mac_key_len], &key[mac_key_len..]))
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))?;
 
-        (|| -> anyhow::Result<()> {
+        // The tag is verified before the ciphertext is decrypted, and a padding error is
+        // reported as a tag mismatch, so that the errors can't be used as a padding oracle.
+        (|| -> anyhow::Result<Vec<u8>> {
             let tag = match tag {
                 Some(val) => val,
                 None => bail!("A tag value is required."),
             };
 
             let calc_tag = self.calcurate_tag(aad, iv, &encrypted_message, mac_key)?;
-            if calc_tag.as_slice() != tag {
-                bail!("The tag doesn't match.");
+            if !util::constant_time_eq(&calc_tag, tag) {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::TagMismatch,
+                    "The tag doesn't match."
+                ));
             }
 
-            Ok(())
-        })()
-        .map_err(|err| JoseError::InvalidSignature(err))?;
+            let cipher = self.cipher();
+            let message = match symm::decrypt(cipher, enc_key, iv, encrypted_message) {
+                Ok(val) => val,
+                Err(_) => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::TagMismatch,
+                    "The tag doesn't match."
+                )),
+            };
 
-        Ok(message)
+            Ok(message)
+        })()
+        .map_err(|err| JoseError::InvalidSignature(err))
     }
 
     fn box_clone(&self) -> Box<dyn JweContentEncryption> {
@@ -189,9 +326,13 @@ impl Deref for AescbcHmacJweEncryption {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use openssl::symm::{Crypter, Mode};
 
-    use super::AescbcHmacJweEncryption;
+    use super::{
+        decode_hex, AescbcHmacJweEncryption, TEST_VECTOR_AAD, TEST_VECTOR_IV, TEST_VECTOR_MESSAGE,
+    };
     use crate::util;
+    use crate::{JoseError, JoseErrorKind};
 
     #[test]
     fn encrypt_and_decrypt_aes_cbc_hmac() -> Result<()> {
@@ -220,4 +361,248 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn self_test_aes_cbc_hmac() -> Result<()> {
+        AescbcHmacJweEncryption::self_test()?;
+        Ok(())
+    }
+
+    #[test]
+    fn calcurate_tag_aes_cbc_hmac_rfc7518() -> Result<()> {
+        for enc in vec![
+            AescbcHmacJweEncryption::A128cbcHs256,
+            AescbcHmacJweEncryption::A192cbcHs384,
+            AescbcHmacJweEncryption::A256cbcHs512,
+        ] {
+            let key: Vec<u8> = (0..enc.key_len() as u8).collect();
+            let mac_key = &key[0..(enc.key_len() / 2)];
+            let (encrypted_message, expected_tag) = enc.test_vector();
+
+            let tag = enc.calcurate_tag(
+                TEST_VECTOR_AAD,
+                Some(&TEST_VECTOR_IV),
+                &decode_hex(encrypted_message),
+                mac_key,
+            )?;
+            assert_eq!(tag, decode_hex(expected_tag));
+
+            let (_, tag) = enc.encrypt(
+                &key,
+                Some(&TEST_VECTOR_IV),
+                TEST_VECTOR_MESSAGE,
+                TEST_VECTOR_AAD,
+            )?;
+            assert_eq!(tag, Some(decode_hex(expected_tag)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_stream_aes_cbc_hmac() -> Result<()> {
+        let message = util::random_bytes(1000);
+        let aad = b"test";
+
+        for enc in vec![
+            AescbcHmacJweEncryption::A128cbcHs256,
+            AescbcHmacJweEncryption::A192cbcHs384,
+            AescbcHmacJweEncryption::A256cbcHs512,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (expected_encrypted_message, expected_tag) =
+                enc.encrypt(&key, Some(&iv), &message, aad)?;
+
+            let mut stream = enc.encrypt_stream(&key, Some(&iv), aad)?;
+            let mut encrypted_message = Vec::new();
+            for chunk in message.chunks(7) {
+                encrypted_message.extend_from_slice(&stream.update(chunk)?);
+            }
+            let (rest, tag) = stream.finalize()?;
+            encrypted_message.extend_from_slice(&rest);
+
+            assert_eq!(encrypted_message, expected_encrypted_message);
+            assert_eq!(tag, expected_tag);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_with_invalid_tag() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        for enc in vec![
+            AescbcHmacJweEncryption::A128cbcHs256,
+            AescbcHmacJweEncryption::A192cbcHs384,
+            AescbcHmacJweEncryption::A256cbcHs512,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            let tag = tag.unwrap();
+
+            let mut modified_tag = tag.clone();
+            modified_tag[0] ^= 0x01;
+            assert!(enc
+                .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&modified_tag))
+                .is_err());
+
+            let truncated_tag = &tag[..tag.len() - 1];
+            assert!(enc
+                .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(truncated_tag))
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_error_kind() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AescbcHmacJweEncryption::A128cbcHs256;
+        let key = util::random_bytes(enc.key_len());
+        let iv = util::random_bytes(enc.iv_len());
+
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+        let mut modified_tag = tag.unwrap();
+        modified_tag[0] ^= 0x01;
+
+        let err = enc
+            .decrypt(
+                &key,
+                Some(&iv),
+                &encrypted_message,
+                aad,
+                Some(&modified_tag),
+            )
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidSignature(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::TagMismatch));
+        assert_eq!(err.to_string(), "Invalid signature: The tag doesn't match.");
+
+        let short_key = &key[..key.len() - 1];
+        let err = enc.encrypt(short_key, Some(&iv), message, aad).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        let err = enc
+            .decrypt(
+                short_key,
+                Some(&iv),
+                &encrypted_message,
+                aad,
+                Some(&modified_tag),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decrypt_aes_cbc_hmac_with_invalid_padding() -> Result<()> {
+        let aad = b"test";
+
+        for enc in vec![
+            AescbcHmacJweEncryption::A128cbcHs256,
+            AescbcHmacJweEncryption::A192cbcHs384,
+            AescbcHmacJweEncryption::A256cbcHs512,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+            let (mac_key, enc_key) = key.split_at(enc.key_len() / 2);
+
+            // A block that ends with 0x00 is never valid PKCS#7 padding.
+            let mut crypter = Crypter::new(enc.cipher(), Mode::Encrypt, enc_key, Some(&iv))?;
+            crypter.pad(false);
+            let mut bad_padding_message = vec![0; 32];
+            let mut len = crypter.update(&[0; 16], &mut bad_padding_message)?;
+            len += crypter.finalize(&mut bad_padding_message[len..])?;
+            bad_padding_message.truncate(len);
+            let bad_padding_tag =
+                enc.calcurate_tag(aad, Some(&iv), &bad_padding_message, mac_key)?;
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), b"abcde12345", aad)?;
+            let mut bad_tag = tag.unwrap();
+            bad_tag[0] ^= 0x01;
+
+            let padding_err = enc
+                .decrypt(
+                    &key,
+                    Some(&iv),
+                    &bad_padding_message,
+                    aad,
+                    Some(&bad_padding_tag),
+                )
+                .unwrap_err();
+            let tag_err = enc
+                .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&bad_tag))
+                .unwrap_err();
+
+            assert!(matches!(padding_err, JoseError::InvalidSignature(_)));
+            assert!(matches!(tag_err, JoseError::InvalidSignature(_)));
+            assert_eq!(padding_err.kind(), tag_err.kind());
+            assert_eq!(padding_err.to_string(), tag_err.to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_cbc_hmac_with_invalid_length() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AescbcHmacJweEncryption::A256cbcHs512;
+        let key = util::random_bytes(enc.key_len());
+        let iv = util::random_bytes(enc.iv_len());
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+
+        let short_key = util::random_bytes(31);
+        let err = enc
+            .encrypt(&short_key, Some(&iv), message, aad)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of content encryption key must be 64: 31"
+        );
+
+        let err = enc
+            .decrypt(
+                &short_key,
+                Some(&iv),
+                &encrypted_message,
+                aad,
+                tag.as_deref(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of content encryption key must be 64: 31"
+        );
+
+        let err = enc
+            .encrypt(&key, Some(&iv[..15]), message, aad)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of initialization vector must be 16: 15"
+        );
+        assert!(matches!(
+            enc.decrypt(&key, None, &encrypted_message, aad, tag.as_deref()),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
 }
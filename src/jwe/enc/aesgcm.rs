@@ -4,8 +4,10 @@ use std::ops::Deref;
 use anyhow::bail;
 use openssl::symm::{self, Cipher};
 
-use crate::jwe::JweContentEncryption;
-use crate::JoseError;
+use crate::jose_error::JoseErrorDetail;
+use crate::jwe::jwe_content_encryption::AeadJweEncryptStream;
+use crate::jwe::{JweContentEncryption, JweEncryptStream};
+use crate::{JoseError, JoseErrorKind};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AesgcmJweEncryption {
@@ -25,6 +27,39 @@ impl AesgcmJweEncryption {
             Self::A256gcm => Cipher::aes_256_gcm(),
         }
     }
+
+    fn check_key_and_iv(&self, key: &[u8], iv: Option<&[u8]>) -> anyhow::Result<()> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of content encryption key must be {}: {}",
+                    expected_len,
+                    key.len()
+                )
+            ));
+        }
+
+        let iv = match iv {
+            Some(val) => val,
+            None => bail!("An initialization vector is required."),
+        };
+
+        let expected_len = self.iv_len();
+        if iv.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidIvLength,
+                format!(
+                    "The length of initialization vector must be {}: {}",
+                    expected_len,
+                    iv.len()
+                )
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl JweContentEncryption for AesgcmJweEncryption {
@@ -56,14 +91,7 @@ impl JweContentEncryption for AesgcmJweEncryption {
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
         (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
+            self.check_key_and_iv(key, iv)?;
 
             let cipher = self.cipher();
             let mut tag = [0; 16];
@@ -81,28 +109,42 @@ impl JweContentEncryption for AesgcmJweEncryption {
         aad: &[u8],
         tag: Option<&[u8]>,
     ) -> Result<Vec<u8>, JoseError> {
-        (|| -> anyhow::Result<Vec<u8>> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
+        self.check_key_and_iv(key, iv)
+            .map_err(|err| JoseError::InvalidKeyFormat(err))?;
 
+        (|| -> anyhow::Result<Vec<u8>> {
             let tag = match tag {
-                Some(val) => val,
+                Some(val) if val.len() == 16 => val,
+                Some(val) => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidTagLength,
+                    format!("The length of tag must be 16: {}", val.len())
+                )),
                 None => bail!("A tag value is required."),
             };
 
             let cipher = self.cipher();
-            let message = symm::decrypt_aead(cipher, key, iv, aad, encrypted_message, tag)?;
+            let message = symm::decrypt_aead(cipher, key, iv, aad, encrypted_message, tag)
+                .map_err(|err| JoseErrorDetail::new(JoseErrorKind::TagMismatch, err.to_string()))?;
             Ok(message)
         })()
         .map_err(|err| JoseError::InvalidJweFormat(err))
     }
 
+    fn encrypt_stream(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        aad: &[u8],
+    ) -> Result<Box<dyn JweEncryptStream>, JoseError> {
+        (|| -> anyhow::Result<Box<dyn JweEncryptStream>> {
+            self.check_key_and_iv(key, iv)?;
+
+            let stream = AeadJweEncryptStream::new(self.cipher(), key, iv, aad, 16)?;
+            Ok(Box::new(stream))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
     fn box_clone(&self) -> Box<dyn JweContentEncryption> {
         Box::new(self.clone())
     }
@@ -128,6 +170,7 @@ mod tests {
 
     use super::AesgcmJweEncryption;
     use crate::util;
+    use crate::{JoseError, JoseErrorKind};
 
     #[test]
     fn encrypt_and_decrypt_aes_gcm() -> Result<()> {
@@ -156,4 +199,92 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_stream_aes_gcm() -> Result<()> {
+        let message = util::random_bytes(1000);
+        let aad = b"test";
+
+        for enc in vec![
+            AesgcmJweEncryption::A128gcm,
+            AesgcmJweEncryption::A192gcm,
+            AesgcmJweEncryption::A256gcm,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (expected_encrypted_message, expected_tag) =
+                enc.encrypt(&key, Some(&iv), &message, aad)?;
+
+            let mut stream = enc.encrypt_stream(&key, Some(&iv), aad)?;
+            let mut encrypted_message = Vec::new();
+            for chunk in message.chunks(7) {
+                encrypted_message.extend_from_slice(&stream.update(chunk)?);
+            }
+            let (rest, tag) = stream.finalize()?;
+            encrypted_message.extend_from_slice(&rest);
+
+            assert_eq!(encrypted_message, expected_encrypted_message);
+            assert_eq!(tag, expected_tag);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_gcm_with_invalid_length() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        let enc = AesgcmJweEncryption::A256gcm;
+        let key = util::random_bytes(enc.key_len());
+        let iv = util::random_bytes(enc.iv_len());
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+
+        let err = enc
+            .encrypt(&key[..31], Some(&iv), message, aad)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of content encryption key must be 32: 31"
+        );
+
+        let err = enc
+            .decrypt(
+                &key,
+                Some(&iv[..11]),
+                &encrypted_message,
+                aad,
+                tag.as_deref(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of initialization vector must be 12: 11"
+        );
+
+        let iv16 = util::random_bytes(16);
+        let err = enc.encrypt(&key, Some(&iv16), message, aad).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidIvLength));
+        assert_eq!(
+            err.to_string(),
+            "Invalid key format: The length of initialization vector must be 12: 16"
+        );
+
+        let tag = tag.unwrap();
+        let err = enc
+            .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&tag[..15]))
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidTagLength));
+        assert_eq!(
+            err.to_string(),
+            "Invalid JWE format: The length of tag must be 16: 15"
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,272 @@
+use std::fmt::Display;
+use std::ops::Deref;
+
+use anyhow::bail;
+
+#[cfg(feature = "aes-gcm-siv")]
+use crate::jose_error::JoseErrorDetail;
+use crate::jwe::JweContentEncryption;
+use crate::JoseError;
+#[cfg(feature = "aes-gcm-siv")]
+use crate::JoseErrorKind;
+
+/// AES-GCM-SIV nonce misuse-resistant content encryption algorithms (RFC 8452).
+///
+/// The synthetic IV is derived from the plaintext and the AAD, so the `iv` argument of
+/// `encrypt` and `decrypt` is only used as the nonce input to the key derivation.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum AesgcmsivJweEncryption {
+    /// AES GCM-SIV using 128-bit key
+    A128gcmsiv,
+    /// AES GCM-SIV using 256-bit key
+    A256gcmsiv,
+}
+
+impl AesgcmsivJweEncryption {
+    fn check_key_and_iv<'a>(&self, key: &[u8], iv: Option<&'a [u8]>) -> anyhow::Result<&'a [u8]> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of content encryption key must be {}: {}",
+                    expected_len,
+                    key.len()
+                )
+            ));
+        }
+
+        let iv = match iv {
+            Some(val) => val,
+            None => bail!("An initialization vector is required."),
+        };
+
+        let expected_len = self.iv_len();
+        if iv.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidIvLength,
+                format!(
+                    "The length of initialization vector must be {}: {}",
+                    expected_len,
+                    iv.len()
+                )
+            ));
+        }
+
+        Ok(iv)
+    }
+}
+
+impl JweContentEncryption for AesgcmsivJweEncryption {
+    fn name(&self) -> &str {
+        match self {
+            Self::A128gcmsiv => "A128GCMSIV",
+            Self::A256gcmsiv => "A256GCMSIV",
+        }
+    }
+
+    fn key_len(&self) -> usize {
+        match self {
+            Self::A128gcmsiv => 16,
+            Self::A256gcmsiv => 32,
+        }
+    }
+
+    fn iv_len(&self) -> usize {
+        12
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        message: &[u8],
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
+        use aes_gcm_siv::aead::{AeadInPlace, KeyInit};
+        use aes_gcm_siv::{Aes128GcmSiv, Aes256GcmSiv, Nonce};
+        use anyhow::anyhow;
+
+        (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+            let iv = self.check_key_and_iv(key, iv)?;
+            let nonce = Nonce::from_slice(iv);
+
+            let mut encrypted_message = message.to_vec();
+            let tag = match self {
+                Self::A128gcmsiv => Aes128GcmSiv::new_from_slice(key)
+                    .map_err(|_| anyhow!("Failed to initialize the cipher."))?
+                    .encrypt_in_place_detached(nonce, aad, &mut encrypted_message),
+                Self::A256gcmsiv => Aes256GcmSiv::new_from_slice(key)
+                    .map_err(|_| anyhow!("Failed to initialize the cipher."))?
+                    .encrypt_in_place_detached(nonce, aad, &mut encrypted_message),
+            }
+            .map_err(|_| anyhow!("Failed to encrypt the message."))?;
+
+            Ok((encrypted_message, Some(tag.to_vec())))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        encrypted_message: &[u8],
+        aad: &[u8],
+        tag: Option<&[u8]>,
+    ) -> Result<Vec<u8>, JoseError> {
+        use aes_gcm_siv::aead::{AeadInPlace, KeyInit};
+        use aes_gcm_siv::{Aes128GcmSiv, Aes256GcmSiv, Nonce, Tag};
+        use anyhow::anyhow;
+
+        (|| -> anyhow::Result<Vec<u8>> {
+            let iv = self.check_key_and_iv(key, iv)?;
+            let nonce = Nonce::from_slice(iv);
+
+            let tag = match tag {
+                Some(val) if val.len() == 16 => Tag::from_slice(val),
+                Some(val) => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidTagLength,
+                    format!("The length of tag must be 16: {}", val.len())
+                )),
+                None => bail!("A tag value is required."),
+            };
+
+            let mut message = encrypted_message.to_vec();
+            match self {
+                Self::A128gcmsiv => Aes128GcmSiv::new_from_slice(key)
+                    .map_err(|_| anyhow!("Failed to initialize the cipher."))?
+                    .decrypt_in_place_detached(nonce, aad, &mut message, tag),
+                Self::A256gcmsiv => Aes256GcmSiv::new_from_slice(key)
+                    .map_err(|_| anyhow!("Failed to initialize the cipher."))?
+                    .decrypt_in_place_detached(nonce, aad, &mut message, tag),
+            }
+            .map_err(|_| {
+                JoseErrorDetail::new(JoseErrorKind::TagMismatch, "Failed to decrypt the message.")
+            })?;
+
+            Ok(message)
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
+
+    fn box_clone(&self) -> Box<dyn JweContentEncryption> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for AesgcmsivJweEncryption {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for AesgcmsivJweEncryption {
+    type Target = dyn JweContentEncryption;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::AesgcmsivJweEncryption;
+    use crate::util::{self, decode_hex};
+
+    #[test]
+    fn encrypt_and_decrypt_aes_gcm_siv() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        for enc in vec![
+            AesgcmsivJweEncryption::A128gcmsiv,
+            AesgcmsivJweEncryption::A256gcmsiv,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            let decrypted_message = enc.decrypt(
+                &key,
+                Some(&iv),
+                &encrypted_message,
+                &aad[..],
+                tag.as_deref(),
+            )?;
+
+            assert_eq!(&message[..], &decrypted_message[..]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_aes_gcm_siv_with_test_vectors() -> Result<()> {
+        // RFC 8452 Appendix C.1. AEAD_AES_128_GCM_SIV and C.2. AEAD_AES_256_GCM_SIV
+        let vectors = vec![
+            (
+                AesgcmsivJweEncryption::A128gcmsiv,
+                "01000000000000000000000000000000",
+                "030000000000000000000000",
+                "",
+                "",
+                "",
+                "dc20e2d83f25705bb49e439eca56de25",
+            ),
+            (
+                AesgcmsivJweEncryption::A128gcmsiv,
+                "01000000000000000000000000000000",
+                "030000000000000000000000",
+                "",
+                "0100000000000000",
+                "b5d839330ac7b786",
+                "578782fff6013b815b287c22493a364c",
+            ),
+            (
+                AesgcmsivJweEncryption::A256gcmsiv,
+                "0100000000000000000000000000000000000000000000000000000000000000",
+                "030000000000000000000000",
+                "",
+                "",
+                "",
+                "07f5f4169bbf55a8400cd47ea6fd400f",
+            ),
+            (
+                AesgcmsivJweEncryption::A256gcmsiv,
+                "0100000000000000000000000000000000000000000000000000000000000000",
+                "030000000000000000000000",
+                "",
+                "0100000000000000",
+                "c2ef328e5c71c83b",
+                "843122130f7364b761e0b97427e3df28",
+            ),
+        ];
+
+        for (enc, key, iv, aad, message, expected_encrypted_message, expected_tag) in vectors {
+            let key = decode_hex(key);
+            let iv = decode_hex(iv);
+            let aad = decode_hex(aad);
+            let message = decode_hex(message);
+            let expected_encrypted_message = decode_hex(expected_encrypted_message);
+            let expected_tag = decode_hex(expected_tag);
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), &message, &aad)?;
+            assert_eq!(encrypted_message, expected_encrypted_message);
+            assert_eq!(tag, Some(expected_tag.clone()));
+
+            let decrypted_message = enc.decrypt(
+                &key,
+                Some(&iv),
+                &expected_encrypted_message,
+                &aad,
+                Some(&expected_tag),
+            )?;
+            assert_eq!(decrypted_message, message);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+use std::fmt::Display;
+use std::ops::Deref;
+
+use anyhow::bail;
+use openssl::symm::{self, Cipher};
+
+use crate::jose_error::JoseErrorDetail;
+use crate::jwe::jwe_content_encryption::AeadJweEncryptStream;
+use crate::jwe::{JweContentEncryption, JweEncryptStream};
+use crate::util;
+use crate::{JoseError, JoseErrorKind};
+
+/// ChaCha20-Poly1305 content encryption algorithms.
+///
+/// These algorithms require OpenSSL 1.1.0 or later. XChaCha20-Poly1305 is built on
+/// ChaCha20-Poly1305 of OpenSSL with a subkey derived by HChaCha20.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ChaCha20Poly1305JweEncryption {
+    /// ChaCha20-Poly1305 using 256-bit key and 96-bit nonce
+    C20p,
+    /// XChaCha20-Poly1305 using 256-bit key and 192-bit nonce
+    Xc20p,
+}
+
+impl ChaCha20Poly1305JweEncryption {
+    fn cipher(&self) -> Cipher {
+        Cipher::chacha20_poly1305()
+    }
+
+    fn derive_key_and_nonce(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let expected_len = self.key_len();
+        if key.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of content encryption key must be {}: {}",
+                    expected_len,
+                    key.len()
+                )
+            ));
+        }
+
+        let iv = match iv {
+            Some(val) => val,
+            None => bail!("An initialization vector is required."),
+        };
+
+        let expected_len = self.iv_len();
+        if iv.len() != expected_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidIvLength,
+                format!(
+                    "The length of initialization vector must be {}: {}",
+                    expected_len,
+                    iv.len()
+                )
+            ));
+        }
+
+        match self {
+            Self::C20p => Ok((key.to_vec(), Some(iv.to_vec()))),
+            Self::Xc20p => {
+                let subkey = hchacha20(key, &iv[0..16]);
+                let mut nonce = vec![0; 4];
+                nonce.extend_from_slice(&iv[16..]);
+                Ok((subkey, Some(nonce)))
+            }
+        }
+    }
+}
+
+impl JweContentEncryption for ChaCha20Poly1305JweEncryption {
+    fn name(&self) -> &str {
+        match self {
+            Self::C20p => "C20P",
+            Self::Xc20p => "XC20P",
+        }
+    }
+
+    fn key_len(&self) -> usize {
+        32
+    }
+
+    fn iv_len(&self) -> usize {
+        match self {
+            Self::C20p => 12,
+            Self::Xc20p => 24,
+        }
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        message: &[u8],
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
+        (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+            let (key, iv) = self.derive_key_and_nonce(key, iv)?;
+
+            let cipher = self.cipher();
+            let mut tag = [0; 16];
+            let encrypted_message =
+                symm::encrypt_aead(cipher, &key, iv.as_deref(), aad, message, &mut tag)?;
+            Ok((encrypted_message, Some(tag.to_vec())))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        encrypted_message: &[u8],
+        aad: &[u8],
+        tag: Option<&[u8]>,
+    ) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let (key, iv) = self.derive_key_and_nonce(key, iv)?;
+
+            let tag = match tag {
+                Some(val) if val.len() == 16 => val,
+                Some(val) => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidTagLength,
+                    format!("The length of tag must be 16: {}", val.len())
+                )),
+                None => bail!("A tag value is required."),
+            };
+
+            let cipher = self.cipher();
+            let message =
+                symm::decrypt_aead(cipher, &key, iv.as_deref(), aad, encrypted_message, tag)
+                    .map_err(|err| {
+                        JoseErrorDetail::new(JoseErrorKind::TagMismatch, err.to_string())
+                    })?;
+            Ok(message)
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
+
+    fn encrypt_stream(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        aad: &[u8],
+    ) -> Result<Box<dyn JweEncryptStream>, JoseError> {
+        (|| -> anyhow::Result<Box<dyn JweEncryptStream>> {
+            let (mut key, iv) = self.derive_key_and_nonce(key, iv)?;
+
+            let stream = AeadJweEncryptStream::new(self.cipher(), &key, iv.as_deref(), aad, 16);
+            util::zeroize(&mut key);
+            Ok(Box::new(stream?))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn box_clone(&self) -> Box<dyn JweContentEncryption> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for ChaCha20Poly1305JweEncryption {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for ChaCha20Poly1305JweEncryption {
+    type Target = dyn JweContentEncryption;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+fn hchacha20(key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_left(16);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_left(12);
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_left(8);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_left(7);
+    }
+
+    let mut state = [0u32; 16];
+    state[0] = 0x61707865;
+    state[1] = 0x3320646e;
+    state[2] = 0x79622d32;
+    state[3] = 0x6b206574;
+    for (i, chunk) in key.chunks_exact(4).enumerate() {
+        state[4 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    for (i, chunk) in nonce.chunks_exact(4).enumerate() {
+        state[12 + i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut subkey = Vec::with_capacity(32);
+    for i in [0, 1, 2, 3, 12, 13, 14, 15] {
+        subkey.extend_from_slice(&state[i].to_le_bytes());
+    }
+    subkey
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{hchacha20, ChaCha20Poly1305JweEncryption};
+    use crate::util::{self, decode_hex};
+    use crate::{JoseError, JoseErrorKind};
+
+    #[test]
+    fn encrypt_and_decrypt_chacha20_poly1305() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        for enc in vec![
+            ChaCha20Poly1305JweEncryption::C20p,
+            ChaCha20Poly1305JweEncryption::Xc20p,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            let decrypted_message = enc.decrypt(
+                &key,
+                Some(&iv),
+                &encrypted_message,
+                &aad[..],
+                tag.as_deref(),
+            )?;
+
+            assert_eq!(&message[..], &decrypted_message[..]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_stream_chacha20_poly1305() -> Result<()> {
+        let message = util::random_bytes(1000);
+        let aad = b"test";
+
+        for enc in vec![
+            ChaCha20Poly1305JweEncryption::C20p,
+            ChaCha20Poly1305JweEncryption::Xc20p,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let (expected_encrypted_message, expected_tag) =
+                enc.encrypt(&key, Some(&iv), &message, aad)?;
+
+            let mut stream = enc.encrypt_stream(&key, Some(&iv), aad)?;
+            let mut encrypted_message = Vec::new();
+            for chunk in message.chunks(7) {
+                encrypted_message.extend_from_slice(&stream.update(chunk)?);
+            }
+            let (rest, tag) = stream.finalize()?;
+            encrypted_message.extend_from_slice(&rest);
+
+            assert_eq!(encrypted_message, expected_encrypted_message);
+            assert_eq!(tag, expected_tag);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_chacha20_poly1305_with_invalid_length() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        for enc in vec![
+            ChaCha20Poly1305JweEncryption::C20p,
+            ChaCha20Poly1305JweEncryption::Xc20p,
+        ] {
+            let key = util::random_bytes(enc.key_len());
+            let iv = util::random_bytes(enc.iv_len());
+
+            let err = enc.encrypt(&key, None, message, aad).unwrap_err();
+            assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+            assert_eq!(
+                err.to_string(),
+                "Invalid key format: An initialization vector is required."
+            );
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            let tag = tag.unwrap();
+            let err = enc
+                .decrypt(&key, Some(&iv), &encrypted_message, aad, Some(&tag[..15]))
+                .unwrap_err();
+            assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidTagLength));
+            assert_eq!(
+                err.to_string(),
+                "Invalid JWE format: The length of tag must be 16: 15"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_chacha20_poly1305_with_test_vector() -> Result<()> {
+        // RFC 8439 Section 2.8.2. Example and Test Vector for AEAD_CHACHA20_POLY1305
+        let enc = ChaCha20Poly1305JweEncryption::C20p;
+        let key = decode_hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let iv = decode_hex("070000004041424344454647");
+        let aad = decode_hex("50515253c0c1c2c3c4c5c6c7");
+        let message = b"Ladies and Gentlemen of the class of '99: \
+            If I could offer you only one tip for the future, sunscreen would be it.";
+        let expected_encrypted_message = decode_hex(concat!(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+            "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+            "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+            "3ff4def08e4b7a9de576d26586cec64b6116",
+        ));
+        let expected_tag = decode_hex("1ae10b594f09e26a7e902ecbd0600691");
+
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, &aad)?;
+        assert_eq!(encrypted_message, expected_encrypted_message);
+        assert_eq!(tag, Some(expected_tag.clone()));
+
+        let decrypted_message = enc.decrypt(
+            &key,
+            Some(&iv),
+            &expected_encrypted_message,
+            &aad,
+            Some(&expected_tag),
+        )?;
+        assert_eq!(&message[..], &decrypted_message[..]);
+
+        let mut modified_tag = expected_tag.clone();
+        modified_tag[15] ^= 0x01;
+        assert!(enc
+            .decrypt(
+                &key,
+                Some(&iv),
+                &expected_encrypted_message,
+                &aad,
+                Some(&modified_tag),
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hchacha20() {
+        // draft-irtf-cfrg-xchacha-03 Section 2.2.1. Test Vector for the HChaCha20 Block Function
+        let key = decode_hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        let nonce = decode_hex("000000090000004a0000000031415927");
+        let expected_subkey =
+            decode_hex("82413b4227b27bfed30e42508a877d73a0f9e4d58a74a853c12ec41326d3ecdc");
+
+        assert_eq!(hchacha20(&key, &nonce), expected_subkey);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_xchacha20_poly1305_with_test_vector() -> Result<()> {
+        // draft-irtf-cfrg-xchacha-03 Appendix A.3.1. AEAD_XCHACHA20_POLY1305
+        let enc = ChaCha20Poly1305JweEncryption::Xc20p;
+        let key = decode_hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let iv = decode_hex("404142434445464748494a4b4c4d4e4f5051525354555657");
+        let aad = decode_hex("50515253c0c1c2c3c4c5c6c7");
+        let message = b"Ladies and Gentlemen of the class of '99: \
+            If I could offer you only one tip for the future, sunscreen would be it.";
+        let expected_encrypted_message = decode_hex(concat!(
+            "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb",
+            "731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452",
+            "2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9",
+            "21f9664c97637da9768812f615c68b13b52e",
+        ));
+        let expected_tag = decode_hex("c0875924c1c7987947deafd8780acf49");
+
+        let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, &aad)?;
+        assert_eq!(encrypted_message, expected_encrypted_message);
+        assert_eq!(tag, Some(expected_tag.clone()));
+
+        let decrypted_message = enc.decrypt(
+            &key,
+            Some(&iv),
+            &expected_encrypted_message,
+            &aad,
+            Some(&expected_tag),
+        )?;
+        assert_eq!(&message[..], &decrypted_message[..]);
+
+        Ok(())
+    }
+}
@@ -11,6 +11,22 @@ pub trait JweCompression: Debug + Send + Sync {
 
     fn decompress(&self, message: &[u8]) -> Result<Vec<u8>, io::Error>;
 
+    /// Decompress a message, failing when the output exceeds `max_len` bytes.
+    ///
+    /// The default implementation checks the length after `decompress`, so implementations
+    /// should override it to stop decompressing as soon as the limit is exceeded.
+    fn decompress_with_limit(&self, message: &[u8], max_len: usize) -> Result<Vec<u8>, io::Error> {
+        let mut vec = self.decompress(message)?;
+        if vec.len() > max_len {
+            crate::util::zeroize(&mut vec);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The decompressed size exceeds {} bytes.", max_len),
+            ));
+        }
+        Ok(vec)
+    }
+
     fn box_clone(&self) -> Box<dyn JweCompression>;
 }
 
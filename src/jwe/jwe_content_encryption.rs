@@ -1,17 +1,51 @@
 use std::cmp::Eq;
 use std::fmt::Debug;
 
+use openssl::symm::{Cipher, Crypter, Mode};
+
+use crate::util;
 use crate::JoseError;
 
 /// Represent a algorithm of JWE enc header claim.
+///
+/// The implementations in `jwe::enc` are authenticated encryption with associated
+/// data (AEAD) and can also be used directly without the JWE framing.
+/// In that case, the caller is responsible for the key and for never reusing
+/// an initialization vector with the same key; a random one of `iv_len` bytes
+/// for each message is usually enough.
+///
+/// ```
+/// use josekit::jwe::enc::A256GCM;
+/// use josekit::jwe::JweContentEncryption;
+/// use josekit::util;
+///
+/// let key = util::random_bytes(A256GCM.key_len());
+/// let iv = util::random_bytes(A256GCM.iv_len());
+/// let aad = b"users.email";
+///
+/// let (encrypted, tag) = A256GCM.encrypt(&key, Some(&iv), b"joe@example.com", aad)?;
+/// let decrypted = A256GCM.decrypt(&key, Some(&iv), &encrypted, aad, tag.as_deref())?;
+/// assert_eq!(decrypted, b"joe@example.com");
+/// # Ok::<(), josekit::JoseError>(())
+/// ```
 pub trait JweContentEncryption: Debug + Send + Sync {
     /// Return the "enc" (encryption) header parameter value of JWE.
     fn name(&self) -> &str;
 
+    /// Return the byte length of the content encryption key.
     fn key_len(&self) -> usize;
 
+    /// Return the byte length of the initialization vector.
     fn iv_len(&self) -> usize;
 
+    /// Encrypt a message and return the encrypted message and the authentication tag.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The content encryption key.
+    /// * `iv` - The initialization vector. It must be unique for the key.
+    /// * `message` - The message to encrypt.
+    /// * `aad` - The additional authenticated data.
     fn encrypt(
         &self,
         key: &[u8],
@@ -20,6 +54,15 @@ pub trait JweContentEncryption: Debug + Send + Sync {
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError>;
 
+    /// Verify the authentication tag and return the decrypted message.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The content encryption key.
+    /// * `iv` - The initialization vector used to encrypt.
+    /// * `encrypted_message` - The encrypted message.
+    /// * `aad` - The additional authenticated data.
+    /// * `tag` - The authentication tag.
     fn decrypt(
         &self,
         key: &[u8],
@@ -29,9 +72,128 @@ pub trait JweContentEncryption: Debug + Send + Sync {
         tag: Option<&[u8]>,
     ) -> Result<Vec<u8>, JoseError>;
 
+    /// Return a stream to encrypt a message chunk by chunk.
+    ///
+    /// The default implementation buffers the whole message and encrypts it
+    /// when the stream is finalized.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The content encryption key.
+    /// * `iv` - The initialization vector.
+    /// * `aad` - The additional authenticated data.
+    fn encrypt_stream(
+        &self,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        aad: &[u8],
+    ) -> Result<Box<dyn JweEncryptStream>, JoseError> {
+        Ok(Box::new(BufferedJweEncryptStream {
+            enc: self.box_clone(),
+            key: key.to_vec(),
+            iv: iv.map(|val| val.to_vec()),
+            aad: aad.to_vec(),
+            message: Vec::new(),
+        }))
+    }
+
     fn box_clone(&self) -> Box<dyn JweContentEncryption>;
 }
 
+/// Represent a stream to encrypt a message of JWE chunk by chunk.
+///
+/// The additional authenticated data is fixed when the stream is created
+/// and the tag is only available after the stream is finalized.
+pub trait JweEncryptStream: Send {
+    /// Encrypt a chunk of the message and return the encrypted data.
+    /// The length of the encrypted data may be different from the chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - A chunk of the message.
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JoseError>;
+
+    /// Finish the encryption and return the rest of the encrypted data and the tag.
+    fn finalize(self: Box<Self>) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError>;
+}
+
+struct BufferedJweEncryptStream {
+    enc: Box<dyn JweContentEncryption>,
+    key: Vec<u8>,
+    iv: Option<Vec<u8>>,
+    aad: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl JweEncryptStream for BufferedJweEncryptStream {
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.message.extend_from_slice(chunk);
+        Ok(Vec::new())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
+        self.enc
+            .encrypt(&self.key, self.iv.as_deref(), &self.message, &self.aad)
+    }
+}
+
+impl Drop for BufferedJweEncryptStream {
+    fn drop(&mut self) {
+        util::zeroize(&mut self.key);
+        util::zeroize(&mut self.message);
+    }
+}
+
+/// A stream of AEAD ciphers that are supported by OpenSSL.
+pub(crate) struct AeadJweEncryptStream {
+    crypter: Crypter,
+    block_size: usize,
+    tag_len: usize,
+}
+
+impl AeadJweEncryptStream {
+    pub fn new(
+        cipher: Cipher,
+        key: &[u8],
+        iv: Option<&[u8]>,
+        aad: &[u8],
+        tag_len: usize,
+    ) -> anyhow::Result<Self> {
+        let mut crypter = Crypter::new(cipher, Mode::Encrypt, key, iv)?;
+        crypter.aad_update(aad)?;
+        Ok(Self {
+            crypter,
+            block_size: cipher.block_size(),
+            tag_len,
+        })
+    }
+}
+
+impl JweEncryptStream for AeadJweEncryptStream {
+    fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let mut encrypted_chunk = vec![0; chunk.len() + self.block_size];
+            let len = self.crypter.update(chunk, &mut encrypted_chunk)?;
+            encrypted_chunk.truncate(len);
+            Ok(encrypted_chunk)
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
+    fn finalize(mut self: Box<Self>) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
+        (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+            let mut encrypted_chunk = vec![0; self.block_size];
+            let len = self.crypter.finalize(&mut encrypted_chunk)?;
+            encrypted_chunk.truncate(len);
+
+            let mut tag = vec![0; self.tag_len];
+            self.crypter.get_tag(&mut tag)?;
+            Ok((encrypted_chunk, Some(tag)))
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+}
+
 impl PartialEq for Box<dyn JweContentEncryption> {
     fn eq(&self, other: &Self) -> bool {
         self == other
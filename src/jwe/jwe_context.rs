@@ -3,27 +3,63 @@ use std::cmp::Eq;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 
-use crate::jwe::enc::{A128CBC_HS256, A128GCM, A192CBC_HS384, A192GCM, A256CBC_HS512, A256GCM};
+use crate::jose_error::JoseErrorDetail;
+use crate::jwe::enc::{
+    A128CBC_HS256, A128GCM, A192CBC_HS384, A192GCM, A256CBC_HS512, A256GCM, C20P, XC20P,
+};
+#[cfg(feature = "aes-gcm-siv")]
+use crate::jwe::enc::{A128GCMSIV, A256GCMSIV};
 use crate::jwe::zip::Def;
 use crate::jwe::{
-    JweCompression, JweContentEncryption, JweDecrypter, JweEncrypter, JweHeader, JweHeaderSet,
+    CekMaterial, Dir, JweAlgorithm, JweCompression, JweContentEncryption, JweDecrypter,
+    JweEncrypter, JweHeader, JweHeaderSet, A128GCMKW, A128KW, A128KWP, A192GCMKW, A192KW, A192KWP,
+    A256GCMKW, A256KW, A256KWP, ECDH_ES, ECDH_ES_A128KW, ECDH_ES_A192KW, ECDH_ES_A256KW, RSA_OAEP,
+    RSA_OAEP_256, RSA_OAEP_384, RSA_OAEP_512,
 };
-use crate::util;
-use crate::{JoseError, JoseHeader, Map, Value};
+#[cfg(feature = "hpke")]
+use crate::jwe::{HPKE_0, HPKE_3};
+use crate::jwk::{Jwk, JwkSet};
+use crate::util::{self, OpensslRandomSource, RandomSource};
+use crate::{AlgorithmPolicy, JoseError, JoseErrorKind, JoseHeader, JsonType, Map, Value};
+
+/// The default maximum size of a decompressed payload (1 MiB).
+const DEFAULT_MAX_DECOMPRESSED_LEN: usize = 1024 * 1024;
+
+/// The default maximum size of a received JWE (8 MiB).
+const DEFAULT_MAX_TOKEN_SIZE: usize = 8 * 1024 * 1024;
+
+/// Key management algorithms that use a shared key or a key agreement result directly
+/// as the content encryption key.
+const DIRECT_ALGORITHMS: [&str; 3] = ["dir", "ECDH-ES", "ECDH-1PU"];
+
+/// Header claim names registered by RFC 7516 and RFC 7518 that must not be listed in crit.
+const REGISTERED_HEADER_CLAIMS: [&str; 20] = [
+    "alg", "enc", "zip", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty",
+    "crit", "epk", "apu", "apv", "iv", "tag", "p2s", "p2c",
+];
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JweContext {
     acceptable_criticals: BTreeSet<String>,
+    header_parameters: BTreeMap<String, JsonType>,
+    strict_header_parameters: bool,
     compressions: BTreeMap<String, Box<dyn JweCompression>>,
     content_encryptions: BTreeMap<String, Box<dyn JweContentEncryption>>,
+    max_decompressed_len: usize,
+    max_token_size: usize,
+    random_source: Option<Box<dyn RandomSource>>,
+    lenient_base64: bool,
+    algorithm_policy: Option<AlgorithmPolicy>,
 }
 
 impl JweContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            header_parameters: BTreeMap::new(),
+            strict_header_parameters: false,
             compressions: {
                 let compressions: Vec<Box<dyn JweCompression>> = vec![Box::new(Def)];
 
@@ -41,6 +77,12 @@ impl JweContext {
                     Box::new(A128GCM),
                     Box::new(A192GCM),
                     Box::new(A256GCM),
+                    Box::new(C20P),
+                    Box::new(XC20P),
+                    #[cfg(feature = "aes-gcm-siv")]
+                    Box::new(A128GCMSIV),
+                    #[cfg(feature = "aes-gcm-siv")]
+                    Box::new(A256GCMSIV),
                 ];
 
                 let mut map = BTreeMap::new();
@@ -49,16 +91,23 @@ impl JweContext {
                 }
                 map
             },
+            max_decompressed_len: DEFAULT_MAX_DECOMPRESSED_LEN,
+            max_token_size: DEFAULT_MAX_TOKEN_SIZE,
+            random_source: None,
+            lenient_base64: false,
+            algorithm_policy: None,
         }
     }
 
     /// Test a critical header claim name is acceptable.
     ///
+    /// A registered header parameter is also acceptable.
+    ///
     /// # Arguments
     ///
     /// * `name` - a critical header claim name
     pub fn is_acceptable_critical(&self, name: &str) -> bool {
-        self.acceptable_criticals.contains(name)
+        self.acceptable_criticals.contains(name) || self.header_parameters.contains_key(name)
     }
 
     /// Add a acceptable critical header claim name
@@ -79,6 +128,178 @@ impl JweContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Register a private header parameter with the expected type of its value.
+    ///
+    /// A registered header parameter is understood, so it is acceptable in the
+    /// crit header claim. When strict header parameters are enabled, a received
+    /// JWE whose value of the header parameter is of a different type is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    /// * `json_type` - the expected type of the value
+    pub fn register_header_parameter(&mut self, name: &str, json_type: JsonType) {
+        self.header_parameters.insert(name.to_string(), json_type);
+    }
+
+    /// Unregister a private header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn unregister_header_parameter(&mut self, name: &str) {
+        self.header_parameters.remove(name);
+    }
+
+    /// Return the expected type of a registered header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn header_parameter_type(&self, name: &str) -> Option<JsonType> {
+        self.header_parameters.get(name).copied()
+    }
+
+    /// Set whether the values of registered header parameters of a received JWE are
+    /// validated against their expected types. The default is false.
+    /// Unregistered header parameters are never validated.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to validate
+    pub fn set_strict_header_parameters(&mut self, value: bool) {
+        self.strict_header_parameters = value;
+    }
+
+    /// Test the values of registered header parameters are validated.
+    pub fn is_strict_header_parameters(&self) -> bool {
+        self.strict_header_parameters
+    }
+
+    fn check_header_parameters(&self, header: &Map<String, Value>) -> anyhow::Result<()> {
+        if !self.strict_header_parameters {
+            return Ok(());
+        }
+
+        for (name, json_type) in &self.header_parameters {
+            if let Some(value) = header.get(name) {
+                if !json_type.is_type_of(value) {
+                    bail!(
+                        "The JWE {} header claim must be a {}.",
+                        name,
+                        json_type.name()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the maximum size in bytes of a payload decompressed by the zip header claim.
+    ///
+    /// A payload that inflates beyond this size is rejected as a decompression bomb.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a maximum byte size
+    pub fn set_max_decompressed_len(&mut self, value: usize) {
+        self.max_decompressed_len = value;
+    }
+
+    /// Return the maximum size in bytes of a decompressed payload.
+    pub fn max_decompressed_len(&self) -> usize {
+        self.max_decompressed_len
+    }
+
+    /// Set the maximum size in bytes of a received JWE.
+    ///
+    /// A larger input is rejected before any part of it is decoded, which also
+    /// bounds the size of every decoded segment.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a maximum byte size
+    pub fn set_max_token_size(&mut self, value: usize) {
+        self.max_token_size = value;
+    }
+
+    /// Return the maximum size in bytes of a received JWE.
+    pub fn max_token_size(&self) -> usize {
+        self.max_token_size
+    }
+
+    fn check_token_size(&self, input: &[u8]) -> anyhow::Result<()> {
+        if input.len() > self.max_token_size {
+            bail!(
+                "The JWE size {} exceeds the maximum token size {}.",
+                input.len(),
+                self.max_token_size
+            );
+        }
+        Ok(())
+    }
+
+    /// Set a policy that restricts the key management algorithms (alg) and
+    /// the content encryption algorithms (enc) used to encrypt and decrypt a JWE.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - a algorithm policy
+    pub fn set_algorithm_policy(&mut self, policy: AlgorithmPolicy) {
+        self.algorithm_policy = Some(policy);
+    }
+
+    /// Return the policy that restricts the algorithms.
+    pub fn algorithm_policy(&self) -> Option<&AlgorithmPolicy> {
+        self.algorithm_policy.as_ref()
+    }
+
+    /// Remove the policy that restricts the algorithms.
+    pub fn remove_algorithm_policy(&mut self) {
+        self.algorithm_policy = None;
+    }
+
+    fn check_algorithm(&self, name: &str) -> Result<(), JoseError> {
+        match &self.algorithm_policy {
+            Some(policy) => policy.check(name),
+            None => Ok(()),
+        }
+    }
+
+    /// Set a source of random bytes for content encryption keys and initialization vectors.
+    ///
+    /// The default is the random generator of OpenSSL.
+    ///
+    /// # Arguments
+    ///
+    /// * `random_source` - a random source
+    pub fn set_random_source(&mut self, random_source: Box<dyn RandomSource>) {
+        self.random_source = Some(random_source);
+    }
+
+    /// Return the source of random bytes.
+    pub fn random_source(&self) -> &dyn RandomSource {
+        match &self.random_source {
+            Some(val) => val.as_ref(),
+            None => &OpensslRandomSource,
+        }
+    }
+
+    /// Set whether base64url segments of a received JWE may contain `=` padding
+    /// or the `+` and `/` characters of standard base64. The default is strict.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to decode leniently
+    pub fn set_lenient_base64(&mut self, value: bool) {
+        self.lenient_base64 = value;
+    }
+
+    /// Test base64url segments of a received JWE are decoded leniently.
+    pub fn is_lenient_base64(&self) -> bool {
+        self.lenient_base64
+    }
+
     /// Get a compression algorithm for zip header claim value.
     ///
     /// # Arguments
@@ -141,6 +362,66 @@ impl JweContext {
         self.content_encryptions.remove(name);
     }
 
+    /// Check a key management algorithm can be used with the number of recipients.
+    ///
+    /// With direct encryption (RFC 7518 section 4.5) and direct key agreement
+    /// (RFC 7518 section 4.6), the content encryption key is determined by the key
+    /// of the recipient, so there must be only one recipient.
+    fn check_recipient_count(alg: &str, count: usize) -> anyhow::Result<()> {
+        if count > 1 && DIRECT_ALGORITHMS.contains(&alg) {
+            bail!(
+                "The {} algorithm uses the key of the recipient as the content encryption key, \
+                so it cannot be used with multiple recipients.",
+                alg
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the critical header claim (crit) of a received JWE.
+    ///
+    /// The crit header claim must be protected, must not be empty, must not contain
+    /// header claim names registered by RFC 7516 or RFC 7518 and every name must be acceptable.
+    fn check_critical(
+        &self,
+        protected: &Map<String, Value>,
+        unprotected: &[&Map<String, Value>],
+    ) -> anyhow::Result<()> {
+        if unprotected.iter().any(|val| val.contains_key("crit")) {
+            bail!("The JWE crit header claim must be protected.");
+        }
+
+        let vals = match protected.get("crit") {
+            Some(Value::Array(vals)) => vals,
+            Some(_) => bail!("The JWE crit header claim must be a array."),
+            None => return Ok(()),
+        };
+
+        if vals.is_empty() {
+            bail!("The JWE crit header claim must not be empty.");
+        }
+
+        for val in vals {
+            let name = match val {
+                Value::String(val) => val.as_str(),
+                _ => bail!("An element of the JWE crit header claim must be a string."),
+            };
+
+            if REGISTERED_HEADER_CLAIMS.contains(&name) {
+                bail!(
+                    "The registered header claim '{}' must not be critical.",
+                    name
+                );
+            }
+
+            if !self.is_acceptable_critical(name) {
+                bail!("The critical name '{}' is not supported.", name);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -157,6 +438,65 @@ impl JweContext {
         self.serialize_compact_with_selector(payload, header, |_header| Some(encrypter))
     }
 
+    /// Return a representation of the data that is formatted by compact serialization,
+    /// using the specified initialization vector instead of a random one.
+    ///
+    /// This is intended for producing reproducible test vectors. Never use it in
+    /// production: encrypting two messages with the same key and IV breaks the
+    /// confidentiality and integrity of AES GCM and ChaCha20-Poly1305.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `header` - The JWE header claims.
+    /// * `encrypter` - The JWE encrypter.
+    /// * `iv` - The initialization vector. Its length must match the content encryption.
+    pub fn serialize_compact_with_iv(
+        &self,
+        payload: &[u8],
+        header: &JweHeader,
+        encrypter: &dyn JweEncrypter,
+        iv: &[u8],
+    ) -> Result<String, JoseError> {
+        self.serialize_compact_with(payload, header, |_header| Some(encrypter), Some(iv))
+    }
+
+    /// Return a representation of the data that is formatted by compact serialization,
+    /// encrypted for a recipient with a public (or shared) key in the JWK set.
+    ///
+    /// The algorithms are tried in order, and the first key that is selected by
+    /// `JwkSet::select_for_encryption` and accepted by the algorithm is used. If the kid
+    /// header claim is set, only keys with the same kid are tried. Otherwise the kid of
+    /// the selected key is set to the kid header claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `header` - The JWE header claims.
+    /// * `jwk_set` - The JWK set of the recipient.
+    /// * `algorithms` - The preferred key management algorithms.
+    pub fn serialize_compact_to_jwk_set(
+        &self,
+        payload: &[u8],
+        header: &JweHeader,
+        jwk_set: &JwkSet,
+        algorithms: &[&dyn JweAlgorithm],
+    ) -> Result<String, JoseError> {
+        for algorithm in algorithms {
+            for jwk in jwk_set.select_for_encryption(*algorithm, header.key_id()) {
+                if let Ok(encrypter) = encrypter_from_jwk(*algorithm, jwk) {
+                    return self.serialize_compact(payload, header, &*encrypter);
+                }
+            }
+        }
+
+        let names: Vec<&str> = algorithms.iter().map(|val| val.name()).collect();
+        Err(JoseError::InvalidKeyFormat(anyhow!(
+            "A key for {} is not found in the JWK set.",
+            names.join(", ")
+        )))
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -170,6 +510,19 @@ impl JweContext {
         header: &JweHeader,
         selector: F,
     ) -> Result<String, JoseError>
+    where
+        F: Fn(&JweHeader) -> Option<&'a dyn JweEncrypter>,
+    {
+        self.serialize_compact_with(payload, header, selector, None)
+    }
+
+    fn serialize_compact_with<'a, F>(
+        &self,
+        payload: &[u8],
+        header: &JweHeader,
+        selector: F,
+        fixed_iv: Option<&[u8]>,
+    ) -> Result<String, JoseError>
     where
         F: Fn(&JweHeader) -> Option<&'a dyn JweEncrypter>,
     {
@@ -186,6 +539,8 @@ impl JweContext {
                 },
                 None => bail!("A enc header claim is required."),
             };
+            self.check_algorithm(encrypter.algorithm().name())?;
+            self.check_algorithm(cencryption.name())?;
 
             let compression = match header.compression() {
                 Some(zip) => match self.get_compression(zip) {
@@ -198,13 +553,13 @@ impl JweContext {
             let mut out_header = header.clone();
 
             let key_len = cencryption.key_len();
-            let key = match encrypter.compute_content_encryption_key(
+            let mut key = match encrypter.compute_content_encryption_key(
                 cencryption,
                 &header,
                 &mut out_header,
             )? {
                 Some(val) => val,
-                None => Cow::Owned(util::random_bytes(key_len)),
+                None => Cow::Owned(self.random_source().random_bytes(key_len)),
             };
 
             let encrypted_key = encrypter.encrypt(&key, &header, &mut out_header)?;
@@ -228,15 +583,37 @@ impl JweContext {
             };
 
             let iv_vec;
-            let iv = if cencryption.iv_len() > 0 {
-                iv_vec = util::random_bytes(cencryption.iv_len());
-                Some(iv_vec.as_slice())
-            } else {
-                None
+            let iv = match fixed_iv {
+                Some(val) => {
+                    if val.len() != cencryption.iv_len() {
+                        bail!(JoseErrorDetail::new(
+                            JoseErrorKind::InvalidIvLength,
+                            format!(
+                                "The length of the IV for {} must be {}: {}",
+                                cencryption.name(),
+                                cencryption.iv_len(),
+                                val.len()
+                            )
+                        ));
+                    }
+                    if !val.is_empty() {
+                        Some(val)
+                    } else {
+                        None
+                    }
+                }
+                None if cencryption.iv_len() > 0 => {
+                    iv_vec = self.random_source().random_bytes(cencryption.iv_len());
+                    Some(iv_vec.as_slice())
+                }
+                None => None,
             };
 
-            let (ciphertext, tag) =
-                cencryption.encrypt(&key, iv, content, header_b64.as_bytes())?;
+            let result = cencryption.encrypt(&key, iv, content, header_b64.as_bytes());
+            if let Cow::Owned(val) = &mut key {
+                util::zeroize(val);
+            }
+            let (ciphertext, tag) = result?;
 
             let mut capacity = 4;
             capacity += header_b64.len();
@@ -397,6 +774,9 @@ impl JweContext {
                     Some(val) => val,
                     None => bail!("A encrypter is not found."),
                 };
+                self.check_algorithm(encrypter.algorithm().name())?;
+                self.check_algorithm(cencryption.name())?;
+                Self::check_recipient_count(encrypter.algorithm().name(), recipient_headers.len())?;
 
                 let mut recipient_header = match recipient_header {
                     Some(val) => (*val).clone(),
@@ -408,13 +788,13 @@ impl JweContext {
                     &merged,
                     &mut recipient_header,
                 )? {
-                    if let Some(selected_key) = &selected_key {
-                        if key.as_ref() != selected_key.as_ref() {
-                            bail!("A content encryption key must be only one.");
-                        }
-                    } else {
-                        selected_key = Some(key);
+                    if recipient_headers.len() > 1 {
+                        bail!(
+                            "The {} algorithm cannot be used with multiple recipients.",
+                            encrypter.algorithm().name()
+                        );
                     }
+                    selected_key = Some(key);
                 };
 
                 match merged.algorithm() {
@@ -441,13 +821,13 @@ impl JweContext {
                 None => bail!("A enc header claim is required."),
             };
 
-            let key = match &selected_key {
+            let mut key = match &selected_key {
                 Some(val) => Cow::Borrowed(val.as_ref()),
-                None => Cow::Owned(util::random_bytes(cencryption.key_len())),
+                None => Cow::Owned(self.random_source().random_bytes(cencryption.key_len())),
             };
 
             let iv = if cencryption.iv_len() > 0 {
-                Some(util::random_bytes(cencryption.iv_len()))
+                Some(self.random_source().random_bytes(cencryption.iv_len()))
             } else {
                 None
             };
@@ -498,8 +878,24 @@ impl JweContext {
                 payload
             };
 
-            let (ciphertext, tag) =
-                cencryption.encrypt(&key, iv.as_deref(), content, full_aad.as_bytes())?;
+            let result = cencryption
+                .encrypt(&key, iv.as_deref(), content, full_aad.as_bytes())
+                .and_then(|(ciphertext, tag)| {
+                    let mut encrypted_key_list = Vec::with_capacity(encrypter_list.len());
+                    for i in 0..encrypter_list.len() {
+                        let encrypted_key = encrypter_list[i].encrypt(
+                            &key,
+                            &merged_list[i],
+                            &mut recipient_header_list[i],
+                        )?;
+                        encrypted_key_list.push(encrypted_key);
+                    }
+                    Ok((ciphertext, tag, encrypted_key_list))
+                });
+            if let Cow::Owned(val) = &mut key {
+                util::zeroize(val);
+            }
+            let (ciphertext, tag, encrypted_key_list) = result?;
 
             let mut writed = false;
             let mut json = String::new();
@@ -528,11 +924,8 @@ impl JweContext {
                     json.push_str(",");
                 }
 
-                let merged = &merged_list[i];
-                let mut header = &mut recipient_header_list[i];
-                let encrypter = encrypter_list[i];
-
-                let encrypted_key = encrypter.encrypt(&key, &merged, &mut header)?;
+                let header = &recipient_header_list[i];
+                let encrypted_key = &encrypted_key_list[i];
 
                 if header.len() == 0 {
                    bail!("The per-recipient header must not be empty");
@@ -543,7 +936,7 @@ impl JweContext {
 
                 if let Some(val) = encrypted_key {
                     json.push_str(",\"encrypted_key\":\"");
-                    base64::encode_config_buf(&val, base64::URL_SAFE_NO_PAD, &mut json);
+                    base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut json);
                     json.push_str("\"");
                 }
                 json.push_str("}");
@@ -672,19 +1065,21 @@ impl JweContext {
                 Some(val) => val,
                 None => bail!("A encrypter is not found."),
             };
+            self.check_algorithm(encrypter.algorithm().name())?;
+            self.check_algorithm(cencryption.name())?;
 
             let mut protected = match header {
                 Some(val) => JweHeader::from_map(val.claims_set(true).clone())?,
                 None => JweHeader::new(),
             };
 
-            let key = match encrypter.compute_content_encryption_key(
+            let mut key = match encrypter.compute_content_encryption_key(
                 cencryption,
                 &merged,
                 &mut protected,
             )? {
                 Some(val) => val,
-                None => Cow::Owned(util::random_bytes(cencryption.key_len())),
+                None => Cow::Owned(self.random_source().random_bytes(cencryption.key_len())),
             };
 
             let encrypted_key = encrypter.encrypt(&key, &merged, &mut protected)?;
@@ -705,7 +1100,7 @@ impl JweContext {
 
             let iv_vec;
             let iv = if cencryption.iv_len() > 0 {
-                iv_vec = util::random_bytes(cencryption.iv_len());
+                iv_vec = self.random_source().random_bytes(cencryption.iv_len());
                 Some(iv_vec.as_slice())
             } else {
                 None
@@ -750,7 +1145,11 @@ impl JweContext {
                 payload
             };
 
-            let (ciphertext, tag) = cencryption.encrypt(&key, iv, content, full_aad.as_bytes())?;
+            let result = cencryption.encrypt(&key, iv, content, full_aad.as_bytes());
+            if let Cow::Owned(val) = &mut key {
+                util::zeroize(val);
+            }
+            let (ciphertext, tag) = result?;
 
             let mut writed = false;
             let mut json = String::new();
@@ -818,6 +1217,44 @@ impl JweContext {
         })
     }
 
+    /// Return the protected header of the input that is formatted by compact
+    /// serialization, without decrypting it.
+    ///
+    /// The returned header is untrusted: it is not authenticated until the
+    /// input has been decrypted. Use it only to decide how to decrypt the
+    /// input (e.g. to look up a key by `kid`).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    pub fn extract_header(&self, input: impl AsRef<[u8]>) -> Result<JweHeader, JoseError> {
+        (|| -> anyhow::Result<JweHeader> {
+            let input = input.as_ref();
+            self.check_token_size(input)?;
+
+            let indexies: Vec<usize> = input
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'.' as u8)
+                .map(|(pos, _)| pos)
+                .collect();
+            if indexies.len() != 4 {
+                bail!(
+                    "The compact serialization form of JWE must be five parts separated by colon."
+                );
+            }
+
+            let header = util::decode_base64_url(&input[0..indexies[0]], self.lenient_base64)?;
+            let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = JweHeader::from_map(header)?;
+            Ok(header)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+
     /// Deserialize the input that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -846,8 +1283,91 @@ impl JweContext {
     where
         F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
     {
-        (|| -> anyhow::Result<(Vec<u8>, JweHeader)> {
-            let input = input.as_ref();
+        let (payload, _, header) =
+            self.deserialize_compact_internal(input.as_ref(), selector, false)?;
+        Ok((payload, header))
+    }
+
+    /// Deserialize the input that is formatted by compact serialization,
+    /// trying the decrypters in order.
+    ///
+    /// This is for key rotation, where a recipient has several valid keys.
+    /// The payload decrypted by the first decrypter that succeeds is returned.
+    /// A failure of one decrypter doesn't stop the remaining ones from being tried,
+    /// and if all of them fail, the error lists every failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypters` - The JWE decrypters.
+    pub fn deserialize_compact_with_decrypters(
+        &self,
+        input: impl AsRef<[u8]>,
+        decrypters: &[&dyn JweDecrypter],
+    ) -> Result<(Vec<u8>, JweHeader), JoseError> {
+        let input = input.as_ref();
+
+        let mut errors = Vec::with_capacity(decrypters.len());
+        for decrypter in decrypters {
+            match self.deserialize_compact(input, *decrypter) {
+                Ok(val) => return Ok(val),
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+
+        if errors.is_empty() {
+            return Err(JoseError::InvalidJweFormat(anyhow!(
+                "A decrypter is not found."
+            )));
+        }
+
+        Err(JoseError::InvalidJweFormat(anyhow!(
+            "None of the decrypters can decrypt the JWE: {}",
+            errors
+                .iter()
+                .enumerate()
+                .map(|(i, err)| format!("[{}] {}", i, err))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )))
+    }
+
+    /// Deserialize the input that is formatted by compact serialization,
+    /// and return the content encryption key (CEK) together with the payload.
+    ///
+    /// This is for protocols that derive further keys from the CEK.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn deserialize_compact_with_cek(
+        &self,
+        input: impl AsRef<[u8]>,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<(Vec<u8>, CekMaterial, JweHeader), JoseError> {
+        let (payload, cek, header) =
+            self.deserialize_compact_internal(input.as_ref(), |_header| Ok(Some(decrypter)), true)?;
+        match cek {
+            Some(val) => Ok((payload, val, header)),
+            None => Err(JoseError::InvalidJweFormat(anyhow!(
+                "The content encryption key is not available."
+            ))),
+        }
+    }
+
+    fn deserialize_compact_internal<'a, F>(
+        &self,
+        input: &[u8],
+        selector: F,
+        return_cek: bool,
+    ) -> Result<(Vec<u8>, Option<CekMaterial>, JweHeader), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<(Vec<u8>, Option<CekMaterial>, JweHeader)> {
+            self.check_token_size(input)?;
+
             let indexies: Vec<usize> = input
                 .iter()
                 .enumerate()
@@ -866,7 +1386,7 @@ impl JweContext {
             let encrypted_key_vec;
             let encrypted_key = if encrypted_key_b64.len() > 0 {
                 encrypted_key_vec =
-                    base64::decode_config(encrypted_key_b64, base64::URL_SAFE_NO_PAD)?;
+                    util::decode_base64_url(encrypted_key_b64, self.lenient_base64)?;
                 Some(encrypted_key_vec.as_slice())
             } else {
                 None
@@ -875,26 +1395,28 @@ impl JweContext {
             let iv_b64 = &input[(indexies[1] + 1)..(indexies[2])];
             let iv_vec;
             let iv = if iv_b64.len() > 0 {
-                iv_vec = base64::decode_config(iv_b64, base64::URL_SAFE_NO_PAD)?;
+                iv_vec = util::decode_base64_url(iv_b64, self.lenient_base64)?;
                 Some(iv_vec.as_slice())
             } else {
                 None
             };
 
             let ciphertext_b64 = &input[(indexies[2] + 1)..(indexies[3])];
-            let ciphertext = base64::decode_config(ciphertext_b64, base64::URL_SAFE_NO_PAD)?;
+            let ciphertext = util::decode_base64_url(ciphertext_b64, self.lenient_base64)?;
 
             let tag_b64 = &input[(indexies[3] + 1)..];
             let tag_vec;
             let tag = if tag_b64.len() > 0 {
-                tag_vec = base64::decode_config(tag_b64, base64::URL_SAFE_NO_PAD)?;
+                tag_vec = util::decode_base64_url(tag_b64, self.lenient_base64)?;
                 Some(tag_vec.as_slice())
             } else {
                 None
             };
 
-            let header = base64::decode_config(header_b64, base64::URL_SAFE_NO_PAD)?;
+            let header = util::decode_base64_url(header_b64, self.lenient_base64)?;
             let merged: Map<String, Value> = serde_json::from_slice(&header)?;
+            self.check_critical(&merged, &[])?;
+            self.check_header_parameters(&merged)?;
             let merged = JweHeader::from_map(merged)?;
 
             let decrypter = match selector(&merged)? {
@@ -910,13 +1432,15 @@ impl JweContext {
                 Some(_) => bail!("A enc header claim must be a string."),
                 None => bail!("A enc header claim is required."),
             };
+            self.check_algorithm(decrypter.algorithm().name())?;
+            self.check_algorithm(cencryption.name())?;
 
             let compression = match merged.claim("zip") {
                 Some(Value::String(val)) => match self.get_compression(val) {
                     Some(val2) => Some(val2),
                     None => bail!("A compression algorithm is not registered: {}", val),
                 },
-                Some(_) => bail!("A enc header claim must be a string."),
+                Some(_) => bail!("A zip header claim must be a string."),
                 None => None,
             };
 
@@ -940,22 +1464,40 @@ impl JweContext {
                 None => {}
             }
 
-            let key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
+            let mut key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
             if key.len() != cencryption.key_len() {
-                bail!(
-                    "The key size is expected to be {}: {}",
-                    cencryption.key_len(),
-                    key.len()
-                );
+                if let Cow::Owned(val) = &mut key {
+                    util::zeroize(val);
+                }
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The key size is expected to be {}: {}",
+                        cencryption.key_len(),
+                        key.len()
+                    )
+                ));
             }
 
-            let content = cencryption.decrypt(&key, iv, &ciphertext, header_b64, tag)?;
+            let result = cencryption.decrypt(&key, iv, &ciphertext, header_b64, tag);
+            let cek = match (&result, return_cek) {
+                (Ok(_), true) => Some(CekMaterial::new(&key)),
+                _ => None,
+            };
+            if let Cow::Owned(val) = &mut key {
+                util::zeroize(val);
+            }
             let content = match compression {
-                Some(val) => val.decompress(&content)?,
-                None => content,
+                Some(val) => {
+                    let mut content = result?;
+                    let result = val.decompress_with_limit(&content, self.max_decompressed_len);
+                    util::zeroize(&mut content);
+                    result?
+                }
+                None => result?,
             };
 
-            Ok((content, merged))
+            Ok((content, cek, merged))
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
             Ok(err) => err,
@@ -1011,8 +1553,73 @@ impl JweContext {
     where
         F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
     {
-        (|| -> anyhow::Result<(Vec<u8>, JweHeader)> {
+        let (payload, header, recipient_header) =
+            self.deserialize_json_header_set_with_selector(input, selector)?;
+        let mut merged = header.to_map();
+        for (key, value) in recipient_header.claims_set() {
+            merged.insert(key.clone(), value.clone());
+        }
+        let merged = JweHeader::from_map(merged)?;
+        Ok((payload, merged))
+    }
+
+    /// Deserialize the input that is formatted by flattened json serialization,
+    /// keeping the shared protected, shared unprotected and per-recipient header claims apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn deserialize_json_header_set<'a>(
+        &self,
+        input: impl AsRef<[u8]>,
+        decrypter: &'a dyn JweDecrypter,
+    ) -> Result<(Vec<u8>, JweHeaderSet, JweHeader), JoseError> {
+        self.deserialize_json_header_set_with_selector(input, |header| {
+            match header.algorithm() {
+                Some(val) => {
+                    let expected_alg = decrypter.algorithm().name();
+                    if val != expected_alg {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+
+            match decrypter.key_id() {
+                Some(expected) => match header.key_id() {
+                    Some(actual) if expected == actual => {}
+                    _ => return Ok(None),
+                },
+                None => {}
+            }
+
+            Ok(Some(decrypter))
+        })
+    }
+
+    /// Deserialize the input that is formatted by flattened json serialization,
+    /// keeping the shared protected, shared unprotected and per-recipient header claims apart.
+    ///
+    /// The selector receives the merged header claims, so unprotected claims
+    /// like kid can be used for key lookup before decryption.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `selector` - a function for selecting the decrypting algorithm.
+    pub fn deserialize_json_header_set_with_selector<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeaderSet, JweHeader), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<(Vec<u8>, JweHeaderSet, JweHeader)> {
             let input = input.as_ref();
+            self.check_token_size(input)?;
+
             let mut map: Map<String, Value> = serde_json::from_slice(input)?;
 
             let (protected, protected_b64) = match map.remove("protected") {
@@ -1020,7 +1627,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The protected field must be empty.");
                     }
-                    let vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    let vec = util::decode_base64_url(&val, self.lenient_base64)?;
                     let json: Map<String, Value> = serde_json::from_slice(&vec)?;
                     (Some(json), Some(val))
                 }
@@ -1040,7 +1647,7 @@ impl JweContext {
             let aad_b64 = match map.remove("aad") {
                 Some(Value::String(val)) => {
                     if val.len() == 0 {
-                        bail!("The JWE aad field must be empty.");
+                        bail!("The JWE aad field must not be empty.");
                     } else if !util::is_base64_url_safe_nopad(&val) {
                         bail!("The JWE aad field must be a base64 string.");
                     }
@@ -1055,7 +1662,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The iv field must be empty.");
                     }
-                    iv_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    iv_vec = util::decode_base64_url(&val, self.lenient_base64)?;
                     Some(iv_vec.as_slice())
                 }
                 Some(_) => bail!("The iv field must be string."),
@@ -1066,7 +1673,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The ciphertext field must be empty.");
                     }
-                    base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?
+                    util::decode_base64_url(&val, self.lenient_base64)?
                 }
                 Some(_) => bail!("The ciphertext field must be string."),
                 None => bail!("The ciphertext field is required."),
@@ -1077,7 +1684,7 @@ impl JweContext {
                     if val.len() == 0 {
                         bail!("The tag field must be empty.");
                     }
-                    tag_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    tag_vec = util::decode_base64_url(&val, self.lenient_base64)?;
                     Some(tag_vec.as_slice())
                 }
                 Some(_) => bail!("The tag field must be string."),
@@ -1107,6 +1714,7 @@ impl JweContext {
                 }
             };
 
+            let mut last_err = None;
             for mut recipient in recipients {
                 let header = recipient.remove("header");
 
@@ -1116,19 +1724,31 @@ impl JweContext {
                         if val.len() == 0 {
                             bail!("The encrypted_key field must be empty.");
                         }
-                        encrypted_key_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                        encrypted_key_vec = util::decode_base64_url(&val, self.lenient_base64)?;
                         Some(encrypted_key_vec.as_slice())
                     }
                     Some(_) => bail!("The encrypted_key field must be a string."),
                     None => None,
                 };
 
-                let mut merged = match header {
+                let recipient_map = match header {
                     Some(Value::Object(val)) => val,
                     Some(_) => bail!("The protected field must be a object."),
                     None => Map::new(),
                 };
 
+                let empty = Map::new();
+                let mut unprotected_maps = vec![&recipient_map];
+                if let Some(val) = &unprotected {
+                    unprotected_maps.push(val);
+                }
+                self.check_critical(protected.as_ref().unwrap_or(&empty), &unprotected_maps)?;
+                if unprotected_maps.iter().any(|val| val.contains_key("zip")) {
+                    bail!("The JWE zip header claim must be protected.");
+                }
+
+                let mut merged = recipient_map.clone();
+
                 if let Some(val) = &unprotected {
                     for (key, value) in val {
                         if merged.contains_key(key) {
@@ -1149,6 +1769,7 @@ impl JweContext {
                     }
                 }
 
+                self.check_header_parameters(&merged)?;
                 let merged = JweHeader::from_map(merged)?;
 
                 let decrypter = match selector(&merged)? {
@@ -1164,13 +1785,15 @@ impl JweContext {
                     Some(_) => bail!("A enc header claim must be string."),
                     None => bail!("A enc header claim is required."),
                 };
+                self.check_algorithm(decrypter.algorithm().name())?;
+                self.check_algorithm(cencryption.name())?;
 
                 let compression = match merged.claim("zip") {
                     Some(Value::String(val)) => match self.get_compression(val) {
                         Some(val2) => Some(val2),
                         None => bail!("A compression algorithm is not registered: {}", val),
                     },
-                    Some(_) => bail!("A enc header claim must be string."),
+                    Some(_) => bail!("A zip header claim must be string."),
                     None => None,
                 };
 
@@ -1193,34 +1816,65 @@ impl JweContext {
                     None => {}
                 }
 
-                let mut full_aad = match protected_b64 {
-                    Some(val) => val,
+                let mut full_aad = match &protected_b64 {
+                    Some(val) => val.clone(),
                     None => String::new(),
                 };
-                if let Some(val) = aad_b64 {
+                if let Some(val) = &aad_b64 {
                     full_aad.push_str(".");
-                    full_aad.push_str(&val);
+                    full_aad.push_str(val);
                 }
 
-                let key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
-                if key.len() != cencryption.key_len() {
-                    bail!(
-                        "The key size is expected to be {}: {}",
-                        cencryption.key_len(),
-                        key.len()
-                    );
-                }
-
-                let content =
-                    cencryption.decrypt(&key, iv, &ciphertext, full_aad.as_bytes(), tag)?;
-                let content = match compression {
-                    Some(val) => val.decompress(&content)?,
-                    None => content,
-                };
+                let result = (|| -> anyhow::Result<Vec<u8>> {
+                    let mut key = decrypter.decrypt(encrypted_key, cencryption, &merged)?;
+                    if key.len() != cencryption.key_len() {
+                        if let Cow::Owned(val) = &mut key {
+                            util::zeroize(val);
+                        }
+                        bail!(JoseErrorDetail::new(
+                            JoseErrorKind::InvalidKeyLength,
+                            format!(
+                                "The key size is expected to be {}: {}",
+                                cencryption.key_len(),
+                                key.len()
+                            )
+                        ));
+                    }
 
-                return Ok((content, merged));
+                    let result =
+                        cencryption.decrypt(&key, iv, &ciphertext, full_aad.as_bytes(), tag);
+                    if let Cow::Owned(val) = &mut key {
+                        util::zeroize(val);
+                    }
+                    let content = match compression {
+                        Some(val) => {
+                            let mut content = result?;
+                            let result =
+                                val.decompress_with_limit(&content, self.max_decompressed_len);
+                            util::zeroize(&mut content);
+                            result?
+                        }
+                        None => result?,
+                    };
+                    Ok(content)
+                })();
+
+                match result {
+                    Ok(content) => {
+                        let header = JweHeaderSet::from_maps(
+                            protected.clone().unwrap_or_default(),
+                            unprotected.clone().unwrap_or_default(),
+                        );
+                        let recipient_header = JweHeader::from_map(recipient_map)?;
+                        return Ok((content, header, recipient_header));
+                    }
+                    Err(err) => last_err = Some(err),
+                }
             }
 
+            if let Some(err) = last_err {
+                return Err(err);
+            }
             bail!("A recipient that matched the header claims is not found.");
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -1230,19 +1884,59 @@ impl JweContext {
     }
 }
 
+fn encrypter_from_jwk(
+    algorithm: &dyn JweAlgorithm,
+    jwk: &Jwk,
+) -> Result<Box<dyn JweEncrypter>, JoseError> {
+    let encrypter: Box<dyn JweEncrypter> = match algorithm.name() {
+        "RSA-OAEP" => Box::new(RSA_OAEP.encrypter_from_jwk(jwk)?),
+        "RSA-OAEP-256" => Box::new(RSA_OAEP_256.encrypter_from_jwk(jwk)?),
+        "RSA-OAEP-384" => Box::new(RSA_OAEP_384.encrypter_from_jwk(jwk)?),
+        "RSA-OAEP-512" => Box::new(RSA_OAEP_512.encrypter_from_jwk(jwk)?),
+        "ECDH-ES" => Box::new(ECDH_ES.encrypter_from_jwk(jwk)?),
+        "ECDH-ES+A128KW" => Box::new(ECDH_ES_A128KW.encrypter_from_jwk(jwk)?),
+        "ECDH-ES+A192KW" => Box::new(ECDH_ES_A192KW.encrypter_from_jwk(jwk)?),
+        "ECDH-ES+A256KW" => Box::new(ECDH_ES_A256KW.encrypter_from_jwk(jwk)?),
+        "A128KW" => Box::new(A128KW.encrypter_from_jwk(jwk)?),
+        "A192KW" => Box::new(A192KW.encrypter_from_jwk(jwk)?),
+        "A256KW" => Box::new(A256KW.encrypter_from_jwk(jwk)?),
+        "A128KWP" => Box::new(A128KWP.encrypter_from_jwk(jwk)?),
+        "A192KWP" => Box::new(A192KWP.encrypter_from_jwk(jwk)?),
+        "A256KWP" => Box::new(A256KWP.encrypter_from_jwk(jwk)?),
+        "A128GCMKW" => Box::new(A128GCMKW.encrypter_from_jwk(jwk)?),
+        "A192GCMKW" => Box::new(A192GCMKW.encrypter_from_jwk(jwk)?),
+        "A256GCMKW" => Box::new(A256GCMKW.encrypter_from_jwk(jwk)?),
+        "dir" => Box::new(Dir.encrypter_from_jwk(jwk)?),
+        #[cfg(feature = "hpke")]
+        "HPKE-0" => Box::new(HPKE_0.encrypter_from_jwk(jwk)?),
+        #[cfg(feature = "hpke")]
+        "HPKE-3" => Box::new(HPKE_3.encrypter_from_jwk(jwk)?),
+        val => {
+            return Err(JoseError::UnsupportedAlgorithm(anyhow!(
+                "The algorithm cannot be used with a JWK set: {}",
+                val
+            )))
+        }
+    };
+    Ok(encrypter)
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use crate::JoseErrorKind;
     use crate::jwe::{
         alg::direct::DirectJweAlgorithm,
         JweHeader, JweHeaderSet,
         serialize_compact, deserialize_compact,
-        serialize_flattened_json, serialize_general_json, deserialize_json
+        serialize_flattened_json, serialize_general_json, deserialize_json,
+        A128KW, Dir, JweContext
     };
 
-    const CONTENT_CIPHERS: [(&str, usize); 6] = [
+    const CONTENT_CIPHERS: [(&str, usize); 8] = [
         ("A128CBC-HS256", 32), ("A192CBC-HS384", 48), ("A256CBC-HS512", 64),
-        ("A128GCM", 16), ("A192GCM", 24), ("A256GCM", 32)
+        ("A128GCM", 16), ("A192GCM", 24), ("A256GCM", 32),
+        ("C20P", 32), ("XC20P", 32)
     ];
 
     #[test]
@@ -1303,4 +1997,75 @@ mod tests {
         }
         Ok(())
     }
+    #[test]
+    fn deserialize_compact_rfc7516_a3() -> Result<()> {
+        // RFC 7516 Appendix A.3: the AAD is the encoded protected header.
+        let jwe = "eyJhbGciOiJBMTI4S1ciLCJlbmMiOiJBMTI4Q0JDLUhTMjU2In0.\
+            6KB707dM9YTIgHtLvtgWQ8mKwboJW3of9locizkDTHzBC2IlrT1oOQ.\
+            AxY8DCtDaGlsbGljb3RoZQ.\
+            KDlTtXchhZTGufMYmOYGS4HffxPSUrfmqCHXaI9wOGY.\
+            U0m_YmjN04DJvceFICbCVQ";
+        let key = base64::decode_config("GawgguFyGrWKav7AX4VKUg", base64::URL_SAFE_NO_PAD)?;
+
+        let decrypter = A128KW.decrypter_from_bytes(&key)?;
+        let (data, header) = deserialize_compact(jwe, &decrypter)?;
+        assert_eq!(data, b"Live long and prosper.");
+        assert_eq!(header.content_encryption(), Some("A128CBC-HS256"));
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_flattened_json_with_aad() -> Result<()> {
+        // Produced by pyca/cryptography AES-GCM with the AAD
+        // ASCII(BASE64URL(protected) || '.' || BASE64URL(aad)).
+        let jwe = r#"{
+            "protected": "eyJhbGciOiJkaXIiLCJlbmMiOiJBMTI4R0NNIn0",
+            "aad": "VGhlIEZlbGxvd3NoaXAgb2YgdGhlIFJpbmc",
+            "iv": "AAECAwQFBgcICQoL",
+            "ciphertext": "3wXRq0Z3mDos8gDkUoMAetxVaoMhww",
+            "tag": "DZSsz4TPcm0yZPIGKn_uOg"
+        }"#;
+        let key = base64::decode_config("AAECAwQFBgcICQoLDA0ODw", base64::URL_SAFE_NO_PAD)?;
+
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+        let (data, _header) = deserialize_json(jwe, &decrypter)?;
+        assert_eq!(data, b"Live long and prosper.");
+
+        let tampered = jwe.replace("VGhlIEZlbGxvd3NoaXAgb2YgdGhlIFJpbmc", "VGhlIFR3byBUb3dlcnM");
+        assert!(deserialize_json(&tampered, &decrypter).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_compact_with_fixed_iv() -> Result<()> {
+        // Produced by pyca/cryptography AES-GCM with the same key and IV.
+        let expected = "eyJlbmMiOiJBMTI4R0NNIiwiYWxnIjoiZGlyIn0..\
+            AAECAwQFBgcICQoL.\
+            3wXRq0Z3mDos8gDkUoMAetxVaoMhww.\
+            5lJyxiwUSPY94IB_jzxUEg";
+        let key = base64::decode_config("AAECAwQFBgcICQoLDA0ODw", base64::URL_SAFE_NO_PAD)?;
+        let iv = base64::decode_config("AAECAwQFBgcICQoL", base64::URL_SAFE_NO_PAD)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        let encrypter = Dir.encrypter_from_bytes(&key)?;
+        let context = JweContext::new();
+        let jwe = context.serialize_compact_with_iv(
+            b"Live long and prosper.",
+            &header,
+            &encrypter,
+            &iv,
+        )?;
+        assert_eq!(jwe, expected);
+
+        let decrypter = Dir.decrypter_from_bytes(&key)?;
+        let (data, _header) = deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(data, b"Live long and prosper.");
+
+        let err = context
+            .serialize_compact_with_iv(b"Live long and prosper.", &header, &encrypter, &iv[..8])
+            .expect_err("A short IV was accepted");
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidIvLength));
+        Ok(())
+    }
 }
@@ -7,7 +7,7 @@ use anyhow::bail;
 
 use crate::jwk::Jwk;
 use crate::util;
-use crate::{JoseError, JoseHeader, Map, Value};
+use crate::{JoseError, JoseHeader, Map, Number, Value};
 
 /// Represent JWE header claims
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -284,18 +284,26 @@ impl JweHeader {
 
     /// Set a value for content type header claim (cty).
     ///
+    /// A media type of the form "application/foo" is stored in the short form "foo",
+    /// as RFC 7515 recommends. Any other value, such as "JWT" or "text/plain", is
+    /// stored as is.
+    ///
     /// # Arguments
     ///
     /// * `value` - a content type (e.g. "JWT")
     pub fn set_content_type(&mut self, value: impl Into<String>) {
         let value: String = value.into();
+        let value = util::compact_media_type(&value).to_string();
         self.claims.insert("cty".to_string(), Value::String(value));
     }
 
-    /// Return the value for content type header claim (cty).
+    /// Return the value for content type header claim (cty) in the short form.
+    ///
+    /// A received "application/foo" is returned as "foo", the same as a value set by
+    /// `set_content_type`. The value as received is available by `claim("cty")`.
     pub fn content_type(&self) -> Option<&str> {
         match self.claims.get("cty") {
-            Some(Value::String(val)) => Some(val),
+            Some(Value::String(val)) => Some(util::compact_media_type(val)),
             _ => None,
         }
     }
@@ -415,6 +423,113 @@ impl JweHeader {
         }
     }
 
+    /// Set a value for ephemeral public key header claim (epk).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A ephemeral public key that is formatted by a JWK
+    pub fn set_ephemeral_public_key(&mut self, value: Jwk) {
+        let key = "epk";
+        let value: Map<String, Value> = value.into();
+        self.claims.insert(key.to_string(), Value::Object(value));
+    }
+
+    /// Return the value for ephemeral public key header claim (epk).
+    pub fn ephemeral_public_key(&self) -> Option<Jwk> {
+        match self.claims.get("epk") {
+            Some(Value::Object(vals)) => match Jwk::from_map(vals.clone()) {
+                Ok(val) => Some(val),
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Set a value for initialization vector header claim (iv) of key wrapping with AES GCM.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A initialization vector
+    pub fn set_initialization_vector(&mut self, value: impl AsRef<[u8]>) {
+        let key = "iv";
+        let val = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
+        self.claims.insert(key.to_string(), Value::String(val));
+    }
+
+    /// Return the value for initialization vector header claim (iv).
+    pub fn initialization_vector(&self) -> Option<Vec<u8>> {
+        match self.claims.get("iv") {
+            Some(Value::String(val)) => match base64::decode_config(val, base64::URL_SAFE_NO_PAD) {
+                Ok(val2) => Some(val2),
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Set a value for authentication tag header claim (tag) of key wrapping with AES GCM.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A authentication tag
+    pub fn set_authentication_tag(&mut self, value: impl AsRef<[u8]>) {
+        let key = "tag";
+        let val = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
+        self.claims.insert(key.to_string(), Value::String(val));
+    }
+
+    /// Return the value for authentication tag header claim (tag).
+    pub fn authentication_tag(&self) -> Option<Vec<u8>> {
+        match self.claims.get("tag") {
+            Some(Value::String(val)) => match base64::decode_config(val, base64::URL_SAFE_NO_PAD) {
+                Ok(val2) => Some(val2),
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Set a value for PBES2 salt input header claim (p2s).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A PBES2 salt input
+    pub fn set_pbes2_salt_input(&mut self, value: impl AsRef<[u8]>) {
+        let key = "p2s";
+        let val = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
+        self.claims.insert(key.to_string(), Value::String(val));
+    }
+
+    /// Return the value for PBES2 salt input header claim (p2s).
+    pub fn pbes2_salt_input(&self) -> Option<Vec<u8>> {
+        match self.claims.get("p2s") {
+            Some(Value::String(val)) => match base64::decode_config(val, base64::URL_SAFE_NO_PAD) {
+                Ok(val2) => Some(val2),
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Set a value for PBES2 count header claim (p2c).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A PBES2 iteration count
+    pub fn set_pbes2_count(&mut self, value: u64) {
+        let key = "p2c";
+        self.claims
+            .insert(key.to_string(), Value::Number(Number::from(value)));
+    }
+
+    /// Return the value for PBES2 count header claim (p2c).
+    pub fn pbes2_count(&self) -> Option<u64> {
+        match self.claims.get("p2c") {
+            Some(Value::Number(val)) => val.as_u64(),
+            _ => None,
+        }
+    }
+
     /// Set a value for issuer header claim (iss).
     ///
     /// # Arguments
@@ -509,14 +624,17 @@ impl JweHeader {
                 self.claims.insert(key.to_string(), val);
             }
             None => {
-                self.claims.remove(key);
+                self.claims.shift_remove(key);
             }
         }
 
         Ok(())
     }
 
-    /// Return values for header claims set
+    /// Return values for header claims set.
+    ///
+    /// The claims are kept in the order they were set, and the header is serialized
+    /// in this order, so the encoded header bytes can be reproduced by the caller.
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
     }
@@ -571,6 +689,14 @@ impl JweHeader {
                     }
                     _ => bail!("The JWE {} header claim must be a string.", key),
                 },
+                "iv" | "tag" | "p2s" => match &value {
+                    Value::String(val) => {
+                        if !util::is_base64_url_safe_nopad(val) {
+                            bail!("The JWE {} header claim must be a base64 string.", key);
+                        }
+                    }
+                    _ => bail!("The JWE {} header claim must be a string.", key),
+                },
                 "x5c" => match &value {
                     Value::Array(vals) => {
                         for val in vals {
@@ -596,6 +722,17 @@ impl JweHeader {
                     Value::Object(vals) => Jwk::check_map(vals)?,
                     _ => bail!("The JWE {} header claim must be a string.", key),
                 },
+                "epk" => match &value {
+                    Value::Object(vals) => Jwk::check_map(vals)?,
+                    _ => bail!("The JWE {} header claim must be a object.", key),
+                },
+                "p2c" => match &value {
+                    Value::Number(val) => match val.as_u64() {
+                        Some(val) if val > 0 => {}
+                        _ => bail!("The JWE {} header claim must be a positive integer.", key),
+                    },
+                    _ => bail!("The JWE {} header claim must be a positive integer.", key),
+                },
                 _ => {}
             }
 
@@ -651,8 +788,9 @@ mod tests {
     use anyhow::Result;
     use serde_json::json;
 
-    use crate::jwe::JweHeader;
-    use crate::jwk::Jwk;
+    use crate::jwe::{self, JweHeader, A128GCMKW, ECDH_ES, PBES2_HS256_A128KW};
+    use crate::jwk::{Jwk, KeyPair, P_256};
+    use crate::JoseError;
 
     #[test]
     fn test_new_jwe_header() -> Result<()> {
@@ -675,6 +813,11 @@ mod tests {
         header.set_nonce(b"nonce");
         header.set_agreement_partyuinfo(b"apu");
         header.set_agreement_partyvinfo(b"apv");
+        header.set_ephemeral_public_key(jwk.clone());
+        header.set_initialization_vector(b"iv");
+        header.set_authentication_tag(b"tag");
+        header.set_pbes2_salt_input(b"p2s");
+        header.set_pbes2_count(1000);
         header.set_issuer("iss");
         header.set_subject("sub");
         header.set_claim("header_claim", Some(json!("header_claim")))?;
@@ -704,6 +847,11 @@ mod tests {
         assert!(matches!(header.nonce(), Some(val) if val == b"nonce".to_vec()));
         assert!(matches!(header.agreement_partyuinfo(), Some(val) if val == b"apu".to_vec()));
         assert!(matches!(header.agreement_partyvinfo(), Some(val) if val == b"apv".to_vec()));
+        assert!(matches!(header.ephemeral_public_key(), Some(val) if val == jwk));
+        assert!(matches!(header.initialization_vector(), Some(val) if val == b"iv".to_vec()));
+        assert!(matches!(header.authentication_tag(), Some(val) if val == b"tag".to_vec()));
+        assert!(matches!(header.pbes2_salt_input(), Some(val) if val == b"p2s".to_vec()));
+        assert!(matches!(header.pbes2_count(), Some(1000)));
         assert!(matches!(header.issuer(), Some("iss")));
         assert!(matches!(header.subject(), Some("sub")));
         assert!(matches!(header.critical(), Some(vals) if vals == vec!["crit0", "crit1"]));
@@ -711,4 +859,103 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_content_type() -> Result<()> {
+        let mut header = JweHeader::new();
+        for (value, expected) in vec![
+            ("JWT", "JWT"),
+            ("application/cbor", "cbor"),
+            ("Application/cbor", "cbor"),
+            ("application/vnd.example+json", "vnd.example+json"),
+            ("application/foo/bar", "application/foo/bar"),
+            ("text/plain", "text/plain"),
+        ] {
+            header.set_content_type(value);
+            assert_eq!(header.content_type(), Some(expected));
+            assert_eq!(header.claim("cty"), Some(&json!(expected)));
+        }
+
+        // A received value is kept as is and read in the short form.
+        let header = JweHeader::from_bytes(br#"{"cty":"application/cbor"}"#)?;
+        assert_eq!(header.content_type(), Some("cbor"));
+        assert_eq!(header.claim("cty"), Some(&json!("application/cbor")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_management_header_claims() -> Result<()> {
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128GCM");
+        src_header.set_agreement_partyuinfo(b"Alice");
+        src_header.set_agreement_partyvinfo(b"Bob");
+
+        // ECDH-ES
+        let key_pair = ECDH_ES.generate_ec_key_pair(P_256)?;
+        let encrypter = ECDH_ES.encrypter_from_jwk(&key_pair.to_jwk_public_key())?;
+        let decrypter = ECDH_ES.decrypter_from_jwk(&key_pair.to_jwk_private_key())?;
+        let jwe = jwe::serialize_compact(b"payload", &src_header, &encrypter)?;
+        let (_, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        let epk = header.ephemeral_public_key().unwrap();
+        assert_eq!(epk.key_type(), "EC");
+        assert_eq!(epk.curve(), Some("P-256"));
+        assert_eq!(epk.parameter("d"), None);
+        assert_eq!(header.agreement_partyuinfo(), Some(b"Alice".to_vec()));
+        assert_eq!(header.agreement_partyvinfo(), Some(b"Bob".to_vec()));
+
+        // A128GCMKW
+        let key = Jwk::generate_oct_key(16)?;
+        let encrypter = A128GCMKW.encrypter_from_jwk(&key)?;
+        let decrypter = A128GCMKW.decrypter_from_jwk(&key)?;
+        let jwe = jwe::serialize_compact(b"payload", &src_header, &encrypter)?;
+        let (_, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(
+            header.initialization_vector().map(|val| val.len()),
+            Some(12)
+        );
+        assert_eq!(header.authentication_tag().map(|val| val.len()), Some(16));
+
+        // PBES2-HS256+A128KW
+        let mut src_header = src_header.clone();
+        src_header.set_pbes2_salt_input(b"saltsaltsaltsalt");
+        src_header.set_pbes2_count(1000);
+        let encrypter = PBES2_HS256_A128KW.encrypter_from_bytes(b"password")?;
+        let decrypter = PBES2_HS256_A128KW.decrypter_from_bytes(b"password")?;
+        let jwe = jwe::serialize_compact(b"payload", &src_header, &encrypter)?;
+        let (_, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(
+            header.pbes2_salt_input(),
+            Some(b"saltsaltsaltsalt".to_vec())
+        );
+        assert_eq!(header.pbes2_count(), Some(1000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_key_management_header_claims() -> Result<()> {
+        for (key, value) in vec![
+            ("epk", json!("epk")),
+            ("epk", json!({ "kty": 1 })),
+            ("iv", json!("!iv")),
+            ("tag", json!(1)),
+            ("p2s", json!("!p2s")),
+            ("p2c", json!(0)),
+            ("p2c", json!(-1)),
+            ("p2c", json!("1000")),
+        ] {
+            let mut header = JweHeader::new();
+            assert!(matches!(
+                header.set_claim(key, Some(value.clone())),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+            assert!(matches!(
+                JweHeader::from_bytes(&serde_json::to_vec(&json!({ key: value }))?),
+                Err(JoseError::InvalidJweFormat(_))
+            ));
+        }
+
+        Ok(())
+    }
 }
@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
+use anyhow::bail;
+
 use crate::jwe::JweHeader;
 use crate::jwk::Jwk;
+use crate::util;
 use crate::{JoseError, JoseHeader, Map, Value};
 
 /// Represent JWE protected and unprotected header claims
@@ -21,6 +24,16 @@ impl JweHeaderSet {
         }
     }
 
+    pub(crate) fn from_maps(
+        protected: Map<String, Value>,
+        unprotected: Map<String, Value>,
+    ) -> Self {
+        Self {
+            protected,
+            unprotected,
+        }
+    }
+
     /// Set a value for algorithm header claim (alg).
     ///
     /// # Arguments
@@ -31,10 +44,10 @@ impl JweHeaderSet {
         let key = "alg";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -58,10 +71,10 @@ impl JweHeaderSet {
         let key = "enc";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -103,10 +116,10 @@ impl JweHeaderSet {
         let key = "jku";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -129,10 +142,10 @@ impl JweHeaderSet {
         let key = "jwk";
         let value: Map<String, Value> = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::Object(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::Object(value));
         }
@@ -158,10 +171,10 @@ impl JweHeaderSet {
         let key = "x5u";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -187,10 +200,10 @@ impl JweHeaderSet {
             .map(|v| Value::String(base64::encode_config(v.as_ref(), base64::URL_SAFE_NO_PAD)))
             .collect();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::Array(vec));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected.insert(key.to_string(), Value::Array(vec));
         }
     }
@@ -230,10 +243,10 @@ impl JweHeaderSet {
         let key = "x5t";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -263,10 +276,10 @@ impl JweHeaderSet {
         let key = "x5t#S256";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -292,10 +305,10 @@ impl JweHeaderSet {
         let key = "kid";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -318,10 +331,10 @@ impl JweHeaderSet {
         let key = "typ";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -337,26 +350,34 @@ impl JweHeaderSet {
 
     /// Set a value for content type header claim (cty).
     ///
+    /// A media type of the form "application/foo" is stored in the short form "foo",
+    /// as RFC 7515 recommends. Any other value, such as "JWT" or "text/plain", is
+    /// stored as is.
+    ///
     /// # Arguments
     ///
     /// * `value` - a content type (e.g. "JWT")
     pub fn set_content_type(&mut self, value: impl Into<String>, protection: bool) {
         let key = "cty";
         let value: String = value.into();
+        let value = util::compact_media_type(&value).to_string();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
     }
 
-    /// Return the value for content type header claim (cty).
+    /// Return the value for content type header claim (cty) in the short form.
+    ///
+    /// A received "application/foo" is returned as "foo", the same as a value set by
+    /// `set_content_type`. The value as received is available by `claim("cty")`.
     pub fn content_type(&self) -> Option<&str> {
         match self.claim("cty") {
-            Some(Value::String(val)) => Some(val),
+            Some(Value::String(val)) => Some(util::compact_media_type(val)),
             _ => None,
         }
     }
@@ -370,9 +391,9 @@ impl JweHeaderSet {
         let key = "crit";
         let vec = values
             .iter()
-            .map(|v| Value::String(base64::encode_config(v.as_ref(), base64::URL_SAFE_NO_PAD)))
+            .map(|v| Value::String(v.as_ref().to_string()))
             .collect();
-        self.unprotected.remove(key);
+        self.unprotected.shift_remove(key);
         self.protected.insert(key.to_string(), Value::Array(vec));
     }
 
@@ -402,10 +423,10 @@ impl JweHeaderSet {
         let key = "url";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -428,10 +449,10 @@ impl JweHeaderSet {
         let key = "nonce";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -457,10 +478,10 @@ impl JweHeaderSet {
         let key = "apu";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -486,10 +507,10 @@ impl JweHeaderSet {
         let key = "apv";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -515,10 +536,10 @@ impl JweHeaderSet {
         let key = "iss";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -541,10 +562,10 @@ impl JweHeaderSet {
         let key = "sub";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -569,10 +590,10 @@ impl JweHeaderSet {
             for val in values {
                 let value = val.into();
                 if protection {
-                    self.unprotected.remove(key);
+                    self.unprotected.shift_remove(key);
                     self.protected.insert(key.to_string(), Value::String(value));
                 } else {
-                    self.protected.remove(key);
+                    self.protected.shift_remove(key);
                     self.unprotected
                         .insert(key.to_string(), Value::String(value));
                 }
@@ -585,10 +606,10 @@ impl JweHeaderSet {
                 vec.push(Value::String(val.clone()));
             }
             if protection {
-                self.unprotected.remove(key);
+                self.unprotected.shift_remove(key);
                 self.protected.insert(key.to_string(), Value::Array(vec));
             } else {
-                self.protected.remove(key);
+                self.protected.shift_remove(key);
                 self.unprotected.insert(key.to_string(), Value::Array(vec));
             }
         }
@@ -624,16 +645,16 @@ impl JweHeaderSet {
             Some(val) => {
                 JweHeader::check_claim(key, &val)?;
                 if protection {
-                    self.unprotected.remove(key);
+                    self.unprotected.shift_remove(key);
                     self.protected.insert(key.to_string(), val);
                 } else {
-                    self.protected.remove(key);
+                    self.protected.shift_remove(key);
                     self.unprotected.insert(key.to_string(), val);
                 }
             }
             None => {
-                self.protected.remove(key);
-                self.unprotected.remove(key);
+                self.protected.shift_remove(key);
+                self.unprotected.shift_remove(key);
             }
         }
 
@@ -656,6 +677,47 @@ impl JweHeaderSet {
         }
         map
     }
+
+    /// Check that no header claim appears in both the protected and shared unprotected header.
+    pub fn validate_disjoint(&self) -> Result<(), JoseError> {
+        self.check_disjoint(None)
+    }
+
+    /// Return the combined header of the protected, shared unprotected
+    /// and per-recipient unprotected header claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_header` - The per-recipient unprotected header claims.
+    pub fn merge(&self, recipient_header: Option<&JweHeader>) -> Result<JweHeader, JoseError> {
+        self.check_disjoint(recipient_header)?;
+        let mut map = self.to_map();
+        if let Some(val) = recipient_header {
+            for (key, value) in val.claims_set() {
+                map.insert(key.clone(), value.clone());
+            }
+        }
+        JweHeader::from_map(map)
+    }
+
+    fn check_disjoint(&self, recipient_header: Option<&JweHeader>) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            for key in self.unprotected.keys() {
+                if self.protected.contains_key(key) {
+                    bail!("A duplicate key exists: {}", key);
+                }
+            }
+            if let Some(val) = recipient_header {
+                for key in val.claims_set().keys() {
+                    if self.protected.contains_key(key) || self.unprotected.contains_key(key) {
+                        bail!("A duplicate key exists: {}", key);
+                    }
+                }
+            }
+            Ok(())
+        })()
+        .map_err(|err| JoseError::InvalidJweFormat(err))
+    }
 }
 
 impl JoseHeader for JweHeaderSet {
@@ -697,3 +759,51 @@ impl Deref for JweHeaderSet {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use crate::jwe::{JweHeader, JweHeaderSet};
+    use crate::{JoseError, Map};
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let mut header = JweHeaderSet::new();
+        header.set_content_encryption("A128GCM", true);
+        header.set_key_id("kid", false);
+        header.validate_disjoint()?;
+
+        let mut recipient_header = JweHeader::new();
+        recipient_header.set_algorithm("A128KW");
+
+        let merged = header.merge(Some(&recipient_header))?;
+        assert_eq!(merged.content_encryption(), Some("A128GCM"));
+        assert_eq!(merged.key_id(), Some("kid"));
+        assert_eq!(merged.algorithm(), Some("A128KW"));
+
+        let mut recipient_header = JweHeader::new();
+        recipient_header.set_key_id("other");
+        assert!(matches!(
+            header.merge(Some(&recipient_header)),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        let mut protected = Map::new();
+        protected.insert("enc".to_string(), json!("A128GCM"));
+        let mut unprotected = Map::new();
+        unprotected.insert("enc".to_string(), json!("A256GCM"));
+        let header = JweHeaderSet::from_maps(protected, unprotected);
+        assert!(matches!(
+            header.validate_disjoint(),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+        assert!(matches!(
+            header.merge(None),
+            Err(JoseError::InvalidJweFormat(_))
+        ));
+
+        Ok(())
+    }
+}
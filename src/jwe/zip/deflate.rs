@@ -7,6 +7,7 @@ use flate2::write::DeflateEncoder;
 use flate2::Compression;
 
 use crate::jwe::JweCompression;
+use crate::util;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum DeflateJweCompression {
@@ -35,6 +36,22 @@ impl JweCompression for DeflateJweCompression {
         Ok(vec)
     }
 
+    fn decompress_with_limit(&self, data: &[u8], max_len: usize) -> Result<Vec<u8>, io::Error> {
+        let decoder = DeflateDecoder::new(data);
+        let mut vec = Vec::new();
+        decoder
+            .take((max_len as u64).saturating_add(1))
+            .read_to_end(&mut vec)?;
+        if vec.len() > max_len {
+            util::zeroize(&mut vec);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The decompressed size exceeds {} bytes.", max_len),
+            ));
+        }
+        Ok(vec)
+    }
+
     fn box_clone(&self) -> Box<dyn JweCompression> {
         Box::new(self.clone())
     }
@@ -53,3 +70,26 @@ impl Deref for DeflateJweCompression {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::DeflateJweCompression;
+
+    #[test]
+    fn compress_and_decompress_deflate() -> Result<()> {
+        let zip = DeflateJweCompression::Def;
+        let message = vec![b'a'; 10000];
+
+        let compressed = zip.compress(&message)?;
+        assert!(compressed.len() < message.len());
+
+        assert_eq!(zip.decompress(&compressed)?, message);
+        assert_eq!(zip.decompress_with_limit(&compressed, 10000)?, message);
+        assert!(zip.decompress_with_limit(&compressed, 9999).is_err());
+        assert_eq!(zip.decompress_with_limit(&compressed, usize::MAX)?, message);
+
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::bn::{BigNum, BigNumContext};
-use openssl::ec::{EcGroup, EcKey};
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
 use openssl::nid::Nid;
 use openssl::pkey::{PKey, Private};
 
@@ -52,7 +52,7 @@ impl EcCurve {
         }
     }
 
-    fn coordinate_size(&self) -> usize {
+    pub(crate) fn coordinate_size(&self) -> usize {
         match self {
             Self::P256 | Self::Secp256k1 => 32,
             Self::P384 => 48,
@@ -318,6 +318,48 @@ impl EcKeyPair {
         ec_key.private_key_to_pem().unwrap()
     }
 
+    /// Return a public key that is a SEC1 encoded EC point.
+    ///
+    /// # Arguments
+    ///
+    /// * `compressed` - true to return the compressed form (0x02 or 0x03 and x),
+    ///   false to return the uncompressed form (0x04, x and y).
+    pub fn to_sec1_public_key(&self, compressed: bool) -> Vec<u8> {
+        let ec_key = self.private_key.ec_key().unwrap();
+        let form = if compressed {
+            PointConversionForm::COMPRESSED
+        } else {
+            PointConversionForm::UNCOMPRESSED
+        };
+        let mut ctx = BigNumContext::new().unwrap();
+        ec_key
+            .public_key()
+            .to_bytes(ec_key.group(), form, &mut ctx)
+            .unwrap()
+    }
+
+    /// Return the x and y coordinates of a public key that is a SEC1 encoded EC point
+    /// of compressed or uncompressed form.
+    pub(crate) fn coordinates_from_sec1(
+        input: &[u8],
+        curve: EcCurve,
+    ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+        let ec_group = EcGroup::from_curve_name(curve.nid())?;
+        let mut ctx = BigNumContext::new()?;
+        let point = EcPoint::from_bytes(&ec_group, input, &mut ctx)?;
+        if point.is_infinity(&ec_group) {
+            bail!("The point at infinity cannot be a public key.");
+        }
+
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        point.affine_coordinates_gfp(&ec_group, &mut x, &mut y, &mut ctx)?;
+
+        let x = util::num_to_vec(&x, curve.coordinate_size());
+        let y = util::num_to_vec(&y, curve.coordinate_size());
+        Ok((x, y))
+    }
+
     fn to_jwk(&self, private: bool, public: bool) -> Jwk {
         let ec_key = self.private_key.ec_key().unwrap();
 
@@ -550,6 +592,7 @@ mod tests {
     use anyhow::Result;
 
     use super::{EcCurve, EcKeyPair};
+    use crate::jwk::{Jwk, KeyPair};
 
     #[test]
     fn test_ec_jwt() -> Result<()> {
@@ -575,4 +618,49 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ec_sec1_public_key() -> Result<()> {
+        for curve in vec![
+            EcCurve::P256,
+            EcCurve::P384,
+            EcCurve::P521,
+            EcCurve::Secp256k1,
+        ] {
+            let key_pair = EcKeyPair::generate(curve)?;
+            let public_jwk = key_pair.to_jwk_public_key();
+
+            let uncompressed = key_pair.to_sec1_public_key(false);
+            assert_eq!(uncompressed.len(), 1 + 2 * curve.coordinate_size());
+            assert_eq!(uncompressed[0], 0x04);
+
+            let compressed = key_pair.to_sec1_public_key(true);
+            assert_eq!(compressed.len(), 1 + curve.coordinate_size());
+            assert!(compressed[0] == 0x02 || compressed[0] == 0x03);
+            assert_eq!(
+                compressed[1..],
+                uncompressed[1..(1 + curve.coordinate_size())]
+            );
+
+            for input in &[&uncompressed, &compressed] {
+                let jwk = Jwk::from_ec_sec1_public_key(input, curve)?;
+                assert_eq!(jwk.parameter("x"), public_jwk.parameter("x"));
+                assert_eq!(jwk.parameter("y"), public_jwk.parameter("y"));
+                assert_eq!(jwk.to_sec1_public_key(false)?, uncompressed);
+                assert_eq!(jwk.to_sec1_public_key(true)?, compressed);
+            }
+        }
+
+        let key_pair = EcKeyPair::generate(EcCurve::P256)?;
+        let mut invalid = key_pair.to_sec1_public_key(true);
+        invalid[0] = 0x05;
+        assert!(Jwk::from_ec_sec1_public_key(&invalid, EcCurve::P256).is_err());
+        assert!(Jwk::from_ec_sec1_public_key(&[0x00], EcCurve::P256).is_err());
+        assert!(
+            Jwk::from_ec_sec1_public_key(&key_pair.to_sec1_public_key(true), EcCurve::P384)
+                .is_err()
+        );
+
+        Ok(())
+    }
 }
@@ -1,20 +1,32 @@
 use serde::{Deserialize, Serialize};
 
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::io::Read;
 use std::string::ToString;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcKey, PointConversionForm};
+use openssl::hash;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::alg::ec::{EcCurve, EcKeyPair};
 use crate::jwk::alg::ecx::{EcxCurve, EcxKeyPair};
 use crate::jwk::alg::ed::{EdCurve, EdKeyPair};
 use crate::jwk::alg::rsa::RsaKeyPair;
-use crate::util;
-use crate::{JoseError, Map, Value};
+use crate::jwk::KeyPair;
+use crate::util::der::{DerBuilder, DerReader, DerType};
+use crate::util::{self, HashAlgorithm};
+use crate::{JoseError, JoseErrorKind, Map, Value};
 
 /// Represents JWK object.
-#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+///
+/// The `Debug` output replaces the private key parameters ("d", "p", "q", "dp", "dq",
+/// "qi", "oth" and "k") with "<redacted>", so a JWK can be logged without leaking
+/// the key. `Display` still writes the full JSON.
+#[derive(Eq, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Jwk {
     #[serde(flatten)]
     map: Map<String, Value>,
@@ -60,6 +72,157 @@ impl Jwk {
         })
     }
 
+    /// Create a JWK from a public or private key of common or traditinal PEM format.
+    ///
+    /// The key type (RSA, EC or OKP) and whether the key is private or public are
+    /// detected from the PEM label and contents. The following labels are supported:
+    /// "PRIVATE KEY" (PKCS#8 PrivateKeyInfo), "PUBLIC KEY" (SubjectPublicKeyInfo),
+    /// "RSA PRIVATE KEY", "RSA PUBLIC KEY" (PKCS#1), "EC PRIVATE KEY" (SEC1) and
+    /// the traditional Ed25519, Ed448, X25519 and X448 private key labels.
+    ///
+    /// # Arguments
+    /// * `input` - A public or private key of common or traditinal PEM format.
+    pub fn from_pem(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let input = input.as_ref();
+            let (alg, data) = util::parse_pem(input)?;
+
+            let jwk = match alg.as_str() {
+                "PRIVATE KEY" => {
+                    if RsaKeyPair::detect_pkcs8(&data, false).is_some() {
+                        RsaKeyPair::from_der(&data)?.to_jwk_key_pair()
+                    } else if let Some(curve) = EcKeyPair::detect_pkcs8(&data, false) {
+                        EcKeyPair::from_der(&data, Some(curve))?.to_jwk_key_pair()
+                    } else if EdKeyPair::detect_pkcs8(&data, false).is_some() {
+                        EdKeyPair::from_der(&data)?.to_jwk_key_pair()
+                    } else if EcxKeyPair::detect_pkcs8(&data, false).is_some() {
+                        EcxKeyPair::from_der(&data)?.to_jwk_key_pair()
+                    } else {
+                        bail!("Unsupported private key type or curve.");
+                    }
+                }
+                "RSA PRIVATE KEY" => RsaKeyPair::from_pem(input)?.to_jwk_key_pair(),
+                "EC PRIVATE KEY" => EcKeyPair::from_pem(input, None)?.to_jwk_key_pair(),
+                "ED25519 PRIVATE KEY" | "ED448 PRIVATE KEY" => {
+                    EdKeyPair::from_pem(input)?.to_jwk_key_pair()
+                }
+                "X25519 PRIVATE KEY" | "X448 PRIVATE KEY" => {
+                    EcxKeyPair::from_pem(input)?.to_jwk_key_pair()
+                }
+                "PUBLIC KEY" => {
+                    if RsaKeyPair::detect_pkcs8(&data, true).is_some() {
+                        let rsa = Rsa::public_key_from_der(&data)?;
+                        Self::from_rsa_public_key(&rsa)
+                    } else if let Some(curve) = EcKeyPair::detect_pkcs8(&data, true) {
+                        let ec_key = EcKey::public_key_from_der(&data)?;
+                        let mut x = BigNum::new()?;
+                        let mut y = BigNum::new()?;
+                        let mut ctx = BigNumContext::new()?;
+                        ec_key.public_key().affine_coordinates_gfp(
+                            ec_key.group(),
+                            &mut x,
+                            &mut y,
+                            &mut ctx,
+                        )?;
+
+                        let x = util::num_to_vec(&x, curve.coordinate_size());
+                        let y = util::num_to_vec(&y, curve.coordinate_size());
+
+                        let mut jwk = Self::new("EC");
+                        jwk.map
+                            .insert("crv".to_string(), Value::String(curve.name().to_string()));
+                        jwk.map.insert(
+                            "x".to_string(),
+                            Value::String(base64::encode_config(&x, base64::URL_SAFE_NO_PAD)),
+                        );
+                        jwk.map.insert(
+                            "y".to_string(),
+                            Value::String(base64::encode_config(&y, base64::URL_SAFE_NO_PAD)),
+                        );
+                        jwk
+                    } else if let Some(curve) = EdKeyPair::detect_pkcs8(&data, true) {
+                        let x = Self::parse_okp_public_key(&data)?;
+                        let mut jwk = Self::new("OKP");
+                        jwk.set_key_use("sig");
+                        jwk.map
+                            .insert("crv".to_string(), Value::String(curve.name().to_string()));
+                        jwk.map.insert(
+                            "x".to_string(),
+                            Value::String(base64::encode_config(&x, base64::URL_SAFE_NO_PAD)),
+                        );
+                        jwk
+                    } else if let Some(curve) = EcxKeyPair::detect_pkcs8(&data, true) {
+                        let x = Self::parse_okp_public_key(&data)?;
+                        let mut jwk = Self::new("OKP");
+                        jwk.set_key_use("enc");
+                        jwk.map
+                            .insert("crv".to_string(), Value::String(curve.name().to_string()));
+                        jwk.map.insert(
+                            "x".to_string(),
+                            Value::String(base64::encode_config(&x, base64::URL_SAFE_NO_PAD)),
+                        );
+                        jwk
+                    } else {
+                        bail!("Unsupported public key type or curve.");
+                    }
+                }
+                "RSA PUBLIC KEY" => {
+                    let rsa = Rsa::public_key_from_der_pkcs1(&data)?;
+                    Self::from_rsa_public_key(&rsa)
+                }
+                _ => bail!("Unsupported PEM label: {}", alg),
+            };
+
+            Ok(jwk)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn from_rsa_public_key(rsa: &Rsa<Public>) -> Self {
+        let n = base64::encode_config(rsa.n().to_vec(), base64::URL_SAFE_NO_PAD);
+        let e = base64::encode_config(rsa.e().to_vec(), base64::URL_SAFE_NO_PAD);
+
+        let mut jwk = Self::new("RSA");
+        jwk.map.insert("n".to_string(), Value::String(n));
+        jwk.map.insert("e".to_string(), Value::String(e));
+        jwk
+    }
+
+    fn parse_okp_public_key(input: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut reader = DerReader::from_reader(input);
+
+        match reader.next()? {
+            Some(DerType::Sequence) => {}
+            _ => bail!("Invalid public key."),
+        }
+
+        match reader.next()? {
+            Some(DerType::Sequence) => {}
+            _ => bail!("Invalid public key."),
+        }
+
+        match reader.next()? {
+            Some(DerType::ObjectIdentifier) => {}
+            _ => bail!("Invalid public key."),
+        }
+
+        match reader.next()? {
+            Some(DerType::EndOfContents) => {}
+            _ => bail!("Invalid public key."),
+        }
+
+        match reader.next()? {
+            Some(DerType::BitString) => match reader.to_bit_vec()? {
+                (x, 0) => Ok(x),
+                _ => bail!("Invalid public key."),
+            },
+            _ => bail!("Invalid public key."),
+        }
+    }
+
     /// Generate a new oct type JWK.
     ///
     /// # Arguments
@@ -111,108 +274,388 @@ impl Jwk {
         Ok(key_pair.to_jwk_key_pair())
     }
 
-    /// Generate private key from private key.
+    /// Create a EC type public key JWK from a SEC1 encoded EC point.
+    ///
+    /// Both the compressed form (0x02 or 0x03 and x) and the uncompressed form
+    /// (0x04, x and y) are accepted. The parameters x and y of the JWK are always
+    /// the full coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A public key that is a SEC1 encoded EC point.
+    /// * `curve` - A EC curve algorithm
+    pub fn from_ec_sec1_public_key(
+        input: impl AsRef<[u8]>,
+        curve: EcCurve,
+    ) -> Result<Self, JoseError> {
+        let (x, y) = EcKeyPair::coordinates_from_sec1(input.as_ref(), curve)
+            .map_err(|err| JoseError::InvalidKeyFormat(err))?;
+
+        let mut jwk = Self::new("EC");
+        jwk.map
+            .insert("crv".to_string(), Value::String(curve.name().to_string()));
+        jwk.map.insert(
+            "x".to_string(),
+            Value::String(base64::encode_config(&x, base64::URL_SAFE_NO_PAD)),
+        );
+        jwk.map.insert(
+            "y".to_string(),
+            Value::String(base64::encode_config(&y, base64::URL_SAFE_NO_PAD)),
+        );
+        Ok(jwk)
+    }
+
+    /// Return a private key that is a DER encoded PKCS#8 PrivateKeyInfo.
+    pub fn to_der_private_key(&self) -> Result<Vec<u8>, JoseError> {
+        let key_pair = self.to_key_pair()?;
+        Ok(key_pair.to_der_private_key())
+    }
+
+    /// Return a private key of common PEM format.
+    ///
+    /// Common PEM format is a DER and base64 encoded PKCS#8 PrivateKeyInfo
+    /// that surrounded by "-----BEGIN/END PRIVATE KEY----".
+    pub fn to_pem_private_key(&self) -> Result<Vec<u8>, JoseError> {
+        let key_pair = self.to_key_pair()?;
+        Ok(key_pair.to_pem_private_key())
+    }
+
+    /// Return a public key that is a DER encoded SubjectPublicKeyInfo.
+    pub fn to_der_public_key(&self) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let public_key = self.to_openssl_public_key()?;
+            let der = public_key.public_key_to_der()?;
+            Ok(der)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Return a public key of common PEM format.
+    ///
+    /// Common PEM format is a DER and base64 encoded SubjectPublicKeyInfo
+    /// that surrounded by "-----BEGIN/END PUBLIC KEY----".
+    pub fn to_pem_public_key(&self) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let public_key = self.to_openssl_public_key()?;
+            let pem = public_key.public_key_to_pem()?;
+            Ok(pem)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    /// Return a public key of EC type JWK that is a SEC1 encoded EC point.
+    ///
+    /// # Arguments
+    ///
+    /// * `compressed` - true to return the compressed form (0x02 or 0x03 and x),
+    ///   false to return the uncompressed form (0x04, x and y).
+    pub fn to_sec1_public_key(&self, compressed: bool) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            if self.key_type() != "EC" {
+                bail!("The key type must be EC: {}", self.key_type());
+            }
+
+            let public_key = self.to_openssl_public_key()?;
+            let ec_key = public_key.ec_key()?;
+            let form = if compressed {
+                PointConversionForm::COMPRESSED
+            } else {
+                PointConversionForm::UNCOMPRESSED
+            };
+            let mut ctx = BigNumContext::new()?;
+            let sec1 = ec_key
+                .public_key()
+                .to_bytes(ec_key.group(), form, &mut ctx)?;
+            Ok(sec1)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
+    fn to_key_pair(&self) -> Result<Box<dyn KeyPair>, JoseError> {
+        let key_pair: Box<dyn KeyPair> = match self.key_type() {
+            "RSA" => Box::new(RsaKeyPair::from_jwk(self)?),
+            "EC" => Box::new(EcKeyPair::from_jwk(self)?),
+            "OKP" => match self.curve() {
+                Some("Ed25519") | Some("Ed448") => Box::new(EdKeyPair::from_jwk(self)?),
+                Some("X25519") | Some("X448") => Box::new(EcxKeyPair::from_jwk(self)?),
+                Some(val) => {
+                    return Err(JoseError::InvalidKeyFormat(anyhow!(
+                        "Unsupported curve: {}",
+                        val
+                    )))
+                }
+                None => {
+                    return Err(JoseError::InvalidKeyFormat(anyhow!(
+                        "A parameter crv is required."
+                    )))
+                }
+            },
+            val => {
+                return Err(JoseError::InvalidKeyFormat(anyhow!(
+                    "The key type '{}' doesn't have PEM or DER format.",
+                    val
+                )))
+            }
+        };
+        Ok(key_pair)
+    }
+
+    fn to_openssl_public_key(&self) -> anyhow::Result<PKey<Public>> {
+        let pkcs8 = match self.key_type() {
+            "RSA" => {
+                let n = self.decode_parameter("n")?;
+                let e = self.decode_parameter("e")?;
+
+                let mut builder = DerBuilder::new();
+                builder.begin(DerType::Sequence);
+                {
+                    builder.append_integer_from_be_slice(&n, false); // n
+                    builder.append_integer_from_be_slice(&e, false); // e
+                }
+                builder.end();
+
+                RsaKeyPair::to_pkcs8(&builder.build(), true)
+            }
+            "EC" => {
+                let curve = match self.curve() {
+                    Some("P-256") => EcCurve::P256,
+                    Some("P-384") => EcCurve::P384,
+                    Some("P-521") => EcCurve::P521,
+                    Some("secp256k1") => EcCurve::Secp256k1,
+                    Some(val) => bail!("Unsupported curve: {}", val),
+                    None => bail!("A parameter crv is required."),
+                };
+                let x = self.decode_parameter("x")?;
+                let y = self.decode_parameter("y")?;
+
+                let mut vec = Vec::with_capacity(1 + x.len() + y.len());
+                vec.push(0x04);
+                vec.extend_from_slice(&x);
+                vec.extend_from_slice(&y);
+
+                EcKeyPair::to_pkcs8(&vec, true, curve)
+            }
+            "OKP" => {
+                let x = self.decode_parameter("x")?;
+                match self.curve() {
+                    Some("Ed25519") => EdKeyPair::to_pkcs8(&x, true, EdCurve::Ed25519),
+                    Some("Ed448") => EdKeyPair::to_pkcs8(&x, true, EdCurve::Ed448),
+                    Some("X25519") => EcxKeyPair::to_pkcs8(&x, true, EcxCurve::X25519),
+                    Some("X448") => EcxKeyPair::to_pkcs8(&x, true, EcxCurve::X448),
+                    Some(val) => bail!("Unsupported curve: {}", val),
+                    None => bail!("A parameter crv is required."),
+                }
+            }
+            val => bail!("The key type '{}' doesn't have PEM or DER format.", val),
+        };
+
+        let public_key = PKey::public_key_from_der(&pkcs8)?;
+        Ok(public_key)
+    }
+
+    fn decode_parameter(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        match self.map.get(key) {
+            Some(Value::String(val)) => Ok(base64::decode_config(val, base64::URL_SAFE_NO_PAD)?),
+            Some(_) => bail!("A parameter {} must be a string.", key),
+            None => bail!("A parameter {} is required.", key),
+        }
+    }
+
+    /// Return the public key of a private key, e.g. for publishing it in a JWK set.
+    ///
+    /// The private key parameters are removed: "d" for EC and OKP, and "d", "p", "q",
+    /// "dp", "dq", "qi" and "oth" for RSA. The other parameters such as "kid", "use"
+    /// and "alg" are kept, except that the private operations of "key_ops" are replaced
+    /// with the public ones ("sign" with "verify", "decrypt" with "encrypt" and
+    /// "unwrapKey" with "wrapKey"). A key of type "oct" has no public key.
     pub fn to_public_key(&self) -> Result<Self, JoseError> {
         (|| -> anyhow::Result<Jwk> {
-            let jwk = match self.key_type() {
-                "oct" => bail!("The key type 'oct' doesn't have public key."),
-                "RSA" => {
-                    let mut jwk = Jwk::new("RSA");
-                    match self.map.get("use") {
-                        Some(Value::String(val)) => {
-                            jwk.map
-                                .insert("use".to_string(), Value::String(val.clone()));
-                        }
-                        _ => {}
-                    }
-                    match self.map.get("e") {
-                        Some(Value::String(val)) => {
-                            jwk.map.insert("e".to_string(), Value::String(val.clone()));
-                        }
-                        Some(_) => bail!("The parameter 'x' must be a string."),
-                        None => bail!("The key type 'RSA' must have parameter 'e'."),
-                    }
-                    match self.map.get("n") {
-                        Some(Value::String(val)) => {
-                            jwk.map.insert("n".to_string(), Value::String(val.clone()));
-                        }
-                        Some(_) => bail!("The parameter 'x' must be a string."),
-                        None => bail!("The key type 'RSA' must have parameter 'n'."),
-                    }
-                    jwk
+            let (members, private_members, curves): (&[&str], &[&str], &[&str]) =
+                match self.key_type() {
+                    "oct" => bail!("The key type 'oct' doesn't have public key."),
+                    "RSA" => (&["n", "e"], &["d", "p", "q", "dp", "dq", "qi", "oth"], &[]),
+                    "EC" => (
+                        &["crv", "x", "y"],
+                        &["d"],
+                        &["P-256", "P-384", "P-521", "secp256k1"],
+                    ),
+                    "OKP" => (
+                        &["crv", "x"],
+                        &["d"],
+                        &["Ed25519", "Ed448", "X25519", "X448"],
+                    ),
+                    val => bail!("Unknown key type: {}", val),
+                };
+
+            for key in members {
+                match self.map.get(*key) {
+                    Some(Value::String(_)) => {}
+                    Some(_) => bail!("The parameter '{}' must be a string.", key),
+                    None => bail!(
+                        "The key type '{}' must have parameter '{}'.",
+                        self.key_type(),
+                        key
+                    ),
                 }
-                "EC" => {
-                    let mut jwk = Jwk::new("EC");
-                    match self.map.get("use") {
-                        Some(Value::String(val)) => {
-                            jwk.map
-                                .insert("use".to_string(), Value::String(val.clone()));
-                        }
-                        _ => {}
-                    }
-                    match self.map.get("crv") {
-                        Some(Value::String(val)) => match val.as_str() {
-                            "P-256" | "P-384" | "P-521" | "secp256k1" => {
-                                jwk.map
-                                    .insert("crv".to_string(), Value::String(val.clone()));
-                            }
-                            val => bail!("Unknown curve: {}", val),
-                        },
-                        Some(_) => bail!("The parameter 'crv' must be a string."),
-                        None => bail!("The key type 'EC' must have parameter 'crv'."),
-                    }
-                    match self.map.get("x") {
-                        Some(Value::String(val)) => {
-                            jwk.map.insert("x".to_string(), Value::String(val.clone()));
-                        }
-                        Some(_) => bail!("The parameter 'x' must be a string."),
-                        None => bail!("The key type 'EC' must have parameter 'x'."),
-                    }
-                    match self.map.get("y") {
-                        Some(Value::String(val)) => {
-                            jwk.map.insert("y".to_string(), Value::String(val.clone()));
-                        }
-                        Some(_) => bail!("The parameter 'x' must be a string."),
-                        None => bail!("The key type 'EC' must have parameter 'y'."),
-                    }
-                    jwk
+            }
+
+            if members.contains(&"crv") {
+                match self.curve() {
+                    Some(val) if curves.contains(&val) => {}
+                    Some(val) => bail!("Unknown curve: {}", val),
+                    None => {}
                 }
-                "OKP" => {
-                    let mut jwk = Jwk::new("OKP");
-                    match self.map.get("use") {
-                        Some(Value::String(val)) => {
-                            jwk.map
-                                .insert("use".to_string(), Value::String(val.clone()));
-                        }
-                        _ => {}
-                    }
-                    match self.map.get("crv") {
-                        Some(Value::String(val)) => match val.as_str() {
-                            "Ed25519" | "Ed448" | "X25519" | "X448" => {
-                                jwk.map
-                                    .insert("crv".to_string(), Value::String(val.clone()));
-                            }
-                            val => bail!("Unknown curve: {}", val),
-                        },
-                        Some(_) => bail!("The parameter 'crv' must be a string."),
-                        None => bail!("The key type 'EC' must have parameter 'crv'."),
-                    }
-                    match self.map.get("x") {
-                        Some(Value::String(val)) => {
-                            jwk.map.insert("x".to_string(), Value::String(val.clone()));
-                        }
-                        Some(_) => bail!("The parameter 'x' must be a string."),
-                        None => bail!("The key type 'OKP' must have parameter 'x'."),
+            }
+
+            let mut map = self.map.clone();
+            for key in private_members {
+                map.remove(*key);
+            }
+
+            if let Some(vals) = self.key_operations() {
+                let mut vec: Vec<Value> = Vec::with_capacity(vals.len());
+                for val in vals {
+                    let val = match val {
+                        "sign" => "verify",
+                        "decrypt" => "encrypt",
+                        "unwrapKey" => "wrapKey",
+                        val => val,
+                    };
+                    if !vec.iter().any(|e| e == val) {
+                        vec.push(Value::String(val.to_string()));
                     }
-                    jwk
                 }
+                map.insert("key_ops".to_string(), Value::Array(vec));
+            }
+
+            Ok(Jwk { map })
+        })()
+        .map_err(|err| JoseError::InvalidJwkFormat(err))
+    }
+
+    /// Return a JWK thumbprint (RFC 7638) that is encoded by base64url.
+    ///
+    /// # Arguments
+    /// * `hash_algorithm` - A hash algorithm (SHA-256 is commonly used)
+    pub fn thumbprint(&self, hash_algorithm: HashAlgorithm) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            let members: &[&str] = match self.key_type() {
+                "oct" => &["k", "kty"],
+                "RSA" => &["e", "kty", "n"],
+                "EC" => &["crv", "kty", "x", "y"],
+                "OKP" => &["crv", "kty", "x"],
                 val => bail!("Unknown key type: {}", val),
             };
-            Ok(jwk)
+
+            let mut json = String::from("{");
+            for (i, key) in members.iter().enumerate() {
+                let val = match self.map.get(*key) {
+                    Some(Value::String(val)) => val,
+                    Some(_) => bail!("The parameter '{}' must be a string.", key),
+                    None => bail!(
+                        "The key type '{}' must have parameter '{}'.",
+                        self.key_type(),
+                        key
+                    ),
+                };
+
+                if i > 0 {
+                    json.push(',');
+                }
+                json.push_str(&serde_json::to_string(key)?);
+                json.push(':');
+                json.push_str(&serde_json::to_string(val)?);
+            }
+            json.push('}');
+
+            let digest = hash::hash(hash_algorithm.message_digest(), json.as_bytes())?;
+            Ok(base64::encode_config(digest, base64::URL_SAFE_NO_PAD))
         })()
         .map_err(|err| JoseError::InvalidJwkFormat(err))
     }
 
+    /// Return a JWK thumbprint URI (RFC 9278).
+    ///
+    /// # Arguments
+    /// * `hash_algorithm` - A hash algorithm (SHA-256, SHA-384 or SHA-512)
+    pub fn thumbprint_uri(&self, hash_algorithm: HashAlgorithm) -> Result<String, JoseError> {
+        let hash_name = (|| -> anyhow::Result<&str> {
+            Ok(match hash_algorithm {
+                HashAlgorithm::Sha256 => "sha-256",
+                HashAlgorithm::Sha384 => "sha-384",
+                HashAlgorithm::Sha512 => "sha-512",
+                val => bail!(
+                    "The hash algorithm is not supported for thumbprint URI: {}",
+                    val
+                ),
+            })
+        })()
+        .map_err(|err| JoseError::InvalidJwkFormat(err))?;
+
+        let thumbprint = self.thumbprint(hash_algorithm)?;
+        Ok(format!(
+            "urn:ietf:params:oauth:jwk-thumbprint:{}:{}",
+            hash_name, thumbprint
+        ))
+    }
+
+    /// Return whether this JWK and the other JWK represent the same public key.
+    ///
+    /// The keys are compared by their SHA-256 JWK thumbprints (RFC 7638), so metadata
+    /// such as kid and alg, and private members are ignored. If a thumbprint cannot be
+    /// computed, false is returned.
+    ///
+    /// # Arguments
+    /// * `other` - The JWK to compare with.
+    pub fn public_eq(&self, other: &Jwk) -> bool {
+        match (
+            self.thumbprint(HashAlgorithm::Sha256),
+            other.thumbprint(HashAlgorithm::Sha256),
+        ) {
+            (Ok(val1), Ok(val2)) => val1 == val2,
+            _ => false,
+        }
+    }
+
+    /// Set a SHA-256 JWK thumbprint (RFC 7638) to a key ID parameter (kid).
+    pub fn set_key_id_to_thumbprint(&mut self) -> Result<(), JoseError> {
+        let thumbprint = self
+            .thumbprint(HashAlgorithm::Sha256)
+            .map_err(|err| match err {
+                JoseError::InvalidJwkFormat(err) => JoseError::InvalidKeyFormat(err),
+                err => err,
+            })?;
+        self.set_key_id(thumbprint);
+        Ok(())
+    }
+
+    /// Set a JWK thumbprint URI (RFC 9278) to a key ID parameter (kid).
+    ///
+    /// # Arguments
+    /// * `hash_algorithm` - A hash algorithm (SHA-256, SHA-384 or SHA-512)
+    pub fn set_key_id_to_thumbprint_uri(
+        &mut self,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<(), JoseError> {
+        let thumbprint_uri = self
+            .thumbprint_uri(hash_algorithm)
+            .map_err(|err| match err {
+                JoseError::InvalidJwkFormat(err) => JoseError::InvalidKeyFormat(err),
+                err => err,
+            })?;
+        self.set_key_id(thumbprint_uri);
+        Ok(())
+    }
+
     /// Set a value for a key type parameter (kty).
     ///
     /// # Arguments
@@ -289,6 +732,50 @@ impl Jwk {
         }
     }
 
+    /// Return a copy of this key without the key use (use) and key operations (key_ops)
+    /// parameters, for callers that want to use a key regardless of its declared usage.
+    ///
+    /// The algorithm parameter (alg) is kept and still checked.
+    pub fn without_key_usage(&self) -> Self {
+        let mut map = self.map.clone();
+        map.remove("use");
+        map.remove("key_ops");
+        Self { map }
+    }
+
+    /// Check that the key use (use), key operations (key_ops) and algorithm (alg)
+    /// parameters, when present, allow the operation.
+    pub(crate) fn check_key_usage(
+        &self,
+        key_use: &str,
+        key_operation: &str,
+        algorithm: &str,
+    ) -> anyhow::Result<()> {
+        match self.key_use() {
+            Some(val) if val == key_use => {}
+            None => {}
+            Some(val) => bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyUsage,
+                format!("A parameter use must be {}: {}", key_use, val),
+            )),
+        }
+        if !self.is_for_key_operation(key_operation) {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyUsage,
+                format!("A parameter key_ops must contains {}.", key_operation),
+            ));
+        }
+        match self.algorithm() {
+            Some(val) if val == algorithm => {}
+            None => {}
+            Some(val) => bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyUsage,
+                format!("A parameter alg must be {} but {}", algorithm, val),
+            )),
+        }
+        Ok(())
+    }
+
     /// Set a value for a algorithm parameter (alg).
     ///
     /// # Arguments
@@ -535,7 +1022,18 @@ impl Jwk {
                     }
                     _ => bail!("The JWK {} parameter must be a array of string.", key),
                 },
-                "x5t" | "x5t#S256" | "k" | "d" | "p" | "q" | "dp" | "dq" | "qi" | "x" | "y" => {
+                "k" => match &value {
+                    Value::String(val) => {
+                        if util::decode_base64_url(val, false).is_err() {
+                            bail!(
+                                "The JWK {} parameter must be a base64url string without padding.",
+                                key
+                            );
+                        }
+                    }
+                    _ => bail!("The JWK {} parameter must be a string.", key),
+                },
+                "x5t" | "x5t#S256" | "d" | "p" | "q" | "dp" | "dq" | "qi" | "x" | "y" => {
                     match &value {
                         Value::String(val) => {
                             if !util::is_base64_url_safe_nopad(val) {
@@ -584,9 +1082,480 @@ impl Into<Map<String, Value>> for Jwk {
     }
 }
 
+impl Debug for Jwk {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let mut map = self.map.clone();
+        for key in &["d", "p", "q", "dp", "dq", "qi", "oth", "k"] {
+            if let Some(val) = map.get_mut(*key) {
+                *val = Value::String(util::REDACTED.to_string());
+            }
+        }
+        fmt.debug_struct("Jwk").field("map", &map).finish()
+    }
+}
+
 impl Display for Jwk {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let val = serde_json::to_string(&self.map).map_err(|_e| std::fmt::Error {})?;
         fmt.write_str(&val)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use openssl::pkey::PKey;
+    use serde_json::json;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::Jwk;
+    use crate::jwe::A128KW;
+    use crate::jwk::alg::ec::EcCurve;
+    use crate::jwk::alg::ecx::EcxCurve;
+    use crate::jwk::alg::ed::EdCurve;
+    use crate::jws::{
+        self, EdDSA, JwsAlgorithm, JwsHeader, JwsSigner, JwsVerifier, ES256, HS256, RS256,
+    };
+    use crate::util::{SHA_1, SHA_256, SHA_384};
+    use crate::{JoseError, JoseErrorKind, Map, Value};
+
+    #[test]
+    fn test_debug_redacts_private_members() -> Result<()> {
+        for path in &[
+            "jwk/RSA_private.jwk",
+            "jwk/EC_P-256_private.jwk",
+            "jwk/OKP_X25519_private.jwk",
+            "jwk/oct_256bit_private.jwk",
+        ] {
+            let mut jwk = Jwk::from_bytes(&load_file(path)?)?;
+            jwk.set_key_id("key-1");
+            let debug = format!("{:?}", jwk);
+
+            for key in &["d", "p", "q", "dp", "dq", "qi", "k"] {
+                if let Some(Value::String(val)) = jwk.parameter(key) {
+                    assert!(!debug.contains(val.as_str()), "{} leaked: {}", key, debug);
+                }
+            }
+            assert!(debug.contains("<redacted>"));
+            assert!(debug.contains(jwk.key_type()));
+            assert!(debug.contains("key-1"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbprint() -> Result<()> {
+        // RFC 7638 Section 3.1. Example JWK Thumbprint Computation
+        let jwk = Jwk::from_map(
+            json!({
+                "kty": "RSA",
+                "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+                "e": "AQAB",
+                "alg": "RS256",
+                "kid": "2011-04-29"
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        )?;
+
+        assert_eq!(
+            jwk.thumbprint(SHA_256)?,
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+        assert_eq!(jwk.thumbprint(SHA_384)?.len(), 64);
+
+        let public_jwk = jwk.to_public_key()?;
+        assert_eq!(public_jwk.thumbprint(SHA_256)?, jwk.thumbprint(SHA_256)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbprint_ignores_member_order() -> Result<()> {
+        let jwk1 = Jwk::from_bytes(
+            br#"{"kty":"OKP","crv":"Ed25519","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#,
+        )?;
+        let jwk2 = Jwk::from_bytes(br#"{"x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo","crv":"Ed25519","kty":"OKP","d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A"}"#)?;
+
+        assert_eq!(jwk1.thumbprint(SHA_256)?, jwk2.thumbprint(SHA_256)?);
+        assert_eq!(
+            jwk1.thumbprint(SHA_256)?,
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_key_id_to_thumbprint() -> Result<()> {
+        for mut jwk in vec![
+            Jwk::generate_oct_key(32)?,
+            Jwk::generate_rsa_key(2048)?,
+            Jwk::generate_ec_key(EcCurve::P256)?,
+            Jwk::generate_ed_key(EdCurve::Ed25519)?,
+            Jwk::generate_ecx_key(EcxCurve::X25519)?,
+        ] {
+            let thumbprint = jwk.thumbprint(SHA_256)?;
+            jwk.set_key_id_to_thumbprint()?;
+            assert_eq!(jwk.key_id(), Some(thumbprint.as_str()));
+
+            // The kid parameter is not a member of the thumbprint.
+            assert_eq!(jwk.thumbprint(SHA_256)?, thumbprint);
+
+            jwk.set_key_id_to_thumbprint_uri(SHA_256)?;
+            assert_eq!(
+                jwk.key_id(),
+                Some(
+                    format!(
+                        "urn:ietf:params:oauth:jwk-thumbprint:sha-256:{}",
+                        thumbprint
+                    )
+                    .as_str()
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_key_id_to_thumbprint_without_required_members() -> Result<()> {
+        let mut jwk = Jwk::new("EC");
+        jwk.set_curve("P-256");
+
+        assert!(matches!(
+            jwk.set_key_id_to_thumbprint(),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            jwk.set_key_id_to_thumbprint_uri(SHA_256),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert_eq!(jwk.key_id(), None);
+
+        let mut jwk = Jwk::generate_oct_key(32)?;
+        assert!(jwk.set_key_id_to_thumbprint_uri(SHA_1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_pem() -> Result<()> {
+        for (kty, crv, name) in vec![
+            ("RSA", None, "RSA_2048bit"),
+            ("EC", Some("P-256"), "EC_P-256"),
+            ("EC", Some("P-384"), "EC_P-384"),
+            ("EC", Some("P-521"), "EC_P-521"),
+            ("EC", Some("secp256k1"), "EC_secp256k1"),
+            ("OKP", Some("Ed25519"), "ED25519"),
+            ("OKP", Some("Ed448"), "ED448"),
+            ("OKP", Some("X25519"), "X25519"),
+            ("OKP", Some("X448"), "X448"),
+        ] {
+            let private_key = Jwk::from_pem(&load_file(&format!("pem/{}_private.pem", name))?)?;
+            let traditional_private_key = Jwk::from_pem(&load_file(&format!(
+                "pem/{}_traditional_private.pem",
+                name
+            ))?)?;
+            let public_key = Jwk::from_pem(&load_file(&format!("pem/{}_public.pem", name))?)?;
+
+            assert_eq!(private_key.key_type(), kty);
+            assert_eq!(private_key.curve(), crv);
+            assert!(private_key.parameter("d").is_some());
+            assert_eq!(private_key, traditional_private_key);
+
+            assert_eq!(public_key.key_type(), kty);
+            assert_eq!(public_key.curve(), crv);
+            assert!(public_key.parameter("d").is_none());
+            assert_eq!(private_key.to_public_key()?, public_key);
+        }
+
+        let public_key = Jwk::from_pem(&load_file("pem/RSA_2048bit_public.pem")?)?;
+        let traditional_public_key =
+            Jwk::from_pem(&load_file("pem/RSA_2048bit_traditional_public.pem")?)?;
+        assert_eq!(public_key, traditional_public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_pem_and_der() -> Result<()> {
+        for name in vec![
+            "RSA_2048bit",
+            "EC_P-256",
+            "EC_P-384",
+            "EC_P-521",
+            "EC_secp256k1",
+            "ED25519",
+            "ED448",
+            "X25519",
+            "X448",
+        ] {
+            let private_pem = load_file(&format!("pem/{}_private.pem", name))?;
+            let public_pem = load_file(&format!("pem/{}_public.pem", name))?;
+            let private_key = Jwk::from_pem(&private_pem)?;
+            let public_key = Jwk::from_pem(&public_pem)?;
+
+            let expected_private_key = PKey::private_key_from_pem(&private_pem)?;
+            let expected_public_key = PKey::public_key_from_pem(&public_pem)?;
+
+            let pem = private_key.to_pem_private_key()?;
+            let actual = PKey::private_key_from_pem(&pem)?;
+            assert!(actual.public_eq(&expected_private_key));
+            assert_eq!(Jwk::from_pem(&pem)?, private_key);
+
+            let der = private_key.to_der_private_key()?;
+            let actual = PKey::private_key_from_der(&der)?;
+            assert!(actual.public_eq(&expected_private_key));
+
+            for jwk in vec![&private_key, &public_key] {
+                let pem = jwk.to_pem_public_key()?;
+                let actual = PKey::public_key_from_pem(&pem)?;
+                assert!(actual.public_eq(&expected_public_key));
+                assert_eq!(Jwk::from_pem(&pem)?, public_key);
+
+                let der = jwk.to_der_public_key()?;
+                let actual = PKey::public_key_from_der(&der)?;
+                assert!(actual.public_eq(&expected_public_key));
+            }
+
+            assert!(matches!(
+                public_key.to_pem_private_key(),
+                Err(JoseError::InvalidKeyFormat(_))
+            ));
+        }
+
+        let jwk = Jwk::generate_oct_key(32)?;
+        assert!(matches!(
+            jwk.to_pem_private_key(),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            jwk.to_der_public_key(),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_pem_with_unsupported_key() -> Result<()> {
+        let key = load_file("pem/RSA-PSS_2048bit_SHA-256_private.pem")?;
+        assert!(matches!(
+            Jwk::from_pem(&key),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        let key = b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+        assert!(matches!(
+            Jwk::from_pem(&key[..]),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_public_key() -> Result<()> {
+        let signers: Vec<(Jwk, &dyn JwsAlgorithm)> = vec![
+            (Jwk::generate_rsa_key(2048)?, &RS256),
+            (Jwk::generate_ec_key(EcCurve::P256)?, &ES256),
+            (Jwk::generate_ed_key(EdCurve::Ed25519)?, &EdDSA),
+        ];
+        for (mut private_key, alg) in signers {
+            private_key.set_key_id("key-1");
+            private_key.set_key_use("sig");
+            private_key.set_algorithm(alg.name());
+            private_key.set_key_operations(vec!["sign", "verify"]);
+
+            let public_key = private_key.to_public_key()?;
+            assert_eq!(public_key.key_id(), Some("key-1"));
+            assert_eq!(public_key.key_use(), Some("sig"));
+            assert_eq!(public_key.algorithm(), Some(alg.name()));
+            assert_eq!(public_key.key_operations(), Some(vec!["verify"]));
+
+            let json: Map<String, Value> = serde_json::from_str(&public_key.to_string())?;
+            for key in &["d", "p", "q", "dp", "dq", "qi", "oth"] {
+                assert!(!json.contains_key(*key));
+            }
+            assert_eq!(
+                public_key.thumbprint(SHA_256)?,
+                private_key.thumbprint(SHA_256)?
+            );
+
+            let (signer, verifier): (Box<dyn JwsSigner>, Box<dyn JwsVerifier>) = match alg.name() {
+                "RS256" => (
+                    Box::new(RS256.signer_from_jwk(&private_key)?),
+                    Box::new(RS256.verifier_from_jwk(&public_key)?),
+                ),
+                "ES256" => (
+                    Box::new(ES256.signer_from_jwk(&private_key)?),
+                    Box::new(ES256.verifier_from_jwk(&public_key)?),
+                ),
+                _ => (
+                    Box::new(EdDSA.signer_from_jwk(&private_key)?),
+                    Box::new(EdDSA.verifier_from_jwk(&public_key)?),
+                ),
+            };
+            let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &*signer)?;
+            let (payload, _) = jws::deserialize_compact(&jws, &*verifier)?;
+            assert_eq!(payload, b"test payload!");
+        }
+
+        assert!(matches!(
+            Jwk::generate_oct_key(32)?.to_public_key(),
+            Err(JoseError::InvalidJwkFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_eq() -> Result<()> {
+        let keys = vec![
+            Jwk::generate_rsa_key(2048)?,
+            Jwk::generate_ec_key(EcCurve::P256)?,
+            Jwk::generate_ed_key(EdCurve::Ed25519)?,
+            Jwk::generate_ecx_key(EcxCurve::X25519)?,
+        ];
+        for private_key in &keys {
+            let mut public_key = private_key.to_public_key()?;
+            public_key.set_key_id("key-1");
+            public_key.set_algorithm("alg");
+            assert!(private_key.public_eq(&public_key));
+            assert!(public_key.public_eq(private_key));
+        }
+
+        assert!(!keys[1].public_eq(&Jwk::generate_ec_key(EcCurve::P256)?));
+        assert!(!keys[0].public_eq(&keys[1]));
+        assert!(!keys[0].public_eq(&Jwk::new("RSA")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_key_usage() -> Result<()> {
+        let mut sig_key = Jwk::generate_oct_key(32)?;
+        assert!(HS256.signer_from_jwk(&sig_key).is_ok());
+        assert!(HS256.verifier_from_jwk(&sig_key).is_ok());
+
+        let mut enc_key = Jwk::generate_oct_key(16)?;
+        assert!(A128KW.encrypter_from_jwk(&enc_key).is_ok());
+        assert!(A128KW.decrypter_from_jwk(&enc_key).is_ok());
+
+        sig_key.set_key_use("enc");
+        match HS256.signer_from_jwk(&sig_key) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+        assert!(HS256.signer_from_jwk(&sig_key.without_key_usage()).is_ok());
+
+        enc_key.set_key_use("sig");
+        match A128KW.encrypter_from_jwk(&enc_key) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+        assert!(A128KW
+            .encrypter_from_jwk(&enc_key.without_key_usage())
+            .is_ok());
+
+        let mut sig_key = Jwk::generate_oct_key(32)?;
+        sig_key.set_key_operations(vec!["verify"]);
+        assert!(HS256.verifier_from_jwk(&sig_key).is_ok());
+        match HS256.signer_from_jwk(&sig_key) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+
+        let mut enc_key = Jwk::generate_oct_key(16)?;
+        enc_key.set_key_operations(vec!["unwrapKey"]);
+        assert!(A128KW.decrypter_from_jwk(&enc_key).is_ok());
+        match A128KW.encrypter_from_jwk(&enc_key) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+
+        let mut sig_key = Jwk::generate_oct_key(32)?;
+        sig_key.set_algorithm("HS512");
+        match HS256.signer_from_jwk(&sig_key) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+        match HS256.signer_from_jwk(&sig_key.without_key_usage()) {
+            Err(err) => assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyUsage)),
+            Ok(_) => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_oct_key_interop() -> Result<()> {
+        // RFC 7515 A.1. Example JWS Using HMAC SHA-256
+        let jwk = Jwk::from_bytes(
+            br#"{"kty":"oct",
+                "k":"AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow"
+            }"#,
+        )?;
+        let jws = concat!(
+            "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9",
+            ".",
+            "eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ",
+            ".",
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+        );
+
+        let verifier = HS256.verifier_from_jwk(&jwk)?;
+        let (payload, _) = jws::deserialize_compact(jws, &verifier)?;
+        assert_eq!(
+            payload,
+            b"{\"iss\":\"joe\",\r\n \"exp\":1300819380,\r\n \"http://example.com/is_root\":true}"
+        );
+
+        let k = match jwk.parameter("k") {
+            Some(Value::String(val)) => val.clone(),
+            _ => unreachable!(),
+        };
+        let mut exported = Jwk::new("oct");
+        exported.set_parameter("k", Some(Value::String(k.clone())))?;
+        assert_eq!(
+            exported.to_string(),
+            format!(r#"{{"kty":"oct","k":"{}"}}"#, k)
+        );
+
+        for k in vec![
+            format!("{}==", k),
+            k.replace('-', "+").replace('_', "/"),
+            format!("{}*", k),
+            "A".to_string(),
+        ] {
+            let mut map = Map::new();
+            map.insert("kty".to_string(), Value::String("oct".to_string()));
+            map.insert("k".to_string(), Value::String(k));
+            assert!(matches!(
+                Jwk::from_map(map),
+                Err(JoseError::InvalidJwkFormat(_))
+            ));
+        }
+
+        let jwk = Jwk::generate_oct_key(32)?;
+        match jwk.parameter("k") {
+            Some(Value::String(val)) => assert!(!val.contains('=')),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
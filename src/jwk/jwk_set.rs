@@ -1,17 +1,23 @@
 use std::collections::BTreeMap;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::io::Read;
 use std::ops::Bound::Included;
 use std::string::ToString;
 use std::sync::Arc;
 
 use anyhow::bail;
+use openssl::x509::X509;
 
+use crate::jwe::JweAlgorithm;
 use crate::jwk::Jwk;
+use crate::jws::JwsAlgorithm;
+use crate::util;
 use crate::{JoseError, Map, Value};
 
 /// Represents JWK set.
-#[derive(Debug, Eq, PartialEq, Clone)]
+///
+/// The `Debug` output shows the keys with their private key parameters redacted.
+#[derive(Eq, PartialEq, Clone)]
 pub struct JwkSet {
     keys: Vec<Arc<Jwk>>,
     params: Map<String, Value>,
@@ -20,9 +26,12 @@ pub struct JwkSet {
 
 impl JwkSet {
     pub fn new() -> Self {
+        let mut params = Map::new();
+        params.insert("keys".to_string(), Value::Array(Vec::new()));
+
         Self {
             keys: Vec::new(),
-            params: Map::new(),
+            params,
             kid_map: BTreeMap::new(),
         }
     }
@@ -87,6 +96,68 @@ impl JwkSet {
         })
     }
 
+    /// Return a JWK set of the keys in PEM blocks, and warnings for the skipped blocks.
+    ///
+    /// The input may contain several concatenated keys and certificates. Each key block
+    /// is imported in the same way as `Jwk::from_pem`, and the public key is imported
+    /// from each "CERTIFICATE" block. Unsupported or unparseable blocks are skipped.
+    ///
+    /// # Arguments
+    /// * `input` - PEM blocks of keys or certificates.
+    pub fn from_pem(input: impl AsRef<[u8]>) -> Result<(Self, Vec<String>), JoseError> {
+        Self::from_pem_internal(input.as_ref(), false)
+    }
+
+    /// Return a JWK set of the keys in PEM blocks.
+    ///
+    /// Unlike `from_pem`, this fails on the first unsupported or unparseable block.
+    ///
+    /// # Arguments
+    /// * `input` - PEM blocks of keys or certificates.
+    pub fn from_pem_strict(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        let (jwks, _) = Self::from_pem_internal(input.as_ref(), true)?;
+        Ok(jwks)
+    }
+
+    fn from_pem_internal(input: &[u8], strict: bool) -> Result<(Self, Vec<String>), JoseError> {
+        (|| -> anyhow::Result<(Self, Vec<String>)> {
+            let blocks = util::split_pem(input);
+            if blocks.is_empty() {
+                bail!("No PEM block is found.");
+            }
+
+            let mut keys = Vec::with_capacity(blocks.len());
+            let mut warnings = Vec::new();
+            for (i, (label, block)) in blocks.iter().enumerate() {
+                let result = match label.as_str() {
+                    "CERTIFICATE" => X509::from_pem(block)
+                        .and_then(|cert| cert.public_key())
+                        .and_then(|public_key| public_key.public_key_to_pem())
+                        .map_err(|err| JoseError::InvalidKeyFormat(err.into()))
+                        .and_then(|pem| Jwk::from_pem(&pem)),
+                    _ => Jwk::from_pem(block),
+                };
+
+                match result {
+                    Ok(jwk) => keys.push(Value::Object(jwk.as_ref().clone())),
+                    Err(err) if strict => return Err(err.into()),
+                    Err(err) => warnings.push(format!(
+                        "The PEM block {} ({}) is skipped: {}",
+                        i, label, err
+                    )),
+                }
+            }
+
+            let mut map = Map::new();
+            map.insert("keys".to_string(), Value::Array(keys));
+            Ok((Self::from_map(map)?, warnings))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidKeyFormat(err),
+        })
+    }
+
     pub fn get(&self, key_id: &str) -> Vec<&Jwk> {
         let mut vec = Vec::new();
         for (_, val) in self.kid_map.range((
@@ -103,12 +174,153 @@ impl JwkSet {
         self.keys.iter().map(|e| e.as_ref()).collect()
     }
 
-    pub fn push_key(&mut self, jwk: Jwk) {
-        match self.params.get_mut("keys") {
-            Some(Value::Array(keys)) => {
-                keys.push(Value::Object(jwk.as_ref().clone()));
+    /// Return the keys that match a predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - a function that returns true for a key to select.
+    pub fn filter<F>(&self, predicate: F) -> Vec<&Jwk>
+    where
+        F: Fn(&Jwk) -> bool,
+    {
+        self.keys
+            .iter()
+            .map(|e| e.as_ref())
+            .filter(|jwk| predicate(jwk))
+            .collect()
+    }
+
+    /// Return the keys that can be used to verify a signature of the algorithm.
+    ///
+    /// A key is selected if the key type (and the curve) is suitable for the algorithm,
+    /// the parameter use is "sig" or absent, the parameter key_ops contains "verify"
+    /// or is absent, and the parameter alg is the algorithm or absent.
+    /// If the key ID is specified, only keys with the same kid parameter are selected.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - a signing algorithm.
+    /// * `key_id` - a key ID.
+    pub fn select_for_verification(
+        &self,
+        algorithm: &dyn JwsAlgorithm,
+        key_id: Option<&str>,
+    ) -> Vec<&Jwk> {
+        let name = algorithm.name();
+        let (key_type, curves): (&str, &[&str]) = match name {
+            "HS256" | "HS384" | "HS512" => ("oct", &[]),
+            "RS256" | "RS384" | "RS512" | "PS256" | "PS384" | "PS512" => ("RSA", &[]),
+            "ES256" => ("EC", &["P-256"]),
+            "ES384" => ("EC", &["P-384"]),
+            "ES512" => ("EC", &["P-521"]),
+            "ES256K" => ("EC", &["secp256k1"]),
+            "EdDSA" => ("OKP", &["Ed25519", "Ed448"]),
+            _ => return Vec::new(),
+        };
+
+        self.filter(|jwk| {
+            if jwk.key_type() != key_type {
+                return false;
+            }
+            if !curves.is_empty() {
+                match jwk.curve() {
+                    Some(val) if curves.contains(&val) => {}
+                    _ => return false,
+                }
+            }
+            match jwk.key_use() {
+                Some(val) if val != "sig" => return false,
+                _ => {}
             }
-            _ => unreachable!(),
+            if !jwk.is_for_key_operation("verify") {
+                return false;
+            }
+            match jwk.algorithm() {
+                Some(val) if val != name => return false,
+                _ => {}
+            }
+            match key_id {
+                Some(expected) => jwk.key_id() == Some(expected),
+                None => true,
+            }
+        })
+    }
+
+    /// Return the keys that can be used to encrypt for a recipient with the key management
+    /// algorithm.
+    ///
+    /// A key is selected if the key type (and the curve) is suitable for the algorithm,
+    /// the parameter use is "enc" or absent, the parameter key_ops contains the key
+    /// operation of the algorithm ("encrypt", "wrapKey" or "deriveKey") or is absent,
+    /// and the parameter alg is the algorithm or absent.
+    /// If the key ID is specified, only keys with the same kid parameter are selected.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - a key management algorithm.
+    /// * `key_id` - a key ID.
+    pub fn select_for_encryption(
+        &self,
+        algorithm: &dyn JweAlgorithm,
+        key_id: Option<&str>,
+    ) -> Vec<&Jwk> {
+        let name = algorithm.name();
+        let (key_types, key_operation): (&[&str], &str) = match name {
+            "RSA-OAEP" | "RSA-OAEP-256" | "RSA-OAEP-384" | "RSA-OAEP-512" => (&["RSA"], "encrypt"),
+            "ECDH-ES" | "ECDH-ES+A128KW" | "ECDH-ES+A192KW" | "ECDH-ES+A256KW" => {
+                (&["EC", "OKP"], "deriveKey")
+            }
+            "A128KW" | "A192KW" | "A256KW" | "A128KWP" | "A192KWP" | "A256KWP" => {
+                (&["oct"], "wrapKey")
+            }
+            "A128GCMKW" | "A192GCMKW" | "A256GCMKW" | "dir" => (&["oct"], "encrypt"),
+            "HPKE-0" | "HPKE-3" => (&["EC", "OKP"], "encrypt"),
+            _ => return Vec::new(),
+        };
+        let curve = match name {
+            "HPKE-0" => Some("P-256"),
+            "HPKE-3" => Some("X25519"),
+            _ => None,
+        };
+
+        self.filter(|jwk| {
+            if !key_types.contains(&jwk.key_type()) {
+                return false;
+            }
+            if jwk.key_type() == "OKP" {
+                match jwk.curve() {
+                    Some("X25519") | Some("X448") => {}
+                    _ => return false,
+                }
+            }
+            if curve.is_some() && jwk.curve() != curve {
+                return false;
+            }
+            match jwk.key_use() {
+                Some(val) if val != "enc" => return false,
+                _ => {}
+            }
+            if !jwk.is_for_key_operation(key_operation) {
+                return false;
+            }
+            match jwk.algorithm() {
+                Some(val) if val != name => return false,
+                _ => {}
+            }
+            match key_id {
+                Some(expected) => jwk.key_id() == Some(expected),
+                None => true,
+            }
+        })
+    }
+
+    pub fn push_key(&mut self, jwk: Jwk) {
+        let keys = self
+            .params
+            .entry("keys")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(keys) = keys {
+            keys.push(Value::Object(jwk.as_ref().clone()));
         }
 
         let jwk = Arc::new(jwk);
@@ -122,11 +334,8 @@ impl JwkSet {
     pub fn remove_key(&mut self, jwk: &Jwk) {
         let index = self.keys.iter().position(|e| e.as_ref() == jwk);
         if let Some(index) = index {
-            match self.params.get_mut("keys") {
-                Some(Value::Array(keys)) => {
-                    keys.remove(index);
-                }
-                _ => unreachable!(),
+            if let Some(Value::Array(keys)) = self.params.get_mut("keys") {
+                keys.remove(index);
             }
             self.keys.remove(index);
         }
@@ -145,6 +354,17 @@ impl Into<Map<String, Value>> for JwkSet {
     }
 }
 
+impl Debug for JwkSet {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let mut params = self.params.clone();
+        params.shift_remove("keys");
+        fmt.debug_struct("JwkSet")
+            .field("keys", &self.keys)
+            .field("params", &params)
+            .finish()
+    }
+}
+
 impl Display for JwkSet {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         fmt.write_str("{\"keys\":[")?;
@@ -168,7 +388,17 @@ impl Display for JwkSet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::jwe::{A128GCMKW, A128KW, ECDH_ES, RSA_OAEP};
+    #[cfg(feature = "hpke")]
+    use crate::jwe::{HPKE_0, HPKE_3};
+    use crate::jws::{ES256, ES512, HS256, HS384, HS512, RS256};
     use anyhow::Result;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::x509::{X509Builder, X509NameBuilder};
     use std::fs::File;
     use std::path::PathBuf;
 
@@ -186,6 +416,169 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_push_and_remove_key() -> Result<()> {
+        let mut jwks = JwkSet::new();
+        assert_eq!(jwks.as_ref().get("keys"), Some(&Value::Array(Vec::new())));
+
+        let jwk = Jwk::from_bytes(br#"{"kid":"1","kty":"oct","k":"AAAA"}"#)?;
+        jwks.push_key(jwk.clone());
+        assert_eq!(jwks.get("1").len(), 1);
+        assert_eq!(JwkSet::from_bytes(jwks.to_string())?.keys().len(), 1);
+
+        jwks.remove_key(&jwk);
+        assert_eq!(jwks.keys().len(), 0);
+        assert_eq!(jwks.as_ref().get("keys"), Some(&Value::Array(Vec::new())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_verification() -> Result<()> {
+        let jwks = JwkSet::from_bytes(
+            br#"{"keys":[
+                {"kid":"1","kty":"oct","alg":"HS256","k":"AAAA"},
+                {"kid":"1","kty":"oct","alg":"HS512","k":"BBBB"},
+                {"kid":"1","kty":"oct","use":"enc","k":"CCCC"},
+                {"kty":"oct","k":"DDDD"},
+                {"kid":"2","kty":"EC","crv":"P-256","use":"sig","x":"AAAA","y":"AAAA"},
+                {"kid":"2","kty":"EC","crv":"P-384","x":"BBBB","y":"BBBB"},
+                {"kid":"3","kty":"oct","key_ops":["sign"],"k":"EEEE"}
+            ]}"#,
+        )?;
+
+        let keys = jwks.select_for_verification(&HS256, Some("1"));
+        assert_eq!(keys.len(), 1);
+        assert_eq!(
+            keys[0].parameter("k"),
+            Some(&Value::String("AAAA".to_string()))
+        );
+
+        let keys = jwks.select_for_verification(&HS512, Some("1"));
+        assert_eq!(keys.len(), 1);
+        assert_eq!(
+            keys[0].parameter("k"),
+            Some(&Value::String("BBBB".to_string()))
+        );
+
+        let keys = jwks.select_for_verification(&HS256, None);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(
+            keys[1].parameter("k"),
+            Some(&Value::String("DDDD".to_string()))
+        );
+
+        let keys = jwks.select_for_verification(&ES256, Some("2"));
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].curve(), Some("P-256"));
+
+        assert_eq!(jwks.select_for_verification(&ES512, Some("2")).len(), 0);
+        assert_eq!(jwks.select_for_verification(&RS256, None).len(), 0);
+        assert_eq!(jwks.select_for_verification(&HS384, Some("3")).len(), 0);
+
+        let keys = jwks.filter(|jwk| jwk.key_id().is_none());
+        assert_eq!(keys.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacts_private_keys() -> Result<()> {
+        let jwks = JwkSet::from_bytes(
+            br#"{"keys":[{"kid":"1","kty":"oct","k":"c2VjcmV0LWtleQ"}],"custom":"value"}"#,
+        )?;
+
+        let debug = format!("{:?}", jwks);
+        assert!(!debug.contains("c2VjcmV0LWtleQ"));
+        assert!(debug.contains("<redacted>"));
+        assert!(debug.contains("custom"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_encryption() -> Result<()> {
+        let jwks = JwkSet::from_bytes(
+            br#"{"keys":[
+                {"kid":"1","kty":"RSA","use":"sig","n":"AAAA","e":"AQAB"},
+                {"kid":"2","kty":"RSA","use":"enc","n":"BBBB","e":"AQAB"},
+                {"kid":"3","kty":"EC","crv":"P-256","x":"AAAA","y":"AAAA"},
+                {"kid":"4","kty":"OKP","crv":"Ed25519","x":"AAAA"},
+                {"kid":"5","kty":"OKP","crv":"X25519","key_ops":["deriveKey"],"x":"BBBB"},
+                {"kid":"6","kty":"oct","alg":"A128KW","k":"AAAA"},
+                {"kid":"7","kty":"oct","key_ops":["wrapKey"],"k":"BBBB"}
+            ]}"#,
+        )?;
+
+        let keys = jwks.select_for_encryption(&RSA_OAEP, None);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].key_id(), Some("2"));
+
+        let keys = jwks.select_for_encryption(&ECDH_ES, None);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].key_id(), Some("3"));
+        assert_eq!(keys[1].key_id(), Some("5"));
+
+        let keys = jwks.select_for_encryption(&A128KW, None);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(jwks.select_for_encryption(&A128KW, Some("7")).len(), 1);
+
+        assert_eq!(jwks.select_for_encryption(&A128GCMKW, None).len(), 0);
+        assert_eq!(jwks.select_for_encryption(&RSA_OAEP, Some("1")).len(), 0);
+
+        #[cfg(feature = "hpke")]
+        {
+            let keys = jwks.select_for_encryption(&HPKE_0, None);
+            assert_eq!(keys.len(), 1);
+            assert_eq!(keys[0].key_id(), Some("3"));
+            assert_eq!(jwks.select_for_encryption(&HPKE_3, None).len(), 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_pem() -> Result<()> {
+        let mut input = Vec::new();
+        load_file("pem/RSA_2048bit_private.pem")?.read_to_end(&mut input)?;
+        load_file("pem/EC_P-256_public.pem")?.read_to_end(&mut input)?;
+
+        let (jwks, warnings) = JwkSet::from_pem(&input)?;
+        assert_eq!(jwks.keys().len(), 2);
+        assert_eq!(jwks.keys()[0].key_type(), "RSA");
+        assert!(jwks.keys()[0].parameter("d").is_some());
+        assert_eq!(jwks.keys()[1].key_type(), "EC");
+        assert_eq!(jwks.keys()[1].curve(), Some("P-256"));
+        assert!(warnings.is_empty());
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = EcKey::generate(&group)?;
+        let key = PKey::from_ec_key(key)?;
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", "test")?;
+        let name = name.build();
+        let mut builder = X509Builder::new()?;
+        builder.set_version(2)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&key)?;
+        builder.set_not_before(&*Asn1Time::days_from_now(0)?)?;
+        builder.set_not_after(&*Asn1Time::days_from_now(1)?)?;
+        builder.sign(&key, MessageDigest::sha256())?;
+        input.extend_from_slice(&builder.build().to_pem()?);
+        input.extend_from_slice(b"-----BEGIN UNKNOWN-----\nAAAA\n-----END UNKNOWN-----\n");
+
+        let (jwks, warnings) = JwkSet::from_pem(&input)?;
+        assert_eq!(jwks.keys().len(), 3);
+        assert!(jwks.keys()[2].public_eq(&Jwk::from_pem(&key.public_key_to_pem()?)?));
+        assert_eq!(warnings.len(), 1);
+
+        assert!(JwkSet::from_pem_strict(&input).is_err());
+        assert!(JwkSet::from_pem(b"no pem").is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<File> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -1,25 +1,39 @@
 //! JSON Web Signature (JWS) support.
 
 pub mod alg;
+mod jwk_signer;
 mod jws_algorithm;
 mod jws_context;
 mod jws_header;
 mod jws_header_set;
+mod x509;
 
 use once_cell::sync::Lazy;
 
 use crate::JoseError;
 
+pub use crate::jws::jwk_signer::signer_from_jwk;
 pub use crate::jws::jws_algorithm::JwsAlgorithm;
 pub use crate::jws::jws_algorithm::JwsSigner;
 pub use crate::jws::jws_algorithm::JwsVerifier;
 pub use crate::jws::jws_context::JwsContext;
 pub use crate::jws::jws_header::JwsHeader;
 pub use crate::jws::jws_header_set::JwsHeaderSet;
+pub use crate::jws::x509::{
+    verifier_from_x509_certificate, verify_x509_certificate_chain,
+    verify_x509_certificate_sha256_thumbprint, x509_certificate_chain,
+    x509_certificate_sha256_thumbprint,
+};
 
 use crate::jws::alg::hmac::HmacJwsAlgorithm;
 pub use HmacJwsAlgorithm::Hs256 as HS256;
 pub use HmacJwsAlgorithm::Hs384 as HS384;
+#[cfg(feature = "sha3")]
+pub use HmacJwsAlgorithm::Hs3_256 as HS3_256;
+#[cfg(feature = "sha3")]
+pub use HmacJwsAlgorithm::Hs3_384 as HS3_384;
+#[cfg(feature = "sha3")]
+pub use HmacJwsAlgorithm::Hs3_512 as HS3_512;
 pub use HmacJwsAlgorithm::Hs512 as HS512;
 
 use crate::jws::alg::rsassa::RsassaJwsAlgorithm;
@@ -76,6 +90,41 @@ where
     DEFAULT_CONTEXT.serialize_compact_with_selector(payload, header, selector)
 }
 
+/// Return a representation of the data that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// # Arguments
+///
+/// * `payload` - The payload data that is signed but not included in the result.
+/// * `header` - The JWS heaser claims.
+/// * `signer` - The JWS signer.
+pub fn serialize_compact_detached(
+    payload: &[u8],
+    header: &JwsHeader,
+    signer: &dyn JwsSigner,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_compact_detached(payload, header, signer)
+}
+
+/// Return a representation of the data that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// # Arguments
+///
+/// * `payload` - The payload data that is signed but not included in the result.
+/// * `header` - The JWS heaser claims.
+/// * `selector` - a function for selecting the signing algorithm.
+pub fn serialize_compact_detached_with_selector<'a, F>(
+    payload: &[u8],
+    header: &JwsHeader,
+    selector: F,
+) -> Result<String, JoseError>
+where
+    F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
+{
+    DEFAULT_CONTEXT.serialize_compact_detached_with_selector(payload, header, selector)
+}
+
 /// Return a representation of the data that is formatted by general json serialization.
 ///
 /// # Arguments
@@ -142,6 +191,20 @@ where
     DEFAULT_CONTEXT.serialize_flattened_json_with_selector(payload, header, selector)
 }
 
+/// Return the protected header of the input that is formatted by compact
+/// serialization, without verifying the signature.
+///
+/// The returned header is untrusted: it may have been forged or tampered
+/// with. Use it only to decide how to verify the input (e.g. to look up
+/// a key by `kid`), never as a substitute for verification.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn extract_header(input: impl AsRef<[u8]>) -> Result<JwsHeader, JoseError> {
+    DEFAULT_CONTEXT.extract_header(input)
+}
+
 /// Deserialize the input that is formatted by compact serialization.
 ///
 /// # Arguments
@@ -173,6 +236,41 @@ where
     DEFAULT_CONTEXT.deserialize_compact_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `payload` - The detached payload data.
+/// * `verifier` - The JWS verifier.
+pub fn deserialize_compact_detached(
+    input: impl AsRef<[u8]>,
+    payload: &[u8],
+    verifier: &dyn JwsVerifier,
+) -> Result<JwsHeader, JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_detached(input, payload, verifier)
+}
+
+/// Deserialize the input that is formatted by compact serialization
+/// with a detached payload (RFC 7515 Appendix F).
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `payload` - The detached payload data.
+/// * `selector` - a function for selecting the verifying algorithm.
+pub fn deserialize_compact_detached_with_selector<'a, F>(
+    input: impl AsRef<[u8]>,
+    payload: &[u8],
+    selector: F,
+) -> Result<JwsHeader, JoseError>
+where
+    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_compact_detached_with_selector(input, payload, selector)
+}
+
 /// Deserialize the input that is formatted by json serialization.
 ///
 /// # Arguments
@@ -204,15 +302,66 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by json serialization,
+/// keeping the protected and unprotected header claims apart.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `verifier` - The JWS verifier.
+pub fn deserialize_json_header_set<'a>(
+    input: impl AsRef<[u8]>,
+    verifier: &'a dyn JwsVerifier,
+) -> Result<(Vec<u8>, JwsHeaderSet), JoseError> {
+    DEFAULT_CONTEXT.deserialize_json_header_set(input, verifier)
+}
+
+/// Deserialize the input that is formatted by json serialization,
+/// keeping the protected and unprotected header claims apart.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `selector` - a function for selecting the verifying algorithm.
+pub fn deserialize_json_header_set_with_selector<'a, F>(
+    input: impl AsRef<[u8]>,
+    selector: F,
+) -> Result<(Vec<u8>, JwsHeaderSet), JoseError>
+where
+    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_json_header_set_with_selector(input, selector)
+}
+
+/// Verify every signature of the input that is formatted by json serialization,
+/// and return whether each signature entry passed.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `verifiers` - The JWS verifiers.
+pub fn verify_all(
+    input: impl AsRef<[u8]>,
+    verifiers: &[&dyn JwsVerifier],
+) -> Result<Vec<bool>, JoseError> {
+    DEFAULT_CONTEXT.verify_all(input, verifiers)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
     use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
 
     use anyhow::Result;
 
-    use crate::jws::{self, EdDSA, JwsHeader, JwsHeaderSet, ES256, RS256};
-    use crate::Value;
+    use crate::jwk::{Jwk, KeyPair};
+    use crate::jws::{
+        self, EdDSA, JwsContext, JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier, ES256, HS256,
+        RS256,
+    };
+    use crate::{AlgorithmPolicy, JoseError, JsonType, Map, Value};
 
     #[test]
     fn test_jws_compact_serialization() -> Result<()> {
@@ -237,6 +386,453 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_compact_detached_serialization() -> Result<()> {
+        let alg = RS256;
+
+        let private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let src_payload = b"test payload!";
+        let signer = alg.signer_from_pem(&private_key)?;
+        let jwt = jws::serialize_compact_detached(src_payload, &src_header, &signer)?;
+
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[1], "");
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        let dst_header = jws::deserialize_compact_detached(&jwt, src_payload, &verifier)?;
+
+        src_header.set_claim("alg", Some(Value::String(alg.name().to_string())))?;
+        assert_eq!(src_header, dst_header);
+
+        assert!(jws::deserialize_compact_detached(&jwt, b"test payload?", &verifier).is_err());
+        assert!(jws::deserialize_compact(&jwt, &verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_serialization_preserves_header_order() -> Result<()> {
+        let key = base64::decode_config(
+            "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8",
+            base64::URL_SAFE_NO_PAD,
+        )?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        src_header.set_claim("a", Some(Value::from(1)))?;
+        src_header.set_claim("b", Some(Value::from(2)))?;
+        src_header.set_claim("c", Some(Value::from(3)))?;
+        src_header.set_claim("a", None)?;
+        let mut signer = HS256.signer_from_bytes(&key)?;
+        signer.set_key_id("key-1");
+        let jwt = jws::serialize_compact_detached(b"test payload!", &src_header, &signer)?;
+
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(
+            base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD)?,
+            br#"{"typ":"JWT","b":2,"c":3,"alg":"HS256","kid":"key-1"}"#.to_vec()
+        );
+        // Produced by Python hmac with the same key and header bytes.
+        assert_eq!(
+            jwt,
+            "eyJ0eXAiOiJKV1QiLCJiIjoyLCJjIjozLCJhbGciOiJIUzI1NiIsImtpZCI6ImtleS0xIn0.\
+             .hlml-OtDn2Oz0jWqReBL2bjrTm82s5n9DXvuUlDCOm8"
+        );
+
+        let verifier = HS256.verifier_from_bytes(&key)?;
+        let dst_header = jws::deserialize_compact_detached(&jwt, b"test payload!", &verifier)?;
+        let names: Vec<&str> = dst_header
+            .claims_set()
+            .keys()
+            .map(|val| val.as_str())
+            .collect();
+        assert_eq!(names, vec!["typ", "b", "c", "alg", "kid"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_serialization_with_whitespace() -> Result<()> {
+        let key = HS256.generate_key();
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+
+        let src_payload = b"test payload!";
+        let jws = jws::serialize_compact(src_payload, &JwsHeader::new(), &signer)?;
+        let wrapped = jws
+            .as_bytes()
+            .chunks(76)
+            .map(|val| std::str::from_utf8(val).unwrap())
+            .collect::<Vec<&str>>()
+            .join("\r\n");
+        let wrapped = format!("  {}\n", wrapped);
+
+        let mut context = JwsContext::new();
+        assert!(matches!(
+            context.deserialize_compact(&wrapped, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(context.extract_header(&wrapped).is_err());
+
+        context.set_lenient_base64(true);
+        let (dst_payload, _) = context.deserialize_compact(&wrapped, &verifier)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(context.extract_header(&wrapped)?.algorithm(), Some("HS256"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_detached_serialization_unencoded() -> Result<()> {
+        let alg = ES256;
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_base64url_encode_payload(false);
+        src_header.set_critical(&vec!["b64"]);
+        let src_payload = b"$.02";
+        let signer = alg.signer_from_pem(&private_key)?;
+        let jwt = jws::serialize_compact_detached(src_payload, &src_header, &signer)?;
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("b64");
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        let dst_header = context.deserialize_compact_detached(&jwt, src_payload, &verifier)?;
+        assert_eq!(dst_header.base64url_encode_payload(), Some(false));
+
+        assert!(context
+            .deserialize_compact_detached(&jwt, b"$.03", &verifier)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_and_verify_raw_message() -> Result<()> {
+        let message = b"@method: POST\n@path: /foo\ncontent-digest: sha-256=:X48E9q==:";
+
+        let rsa_private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let rsa_public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let ec_private_key = load_file("pem/EC_P-256_private.pem")?;
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+
+        let pairs: Vec<(Box<dyn JwsSigner>, Box<dyn JwsVerifier>)> = vec![
+            (
+                Box::new(HS256.signer_from_bytes(key)?),
+                Box::new(HS256.verifier_from_bytes(key)?),
+            ),
+            (
+                Box::new(RS256.signer_from_pem(&rsa_private_key)?),
+                Box::new(RS256.verifier_from_pem(&rsa_public_key)?),
+            ),
+            (
+                Box::new(ES256.signer_from_pem(&ec_private_key)?),
+                Box::new(ES256.verifier_from_pem(&ec_public_key)?),
+            ),
+        ];
+
+        for (signer, verifier) in &pairs {
+            let signature = signer.sign(message)?;
+            verifier.verify(message, &signature)?;
+            assert!(verifier.verify(b"another message", &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reuse_verifier() -> Result<()> {
+        let private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+
+        let signer = RS256.signer_from_pem(&private_key)?;
+        let verifier = RS256.verifier_from_pem(&public_key)?;
+
+        let jwss = (0..100)
+            .map(|i| {
+                let payload = format!("test payload {}", i);
+                jws::serialize_compact(payload.as_bytes(), &JwsHeader::new(), &signer)
+            })
+            .collect::<Result<Vec<String>, JoseError>>()?;
+
+        let verifier = Arc::new(verifier);
+        let handles: Vec<_> = jwss
+            .chunks(25)
+            .map(|chunk| {
+                let chunk = chunk.to_vec();
+                let verifier = Arc::clone(&verifier);
+                thread::spawn(move || {
+                    for jws in &chunk {
+                        jws::deserialize_compact(jws, &*verifier).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for (i, jws) in jwss.iter().enumerate() {
+            let (payload, _) = jws::deserialize_compact(jws, &*verifier)?;
+            assert_eq!(payload, format!("test payload {}", i).as_bytes());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_unencoded_payload_rfc7797() -> Result<()> {
+        // RFC 7797 4. Examples
+        let key = base64::decode_config(
+            "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow",
+            base64::URL_SAFE_NO_PAD,
+        )?;
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+        let payload = b"$.02";
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("b64");
+
+        // 4.1. Example with Header Parameters {"alg":"HS256"}
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS256");
+        let jws = context.serialize_compact(payload, &header, &signer)?;
+        assert_eq!(
+            jws,
+            "eyJhbGciOiJIUzI1NiJ9.JC4wMg.5mvfOroL-g7HyqJoozehmsaqmvTYGEq5jTI1gVvoEoQ"
+        );
+
+        // 4.2. Example with Header Parameters {"alg":"HS256","b64":false,"crit":["b64"]}
+        header.set_base64url_encode_payload(false);
+        header.set_critical(&vec!["b64"]);
+        let jws = context.serialize_compact_detached(payload, &header, &signer)?;
+        assert_eq!(
+            jws,
+            "eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19..A5dxf2s96_n5FLueVuW1Z_vh161FwXZC4YLPff6dmDY"
+        );
+        let dst_header = context.deserialize_compact_detached(&jws, payload, &verifier)?;
+        assert_eq!(dst_header.base64url_encode_payload(), Some(false));
+
+        // The unencoded payload contains a dot, so it cannot be attached in compact form.
+        assert!(matches!(
+            context.serialize_compact(payload, &header, &signer),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let mut header_set = JwsHeaderSet::new();
+        header_set.set_algorithm("HS256", true);
+        header_set.set_base64url_encode_payload(false);
+        header_set.set_critical(&vec!["b64"]);
+        let json = context.serialize_flattened_json(payload, &header_set, &signer)?;
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"protected":"eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19","#,
+                r#""payload":"$.02","#,
+                r#""signature":"A5dxf2s96_n5FLueVuW1Z_vh161FwXZC4YLPff6dmDY"}"#
+            )
+        );
+        let (dst_payload, _) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(dst_payload, payload);
+
+        let signer: Box<dyn JwsSigner> = Box::new(signer);
+        let json = context.serialize_general_json(payload, &vec![(&header_set, &*signer)])?;
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"signatures":[{"protected":"eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19","#,
+                r#""signature":"A5dxf2s96_n5FLueVuW1Z_vh161FwXZC4YLPff6dmDY"}],"payload":"$.02"}"#
+            )
+        );
+        let (dst_payload, _) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(dst_payload, payload);
+
+        // The b64 header claim must agree among all signatures.
+        let mut encoded_header_set = JwsHeaderSet::new();
+        encoded_header_set.set_algorithm("HS256", true);
+        assert!(matches!(
+            context.serialize_general_json(
+                payload,
+                &vec![(&header_set, &*signer), (&encoded_header_set, &*signer)]
+            ),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        // An unencoded payload is escaped in JSON serialization.
+        let payload = br#"{"quote":"\""}"#;
+        let json = context.serialize_flattened_json(payload, &header_set, &*signer)?;
+        let (dst_payload, _) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(dst_payload, payload);
+
+        Ok(())
+    }
+
+    // Signs a 50 MiB payload, so run this explicitly with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_jws_compact_detached_serialization_large_payload() -> Result<()> {
+        let alg = RS256;
+
+        let private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+
+        let src_header = JwsHeader::new();
+        let src_payload: Vec<u8> = (0..(50 * 1024 * 1024)).map(|i| (i % 251) as u8).collect();
+        let signer = alg.signer_from_pem(&private_key)?;
+        let jwt = jws::serialize_compact_detached(&src_payload, &src_header, &signer)?;
+
+        let parts: Vec<&str> = jwt.split('.').collect();
+        let mut message = format!("{}.", parts[0]);
+        base64::encode_config_buf(&src_payload, base64::URL_SAFE_NO_PAD, &mut message);
+        let expected = signer.sign(message.as_bytes())?;
+        assert_eq!(
+            base64::decode_config(parts[2], base64::URL_SAFE_NO_PAD)?,
+            expected
+        );
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        jws::deserialize_compact_detached(&jwt, &src_payload, &verifier)?;
+        verifier.verify(message.as_bytes(), &expected)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_serialization_with_padded_base64() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let header_b64 = base64::encode_config(br#"{"alg":"HS256"}"#, base64::URL_SAFE);
+        let payload_b64 = base64::encode_config(b"test payload!", base64::URL_SAFE);
+        assert!(payload_b64.ends_with("=="));
+        let message = format!("{}.{}", header_b64, payload_b64);
+        let signature = signer.sign(message.as_bytes())?;
+        let signature_b64 = base64::encode_config(&signature, base64::STANDARD);
+        let jws = format!("{}.{}", message, signature_b64);
+
+        let mut context = JwsContext::new();
+        assert!(!context.is_lenient_base64());
+        assert!(matches!(
+            context.deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        context.set_lenient_base64(true);
+        let (dst_payload, dst_header) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(dst_header.algorithm(), Some("HS256"));
+        assert_eq!(dst_payload, b"test payload!");
+
+        let jws = format!("{}.{}*", message, signature_b64);
+        assert!(matches!(
+            context.deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_max_token_size() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let src_payload = vec![b'a'; 10000];
+        let mut context = JwsContext::new();
+        let jws = context.serialize_compact(&src_payload, &JwsHeader::new(), &signer)?;
+        let json = context.serialize_flattened_json(&src_payload, &JwsHeaderSet::new(), &signer)?;
+
+        context.set_max_token_size(jws.len() - 1);
+        assert!(matches!(
+            context.deserialize_compact_with_selector(&jws, |_header| {
+                panic!("The header must not be decoded.")
+            }),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            context.extract_header(&jws),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        context.set_max_token_size(json.len());
+        let (dst_payload, _) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(src_payload, dst_payload);
+        let (dst_payload, _) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_critical_header_claims() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let signer = ES256.signer_from_pem(&private_key)?;
+        let verifier = ES256.verifier_from_pem(&public_key)?;
+
+        let src_payload = b"test payload!";
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("exp1");
+        context.add_acceptable_critical("kid");
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_critical(&vec!["exp1"]);
+        src_header.set_claim("exp1", Some(Value::String("value".to_string())))?;
+        let jwt = jws::serialize_compact(src_payload, &src_header, &signer)?;
+        let (dst_payload, _) = context.deserialize_compact(&jwt, &verifier)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let mut header_set = JwsHeaderSet::new();
+        header_set.set_critical(&vec!["exp1"]);
+        header_set.set_claim("exp1", Some(Value::String("value".to_string())), true)?;
+        let json = jws::serialize_flattened_json(src_payload, &header_set, &signer)?;
+        let (dst_payload, _) = context.deserialize_json(&json, &verifier)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_critical(&vec!["exp2"]);
+        src_header.set_claim("exp2", Some(Value::String("value".to_string())))?;
+        let jwt = jws::serialize_compact(src_payload, &src_header, &signer)?;
+        assert!(matches!(
+            context.deserialize_compact(&jwt, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_key_id("xxx");
+        src_header.set_critical(&vec!["kid"]);
+        let jwt = jws::serialize_compact(src_payload, &src_header, &signer)?;
+        assert!(matches!(
+            context.deserialize_compact(&jwt, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let mut header_set = JwsHeaderSet::new();
+        header_set.set_claim("exp1", Some(Value::String("value".to_string())), true)?;
+        header_set.set_claim("crit", Some(serde_json::json!(["exp1"])), false)?;
+        let json = jws::serialize_flattened_json(src_payload, &header_set, &signer)?;
+        assert!(matches!(
+            context.deserialize_json(&json, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_jws_json_serialization() -> Result<()> {
         let alg = RS256;
@@ -307,6 +903,346 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_verify_all() -> Result<()> {
+        let private_key_1 = load_file("pem/RSA_2048bit_private.pem")?;
+        let private_key_2 = load_file("pem/EC_P-256_private.pem")?;
+        let private_key_3 = load_file("pem/ED25519_private.pem")?;
+        let public_key_1 = load_file("pem/RSA_2048bit_public.pem")?;
+        let public_key_2 = load_file("pem/EC_P-256_public.pem")?;
+
+        let signer_1 = RS256.signer_from_pem(&private_key_1)?;
+        let signer_2 = ES256.signer_from_pem(&private_key_2)?;
+        let signer_3 = EdDSA.signer_from_pem(&private_key_3)?;
+        let signer_4 = HS256.signer_from_bytes(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let json = jws::serialize_general_json(
+            b"test payload!",
+            &vec![
+                (&JwsHeaderSet::new(), &*signer_1),
+                (&JwsHeaderSet::new(), &*signer_2),
+                (&JwsHeaderSet::new(), &*signer_3),
+                (&JwsHeaderSet::new(), &*signer_4),
+            ],
+        )?;
+
+        let verifier_1 = RS256.verifier_from_pem(&public_key_1)?;
+        let verifier_2 = ES256.verifier_from_pem(&public_key_2)?;
+        let verifier_4 = HS256.verifier_from_bytes(b"FEDCBA9876543210FEDCBA9876543210")?;
+        let results = jws::verify_all(&json, &[&*verifier_1, &*verifier_2, &*verifier_4])?;
+        assert_eq!(results, vec![true, true, false, false]);
+
+        assert_eq!(jws::verify_all(&json, &[])?, vec![false; 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_general_json_serialization_with_invalid_signature() -> Result<()> {
+        let key_pair_1 = ES256.generate_key_pair()?;
+        let key_pair_2 = ES256.generate_key_pair()?;
+
+        let src_payload = b"test payload!";
+
+        let src_header = JwsHeaderSet::new();
+        let signer_1 = ES256.signer_from_der(key_pair_1.to_der_private_key())?;
+        let signer_2 = ES256.signer_from_der(key_pair_2.to_der_private_key())?;
+
+        let json = jws::serialize_general_json(
+            src_payload,
+            &[(&src_header, &signer_1), (&src_header, &signer_2)],
+        )?;
+
+        let mut map: Map<String, Value> = serde_json::from_str(&json)?;
+        if let Some(Value::Array(signatures)) = map.get_mut("signatures") {
+            let signature = base64::encode_config([0; 64], base64::URL_SAFE_NO_PAD);
+            signatures[0]["signature"] = Value::String(signature);
+        }
+        let json = serde_json::to_string(&map)?;
+
+        let verifier = ES256.verifier_from_der(key_pair_2.to_der_public_key())?;
+        let (dst_payload, dst_header) = jws::deserialize_json(&json, &verifier)?;
+        assert_eq!(dst_header.algorithm(), Some("ES256"));
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let verifier = ES256.verifier_from_der(key_pair_1.to_der_public_key())?;
+        assert!(matches!(
+            jws::deserialize_json(&json, &verifier),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_flattened_json_deserialization_rfc7515() -> Result<()> {
+        // RFC 7515 A.7. Example JWS Using Flattened JWS JSON Serialization
+        let json = r#"{
+            "payload": "eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ",
+            "protected": "eyJhbGciOiJFUzI1NiJ9",
+            "header": {"kid":"e9bc097a-ce51-4036-9562-d2ade882db0d"},
+            "signature": "DtEhU3ljbEg8L38VWAfUAqOyKAM6-Xx-F4GawxaepmXFCgfTjDxw5djxLa8ISlSApmWQxfKTUJqPP3-Kg6NU1Q"
+        }"#;
+
+        let jwk = Jwk::from_bytes(
+            br#"{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+                "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"
+            }"#,
+        )?;
+        let verifier = ES256.verifier_from_jwk(&jwk)?;
+        let (payload, header) = jws::deserialize_json(json, &verifier)?;
+        assert_eq!(
+            payload,
+            b"{\"iss\":\"joe\",\r\n \"exp\":1300819380,\r\n \"http://example.com/is_root\":true}"
+        );
+        assert_eq!(header.algorithm(), Some("ES256"));
+        assert_eq!(
+            header.key_id(),
+            Some("e9bc097a-ce51-4036-9562-d2ade882db0d")
+        );
+
+        let json = json.replace("DtEhU3ljbEg8", "DtEhU3ljbEg9");
+        assert!(jws::deserialize_json(&json, &verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_flattened_json_serialization_with_unprotected_header() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let src_payload = b"test payload!";
+        let mut src_header = JwsHeaderSet::new();
+        src_header.set_token_type("JWT", true);
+        src_header.set_key_id("xxx", false);
+
+        let signer = ES256.signer_from_pem(&private_key)?;
+        let json = jws::serialize_flattened_json(src_payload, &src_header, &signer)?;
+
+        let verifier = ES256.verifier_from_pem(&public_key)?;
+        let (dst_payload, dst_header) =
+            jws::deserialize_json_header_set_with_selector(&json, |header| {
+                assert_eq!(header.key_id(), Some("xxx"));
+                Ok(Some(&verifier))
+            })?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+        assert_eq!(dst_header.key_id(), Some("xxx"));
+        assert_eq!(dst_header.token_type(), Some("JWT"));
+        assert!(dst_header.claims_set(false).contains_key("kid"));
+        assert!(!dst_header.claims_set(true).contains_key("kid"));
+        assert!(dst_header.claims_set(true).contains_key("alg"));
+
+        // The unprotected header is not part of the signing input.
+        let mut map: Map<String, Value> = serde_json::from_str(&json)?;
+        map.insert("header".to_string(), serde_json::json!({ "kid": "yyy" }));
+        let json = serde_json::to_string(&map)?;
+
+        let (dst_payload, dst_header) = jws::deserialize_json(&json, &verifier)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+        assert_eq!(dst_header.key_id(), Some("yyy"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_content_type_round_trip() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        for (value, expected) in vec![
+            ("JWT", "JWT"),
+            ("text/plain", "text/plain"),
+            ("application/cbor", "cbor"),
+        ] {
+            let mut src_header = JwsHeader::new();
+            src_header.set_content_type(value);
+            let jws = jws::serialize_compact(b"test payload!", &src_header, &signer)?;
+
+            let (_, dst_header) = jws::deserialize_compact(&jws, &verifier)?;
+            assert_eq!(dst_header.content_type(), Some(expected));
+            assert_eq!(dst_header.claim("cty"), Some(&Value::from(expected)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_reject_algorithm_confusion() -> Result<()> {
+        let private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let header = JwsHeader::new();
+        let signer = RS256.signer_from_pem(&private_key)?;
+        let jws = jws::serialize_compact(b"test payload!", &header, &signer)?;
+        let json = jws::serialize_flattened_json(b"test payload!", &JwsHeaderSet::new(), &signer)?;
+
+        let es256_verifier = ES256.verifier_from_pem(&ec_public_key)?;
+        let hs256_verifier = HS256.verifier_from_bytes(&public_key)?;
+        let verifiers: Vec<&dyn JwsVerifier> = vec![&es256_verifier, &hs256_verifier];
+        for verifier in verifiers {
+            assert!(matches!(
+                jws::deserialize_compact(&jws, verifier),
+                Err(JoseError::InvalidJwsFormat(_))
+            ));
+            assert!(matches!(
+                jws::deserialize_json(&json, verifier),
+                Err(JoseError::InvalidJwsFormat(_))
+            ));
+        }
+
+        // A token signed with HS256 using the RSA public key as the secret.
+        let signer = HS256.signer_from_bytes(&public_key)?;
+        let jws = jws::serialize_compact(b"test payload!", &header, &signer)?;
+        let verifier = RS256.verifier_from_pem(&public_key)?;
+        assert!(matches!(
+            jws::deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_parameters() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+
+        let mut context = JwsContext::new();
+        context.register_header_parameter("exp1", JsonType::Number);
+        assert_eq!(
+            context.header_parameter_type("exp1"),
+            Some(JsonType::Number)
+        );
+
+        // A registered parameter is understood in crit.
+        let mut src_header = JwsHeader::new();
+        src_header.set_critical(&vec!["exp1"]);
+        src_header.set_claim("exp1", Some(serde_json::json!(1)))?;
+        let jws = context.serialize_compact(b"test payload!", &src_header, &signer)?;
+        let (_, dst_header) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(dst_header.claim("exp1"), Some(&serde_json::json!(1)));
+
+        // The type is only validated when strict header parameters are enabled.
+        let mut src_header = JwsHeader::new();
+        src_header.set_claim("exp1", Some(serde_json::json!("1")))?;
+        src_header.set_claim("exp2", Some(serde_json::json!({ "a": [1] })))?;
+        let jws = context.serialize_compact(b"test payload!", &src_header, &signer)?;
+        let mut header_set = JwsHeaderSet::new();
+        header_set.set_claim("exp1", Some(serde_json::json!("1")), false)?;
+        let json = context.serialize_flattened_json(b"test payload!", &header_set, &signer)?;
+        context.deserialize_compact(&jws, &verifier)?;
+        context.deserialize_json(&json, &verifier)?;
+
+        context.set_strict_header_parameters(true);
+        assert!(matches!(
+            context.deserialize_compact(&jws, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        // An unregistered parameter is passed through untouched.
+        context.unregister_header_parameter("exp1");
+        let (_, dst_header) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(dst_header.claim("exp1"), Some(&serde_json::json!("1")));
+        assert_eq!(
+            dst_header.claim("exp2"),
+            Some(&serde_json::json!({ "a": [1] }))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_algorithm_policy() -> Result<()> {
+        let private_key = load_file("pem/ED25519_private.pem")?;
+        let public_key = load_file("pem/ED25519_public.pem")?;
+        let signer = EdDSA.signer_from_pem(&private_key)?;
+        let verifier = EdDSA.verifier_from_pem(&public_key)?;
+
+        let mut context = JwsContext::new();
+        let jws = context.serialize_compact(b"test payload!", &JwsHeader::new(), &signer)?;
+        let json =
+            context.serialize_flattened_json(b"test payload!", &JwsHeaderSet::new(), &signer)?;
+        context.deserialize_compact(&jws, &verifier)?;
+        context.deserialize_json(&json, &verifier)?;
+
+        context.set_algorithm_policy(AlgorithmPolicy::fips());
+        assert!(matches!(
+            context.serialize_compact(b"test payload!", &JwsHeader::new(), &signer),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.serialize_flattened_json(b"test payload!", &JwsHeaderSet::new(), &signer),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.deserialize_compact(&jws, &verifier),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+        assert!(matches!(
+            context.deserialize_json(&json, &verifier),
+            Err(JoseError::UnsupportedAlgorithm(_))
+        ));
+
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+        let verifier = HS256.verifier_from_bytes(key)?;
+        let jws = context.serialize_compact(b"test payload!", &JwsHeader::new(), &signer)?;
+        let (payload, _) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_extract_header() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_bytes(key)?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_key_id("key-1");
+        let jws = jws::serialize_compact(b"test payload!", &src_header, &signer)?;
+
+        let dst_header = jws::extract_header(&jws)?;
+        assert_eq!(dst_header.key_id(), Some("key-1"));
+        assert_eq!(dst_header.algorithm(), Some("HS256"));
+
+        // The signature is not verified.
+        let tampered = format!("{}AAAA", jws);
+        let dst_header = jws::extract_header(&tampered)?;
+        assert_eq!(dst_header.key_id(), Some("key-1"));
+
+        let header_b64 = base64::encode_config(br#"{"alg":"HS256""#, base64::URL_SAFE_NO_PAD);
+        for input in vec![
+            "".to_string(),
+            "e30.e30".to_string(),
+            "e30.e30.e30.e30".to_string(),
+            "*.e30.".to_string(),
+            format!("{}.e30.", header_b64),
+            format!(
+                "{}.e30.",
+                base64::encode_config(b"[]", base64::URL_SAFE_NO_PAD)
+            ),
+        ] {
+            assert!(matches!(
+                jws::extract_header(&input),
+                Err(JoseError::InvalidJwsFormat(_))
+            ));
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
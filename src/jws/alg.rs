@@ -1,5 +1,7 @@
 pub mod ecdsa;
 pub mod eddsa;
+pub mod external;
 pub mod hmac;
+pub mod rsa_any;
 pub mod rsassa;
 pub mod rsassa_pss;
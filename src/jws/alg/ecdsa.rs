@@ -2,9 +2,13 @@ use std::fmt::Display;
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::EcPoint;
+use openssl::hash::{Hasher, MessageDigest};
 use openssl::pkey::{PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::{
     alg::ec::{EcCurve, EcKeyPair},
     Jwk,
@@ -12,8 +16,20 @@ use crate::jwk::{
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerReader, DerType};
 use crate::util::{self, HashAlgorithm};
-use crate::{JoseError, Value};
-
+use crate::{JoseError, JoseErrorKind, Value};
+
+/// The order of the secp256k1 curve.
+const SECP256K1_ORDER: [u8; 32] = [
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
+    0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x41,
+];
+
+/// ECDSA signature algorithms.
+///
+/// For ES256K, signers always produce low-S signatures (s <= n/2). Verifiers accept
+/// high-S signatures by default for compatibility, so that a signature is malleable.
+/// Use `EcdsaJwsVerifier::set_low_s_required` to reject them, as is required by most
+/// secp256k1 ecosystems.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum EcdsaJwsAlgorithm {
     /// ECDSA using P-256 and SHA-256
@@ -70,6 +86,7 @@ impl EcdsaJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            deterministic: false,
         })
     }
 
@@ -89,6 +106,7 @@ impl EcdsaJwsAlgorithm {
             algorithm: self.clone(),
             private_key: key_pair.into_private_key(),
             key_id: None,
+            deterministic: false,
         })
     }
 
@@ -98,19 +116,7 @@ impl EcdsaJwsAlgorithm {
     /// * `jwk` - A private key that is formatted by a JWK of EC type.
     pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<EcdsaJwsSigner, JoseError> {
         (|| -> anyhow::Result<EcdsaJwsSigner> {
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("sign") {
-                bail!("A parameter key_ops must contains sign.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-                None => {}
-            }
+            jwk.check_key_usage("sig", "sign", self.name())?;
             match jwk.curve() {
                 Some(val) if val == self.curve().name() => {}
                 Some(val) => bail!("A parameter crv must be {} but {}", self.name(), val),
@@ -125,6 +131,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                deterministic: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -153,6 +160,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                low_s_required: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -188,6 +196,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                low_s_required: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -205,19 +214,7 @@ impl EcdsaJwsAlgorithm {
                 val if val == "EC" => {}
                 val => bail!("A parameter kty must be EC: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("verify") {
-                bail!("A parameter key_ops must contains verify.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "verify", self.name())?;
             match jwk.parameter("crv") {
                 Some(Value::String(val)) if val == curve.name() => {}
                 Some(Value::String(val)) => {
@@ -250,6 +247,7 @@ impl EcdsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                low_s_required: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -316,6 +314,7 @@ pub struct EcdsaJwsSigner {
     algorithm: EcdsaJwsAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    deterministic: bool,
 }
 
 impl EcdsaJwsSigner {
@@ -326,6 +325,85 @@ impl EcdsaJwsSigner {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set whether signatures are generated with a deterministic nonce (RFC 6979)
+    /// instead of a random one. The default value is false.
+    ///
+    /// # Arguments
+    /// * `value` - true to generate deterministic signatures.
+    pub fn set_deterministic(&mut self, value: bool) {
+        self.deterministic = value;
+    }
+
+    /// Return whether signatures are generated with a deterministic nonce.
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    fn sign_deterministic(&self, md: MessageDigest, digest: &[u8]) -> anyhow::Result<Vec<u8>> {
+        // RFC 6979 3.2. Generation of k
+        let ec_key = self.private_key.ec_key()?;
+        let group = ec_key.group();
+        let mut ctx = BigNumContext::new()?;
+        let mut order = BigNum::new()?;
+        group.order(&mut order, &mut ctx)?;
+
+        let qlen = order.num_bits() as usize;
+        let rolen = ((qlen + 7) / 8) as i32;
+        let mut x = ec_key.private_key().to_owned()?;
+        x.set_const_time();
+        let e = bits_to_int(digest, qlen)?;
+        let mut h = BigNum::new()?;
+        h.nnmod(&e, &order, &mut ctx)?;
+
+        let x_octets = x.to_vec_padded(rolen)?;
+        let h_octets = h.to_vec_padded(rolen)?;
+        let mut v = vec![0x01; md.size()];
+        let mut k = vec![0x00; md.size()];
+        k = hmac(md, &k, &[&v, &[0x00], &x_octets, &h_octets])?;
+        v = hmac(md, &k, &[&v])?;
+        k = hmac(md, &k, &[&v, &[0x01], &x_octets, &h_octets])?;
+        v = hmac(md, &k, &[&v])?;
+
+        loop {
+            let mut t = Vec::with_capacity(rolen as usize + md.size());
+            while t.len() < rolen as usize {
+                v = hmac(md, &k, &[&v])?;
+                t.extend_from_slice(&v);
+            }
+
+            let mut nonce = bits_to_int(&t, qlen)?;
+            nonce.set_const_time();
+            if nonce.num_bits() > 0 && nonce < order {
+                let mut point = EcPoint::new(group)?;
+                point.mul_generator2(group, &nonce, &mut ctx)?;
+                let mut px = BigNum::new()?;
+                let mut py = BigNum::new()?;
+                point.affine_coordinates_gfp(group, &mut px, &mut py, &mut ctx)?;
+
+                let mut r = BigNum::new()?;
+                r.nnmod(&px, &order, &mut ctx)?;
+                if r.num_bits() > 0 {
+                    let mut nonce_inv = BigNum::new()?;
+                    nonce_inv.mod_inverse(&nonce, &order, &mut ctx)?;
+                    let mut rx = BigNum::new()?;
+                    rx.mod_mul(&r, &x, &order, &mut ctx)?;
+                    let mut sum = BigNum::new()?;
+                    sum.mod_add(&h, &rx, &order, &mut ctx)?;
+                    let mut s = BigNum::new()?;
+                    s.mod_mul(&nonce_inv, &sum, &order, &mut ctx)?;
+                    if s.num_bits() > 0 {
+                        let mut signature = r.to_vec_padded(rolen)?;
+                        signature.extend_from_slice(&s.to_vec_padded(rolen)?);
+                        return Ok(signature);
+                    }
+                }
+            }
+
+            k = hmac(md, &k, &[&v, &[0x00]])?;
+            v = hmac(md, &k, &[&v])?;
+        }
+    }
 }
 
 impl JwsSigner for EcdsaJwsSigner {
@@ -345,33 +423,59 @@ impl JwsSigner for EcdsaJwsSigner {
     }
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_parts(&[message])
+    }
+
+    fn sign_parts(&self, parts: &[&[u8]]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
-            let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
-            let der_signature = signer.sign_to_vec()?;
-
             let signature_len = self.signature_len();
             let sep = signature_len / 2;
 
-            let mut signature = Vec::with_capacity(signature_len);
-            let mut reader = DerReader::from_bytes(&der_signature);
-            match reader.next()? {
-                Some(DerType::Sequence) => {}
-                _ => unreachable!("A generated signature is invalid."),
-            }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+            let mut signature = if self.deterministic {
+                let mut hasher = Hasher::new(md)?;
+                for part in parts {
+                    hasher.update(part)?;
                 }
-                _ => unreachable!("A generated signature is invalid."),
-            }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+                let digest = hasher.finish()?;
+                self.sign_deterministic(md, &digest)?
+            } else {
+                let mut signer = Signer::new(md, &self.private_key)?;
+                for part in parts {
+                    signer.update(part)?;
+                }
+                let der_signature = signer.sign_to_vec()?;
+
+                let mut signature = Vec::with_capacity(signature_len);
+                let mut reader = DerReader::from_bytes(&der_signature);
+                match reader.next()? {
+                    Some(DerType::Sequence) => {}
+                    _ => unreachable!("A generated signature is invalid."),
+                }
+                match reader.next()? {
+                    Some(DerType::Integer) => {
+                        signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+                    }
+                    _ => unreachable!("A generated signature is invalid."),
+                }
+                match reader.next()? {
+                    Some(DerType::Integer) => {
+                        signature.extend_from_slice(&reader.to_be_bytes(false, sep));
+                    }
+                    _ => unreachable!("A generated signature is invalid."),
+                }
+                signature
+            };
+
+            if let EcdsaJwsAlgorithm::Es256k = self.algorithm {
+                if is_high_s(&signature[sep..])? {
+                    let order = BigNum::from_slice(&SECP256K1_ORDER)?;
+                    let s = BigNum::from_slice(&signature[sep..])?;
+                    let low_s = (&order - &s).to_vec_padded(sep as i32)?;
+                    signature.truncate(sep);
+                    signature.extend_from_slice(&low_s);
                 }
-                _ => unreachable!("A generated signature is invalid."),
             }
 
             Ok(signature)
@@ -397,6 +501,7 @@ pub struct EcdsaJwsVerifier {
     algorithm: EcdsaJwsAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    low_s_required: bool,
 }
 
 impl EcdsaJwsVerifier {
@@ -407,6 +512,20 @@ impl EcdsaJwsVerifier {
     pub fn remove_key_id(&mut self) {
         self.key_id = None;
     }
+
+    /// Set whether a high-S signature (s > n/2) is rejected. It is only used for ES256K.
+    /// The default value is false.
+    ///
+    /// # Arguments
+    /// * `value` - true to reject a high-S signature.
+    pub fn set_low_s_required(&mut self, value: bool) {
+        self.low_s_required = value;
+    }
+
+    /// Return whether a high-S signature is rejected.
+    pub fn is_low_s_required(&self) -> bool {
+        self.low_s_required
+    }
 }
 
 impl JwsVerifier for EcdsaJwsVerifier {
@@ -422,6 +541,10 @@ impl JwsVerifier for EcdsaJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_parts(&[message], signature)
+    }
+
+    fn verify_parts(&self, parts: &[&[u8]], signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             let signature_len = self.algorithm.signature_len();
             if signature.len() != signature_len {
@@ -432,6 +555,12 @@ impl JwsVerifier for EcdsaJwsVerifier {
                 );
             }
 
+            if let (EcdsaJwsAlgorithm::Es256k, true) = (self.algorithm, self.low_s_required) {
+                if is_high_s(&signature[(signature_len / 2)..])? {
+                    bail!("A high-S signature is not allowed.");
+                }
+            }
+
             let mut der_builder = DerBuilder::new();
             der_builder.begin(DerType::Sequence);
             {
@@ -448,9 +577,14 @@ impl JwsVerifier for EcdsaJwsVerifier {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
+            for part in parts {
+                verifier.update(part)?;
+            }
             if !verifier.verify(&der_signature)? {
-                bail!("The signature does not match.");
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::SignatureMismatch,
+                    "The signature does not match."
+                ));
             }
             Ok(())
         })()
@@ -470,11 +604,40 @@ impl Deref for EcdsaJwsVerifier {
     }
 }
 
+fn bits_to_int(input: &[u8], qlen: usize) -> anyhow::Result<BigNum> {
+    let mut val = BigNum::from_slice(input)?;
+    if input.len() * 8 > qlen {
+        let mut shifted = BigNum::new()?;
+        shifted.rshift(&val, (input.len() * 8 - qlen) as i32)?;
+        val = shifted;
+    }
+    Ok(val)
+}
+
+fn hmac(md: MessageDigest, key: &[u8], parts: &[&[u8]]) -> anyhow::Result<Vec<u8>> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(md, &key)?;
+    for part in parts {
+        signer.update(part)?;
+    }
+    Ok(signer.sign_to_vec()?)
+}
+
+fn is_high_s(s: &[u8]) -> anyhow::Result<bool> {
+    let order = BigNum::from_slice(&SECP256K1_ORDER)?;
+    let mut half_order = BigNum::new()?;
+    half_order.rshift1(&order)?;
+    let s = BigNum::from_slice(s)?;
+    Ok(s > half_order)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use anyhow::Result;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
     use std::fs;
     use std::path::PathBuf;
 
@@ -714,6 +877,110 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_es256k_with_test_vector() -> Result<()> {
+        // Generated by the Python cryptography package with a low-S signature.
+        let input = b"eyJhbGciOiJFUzI1NksifQ.eyJpc3MiOiJqb2UifQ";
+        let signature = base64::decode_config(
+            "gJE15I6CHX1uzV9nHVNM6Ax-T-x6VYhMFmjhsUehQmNNf_EZWJ_dXDPqZUOaln8aa4XV1YMJZgQq9sgSL427mw",
+            base64::URL_SAFE_NO_PAD,
+        )?;
+        let high_s_signature = base64::decode_config(
+            "gJE15I6CHX1uzV9nHVNM6Ax-T-x6VYhMFmjhsUehQmOygA7mp2Aio8wVmrxlaYDkTykHESw_OjeU25Z6oKiFpg",
+            base64::URL_SAFE_NO_PAD,
+        )?;
+
+        let alg = EcdsaJwsAlgorithm::Es256k;
+        let public_key = Jwk::from_bytes(&load_file("jwk/EC_secp256k1_public.jwk")?)?;
+        let mut verifier = alg.verifier_from_jwk(&public_key)?;
+        verifier.verify(input, &signature)?;
+        verifier.verify(input, &high_s_signature)?;
+
+        verifier.set_low_s_required(true);
+        verifier.verify(input, &signature)?;
+        verifier
+            .verify(input, &high_s_signature)
+            .expect_err("High-S signature did not fail");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_es256k_with_low_s() -> Result<()> {
+        let input = b"abcde12345";
+
+        let alg = EcdsaJwsAlgorithm::Es256k;
+        let private_key = Jwk::from_bytes(&load_file("jwk/EC_secp256k1_private.jwk")?)?;
+        let signer = alg.signer_from_jwk(&private_key)?;
+        for _ in 0..32 {
+            let signature = signer.sign(input)?;
+            assert!(!is_high_s(&signature[32..])?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_ecdsa_deterministic() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::Es256,
+            EcdsaJwsAlgorithm::Es384,
+            EcdsaJwsAlgorithm::Es512,
+            EcdsaJwsAlgorithm::Es256k,
+        ] {
+            let key_pair = alg.generate_key_pair()?;
+
+            let mut signer = alg.signer_from_der(&key_pair.to_der_private_key())?;
+            assert!(!signer.is_deterministic());
+            signer.set_deterministic(true);
+            let signature = signer.sign(input)?;
+            assert_eq!(signer.sign(input)?, signature);
+            assert_ne!(signer.sign(b"abcde12346")?, signature);
+
+            let verifier = alg.verifier_from_der(&key_pair.to_der_public_key())?;
+            verifier.verify(input, &signature)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_ecdsa_deterministic_rfc6979() -> Result<()> {
+        // RFC 6979 A.2.5. ECDSA, 256 Bits (Prime Field), With SHA-256, message "sample"
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let d = BigNum::from_hex_str(
+            "C9AFA9D845BA75166B5C215767B1D6934E50C3DB36E89B127B8A622B120F6721",
+        )?;
+        let mut ctx = BigNumContext::new()?;
+        let mut public_key = EcPoint::new(&group)?;
+        public_key.mul_generator2(&group, &d, &mut ctx)?;
+        let ec_key = EcKey::from_private_components(&group, &d, &public_key)?;
+
+        let signer = EcdsaJwsSigner {
+            algorithm: EcdsaJwsAlgorithm::Es256,
+            private_key: PKey::from_ec_key(ec_key)?,
+            key_id: None,
+            deterministic: true,
+        };
+        let signature = signer.sign(b"sample")?;
+
+        let mut expected = BigNum::from_hex_str(
+            "EFD48B2AACB6A8FD1140DD9CD45E81D69D2C877B56AAF991C34D0EA84EAF3716",
+        )?
+        .to_vec();
+        expected.extend_from_slice(
+            &BigNum::from_hex_str(
+                "F7CB1C942D657C41D436C7A1B6E29F65F3E900DBB9AFF4064DC4AB2F843ACDA8",
+            )?
+            .to_vec(),
+        );
+        assert_eq!(signature, expected);
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
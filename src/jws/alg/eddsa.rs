@@ -2,16 +2,17 @@ use std::fmt::Display;
 use std::ops::Deref;
 
 use anyhow::bail;
-use openssl::pkey::{PKey, Private, Public};
+use openssl::pkey::{Id, PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::{
     alg::ed::{EdCurve, EdKeyPair},
     Jwk,
 };
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util;
-use crate::{JoseError, Value};
+use crate::{JoseError, JoseErrorKind, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum EddsaJwsAlgorithm {
@@ -96,19 +97,7 @@ impl EddsaJwsAlgorithm {
     /// * `jwk` - A private key that is formatted by a JWK of OKP type.
     pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<EddsaJwsSigner, JoseError> {
         (|| -> anyhow::Result<EddsaJwsSigner> {
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("sign") {
-                bail!("A parameter key_ops must contains sign.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "sign", self.name())?;
 
             let key_pair = EdKeyPair::from_jwk(jwk)?;
             let curve = key_pair.curve();
@@ -125,6 +114,43 @@ impl EddsaJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a signer from a raw private key.
+    ///
+    /// A raw private key is the 32 bytes seed of Ed25519 or the 57 bytes seed of Ed448
+    /// (RFC 8032), as stored by libsodium or OpenSSH. Other lengths are rejected.
+    ///
+    /// # Arguments
+    /// * `input` - A raw private key.
+    pub fn signer_from_raw_private_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<EddsaJwsSigner, JoseError> {
+        (|| -> anyhow::Result<EddsaJwsSigner> {
+            let input = input.as_ref();
+            let (curve, id) = match input.len() {
+                32 => (EdCurve::Ed25519, Id::ED25519),
+                57 => (EdCurve::Ed448, Id::ED448),
+                len => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The EdDSA raw private key must be 32 (Ed25519) or 57 (Ed448) bytes: {}",
+                        len
+                    )
+                )),
+            };
+
+            let private_key = PKey::private_key_from_raw_bytes(input, id)?;
+
+            Ok(EddsaJwsSigner {
+                algorithm: self.clone(),
+                curve,
+                private_key,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
     /// Return a verifier from a public key that is a DER encoded SubjectPublicKeyInfo.
     ///
     /// # Arguments
@@ -150,6 +176,42 @@ impl EddsaJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a verifier from a raw public key.
+    ///
+    /// A raw public key is the 32 bytes key of Ed25519 or the 57 bytes key of Ed448
+    /// (RFC 8032). Other lengths are rejected.
+    ///
+    /// # Arguments
+    /// * `input` - A raw public key.
+    pub fn verifier_from_raw_public_key(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<EddsaJwsVerifier, JoseError> {
+        (|| -> anyhow::Result<EddsaJwsVerifier> {
+            let input = input.as_ref();
+            let id = match input.len() {
+                32 => Id::ED25519,
+                57 => Id::ED448,
+                len => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidKeyLength,
+                    format!(
+                        "The EdDSA raw public key must be 32 (Ed25519) or 57 (Ed448) bytes: {}",
+                        len
+                    )
+                )),
+            };
+
+            let public_key = PKey::public_key_from_raw_bytes(input, id)?;
+
+            Ok(EddsaJwsVerifier {
+                algorithm: self.clone(),
+                public_key,
+                key_id: None,
+            })
+        })()
+        .map_err(|err| JoseError::InvalidKeyFormat(err))
+    }
+
     /// Return a verifier from a key of common PEM format.
     ///
     /// Common PEM format is a DER and base64 encoded SubjectPublicKeyInfo
@@ -194,19 +256,7 @@ impl EddsaJwsAlgorithm {
                 val if val == "OKP" => {}
                 val => bail!("A parameter kty must be OKP: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("verify") {
-                bail!("A parameter key_ops must contains verify.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "verify", self.name())?;
             let curve = match jwk.parameter("crv") {
                 Some(Value::String(val)) if val == "Ed25519" => EdCurve::Ed25519,
                 Some(Value::String(val)) if val == "Ed448" => EdCurve::Ed448,
@@ -267,6 +317,11 @@ pub struct EddsaJwsSigner {
 }
 
 impl EddsaJwsSigner {
+    /// Return the curve of the private key.
+    pub fn curve(&self) -> EdCurve {
+        self.curve
+    }
+
     pub fn set_key_id(&mut self, value: impl Into<String>) {
         self.key_id = Some(value.into());
     }
@@ -351,7 +406,10 @@ impl JwsVerifier for EddsaJwsVerifier {
         (|| -> anyhow::Result<()> {
             let mut verifier = Verifier::new_without_digest(&self.public_key)?;
             if !verifier.verify_oneshot(signature, message)? {
-                bail!("The signature does not match.")
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::SignatureMismatch,
+                    "The signature does not match."
+                ))
             }
             Ok(())
         })()
@@ -379,6 +437,8 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
+    use crate::util::decode_hex;
+
     #[test]
     fn sign_and_verify_eddsa_generated_der() -> Result<()> {
         let input = b"abcde12345";
@@ -530,6 +590,128 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_eddsa_ed448_with_test_vector() -> Result<()> {
+        // RFC 8032 Section 7.4. Test Vectors for Ed448 (-----Blank)
+        let private_key = decode_hex(concat!(
+            "6c82a562cb808d10d632be89c8513ebf6c929f34ddfa8c9f63c9960ef6e348a3",
+            "528c8a3fcc2f044e39a3fc5b94492f8f032e7549a20098f95b",
+        ));
+        let public_key = decode_hex(concat!(
+            "5fd7449b59b461fd2ce787ec616ad46a1da1342485a70e1f8a0ea75d80e96778",
+            "edf124769b46c7061bd6783df1e50f6cd1fa1abeafe8256180",
+        ));
+        let expected_signature = decode_hex(concat!(
+            "533a37f6bbe457251f023c0d88f976ae2dfb504a843e34d2074fd823d41a591f",
+            "2b233f034f628281f2fd7a22ddd47d7828c59bd0a21bfd3980ff0d2028d4b18a",
+            "9df63e006c5d1c2d345b925d8dc00b4104852db99ac5c7cdda8530a113a0f4db",
+            "b61149f05a7363268c71d95808ff2e652600",
+        ));
+
+        let mut jwk = Jwk::new("OKP");
+        jwk.set_parameter("crv", Some(Value::String("Ed448".to_string())))?;
+        jwk.set_parameter(
+            "x",
+            Some(Value::String(base64::encode_config(
+                &public_key,
+                base64::URL_SAFE_NO_PAD,
+            ))),
+        )?;
+        let public_jwk = jwk.clone();
+        jwk.set_parameter(
+            "d",
+            Some(Value::String(base64::encode_config(
+                &private_key,
+                base64::URL_SAFE_NO_PAD,
+            ))),
+        )?;
+
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        let signer = alg.signer_from_jwk(&jwk)?;
+        assert_eq!(signer.curve(), EdCurve::Ed448);
+        assert_eq!(signer.signature_len(), 114);
+
+        let signature = signer.sign(b"")?;
+        assert_eq!(signature, expected_signature);
+
+        let verifier = alg.verifier_from_jwk(&public_jwk)?;
+        verifier.verify(b"", &expected_signature)?;
+        verifier
+            .verify(b"a", &expected_signature)
+            .expect_err("Unmatched signature did not fail");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_eddsa_raw_keys_with_test_vector() -> Result<()> {
+        // RFC 8032 Section 7.1. Test Vectors for Ed25519 (TEST 1)
+        let private_key =
+            decode_hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+        let public_key =
+            decode_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let expected_signature = decode_hex(concat!(
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155",
+            "5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ));
+
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        let signer = alg.signer_from_raw_private_key(&private_key)?;
+        assert_eq!(signer.curve(), EdCurve::Ed25519);
+
+        let signature = signer.sign(b"")?;
+        assert_eq!(signature, expected_signature);
+
+        let verifier = alg.verifier_from_raw_public_key(&public_key)?;
+        verifier.verify(b"", &signature)?;
+        verifier
+            .verify(b"a", &signature)
+            .expect_err("Unmatched signature did not fail");
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_eddsa_raw_keys_with_wrong_length() -> Result<()> {
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        for len in vec![0, 31, 33, 56, 64] {
+            let key = vec![1; len];
+
+            let err = alg
+                .signer_from_raw_private_key(&key)
+                .expect_err("A raw private key of wrong length was accepted");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+            let err = alg
+                .verifier_from_raw_public_key(&key)
+                .expect_err("A raw public key of wrong length was accepted");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_eddsa_with_curve_detected_from_jwk() -> Result<()> {
+        let alg = EddsaJwsAlgorithm::Eddsa;
+        for (filename, curve) in vec![
+            ("jwk/OKP_Ed25519_private.jwk", EdCurve::Ed25519),
+            ("jwk/OKP_Ed448_private.jwk", EdCurve::Ed448),
+        ] {
+            let jwk = Jwk::from_bytes(&load_file(filename)?)?;
+            let signer = alg.signer_from_jwk(&jwk)?;
+            assert_eq!(signer.curve(), curve);
+        }
+
+        let jwk = Jwk::from_bytes(&load_file("jwk/OKP_X448_private.jwk")?)?;
+        assert!(matches!(
+            alg.signer_from_jwk(&jwk),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -0,0 +1,246 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
+use crate::JoseError;
+
+type SignFn = dyn Fn(&[u8]) -> Result<Vec<u8>, Box<dyn Error>> + Send + Sync;
+type VerifyFn = dyn Fn(&[u8], &[u8]) -> Result<(), Box<dyn Error>> + Send + Sync;
+
+/// A signer that delegates signing to an external function, such as a HSM or KMS client.
+///
+/// The function receives the JWS signing input and must return the signature in the
+/// JWS format of the algorithm (e.g. R || S for ECDSA, not DER).
+#[derive(Clone)]
+pub struct ExternalJwsSigner {
+    algorithm: Box<dyn JwsAlgorithm>,
+    key_id: Option<String>,
+    signature_len: usize,
+    sign: Arc<SignFn>,
+}
+
+impl ExternalJwsSigner {
+    /// Return a signer that signs by an external function.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The declared signing algorithm.
+    /// * `key_id` - The key ID of the external key.
+    /// * `signature_len` - The length in bytes of the signatures returned by `sign`.
+    /// * `sign` - A function that returns a signature of the signing input.
+    pub fn new<F>(
+        algorithm: &dyn JwsAlgorithm,
+        key_id: Option<&str>,
+        signature_len: usize,
+        sign: F,
+    ) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        Self {
+            algorithm: algorithm.box_clone(),
+            key_id: key_id.map(|val| val.to_string()),
+            signature_len,
+            sign: Arc::new(sign),
+        }
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JwsSigner for ExternalJwsSigner {
+    fn algorithm(&self) -> &dyn JwsAlgorithm {
+        self.algorithm.as_ref()
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn signature_len(&self) -> usize {
+        self.signature_len
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        (self.sign)(message).map_err(|err| {
+            JoseError::InvalidSignature(anyhow!("The external signer failed: {}", err))
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsSigner> {
+        Box::new(self.clone())
+    }
+}
+
+impl fmt::Debug for ExternalJwsSigner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ExternalJwsSigner")
+            .field("algorithm", &self.algorithm)
+            .field("key_id", &self.key_id)
+            .field("signature_len", &self.signature_len)
+            .finish()
+    }
+}
+
+impl Deref for ExternalJwsSigner {
+    type Target = dyn JwsSigner;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+/// A verifier that delegates verification to an external function, such as a HSM or KMS client.
+///
+/// The function receives the JWS signing input and the decoded signature, and must return
+/// an error if the signature is invalid.
+#[derive(Clone)]
+pub struct ExternalJwsVerifier {
+    algorithm: Box<dyn JwsAlgorithm>,
+    key_id: Option<String>,
+    verify: Arc<VerifyFn>,
+}
+
+impl ExternalJwsVerifier {
+    /// Return a verifier that verifies by an external function.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The declared signing algorithm.
+    /// * `key_id` - The key ID of the external key.
+    /// * `verify` - A function that verifies a signature of the signing input.
+    pub fn new<F>(algorithm: &dyn JwsAlgorithm, key_id: Option<&str>, verify: F) -> Self
+    where
+        F: Fn(&[u8], &[u8]) -> Result<(), Box<dyn Error>> + Send + Sync + 'static,
+    {
+        Self {
+            algorithm: algorithm.box_clone(),
+            key_id: key_id.map(|val| val.to_string()),
+            verify: Arc::new(verify),
+        }
+    }
+
+    pub fn set_key_id(&mut self, value: impl Into<String>) {
+        self.key_id = Some(value.into());
+    }
+
+    pub fn remove_key_id(&mut self) {
+        self.key_id = None;
+    }
+}
+
+impl JwsVerifier for ExternalJwsVerifier {
+    fn algorithm(&self) -> &dyn JwsAlgorithm {
+        self.algorithm.as_ref()
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        (self.verify)(message, signature).map_err(|err| {
+            JoseError::InvalidSignature(anyhow!("The external verifier failed: {}", err))
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsVerifier> {
+        Box::new(self.clone())
+    }
+}
+
+impl fmt::Debug for ExternalJwsVerifier {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ExternalJwsVerifier")
+            .field("algorithm", &self.algorithm)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl Deref for ExternalJwsVerifier {
+    type Target = dyn JwsVerifier;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::jwk::Jwk;
+    use crate::jws::{self, JwsHeader, ES256};
+
+    #[test]
+    fn sign_and_verify_external() -> Result<()> {
+        let private_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/EC_P-256_public.jwk")?)?;
+
+        let local_signer = ES256.signer_from_jwk(&private_key)?;
+        let local_signer_len = local_signer.signature_len();
+        let signer = ExternalJwsSigner::new(&ES256, Some("remote-key"), 64, move |message| {
+            Ok(local_signer.sign(message)?)
+        });
+        assert_eq!(signer.signature_len(), local_signer_len);
+        let local_verifier = ES256.verifier_from_jwk(&public_key)?;
+        let verifier = ExternalJwsVerifier::new(&ES256, None, move |message, signature| {
+            Ok(local_verifier.verify(message, signature)?)
+        });
+
+        let src_payload = b"test payload!";
+        let token = jws::serialize_compact(src_payload, &JwsHeader::new(), &signer)?;
+
+        let local_verifier = ES256.verifier_from_jwk(&public_key)?;
+        let (dst_payload, dst_header) = jws::deserialize_compact(&token, &local_verifier)?;
+        assert_eq!(dst_header.algorithm(), Some("ES256"));
+        assert_eq!(dst_header.key_id(), Some("remote-key"));
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        let (dst_payload, _) = jws::deserialize_compact(&token, &verifier)?;
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_external_with_error() -> Result<()> {
+        let signer = ExternalJwsSigner::new(&ES256, None, 64, |_message| Err("unavailable".into()));
+        assert!(matches!(
+            jws::serialize_compact(b"test payload!", &JwsHeader::new(), &signer),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
@@ -2,15 +2,25 @@ use std::fmt::Display;
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::hash::MessageDigest;
 use openssl::pkey::{PKey, Private};
 use openssl::sign::Signer;
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::Jwk;
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
-use crate::util::HashAlgorithm;
-use crate::{JoseError, Value};
-
+use crate::util;
+use crate::{JoseError, JoseErrorKind, Value};
+
+/// HMAC signing algorithms.
+///
+/// Besides HS256, HS384 and HS512 of RFC 7518, HMAC using SHA3-256, SHA3-384 and
+/// SHA3-512 is provided as a non-standard extension. Their alg names are prefixed
+/// with "josekit:" because they are not registered, so other JOSE implementations
+/// cannot verify the produced JWS. They are available only when the `sha3` feature
+/// is enabled.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[allow(non_camel_case_types)]
 pub enum HmacJwsAlgorithm {
     /// HMAC using SHA-256
     Hs256,
@@ -18,9 +28,23 @@ pub enum HmacJwsAlgorithm {
     Hs384,
     /// HMAC using SHA-512
     Hs512,
+    /// HMAC using SHA3-256 (non-standard)
+    #[cfg(feature = "sha3")]
+    Hs3_256,
+    /// HMAC using SHA3-384 (non-standard)
+    #[cfg(feature = "sha3")]
+    Hs3_384,
+    /// HMAC using SHA3-512 (non-standard)
+    #[cfg(feature = "sha3")]
+    Hs3_512,
 }
 
 impl HmacJwsAlgorithm {
+    /// Generate a random secret key that has the same length as the hash output.
+    pub fn generate_key(&self) -> Vec<u8> {
+        util::random_bytes(self.output_len())
+    }
+
     /// Make a JWK encoded oct private key.
     ///
     /// # Arguments
@@ -39,25 +63,162 @@ impl HmacJwsAlgorithm {
 
     /// Return a signer from a secret key.
     ///
+    /// The secret key must be at least as long as the hash output.
+    ///
     /// # Arguments
     /// * `data` - A secret key.
     pub fn signer_from_bytes(&self, input: impl AsRef<[u8]>) -> Result<HmacJwsSigner, JoseError> {
-        (|| -> anyhow::Result<HmacJwsSigner> {
-            let input = input.as_ref();
+        self.key_policy(false).signer_from_bytes(input)
+    }
+
+    /// Return a signer from a secret key that is formatted by a JWK of oct type.
+    ///
+    /// The secret key must be at least as long as the hash output.
+    ///
+    /// # Arguments
+    /// * `jwk` - A secret key that is formatted by a JWK of oct type.
+    pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<HmacJwsSigner, JoseError> {
+        self.key_policy(false).signer_from_jwk(jwk)
+    }
+
+    /// Return a verifier from a secret key.
+    ///
+    /// The secret key must be at least as long as the hash output.
+    ///
+    /// # Arguments
+    /// * `input` - A secret key.
+    pub fn verifier_from_bytes(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<HmacJwsVerifier, JoseError> {
+        self.key_policy(false).verifier_from_bytes(input)
+    }
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if input.len() < min_key_len {
-                bail!(
+    /// Return a verifier from a secret key that is formatted by a JWK of oct type.
+    ///
+    /// The secret key must be at least as long as the hash output.
+    ///
+    /// # Arguments
+    /// * `jwk` - A secret key that is formatted by a JWK of oct type.
+    pub fn verifier_from_jwk(&self, jwk: &Jwk) -> Result<HmacJwsVerifier, JoseError> {
+        self.key_policy(false).verifier_from_jwk(jwk)
+    }
+
+    /// Return the same algorithm without the minimum secret key length.
+    ///
+    /// RFC 7518 requires a secret key of at least the hash output length.
+    /// This is only for interoperating with legacy systems that use shorter keys.
+    pub fn allow_weak_keys(&self) -> HmacJwsKeyPolicy {
+        self.key_policy(true)
+    }
+
+    fn key_policy(&self, allow_weak_keys: bool) -> HmacJwsKeyPolicy {
+        HmacJwsKeyPolicy {
+            algorithm: self.clone(),
+            allow_weak_keys,
+        }
+    }
+
+    fn check_key_len(&self, key_len: usize) -> anyhow::Result<()> {
+        let min_key_len = self.output_len();
+        if key_len < min_key_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
                     "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    input.len()
-                );
-            }
+                    min_key_len, key_len
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    fn output_len(&self) -> usize {
+        match self {
+            Self::Hs256 => 32,
+            Self::Hs384 => 48,
+            Self::Hs512 => 64,
+            #[cfg(feature = "sha3")]
+            Self::Hs3_256 => 32,
+            #[cfg(feature = "sha3")]
+            Self::Hs3_384 => 48,
+            #[cfg(feature = "sha3")]
+            Self::Hs3_512 => 64,
+        }
+    }
+
+    fn message_digest(&self) -> MessageDigest {
+        match self {
+            Self::Hs256 => MessageDigest::sha256(),
+            Self::Hs384 => MessageDigest::sha384(),
+            Self::Hs512 => MessageDigest::sha512(),
+            #[cfg(feature = "sha3")]
+            Self::Hs3_256 => MessageDigest::sha3_256(),
+            #[cfg(feature = "sha3")]
+            Self::Hs3_384 => MessageDigest::sha3_384(),
+            #[cfg(feature = "sha3")]
+            Self::Hs3_512 => MessageDigest::sha3_512(),
+        }
+    }
+}
+
+impl JwsAlgorithm for HmacJwsAlgorithm {
+    fn name(&self) -> &str {
+        match self {
+            Self::Hs256 => "HS256",
+            Self::Hs384 => "HS384",
+            Self::Hs512 => "HS512",
+            #[cfg(feature = "sha3")]
+            Self::Hs3_256 => "josekit:HS3-256",
+            #[cfg(feature = "sha3")]
+            Self::Hs3_384 => "josekit:HS3-384",
+            #[cfg(feature = "sha3")]
+            Self::Hs3_512 => "josekit:HS3-512",
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for HmacJwsAlgorithm {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for HmacJwsAlgorithm {
+    type Target = dyn JwsAlgorithm;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+/// A HMAC algorithm with its secret key length policy.
+///
+/// See `HmacJwsAlgorithm::allow_weak_keys`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct HmacJwsKeyPolicy {
+    algorithm: HmacJwsAlgorithm,
+    allow_weak_keys: bool,
+}
+
+impl HmacJwsKeyPolicy {
+    /// Return a signer from a secret key.
+    ///
+    /// # Arguments
+    /// * `input` - A secret key.
+    pub fn signer_from_bytes(&self, input: impl AsRef<[u8]>) -> Result<HmacJwsSigner, JoseError> {
+        (|| -> anyhow::Result<HmacJwsSigner> {
+            let input = input.as_ref();
+            self.check_key_len(input.len())?;
 
             let private_key = PKey::hmac(input)?;
 
             Ok(HmacJwsSigner {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 private_key,
                 key_id: None,
             })
@@ -75,39 +236,20 @@ impl HmacJwsAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("sign") {
-                bail!("A parameter key_ops must contains sign.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "sign", self.algorithm.name())?;
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(val) => bail!("A parameter k must be string type but {:?}", val),
                 None => bail!("A parameter k is required."),
             };
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if k.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    k.len()
-                );
-            }
+            self.check_key_len(k.len())?;
 
             let private_key = PKey::hmac(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(HmacJwsSigner {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 private_key,
                 key_id,
             })
@@ -125,20 +267,12 @@ impl HmacJwsAlgorithm {
     ) -> Result<HmacJwsVerifier, JoseError> {
         (|| -> anyhow::Result<HmacJwsVerifier> {
             let input = input.as_ref();
-
-            let min_key_len = self.hash_algorithm().output_len();
-            if input.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    input.len()
-                );
-            }
+            self.check_key_len(input.len())?;
 
             let private_key = PKey::hmac(input)?;
 
             Ok(HmacJwsVerifier {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 private_key,
                 key_id: None,
             })
@@ -156,19 +290,7 @@ impl HmacJwsAlgorithm {
                 val if val == "oct" => {}
                 val => bail!("A parameter kty must be oct: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("verify") {
-                bail!("A parameter key_ops must contains verify.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "verify", self.algorithm.name())?;
 
             let k = match jwk.parameter("k") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -176,20 +298,13 @@ impl HmacJwsAlgorithm {
                 None => bail!("A parameter k is required."),
             };
 
-            let min_key_len = self.hash_algorithm().output_len();
-            if k.len() < min_key_len {
-                bail!(
-                    "Secret key size must be larger than or equal to {}: {}",
-                    min_key_len,
-                    k.len()
-                );
-            }
+            self.check_key_len(k.len())?;
 
             let private_key = PKey::hmac(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(HmacJwsVerifier {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 private_key,
                 key_id,
             })
@@ -197,40 +312,11 @@ impl HmacJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
-    fn hash_algorithm(&self) -> HashAlgorithm {
-        match self {
-            Self::Hs256 => HashAlgorithm::Sha256,
-            Self::Hs384 => HashAlgorithm::Sha384,
-            Self::Hs512 => HashAlgorithm::Sha512,
-        }
-    }
-}
-
-impl JwsAlgorithm for HmacJwsAlgorithm {
-    fn name(&self) -> &str {
-        match self {
-            Self::Hs256 => "HS256",
-            Self::Hs384 => "HS384",
-            Self::Hs512 => "HS512",
+    fn check_key_len(&self, key_len: usize) -> anyhow::Result<()> {
+        if self.allow_weak_keys {
+            return Ok(());
         }
-    }
-
-    fn box_clone(&self) -> Box<dyn JwsAlgorithm> {
-        Box::new(self.clone())
-    }
-}
-
-impl Display for HmacJwsAlgorithm {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        fmt.write_str(self.name())
-    }
-}
-
-impl Deref for HmacJwsAlgorithm {
-    type Target = dyn JwsAlgorithm;
-
-    fn deref(&self) -> &Self::Target {
-        self
+        self.algorithm.check_key_len(key_len)
     }
 }
 
@@ -257,7 +343,7 @@ impl JwsSigner for HmacJwsSigner {
     }
 
     fn signature_len(&self) -> usize {
-        self.algorithm.hash_algorithm().output_len()
+        self.algorithm.output_len()
     }
 
     fn key_id(&self) -> Option<&str> {
@@ -268,11 +354,17 @@ impl JwsSigner for HmacJwsSigner {
     }
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_parts(&[message])
+    }
+
+    fn sign_parts(&self, parts: &[&[u8]]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
-            let md = self.algorithm.hash_algorithm().message_digest();
+            let md = self.algorithm.message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            for part in parts {
+                signer.update(part)?;
+            }
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -322,14 +414,23 @@ impl JwsVerifier for HmacJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_parts(&[message], signature)
+    }
+
+    fn verify_parts(&self, parts: &[&[u8]], signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
-            let md = self.algorithm.hash_algorithm().message_digest();
+            let md = self.algorithm.message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            for part in parts {
+                signer.update(part)?;
+            }
             let new_signature = signer.sign_to_vec()?;
-            if new_signature.as_slice() != signature {
-                bail!("Failed to verify.");
+            if !util::constant_time_eq(&new_signature, signature) {
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::SignatureMismatch,
+                    "Failed to verify."
+                ));
             }
             Ok(())
         })()
@@ -381,6 +482,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_hmac_generated_key() -> Result<()> {
+        let input = b"12345abcde";
+
+        for alg in &[
+            HmacJwsAlgorithm::Hs256,
+            HmacJwsAlgorithm::Hs384,
+            HmacJwsAlgorithm::Hs512,
+        ] {
+            let private_key = alg.generate_key();
+            assert_eq!(private_key.len(), alg.output_len());
+
+            let signer = alg.signer_from_bytes(&private_key)?;
+            let signature = signer.sign(input)?;
+
+            let verifier = alg.verifier_from_jwk(&alg.to_jwk(&private_key))?;
+            verifier.verify(input, &signature)?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_hmac_jwk() -> Result<()> {
         let input = b"abcde12345";
@@ -423,6 +546,94 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_hmac_short_key() -> Result<()> {
+        let private_key = util::random_bytes(16);
+
+        let err = HmacJwsAlgorithm::Hs256
+            .signer_from_bytes(&private_key)
+            .expect_err("A short key was accepted by the signer");
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        let err = HmacJwsAlgorithm::Hs256
+            .verifier_from_jwk(&HmacJwsAlgorithm::Hs256.to_jwk(&private_key))
+            .expect_err("A short key was accepted by the verifier");
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_hmac_short_key_allowing_weak_keys() -> Result<()> {
+        let private_key = util::random_bytes(16);
+        let input = b"abcde12345";
+
+        let alg = HmacJwsAlgorithm::Hs256.allow_weak_keys();
+
+        let signer = alg.signer_from_bytes(&private_key)?;
+        let signature = signer.sign(input)?;
+
+        let verifier = alg.verifier_from_jwk(&HmacJwsAlgorithm::Hs256.to_jwk(&private_key))?;
+        verifier.verify(input, &signature)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "sha3")]
+    fn sign_and_verify_hmac_sha3() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            HmacJwsAlgorithm::Hs3_256,
+            HmacJwsAlgorithm::Hs3_384,
+            HmacJwsAlgorithm::Hs3_512,
+        ] {
+            let private_key = alg.generate_key();
+
+            let signer = alg.signer_from_bytes(&private_key)?;
+            let signature = signer.sign(input)?;
+            assert_eq!(signature.len(), signer.signature_len());
+
+            let verifier = alg.verifier_from_jwk(&alg.to_jwk(&private_key))?;
+            verifier.verify(input, &signature)?;
+
+            let other_alg = match alg {
+                HmacJwsAlgorithm::Hs3_256 => HmacJwsAlgorithm::Hs256,
+                HmacJwsAlgorithm::Hs3_384 => HmacJwsAlgorithm::Hs384,
+                _ => HmacJwsAlgorithm::Hs512,
+            };
+            let verifier = other_alg.verifier_from_bytes(&private_key)?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "sha3")]
+    fn reject_hmac_sha3_short_key() -> Result<()> {
+        for alg in &[
+            HmacJwsAlgorithm::Hs3_256,
+            HmacJwsAlgorithm::Hs3_384,
+            HmacJwsAlgorithm::Hs3_512,
+        ] {
+            let private_key = util::random_bytes(alg.output_len() - 1);
+
+            let err = alg
+                .signer_from_bytes(&private_key)
+                .expect_err("A short key was accepted by the signer");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+            let err = alg
+                .verifier_from_jwk(&alg.to_jwk(&private_key))
+                .expect_err("A short key was accepted by the verifier");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -0,0 +1,170 @@
+use anyhow::anyhow;
+
+use crate::jwk::Jwk;
+use crate::jws::alg::rsassa::RsassaJwsAlgorithm;
+use crate::jws::alg::rsassa_pss::RsassaPssJwsAlgorithm;
+use crate::jws::{JwsAlgorithm, JwsHeader, JwsVerifier};
+use crate::JoseError;
+
+/// A verifier that accepts several RSA signature algorithms for the same RSA key.
+///
+/// The algorithm is chosen by the alg header claim of a token and must be one of
+/// the explicitly allowed algorithms (RS256, RS384, RS512, PS256, PS384 or PS512).
+/// Use `select` as the selector of `deserialize_compact_with_selector` or
+/// `decode_with_verifier_selector`.
+#[derive(Debug, Clone)]
+pub struct RsaAnyJwsVerifier {
+    verifiers: Vec<Box<dyn JwsVerifier>>,
+}
+
+impl RsaAnyJwsVerifier {
+    /// Return a verifier from a public key that is formatted by a JWK of RSA type.
+    ///
+    /// # Arguments
+    /// * `jwk` - A public key that is formatted by a JWK of RSA type.
+    /// * `algorithms` - The allowed RSA signature algorithms.
+    pub fn from_jwk_accepting(
+        jwk: &Jwk,
+        algorithms: &[&dyn JwsAlgorithm],
+    ) -> Result<Self, JoseError> {
+        if algorithms.is_empty() {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                "At least one algorithm is required."
+            )));
+        }
+
+        let mut verifiers: Vec<Box<dyn JwsVerifier>> = Vec::with_capacity(algorithms.len());
+        for alg in algorithms {
+            let verifier: Box<dyn JwsVerifier> = match alg.name() {
+                "RS256" => Box::new(RsassaJwsAlgorithm::Rs256.verifier_from_jwk(jwk)?),
+                "RS384" => Box::new(RsassaJwsAlgorithm::Rs384.verifier_from_jwk(jwk)?),
+                "RS512" => Box::new(RsassaJwsAlgorithm::Rs512.verifier_from_jwk(jwk)?),
+                "PS256" => Box::new(RsassaPssJwsAlgorithm::Ps256.verifier_from_jwk(jwk)?),
+                "PS384" => Box::new(RsassaPssJwsAlgorithm::Ps384.verifier_from_jwk(jwk)?),
+                "PS512" => Box::new(RsassaPssJwsAlgorithm::Ps512.verifier_from_jwk(jwk)?),
+                val => {
+                    return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                        "The algorithm is not a RSA signature algorithm: {}",
+                        val
+                    )))
+                }
+            };
+            verifiers.push(verifier);
+        }
+
+        Ok(Self { verifiers })
+    }
+
+    /// Return the allowed algorithms.
+    pub fn algorithms(&self) -> Vec<&dyn JwsAlgorithm> {
+        self.verifiers.iter().map(|val| val.algorithm()).collect()
+    }
+
+    /// Return the verifier for the alg header claim of a JWS header.
+    ///
+    /// # Arguments
+    /// * `header` - The JWS header.
+    pub fn select(&self, header: &JwsHeader) -> Result<Option<&dyn JwsVerifier>, JoseError> {
+        let alg = match header.algorithm() {
+            Some(val) => val,
+            None => {
+                return Err(JoseError::InvalidJwsFormat(anyhow!(
+                    "The JWS alg header claim is required."
+                )))
+            }
+        };
+
+        match self
+            .verifiers
+            .iter()
+            .find(|val| val.algorithm().name() == alg)
+        {
+            Some(val) => Ok(Some(val.as_ref())),
+            None => Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                "The JWS alg header claim is not allowed: {}",
+                alg
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Result;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::jws::{self, JwsSigner, ES256, PS256, PS384, RS256, RS512};
+    use crate::JoseError;
+
+    #[test]
+    fn verify_rsa_any_with_allowed_algorithms() -> Result<()> {
+        let private_key = Jwk::from_bytes(&load_file("jwk/RSA_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/RSA_public.jwk")?)?;
+        let verifier = RsaAnyJwsVerifier::from_jwk_accepting(&public_key, &[&RS256, &PS256])?;
+
+        let src_payload = b"test payload!";
+        let rs256_signer = RS256.signer_from_jwk(&private_key)?;
+        let ps256_signer = PS256.signer_from_jwk(&private_key)?;
+        for signer in vec![&rs256_signer as &dyn JwsSigner, &ps256_signer] {
+            let token = jws::serialize_compact(src_payload, &JwsHeader::new(), signer)?;
+
+            let (dst_payload, dst_header) =
+                jws::deserialize_compact_with_selector(&token, |header| verifier.select(header))?;
+            assert_eq!(dst_header.algorithm(), Some(signer.algorithm().name()));
+            assert_eq!(src_payload.to_vec(), dst_payload);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_rsa_any_with_disallowed_algorithm() -> Result<()> {
+        let private_key = Jwk::from_bytes(&load_file("jwk/RSA_private.jwk")?)?;
+        let public_key = Jwk::from_bytes(&load_file("jwk/RSA_public.jwk")?)?;
+        let verifier = RsaAnyJwsVerifier::from_jwk_accepting(&public_key, &[&RS256, &PS256])?;
+
+        let src_payload = b"test payload!";
+        let signer = RS512.signer_from_jwk(&private_key)?;
+        let token = jws::serialize_compact(src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jws::deserialize_compact_with_selector(&token, |header| verifier.select(header)),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        let signer = PS384.signer_from_jwk(&private_key)?;
+        let token = jws::serialize_compact(src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jws::deserialize_compact_with_selector(&token, |header| verifier.select(header)),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_rsa_any_with_non_rsa_algorithm() -> Result<()> {
+        let public_key = Jwk::from_bytes(&load_file("jwk/RSA_public.jwk")?)?;
+        assert!(matches!(
+            RsaAnyJwsVerifier::from_jwk_accepting(&public_key, &[&RS256, &ES256]),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+        assert!(matches!(
+            RsaAnyJwsVerifier::from_jwk_accepting(&public_key, &[]),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
@@ -5,11 +5,12 @@ use anyhow::bail;
 use openssl::pkey::{PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::{alg::rsa::RsaKeyPair, Jwk};
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerType};
 use crate::util::{self, HashAlgorithm};
-use crate::{JoseError, Value};
+use crate::{JoseError, JoseErrorKind, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum RsassaJwsAlgorithm {
@@ -52,9 +53,7 @@ impl RsassaJwsAlgorithm {
         (|| -> anyhow::Result<RsaKeyPair> {
             let mut key_pair = RsaKeyPair::from_der(input)?;
 
-            if key_pair.key_len() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            check_key_len(key_pair.key_len())?;
 
             key_pair.set_algorithm(Some(self.name()));
             Ok(key_pair)
@@ -79,9 +78,7 @@ impl RsassaJwsAlgorithm {
         (|| -> anyhow::Result<RsaKeyPair> {
             let mut key_pair = RsaKeyPair::from_pem(input.as_ref())?;
 
-            if key_pair.key_len() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            check_key_len(key_pair.key_len())?;
 
             key_pair.set_algorithm(Some(self.name()));
             Ok(key_pair)
@@ -94,15 +91,12 @@ impl RsassaJwsAlgorithm {
 
     /// Return a signer from a private key that is a DER encoded PKCS#8 PrivateKeyInfo or PKCS#1 RSAPrivateKey.
     ///
+    /// The key length must be 2048 bits or more.
+    ///
     /// # Arguments
     /// * `input` - A private key that is a DER encoded PKCS#8 PrivateKeyInfo or PKCS#1 RSAPrivateKey.
     pub fn signer_from_der(&self, input: impl AsRef<[u8]>) -> Result<RsassaJwsSigner, JoseError> {
-        let key_pair = self.key_pair_from_der(input.as_ref())?;
-        Ok(RsassaJwsSigner {
-            algorithm: self.clone(),
-            private_key: key_pair.into_private_key(),
-            key_id: None,
-        })
+        self.key_policy(false).signer_from_der(input)
     }
 
     /// Return a signer from a private key of common or traditinal PEM format.
@@ -113,12 +107,165 @@ impl RsassaJwsAlgorithm {
     /// Traditional PEM format is a DER and base64 encoded PKCS#1 RSAPrivateKey
     /// that surrounded by "-----BEGIN/END RSA PRIVATE KEY----".
     ///
+    /// The key length must be 2048 bits or more.
+    ///
     /// # Arguments
     /// * `input` - A private key of common or traditinal PEM format.
     pub fn signer_from_pem(&self, input: impl AsRef<[u8]>) -> Result<RsassaJwsSigner, JoseError> {
-        let key_pair = self.key_pair_from_pem(input.as_ref())?;
-        Ok(RsassaJwsSigner {
+        self.key_policy(false).signer_from_pem(input)
+    }
+
+    /// Return a signer from a private key that is formatted by a JWK of RSA type.
+    ///
+    /// The key length must be 2048 bits or more.
+    ///
+    /// # Arguments
+    /// * `jwk` - A private key that is formatted by a JWK of RSA type.
+    pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<RsassaJwsSigner, JoseError> {
+        self.key_policy(false).signer_from_jwk(jwk)
+    }
+
+    /// Return the verifier from a public key that is a DER encoded SubjectPublicKeyInfo or PKCS#1 RSAPublicKey.
+    ///
+    /// The key length must be 2048 bits or more.
+    ///
+    /// # Arguments
+    /// * `input` - A public key that is a DER encoded SubjectPublicKeyInfo or PKCS#1 RSAPublicKey.
+    pub fn verifier_from_der(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<RsassaJwsVerifier, JoseError> {
+        self.key_policy(false).verifier_from_der(input)
+    }
+
+    /// Return a verifier from a key of common or traditional PEM format.
+    ///
+    /// Common PEM format is a DER and base64 encoded SubjectPublicKeyInfo
+    /// that surrounded by "-----BEGIN/END PUBLIC KEY----".
+    ///
+    /// Traditional PEM format is a DER and base64 PKCS#1 RSAPublicKey
+    /// that surrounded by "-----BEGIN/END RSA PUBLIC KEY----".
+    ///
+    /// The key length must be 2048 bits or more.
+    ///
+    /// # Arguments
+    /// * `input` - A public key of common or traditional PEM format.
+    pub fn verifier_from_pem(
+        &self,
+        input: impl AsRef<[u8]>,
+    ) -> Result<RsassaJwsVerifier, JoseError> {
+        self.key_policy(false).verifier_from_pem(input)
+    }
+
+    /// Return a verifier from a public key that is formatted by a JWK of RSA type.
+    ///
+    /// The key length must be 2048 bits or more.
+    ///
+    /// # Arguments
+    /// * `jwk` - A public key that is formatted by a JWK of RSA type.
+    pub fn verifier_from_jwk(&self, jwk: &Jwk) -> Result<RsassaJwsVerifier, JoseError> {
+        self.key_policy(false).verifier_from_jwk(jwk)
+    }
+
+    /// Return the same algorithm without the minimum key length.
+    ///
+    /// RFC 7518 requires a RSA key of 2048 bits or more.
+    /// This is only for interoperating with legacy systems that use shorter keys.
+    pub fn allow_weak_keys(&self) -> RsassaJwsKeyPolicy {
+        self.key_policy(true)
+    }
+
+    fn key_policy(&self, allow_weak_keys: bool) -> RsassaJwsKeyPolicy {
+        RsassaJwsKeyPolicy {
             algorithm: self.clone(),
+            allow_weak_keys,
+        }
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        match self {
+            Self::Rs256 => HashAlgorithm::Sha256,
+            Self::Rs384 => HashAlgorithm::Sha384,
+            Self::Rs512 => HashAlgorithm::Sha512,
+        }
+    }
+}
+
+fn check_key_len(key_len: u32) -> anyhow::Result<()> {
+    if key_len * 8 < 2048 {
+        bail!(JoseErrorDetail::new(
+            JoseErrorKind::InvalidKeyLength,
+            format!("key length must be 2048 or more: {}", key_len * 8)
+        ));
+    }
+    Ok(())
+}
+
+impl JwsAlgorithm for RsassaJwsAlgorithm {
+    fn name(&self) -> &str {
+        match self {
+            Self::Rs256 => "RS256",
+            Self::Rs384 => "RS384",
+            Self::Rs512 => "RS512",
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Display for RsassaJwsAlgorithm {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+impl Deref for RsassaJwsAlgorithm {
+    type Target = dyn JwsAlgorithm;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+/// A RSASSA-PKCS1-v1_5 algorithm with its key length policy.
+///
+/// See `RsassaJwsAlgorithm::allow_weak_keys`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct RsassaJwsKeyPolicy {
+    algorithm: RsassaJwsAlgorithm,
+    allow_weak_keys: bool,
+}
+
+impl RsassaJwsKeyPolicy {
+    /// Return a signer from a private key that is a DER encoded PKCS#8 PrivateKeyInfo or PKCS#1 RSAPrivateKey.
+    ///
+    /// # Arguments
+    /// * `input` - A private key that is a DER encoded PKCS#8 PrivateKeyInfo or PKCS#1 RSAPrivateKey.
+    pub fn signer_from_der(&self, input: impl AsRef<[u8]>) -> Result<RsassaJwsSigner, JoseError> {
+        let key_pair = RsaKeyPair::from_der(input)?;
+        self.check_key_len(key_pair.key_len())
+            .map_err(|err| JoseError::InvalidKeyFormat(err))?;
+
+        Ok(RsassaJwsSigner {
+            algorithm: self.algorithm,
+            private_key: key_pair.into_private_key(),
+            key_id: None,
+        })
+    }
+
+    /// Return a signer from a private key of common or traditinal PEM format.
+    ///
+    /// # Arguments
+    /// * `input` - A private key of common or traditinal PEM format.
+    pub fn signer_from_pem(&self, input: impl AsRef<[u8]>) -> Result<RsassaJwsSigner, JoseError> {
+        let key_pair = RsaKeyPair::from_pem(input)?;
+        self.check_key_len(key_pair.key_len())
+            .map_err(|err| JoseError::InvalidKeyFormat(err))?;
+
+        Ok(RsassaJwsSigner {
+            algorithm: self.algorithm,
             private_key: key_pair.into_private_key(),
             key_id: None,
         })
@@ -130,30 +277,16 @@ impl RsassaJwsAlgorithm {
     /// * `jwk` - A private key that is formatted by a JWK of RSA type.
     pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<RsassaJwsSigner, JoseError> {
         (|| -> anyhow::Result<RsassaJwsSigner> {
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("sign") {
-                bail!("A parameter key_ops must contains sign.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "sign", self.algorithm.name())?;
 
             let key_pair = RsaKeyPair::from_jwk(jwk)?;
-            if key_pair.key_len() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            self.check_key_len(key_pair.key_len())?;
 
             let private_key = key_pair.into_private_key();
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(RsassaJwsSigner {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 private_key,
                 key_id,
             })
@@ -170,24 +303,23 @@ impl RsassaJwsAlgorithm {
         input: impl AsRef<[u8]>,
     ) -> Result<RsassaJwsVerifier, JoseError> {
         (|| -> anyhow::Result<RsassaJwsVerifier> {
+            let input = input.as_ref();
+
             let spki_der_vec;
-            let spki_der = match RsaKeyPair::detect_pkcs8(input.as_ref(), true) {
-                Some(_) => input.as_ref(),
+            let spki_der = match RsaKeyPair::detect_pkcs8(input, true) {
+                Some(_) => input,
                 None => {
-                    spki_der_vec = RsaKeyPair::to_pkcs8(input.as_ref(), true);
+                    spki_der_vec = RsaKeyPair::to_pkcs8(input, true);
                     spki_der_vec.as_slice()
                 }
             };
 
             let public_key = PKey::public_key_from_der(spki_der)?;
 
-            let rsa = public_key.rsa()?;
-            if rsa.size() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            self.check_key_len(public_key.rsa()?.size())?;
 
             Ok(RsassaJwsVerifier {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 public_key,
                 key_id: None,
             })
@@ -197,12 +329,6 @@ impl RsassaJwsAlgorithm {
 
     /// Return a verifier from a key of common or traditional PEM format.
     ///
-    /// Common PEM format is a DER and base64 encoded SubjectPublicKeyInfo
-    /// that surrounded by "-----BEGIN/END PUBLIC KEY----".
-    ///
-    /// Traditional PEM format is a DER and base64 PKCS#1 RSAPublicKey
-    /// that surrounded by "-----BEGIN/END RSA PUBLIC KEY----".
-    ///
     /// # Arguments
     /// * `input` - A public key of common or traditional PEM format.
     pub fn verifier_from_pem(
@@ -210,7 +336,9 @@ impl RsassaJwsAlgorithm {
         input: impl AsRef<[u8]>,
     ) -> Result<RsassaJwsVerifier, JoseError> {
         (|| -> anyhow::Result<RsassaJwsVerifier> {
-            let (alg, data) = util::parse_pem(input.as_ref())?;
+            let input = input.as_ref();
+
+            let (alg, data) = util::parse_pem(input)?;
 
             let spki_der_vec;
             let spki_der = match alg.as_str() {
@@ -227,13 +355,10 @@ impl RsassaJwsAlgorithm {
 
             let public_key = PKey::public_key_from_der(spki_der)?;
 
-            let rsa = public_key.rsa()?;
-            if rsa.size() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            self.check_key_len(public_key.rsa()?.size())?;
 
             Ok(RsassaJwsVerifier {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 public_key,
                 key_id: None,
             })
@@ -251,19 +376,7 @@ impl RsassaJwsAlgorithm {
                 val if val == "RSA" => {}
                 val => bail!("A parameter kty must be RSA: {}", val),
             }
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("verify") {
-                bail!("A parameter key_ops must contains verify.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "verify", self.algorithm.name())?;
 
             let n = match jwk.parameter("n") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
@@ -288,13 +401,10 @@ impl RsassaJwsAlgorithm {
             let public_key = PKey::public_key_from_der(&pkcs8)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
-            let rsa = public_key.rsa()?;
-            if rsa.size() * 8 < 2048 {
-                bail!("key length must be 2048 or more.");
-            }
+            self.check_key_len(public_key.rsa()?.size())?;
 
             Ok(RsassaJwsVerifier {
-                algorithm: self.clone(),
+                algorithm: self.algorithm,
                 public_key,
                 key_id,
             })
@@ -302,40 +412,11 @@ impl RsassaJwsAlgorithm {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
-    fn hash_algorithm(&self) -> HashAlgorithm {
-        match self {
-            Self::Rs256 => HashAlgorithm::Sha256,
-            Self::Rs384 => HashAlgorithm::Sha384,
-            Self::Rs512 => HashAlgorithm::Sha512,
+    fn check_key_len(&self, key_len: u32) -> anyhow::Result<()> {
+        if self.allow_weak_keys {
+            return Ok(());
         }
-    }
-}
-
-impl JwsAlgorithm for RsassaJwsAlgorithm {
-    fn name(&self) -> &str {
-        match self {
-            Self::Rs256 => "RS256",
-            Self::Rs384 => "RS384",
-            Self::Rs512 => "RS512",
-        }
-    }
-
-    fn box_clone(&self) -> Box<dyn JwsAlgorithm> {
-        Box::new(self.clone())
-    }
-}
-
-impl Display for RsassaJwsAlgorithm {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        fmt.write_str(self.name())
-    }
-}
-
-impl Deref for RsassaJwsAlgorithm {
-    type Target = dyn JwsAlgorithm;
-
-    fn deref(&self) -> &Self::Target {
-        self
+        check_key_len(key_len)
     }
 }
 
@@ -373,11 +454,17 @@ impl JwsSigner for RsassaJwsSigner {
     }
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_parts(&[message])
+    }
+
+    fn sign_parts(&self, parts: &[&[u8]]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            for part in parts {
+                signer.update(part)?;
+            }
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -427,13 +514,22 @@ impl JwsVerifier for RsassaJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_parts(&[message], signature)
+    }
+
+    fn verify_parts(&self, parts: &[&[u8]], signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
+            for part in parts {
+                verifier.update(part)?;
+            }
             if !verifier.verify(signature)? {
-                bail!("The signature does not match.")
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::SignatureMismatch,
+                    "The signature does not match."
+                ))
             }
             Ok(())
         })()
@@ -700,6 +796,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_rsassa_1024bit_key() -> Result<()> {
+        let key_pair = RsaKeyPair::generate(1024)?;
+
+        for alg in &[
+            RsassaJwsAlgorithm::Rs256,
+            RsassaJwsAlgorithm::Rs384,
+            RsassaJwsAlgorithm::Rs512,
+        ] {
+            let err = alg
+                .signer_from_der(&key_pair.to_der_private_key())
+                .expect_err("A 1024 bit key was accepted by the signer");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+            let err = alg
+                .verifier_from_jwk(&key_pair.to_jwk_public_key())
+                .expect_err("A 1024 bit key was accepted by the verifier");
+            assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_rsassa_1024bit_key_allowing_weak_keys() -> Result<()> {
+        let input = b"abcde12345";
+        let key_pair = RsaKeyPair::generate(1024)?;
+
+        for alg in &[
+            RsassaJwsAlgorithm::Rs256,
+            RsassaJwsAlgorithm::Rs384,
+            RsassaJwsAlgorithm::Rs512,
+        ] {
+            let alg = alg.allow_weak_keys();
+
+            let signer = alg.signer_from_pem(&key_pair.to_pem_private_key())?;
+            let signature = signer.sign(input)?;
+
+            let verifier = alg.verifier_from_pem(&key_pair.to_pem_public_key())?;
+            verifier.verify(input, &signature)?;
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
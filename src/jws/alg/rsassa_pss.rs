@@ -6,11 +6,12 @@ use openssl::pkey::{PKey, Private, Public};
 use openssl::rsa::Rsa;
 use openssl::sign::{Signer, Verifier};
 
+use crate::jose_error::JoseErrorDetail;
 use crate::jwk::{alg::rsa::RsaKeyPair, alg::rsapss::RsaPssKeyPair, Jwk};
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::util::der::{DerBuilder, DerType};
 use crate::util::{self, HashAlgorithm};
-use crate::{JoseError, Value};
+use crate::{JoseError, JoseErrorKind, Value};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum RsassaPssJwsAlgorithm {
@@ -150,19 +151,7 @@ impl RsassaPssJwsAlgorithm {
     /// * `jwk` - A private key that is formatted by a JWK of RSA type.
     pub fn signer_from_jwk(&self, jwk: &Jwk) -> Result<RsassaPssJwsSigner, JoseError> {
         (|| -> anyhow::Result<RsassaPssJwsSigner> {
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            }
-            if !jwk.is_for_key_operation("sign") {
-                bail!("A parameter key_ops must contains sign.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "sign", self.name())?;
 
             let key_pair = RsaPssKeyPair::from_jwk(
                 jwk,
@@ -321,19 +310,7 @@ impl RsassaPssJwsAlgorithm {
                 val if val == "RSA" => {}
                 val => bail!("A parameter kty must be RSA: {}", val),
             };
-            match jwk.key_use() {
-                Some(val) if val == "sig" => {}
-                None => {}
-                Some(val) => bail!("A parameter use must be sig: {}", val),
-            };
-            if !jwk.is_for_key_operation("verify") {
-                bail!("A parameter key_ops must contains verify.");
-            }
-            match jwk.algorithm() {
-                Some(val) if val == self.name() => {}
-                None => {}
-                Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
-            }
+            jwk.check_key_usage("sig", "verify", self.name())?;
             let n = match jwk.parameter("n") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(_) => bail!("A parameter n must be a string."),
@@ -456,11 +433,17 @@ impl JwsSigner for RsassaPssJwsSigner {
     }
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        self.sign_parts(&[message])
+    }
+
+    fn sign_parts(&self, parts: &[&[u8]]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
+            for part in parts {
+                signer.update(part)?;
+            }
             let signature = signer.sign_to_vec()?;
             Ok(signature)
         })()
@@ -510,13 +493,22 @@ impl JwsVerifier for RsassaPssJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_parts(&[message], signature)
+    }
+
+    fn verify_parts(&self, parts: &[&[u8]], signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
+            for part in parts {
+                verifier.update(part)?;
+            }
             if !verifier.verify(signature)? {
-                bail!("The signature does not match.");
+                bail!(JoseErrorDetail::new(
+                    JoseErrorKind::SignatureMismatch,
+                    "The signature does not match."
+                ));
             }
             Ok(())
         })()
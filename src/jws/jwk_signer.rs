@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+
+use anyhow::anyhow;
+
+use crate::jwk::Jwk;
+use crate::jws::{
+    EdDSA, JwsAlgorithm, JwsSigner, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384,
+    PS512, RS256, RS384, RS512,
+};
+#[cfg(feature = "sha3")]
+use crate::jws::{HS3_256, HS3_384, HS3_512};
+use crate::JoseError;
+
+/// Return a signer from a private key that is formatted by a JWK.
+///
+/// The key ID parameter (kid) of the JWK becomes the key ID of the signer,
+/// so it is set to the kid header claim of the produced JWS. If the JWK has
+/// no kid and `thumbprint_kid` is true, the SHA-256 JWK thumbprint (RFC 7638)
+/// is used instead; otherwise the kid header claim is left unset.
+///
+/// # Arguments
+///
+/// * `algorithm` - The signing algorithm.
+/// * `jwk` - A private key that is formatted by a JWK.
+/// * `thumbprint_kid` - true to use the JWK thumbprint when the JWK has no kid.
+pub fn signer_from_jwk(
+    algorithm: &dyn JwsAlgorithm,
+    jwk: &Jwk,
+    thumbprint_kid: bool,
+) -> Result<Box<dyn JwsSigner>, JoseError> {
+    let jwk = match jwk.key_id() {
+        None if thumbprint_kid => {
+            let mut jwk = jwk.clone();
+            jwk.set_key_id_to_thumbprint()?;
+            Cow::Owned(jwk)
+        }
+        _ => Cow::Borrowed(jwk),
+    };
+
+    let signer: Box<dyn JwsSigner> = match algorithm.name() {
+        "HS256" => Box::new(HS256.signer_from_jwk(&jwk)?),
+        "HS384" => Box::new(HS384.signer_from_jwk(&jwk)?),
+        "HS512" => Box::new(HS512.signer_from_jwk(&jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-256" => Box::new(HS3_256.signer_from_jwk(&jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-384" => Box::new(HS3_384.signer_from_jwk(&jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-512" => Box::new(HS3_512.signer_from_jwk(&jwk)?),
+        "RS256" => Box::new(RS256.signer_from_jwk(&jwk)?),
+        "RS384" => Box::new(RS384.signer_from_jwk(&jwk)?),
+        "RS512" => Box::new(RS512.signer_from_jwk(&jwk)?),
+        "PS256" => Box::new(PS256.signer_from_jwk(&jwk)?),
+        "PS384" => Box::new(PS384.signer_from_jwk(&jwk)?),
+        "PS512" => Box::new(PS512.signer_from_jwk(&jwk)?),
+        "ES256" => Box::new(ES256.signer_from_jwk(&jwk)?),
+        "ES256K" => Box::new(ES256K.signer_from_jwk(&jwk)?),
+        "ES384" => Box::new(ES384.signer_from_jwk(&jwk)?),
+        "ES512" => Box::new(ES512.signer_from_jwk(&jwk)?),
+        "EdDSA" => Box::new(EdDSA.signer_from_jwk(&jwk)?),
+        val => {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                "The algorithm cannot be used with a JWK: {}",
+                val
+            )))
+        }
+    };
+    Ok(signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+
+    use super::signer_from_jwk;
+    use crate::jwk::Jwk;
+    use crate::jws::{self, JwsHeader, ES256, HS256};
+    use crate::util::HashAlgorithm;
+
+    #[test]
+    fn test_signer_from_jwk_sets_kid() -> Result<()> {
+        let mut jwk = Jwk::from_bytes(&load_file("jwk/EC_P-256_private.jwk")?)?;
+        jwk.set_key_id("key-2");
+
+        let signer = signer_from_jwk(&ES256, &jwk, true)?;
+        assert_eq!(signer.key_id(), Some("key-2"));
+
+        let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &*signer)?;
+        assert_eq!(jws::extract_header(&jws)?.key_id(), Some("key-2"));
+
+        let verifier = ES256.verifier_from_jwk(&jwk.to_public_key()?)?;
+        let (_, header) = jws::deserialize_compact(&jws, &verifier)?;
+        assert_eq!(header.key_id(), jwk.key_id());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signer_from_jwk_without_kid() -> Result<()> {
+        let jwk = Jwk::from_bytes(&load_file("jwk/oct_256bit_private.jwk")?)?;
+        assert_eq!(jwk.key_id(), None);
+
+        let signer = signer_from_jwk(&HS256, &jwk, true)?;
+        let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &*signer)?;
+        let thumbprint = jwk.thumbprint(HashAlgorithm::Sha256)?;
+        assert_eq!(
+            jws::extract_header(&jws)?.key_id(),
+            Some(thumbprint.as_str())
+        );
+
+        let signer = signer_from_jwk(&HS256, &jwk, false)?;
+        let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &*signer)?;
+        assert_eq!(jws::extract_header(&jws)?.key_id(), None);
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
@@ -23,6 +23,9 @@ impl Clone for Box<dyn JwsAlgorithm> {
     }
 }
 
+/// A signer holds a key that is parsed when it is created. Only a signing
+/// context is created for each signature, so a signer should be created once
+/// and reused, also across threads.
 pub trait JwsSigner: Debug + Send + Sync {
     /// Return the source algorithm instance.
     fn algorithm(&self) -> &dyn JwsAlgorithm;
@@ -36,11 +39,25 @@ pub trait JwsSigner: Debug + Send + Sync {
 
     /// Return a signature of the data.
     ///
+    /// The message is signed as is, so this can be used for a signing input other
+    /// than JWS (e.g. HTTP message signatures). The caller is responsible for
+    /// constructing the message.
+    ///
     /// # Arguments
     ///
     /// * `message` - The message data to sign.
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError>;
 
+    /// Return a signature of the data that is given in parts, without concatenating them.
+    /// The default implementation concatenates the parts and calls `sign`.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message data to sign.
+    fn sign_parts(&self, parts: &[&[u8]]) -> Result<Vec<u8>, JoseError> {
+        self.sign(&parts.concat())
+    }
+
     fn box_clone(&self) -> Box<dyn JwsSigner>;
 }
 
@@ -50,6 +67,9 @@ impl Clone for Box<dyn JwsSigner> {
     }
 }
 
+/// A verifier holds a key that is parsed when it is created. Only a verifying
+/// context is created for each signature, so a verifier should be created once
+/// and reused, also across threads.
 pub trait JwsVerifier: Debug + Send + Sync {
     /// Return the source algrithm instance.
     fn algorithm(&self) -> &dyn JwsAlgorithm;
@@ -60,12 +80,26 @@ pub trait JwsVerifier: Debug + Send + Sync {
 
     /// Verify the data by the signature.
     ///
+    /// The message is verified as is, so this can be used for a signing input other
+    /// than JWS. The caller is responsible for constructing the message.
+    ///
     /// # Arguments
     ///
     /// * `message` - a message data to verify.
     /// * `signature` - a signature data.
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError>;
 
+    /// Verify the data that is given in parts by the signature, without concatenating them.
+    /// The default implementation concatenates the parts and calls `verify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message data to verify.
+    /// * `signature` - a signature data.
+    fn verify_parts(&self, parts: &[&[u8]], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify(&parts.concat(), signature)
+    }
+
     fn box_clone(&self) -> Box<dyn JwsVerifier>;
 }
 
@@ -1,31 +1,52 @@
-use std::collections::BTreeSet;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 
 use anyhow::bail;
 
 use crate::jws::{JwsHeader, JwsHeaderSet, JwsSigner, JwsVerifier};
 use crate::util;
-use crate::{JoseError, Map, Value};
+use crate::{AlgorithmPolicy, JoseError, JsonType, Map, Value};
+
+/// The default maximum size of a received JWS (8 MiB).
+const DEFAULT_MAX_TOKEN_SIZE: usize = 8 * 1024 * 1024;
+
+/// Header claim names registered by RFC 7515 that must not be listed in crit.
+const REGISTERED_HEADER_CLAIMS: [&str; 11] = [
+    "alg", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty", "crit",
+];
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JwsContext {
     acceptable_criticals: BTreeSet<String>,
+    header_parameters: BTreeMap<String, JsonType>,
+    strict_header_parameters: bool,
+    lenient_base64: bool,
+    max_token_size: usize,
+    algorithm_policy: Option<AlgorithmPolicy>,
 }
 
 impl JwsContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            header_parameters: BTreeMap::new(),
+            strict_header_parameters: false,
+            lenient_base64: false,
+            max_token_size: DEFAULT_MAX_TOKEN_SIZE,
+            algorithm_policy: None,
         }
     }
 
     /// Test a critical header claim name is acceptable.
     ///
+    /// A registered header parameter is also acceptable.
+    ///
     /// # Arguments
     ///
     /// * `name` - a critical header claim name
     pub fn is_acceptable_critical(&self, name: &str) -> bool {
-        self.acceptable_criticals.contains(name)
+        self.acceptable_criticals.contains(name) || self.header_parameters.contains_key(name)
     }
 
     /// Add a acceptable critical header claim name
@@ -46,6 +67,206 @@ impl JwsContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Register a private header parameter with the expected type of its value.
+    ///
+    /// A registered header parameter is understood, so it is acceptable in the
+    /// crit header claim. When strict header parameters are enabled, a received
+    /// JWS whose value of the header parameter is of a different type is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    /// * `json_type` - the expected type of the value
+    pub fn register_header_parameter(&mut self, name: &str, json_type: JsonType) {
+        self.header_parameters.insert(name.to_string(), json_type);
+    }
+
+    /// Unregister a private header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn unregister_header_parameter(&mut self, name: &str) {
+        self.header_parameters.remove(name);
+    }
+
+    /// Return the expected type of a registered header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn header_parameter_type(&self, name: &str) -> Option<JsonType> {
+        self.header_parameters.get(name).copied()
+    }
+
+    /// Set whether the values of registered header parameters of a received JWS are
+    /// validated against their expected types. The default is false.
+    /// Unregistered header parameters are never validated.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to validate
+    pub fn set_strict_header_parameters(&mut self, value: bool) {
+        self.strict_header_parameters = value;
+    }
+
+    /// Test the values of registered header parameters are validated.
+    pub fn is_strict_header_parameters(&self) -> bool {
+        self.strict_header_parameters
+    }
+
+    fn check_header_parameters(&self, header: &Map<String, Value>) -> anyhow::Result<()> {
+        if !self.strict_header_parameters {
+            return Ok(());
+        }
+
+        for (name, json_type) in &self.header_parameters {
+            if let Some(value) = header.get(name) {
+                if !json_type.is_type_of(value) {
+                    bail!(
+                        "The JWS {} header claim must be a {}.",
+                        name,
+                        json_type.name()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Set whether base64url segments of a received JWS may contain `=` padding
+    /// or the `+` and `/` characters of standard base64. The default is strict.
+    ///
+    /// In lenient mode, ASCII whitespace such as the line breaks of a wrapped token is
+    /// also removed from a compact serialization before it is split. Whitespace in an
+    /// unencoded payload (b64 false) is removed as well, so the signature of such a
+    /// token doesn't match.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to decode leniently
+    pub fn set_lenient_base64(&mut self, value: bool) {
+        self.lenient_base64 = value;
+    }
+
+    /// Test base64url segments of a received JWS are decoded leniently.
+    pub fn is_lenient_base64(&self) -> bool {
+        self.lenient_base64
+    }
+
+    /// Set the maximum size in bytes of a received JWS.
+    ///
+    /// A larger input is rejected before any part of it is decoded, which also
+    /// bounds the size of every decoded segment.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a maximum byte size
+    pub fn set_max_token_size(&mut self, value: usize) {
+        self.max_token_size = value;
+    }
+
+    /// Return the maximum size in bytes of a received JWS.
+    pub fn max_token_size(&self) -> usize {
+        self.max_token_size
+    }
+
+    pub(crate) fn check_token_size(&self, input: &[u8]) -> anyhow::Result<()> {
+        if input.len() > self.max_token_size {
+            bail!(
+                "The JWS size {} exceeds the maximum token size {}.",
+                input.len(),
+                self.max_token_size
+            );
+        }
+        Ok(())
+    }
+
+    /// Set a policy that restricts the algorithms used to sign and verify a JWS.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - a algorithm policy
+    pub fn set_algorithm_policy(&mut self, policy: AlgorithmPolicy) {
+        self.algorithm_policy = Some(policy);
+    }
+
+    /// Return the policy that restricts the algorithms.
+    pub fn algorithm_policy(&self) -> Option<&AlgorithmPolicy> {
+        self.algorithm_policy.as_ref()
+    }
+
+    /// Remove the policy that restricts the algorithms.
+    pub fn remove_algorithm_policy(&mut self) {
+        self.algorithm_policy = None;
+    }
+
+    fn check_algorithm(&self, name: &str) -> Result<(), JoseError> {
+        match &self.algorithm_policy {
+            Some(policy) => policy.check(name),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the critical header claim (crit) of a received JWS.
+    ///
+    /// The crit header claim must be protected, must not be empty, must not contain
+    /// header claim names registered by RFC 7515 and every name must be acceptable.
+    fn check_critical(
+        &self,
+        protected: &Map<String, Value>,
+        unprotected: &[&Map<String, Value>],
+    ) -> anyhow::Result<()> {
+        if unprotected.iter().any(|val| val.contains_key("crit")) {
+            bail!("The JWS crit header claim must be protected.");
+        }
+
+        let vals = match protected.get("crit") {
+            Some(Value::Array(vals)) => vals,
+            Some(_) => bail!("The JWS crit header claim must be a array."),
+            None => return Ok(()),
+        };
+
+        if vals.is_empty() {
+            bail!("The JWS crit header claim must not be empty.");
+        }
+
+        for val in vals {
+            let name = match val {
+                Value::String(val) => val.as_str(),
+                _ => bail!("An element of the JWS crit header claim must be a string."),
+            };
+
+            if REGISTERED_HEADER_CLAIMS.contains(&name) {
+                bail!(
+                    "The registered header claim '{}' must not be critical.",
+                    name
+                );
+            }
+
+            if !self.is_acceptable_critical(name) {
+                bail!("The critical name '{}' is not supported.", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return whether the payload is base64url-encoded according to the b64
+    /// header claim of RFC 7797. The claim is only honored when it is listed in crit.
+    fn base64url_encode_payload(protected: &Map<String, Value>) -> anyhow::Result<bool> {
+        if let Some(Value::Array(vals)) = protected.get("crit") {
+            if vals.iter().any(|val| val == "b64") {
+                match protected.get("b64") {
+                    Some(Value::Bool(val)) => return Ok(*val),
+                    Some(_) => bail!("The JWS b64 header claim must be bool."),
+                    None => {}
+                }
+            }
+        }
+        Ok(true)
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -75,6 +296,56 @@ impl JwsContext {
         header: &JwsHeader,
         selector: F,
     ) -> Result<String, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
+    {
+        self.serialize_compact_internal(payload, header, selector, false)
+    }
+
+    /// Return a representation of the data that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data that is signed but not included in the result.
+    /// * `header` - The JWS heaser claims.
+    /// * `signer` - The JWS signer.
+    pub fn serialize_compact_detached(
+        &self,
+        payload: &[u8],
+        header: &JwsHeader,
+        signer: &dyn JwsSigner,
+    ) -> Result<String, JoseError> {
+        self.serialize_compact_detached_with_selector(payload, header, |_header| Some(signer))
+    }
+
+    /// Return a representation of the data that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data that is signed but not included in the result.
+    /// * `header` - The JWS heaser claims.
+    /// * `selector` - a function for selecting the signing algorithm.
+    pub fn serialize_compact_detached_with_selector<'a, F>(
+        &self,
+        payload: &[u8],
+        header: &JwsHeader,
+        selector: F,
+    ) -> Result<String, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
+    {
+        self.serialize_compact_internal(payload, header, selector, true)
+    }
+
+    fn serialize_compact_internal<'a, F>(
+        &self,
+        payload: &[u8],
+        header: &JwsHeader,
+        selector: F,
+        detached: bool,
+    ) -> Result<String, JoseError>
     where
         F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
     {
@@ -92,6 +363,7 @@ impl JwsContext {
                 Some(val) => val,
                 None => bail!("A signer is not found."),
             };
+            self.check_algorithm(signer.algorithm().name())?;
 
             let mut header = header.claims_set().clone();
             header.insert(
@@ -105,16 +377,36 @@ impl JwsContext {
 
             let mut capacity = 2;
             capacity += util::ceiling(header_bytes.len() * 4, 3);
-            capacity += if b64 {
-                util::ceiling(payload.len() * 4, 3)
-            } else {
-                payload.len()
-            };
+            if !detached {
+                capacity += if b64 {
+                    util::ceiling(payload.len() * 4, 3)
+                } else {
+                    payload.len()
+                };
+            }
             capacity += util::ceiling(signer.signature_len() * 4, 3);
 
             let mut message = String::with_capacity(capacity);
             base64::encode_config_buf(header_bytes, base64::URL_SAFE_NO_PAD, &mut message);
             message.push_str(".");
+
+            if detached {
+                // The payload is not part of the output, so it is fed to the
+                // signer separately instead of being copied into the message.
+                let payload_b64;
+                let payload = if b64 {
+                    payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+                    payload_b64.as_bytes()
+                } else {
+                    std::str::from_utf8(payload)?;
+                    payload
+                };
+                let signature = signer.sign_parts(&[message.as_bytes(), payload])?;
+                message.push_str(".");
+                base64::encode_config_buf(signature, base64::URL_SAFE_NO_PAD, &mut message);
+                return Ok(message);
+            }
+
             if b64 {
                 base64::encode_config_buf(payload, base64::URL_SAFE_NO_PAD, &mut message);
             } else {
@@ -179,7 +471,24 @@ impl JwsContext {
         F: Fn(usize, &JwsHeader) -> Option<&'a dyn JwsSigner>,
     {
         (|| -> anyhow::Result<String> {
-            let payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+            let mut b64 = None;
+            for header in headers {
+                let val = Self::base64url_encode_payload(header.claims_set(true))?;
+                match b64 {
+                    Some(b64) if b64 != val => {
+                        bail!("The JWS b64 header claim must be the same in all signatures.")
+                    }
+                    _ => b64 = Some(val),
+                }
+            }
+
+            let payload_b64;
+            let payload = if b64.unwrap_or(true) {
+                payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+                &payload_b64
+            } else {
+                std::str::from_utf8(payload)?
+            };
 
             let mut result = String::new();
             result.push_str("{\"signatures\":[");
@@ -191,6 +500,7 @@ impl JwsContext {
                     Some(val) => val,
                     None => bail!("A signer is not found."),
                 };
+                self.check_algorithm(signer.algorithm().name())?;
 
                 let mut protected_map = header.claims_set(true).clone();
 
@@ -221,8 +531,8 @@ impl JwsContext {
 
                 let unprotected_map = header.claims_set(false);
 
-                let message = format!("{}.{}", &protected_b64, &payload_b64);
-                let signature = signer.sign(message.as_bytes())?;
+                let signature =
+                    signer.sign_parts(&[protected_b64.as_bytes(), b".", payload.as_bytes()])?;
 
                 result.push_str("{\"protected\":\"");
                 result.push_str(&protected_b64);
@@ -239,9 +549,9 @@ impl JwsContext {
                 result.push_str("\"}");
             }
 
-            result.push_str("],\"payload\":\"");
-            result.push_str(&payload_b64);
-            result.push_str("\"}");
+            result.push_str("],\"payload\":");
+            result.push_str(&serde_json::to_string(payload)?);
+            result.push_str("}");
 
             Ok(result)
         })()
@@ -285,21 +595,7 @@ impl JwsContext {
     {
         (|| -> anyhow::Result<String> {
             let protected_map = header.claims_set(true);
-            let mut b64 = true;
-            match protected_map.get("crit") {
-                Some(Value::Array(vals)) => {
-                    if vals.iter().any(|val| match val {
-                        Value::String(val2) => val2 == "b64",
-                        _ => false,
-                    }) {
-                        b64 = match protected_map.get("b64") {
-                            Some(Value::Bool(val3)) => *val3,
-                            _ => false,
-                        };
-                    }
-                }
-                _ => {}
-            }
+            let b64 = Self::base64url_encode_payload(protected_map)?;
 
             let merged_map = header.to_map();
             let merged = JwsHeader::from_map(merged_map)?;
@@ -307,6 +603,7 @@ impl JwsContext {
                 Some(val) => val,
                 None => bail!("A signer is not found."),
             };
+            self.check_algorithm(signer.algorithm().name())?;
 
             let mut protected_map = protected_map.clone();
 
@@ -338,8 +635,8 @@ impl JwsContext {
                 std::str::from_utf8(payload)?
             };
 
-            let message = format!("{}.{}", &protected_b64, payload);
-            let signature = signer.sign(message.as_bytes())?;
+            let signature =
+                signer.sign_parts(&[protected_b64.as_bytes(), b".", payload.as_bytes()])?;
 
             let mut json = String::new();
             json.push_str("{\"protected\":\"");
@@ -353,9 +650,8 @@ impl JwsContext {
                 json.push_str(&unprotcted_json);
             }
 
-            json.push_str(",\"payload\":\"");
-            json.push_str(&payload);
-            json.push_str("\"");
+            json.push_str(",\"payload\":");
+            json.push_str(&serde_json::to_string(payload)?);
 
             json.push_str(",\"signature\":\"");
             base64::encode_config_buf(&signature, base64::URL_SAFE_NO_PAD, &mut json);
@@ -369,6 +665,51 @@ impl JwsContext {
         })
     }
 
+    /// Return the protected header of the input that is formatted by compact
+    /// serialization, without verifying the signature.
+    ///
+    /// The returned header is untrusted: it may have been forged or tampered
+    /// with. Use it only to decide how to verify the input (e.g. to look up
+    /// a key by `kid`), never as a substitute for verification.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    pub fn extract_header(&self, input: impl AsRef<[u8]>) -> Result<JwsHeader, JoseError> {
+        (|| -> anyhow::Result<JwsHeader> {
+            let input = input.as_ref();
+            self.check_token_size(input)?;
+
+            let input = if self.lenient_base64 {
+                util::strip_ascii_whitespace(input)
+            } else {
+                Cow::Borrowed(input)
+            };
+            let input = input.as_ref();
+
+            let indexies: Vec<usize> = input
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'.' as u8)
+                .map(|(pos, _)| pos)
+                .collect();
+            if indexies.len() != 2 {
+                bail!(
+                    "The compact serialization form of JWS must be three parts separated by colon."
+                );
+            }
+
+            let header = util::decode_base64_url(&input[0..indexies[0]], self.lenient_base64)?;
+            let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = JwsHeader::from_map(header)?;
+            Ok(header)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwsFormat(err),
+        })
+    }
+
     /// Deserialize the input that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -396,11 +737,72 @@ impl JwsContext {
         input: impl AsRef<[u8]>,
         selector: F,
     ) -> Result<(Vec<u8>, JwsHeader), JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        self.deserialize_compact_internal(input.as_ref(), None, selector)
+    }
+
+    /// Deserialize the input that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `payload` - The detached payload data.
+    /// * `verifier` - The JWS verifier.
+    pub fn deserialize_compact_detached(
+        &self,
+        input: impl AsRef<[u8]>,
+        payload: &[u8],
+        verifier: &dyn JwsVerifier,
+    ) -> Result<JwsHeader, JoseError> {
+        self.deserialize_compact_detached_with_selector(input, payload, |_header| {
+            Ok(Some(verifier))
+        })
+    }
+
+    /// Deserialize the input that is formatted by compact serialization
+    /// with a detached payload (RFC 7515 Appendix F).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `payload` - The detached payload data.
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn deserialize_compact_detached_with_selector<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        payload: &[u8],
+        selector: F,
+    ) -> Result<JwsHeader, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        let (_, header) =
+            self.deserialize_compact_internal(input.as_ref(), Some(payload), selector)?;
+        Ok(header)
+    }
+
+    fn deserialize_compact_internal<'a, F>(
+        &self,
+        input: &[u8],
+        detached_payload: Option<&[u8]>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError>
     where
         F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
         (|| -> anyhow::Result<(Vec<u8>, JwsHeader)> {
+            self.check_token_size(input)?;
+
+            let input = if self.lenient_base64 {
+                util::strip_ascii_whitespace(input)
+            } else {
+                Cow::Borrowed(input)
+            };
             let input = input.as_ref();
+
             let indexies: Vec<usize> = input
                 .iter()
                 .enumerate()
@@ -417,14 +819,17 @@ impl JwsContext {
             let payload = &input[(indexies[0] + 1)..(indexies[1])];
             let signature = &input[(indexies[1] + 1)..];
 
-            let header = base64::decode_config(header, base64::URL_SAFE_NO_PAD)?;
+            let header = util::decode_base64_url(header, self.lenient_base64)?;
             let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            self.check_critical(&header, &[])?;
+            self.check_header_parameters(&header)?;
             let header = JwsHeader::from_map(header)?;
 
             let verifier = match selector(&header)? {
                 Some(val) => val,
                 None => bail!("A verifier is not found."),
             };
+            self.check_algorithm(verifier.algorithm().name())?;
 
             match header.claim("alg") {
                 Some(Value::String(val)) => {
@@ -447,30 +852,44 @@ impl JwsContext {
             }
 
             let mut b64 = true;
-            if let Some(Value::Array(vals)) = header.claim("crit") {
-                for val in vals {
-                    if let Value::String(val2) = val {
-                        if !self.is_acceptable_critical(val2) {
-                            bail!("The critical name '{}' is not supported.", val2);
-                        }
-
-                        if val2 == "b64" {
-                            if let Some(val) = header.base64url_encode_payload() {
-                                b64 = val;
-                            }
-                        }
+            if let Some(vals) = header.critical() {
+                if vals.contains(&"b64") {
+                    if let Some(val) = header.base64url_encode_payload() {
+                        b64 = val;
                     }
                 }
             }
 
-            let message = &input[..(indexies[1])];
-            let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)?;
-            verifier.verify(message, &signature)?;
+            let signature = util::decode_base64_url(signature, self.lenient_base64)?;
 
-            let payload = if b64 {
-                base64::decode_config(payload, base64::URL_SAFE_NO_PAD)?
-            } else {
-                payload.to_vec()
+            let payload = match detached_payload {
+                Some(detached_payload) => {
+                    if !payload.is_empty() {
+                        bail!("The payload part of detached JWS must be empty.");
+                    }
+
+                    let header_part = &input[..(indexies[0] + 1)];
+                    if b64 {
+                        let payload_b64 =
+                            base64::encode_config(detached_payload, base64::URL_SAFE_NO_PAD);
+                        verifier
+                            .verify_parts(&[header_part, payload_b64.as_bytes()], &signature)?;
+                    } else {
+                        verifier.verify_parts(&[header_part, detached_payload], &signature)?;
+                    }
+
+                    detached_payload.to_vec()
+                }
+                None => {
+                    let message = &input[..(indexies[1])];
+                    verifier.verify(message, &signature)?;
+
+                    if b64 {
+                        util::decode_base64_url(payload, self.lenient_base64)?
+                    } else {
+                        payload.to_vec()
+                    }
+                }
             };
 
             Ok((payload, header))
@@ -531,104 +950,83 @@ impl JwsContext {
     where
         F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
-        (|| -> anyhow::Result<(Vec<u8>, JwsHeader)> {
-            let input = input.as_ref();
-            let mut map: Map<String, Value> = serde_json::from_slice(input)?;
-
-            let payload_b64 = match map.remove("payload") {
-                Some(Value::String(val)) => val,
-                Some(_) => bail!("The payload field must be string."),
-                None => bail!("The payload field is required."),
-            };
-
-            let signatures = match map.remove("signatures") {
-                Some(Value::Array(vals)) => {
-                    let mut vec = Vec::with_capacity(vals.len());
-                    for val in vals {
-                        if let Value::Object(val) = val {
-                            vec.push(val);
-                        } else {
-                            bail!("The signatures field must be a array of object.");
-                        }
-                    }
-                    vec
-                }
-                Some(_) => bail!("The signatures field must be a array."),
-                None => {
-                    let mut vec = Vec::with_capacity(1);
-                    vec.push(map);
-                    vec
-                }
-            };
-
-            for mut sig in signatures {
-                let header = sig.remove("header");
-
-                let protected_b64 = match sig.get("protected") {
-                    Some(Value::String(val)) => val,
-                    Some(_) => bail!("The protected field must be a string."),
-                    None => bail!("The JWS alg header claim must be in protected."),
-                };
+        let (payload, header) = self.deserialize_json_header_set_with_selector(input, selector)?;
+        let header = JwsHeader::from_map(header.to_map())?;
+        Ok((payload, header))
+    }
 
-                let protected_vec = base64::decode_config(&protected_b64, base64::URL_SAFE_NO_PAD)?;
-                let protected_map: Map<String, Value> = serde_json::from_slice(&protected_vec)?;
-
-                let mut b64 = true;
-                if let Some(Value::Array(vals)) = protected_map.get("critical") {
-                    for val in vals {
-                        match val {
-                            Value::String(name) => {
-                                if !self.is_acceptable_critical(name) {
-                                    bail!("The critical name '{}' is not supported.", name);
-                                }
-
-                                if name == "b64" {
-                                    match protected_map.get("b64") {
-                                        Some(Value::Bool(b64_val)) => {
-                                            b64 = *b64_val;
-                                        }
-                                        Some(_) => bail!("The JWS b64 header claim must be bool."),
-                                        None => {}
-                                    }
-                                }
-                            }
-                            _ => bail!("The JWS critical header claim must be a array of string."),
-                        }
+    /// Deserialize the input that is formatted by json serialization,
+    /// keeping the protected and unprotected header claims apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `verifier` - The JWS verifier.
+    pub fn deserialize_json_header_set<'a>(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier: &'a dyn JwsVerifier,
+    ) -> Result<(Vec<u8>, JwsHeaderSet), JoseError> {
+        self.deserialize_json_header_set_with_selector(input, |header| {
+            match header.algorithm() {
+                Some(val) => {
+                    let expected_alg = verifier.algorithm().name();
+                    if val != expected_alg {
+                        return Ok(None);
                     }
                 }
+                _ => return Ok(None),
+            }
 
-                let merged_map = match header {
-                    Some(Value::Object(mut val)) => {
-                        for (key, value) in protected_map {
-                            if val.contains_key(&key) {
-                                bail!("A duplicate key exists: {}", key);
-                            } else {
-                                val.insert(key.clone(), value.clone());
-                            }
-                        }
-                        val
-                    }
-                    Some(_) => bail!("The protected field must be a object."),
-                    None => protected_map.clone(),
-                };
+            match verifier.key_id() {
+                Some(expected) => match header.key_id() {
+                    Some(actual) if expected == actual => {}
+                    _ => return Ok(None),
+                },
+                None => {}
+            }
 
-                if let None = merged_map.get("alg") {
-                    bail!("The JWS alg header claim must be in protected.");
-                }
+            Ok(Some(verifier))
+        })
+    }
 
-                let signature = match sig.get("signature") {
-                    Some(Value::String(val)) => {
-                        base64::decode_config(val, base64::URL_SAFE_NO_PAD)?
-                    }
-                    Some(_) => bail!("The signature field must be string."),
-                    None => bail!("The signature field is required."),
-                };
+    /// Deserialize the input that is formatted by json serialization,
+    /// keeping the protected and unprotected header claims apart.
+    ///
+    /// The selector receives the merged header claims, so unprotected claims
+    /// like kid can be used for key lookup before verification.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn deserialize_json_header_set_with_selector<'a, F>(
+        &self,
+        input: impl AsRef<[u8]>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JwsHeaderSet), JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        (|| -> anyhow::Result<(Vec<u8>, JwsHeaderSet)> {
+            let (payload_b64, signatures) = self.decode_json(input.as_ref())?;
+
+            let mut last_err = None;
+            for sig in signatures {
+                let JsonSignature {
+                    protected_b64,
+                    protected_map,
+                    unprotected_map,
+                    merged,
+                    signature,
+                    b64,
+                } = self.decode_json_signature(sig)?;
 
-                let merged = JwsHeader::from_map(merged_map)?;
                 let verifier = match selector(&merged)? {
                     Some(val) => val,
                     None => continue,
                 };
+                self.check_algorithm(verifier.algorithm().name())?;
 
                 match merged.claim("alg") {
                     Some(Value::String(val)) => {
@@ -650,18 +1048,28 @@ impl JwsContext {
                     None => {}
                 }
 
-                let message = format!("{}.{}", &protected_b64, &payload_b64);
-                verifier.verify(message.as_bytes(), &signature)?;
+                // Later signatures may also match this verifier.
+                if let Err(err) = verifier.verify_parts(
+                    &[protected_b64.as_bytes(), b".", payload_b64.as_bytes()],
+                    &signature,
+                ) {
+                    last_err = Some(err);
+                    continue;
+                }
 
                 let payload = if b64 {
-                    base64::decode_config(&payload_b64, base64::URL_SAFE_NO_PAD)?
+                    util::decode_base64_url(&payload_b64, self.lenient_base64)?
                 } else {
                     payload_b64.into_bytes()
                 };
 
-                return Ok((payload, merged));
+                let header = JwsHeaderSet::from_maps(protected_map, unprotected_map);
+                return Ok((payload, header));
             }
 
+            if let Some(err) = last_err {
+                return Err(err.into());
+            }
             bail!("A signature that matched the header claims is not found.");
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -669,4 +1077,186 @@ impl JwsContext {
             Err(err) => JoseError::InvalidJwsFormat(err),
         })
     }
+
+    /// Verify every signature of the input that is formatted by json serialization,
+    /// and return whether each signature entry passed, in the order of the entries.
+    ///
+    /// A signature entry passes if one of the verifiers whose algorithm and key ID
+    /// match its header claims verifies it. With the `rayon` feature the entries are
+    /// verified in parallel; otherwise they are verified one by one.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `verifiers` - The JWS verifiers.
+    pub fn verify_all(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifiers: &[&dyn JwsVerifier],
+    ) -> Result<Vec<bool>, JoseError> {
+        (|| -> anyhow::Result<Vec<bool>> {
+            let (payload_b64, signatures) = self.decode_json(input.as_ref())?;
+
+            let mut vec = Vec::with_capacity(signatures.len());
+            for sig in signatures {
+                vec.push(self.decode_json_signature(sig)?);
+            }
+
+            #[cfg(feature = "rayon")]
+            let results = {
+                use rayon::prelude::*;
+
+                vec.par_iter()
+                    .map(|sig| self.verify_json_signature(&payload_b64, sig, verifiers))
+                    .collect()
+            };
+
+            #[cfg(not(feature = "rayon"))]
+            let results = vec
+                .iter()
+                .map(|sig| self.verify_json_signature(&payload_b64, sig, verifiers))
+                .collect();
+
+            Ok(results)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwsFormat(err),
+        })
+    }
+
+    /// Return the base64url encoded payload and the signature entries of the input
+    /// that is formatted by json serialization.
+    fn decode_json(&self, input: &[u8]) -> anyhow::Result<(String, Vec<Map<String, Value>>)> {
+        self.check_token_size(input)?;
+
+        let mut map: Map<String, Value> = serde_json::from_slice(input)?;
+
+        let payload_b64 = match map.remove("payload") {
+            Some(Value::String(val)) => val,
+            Some(_) => bail!("The payload field must be string."),
+            None => bail!("The payload field is required."),
+        };
+
+        let signatures = match map.remove("signatures") {
+            Some(Value::Array(vals)) => {
+                let mut vec = Vec::with_capacity(vals.len());
+                for val in vals {
+                    if let Value::Object(val) = val {
+                        vec.push(val);
+                    } else {
+                        bail!("The signatures field must be a array of object.");
+                    }
+                }
+                vec
+            }
+            Some(_) => bail!("The signatures field must be a array."),
+            None => {
+                let mut vec = Vec::with_capacity(1);
+                vec.push(map);
+                vec
+            }
+        };
+
+        Ok((payload_b64, signatures))
+    }
+
+    fn decode_json_signature(&self, mut sig: Map<String, Value>) -> anyhow::Result<JsonSignature> {
+        let header = sig.remove("header");
+
+        let protected_b64 = match sig.remove("protected") {
+            Some(Value::String(val)) => val,
+            Some(_) => bail!("The protected field must be a string."),
+            None => bail!("The JWS alg header claim must be in protected."),
+        };
+
+        let protected_vec = util::decode_base64_url(&protected_b64, self.lenient_base64)?;
+        let protected_map: Map<String, Value> = serde_json::from_slice(&protected_vec)?;
+
+        let unprotected_map = match header {
+            Some(Value::Object(val)) => val,
+            Some(_) => bail!("The protected field must be a object."),
+            None => Map::new(),
+        };
+
+        self.check_critical(&protected_map, &[&unprotected_map])?;
+
+        let b64 = Self::base64url_encode_payload(&protected_map)?;
+
+        let mut merged_map = unprotected_map.clone();
+        for (key, value) in &protected_map {
+            if merged_map.contains_key(key) {
+                bail!("A duplicate key exists: {}", key);
+            } else {
+                merged_map.insert(key.clone(), value.clone());
+            }
+        }
+
+        if let None = merged_map.get("alg") {
+            bail!("The JWS alg header claim must be in protected.");
+        }
+
+        let signature = match sig.get("signature") {
+            Some(Value::String(val)) => util::decode_base64_url(val, self.lenient_base64)?,
+            Some(_) => bail!("The signature field must be string."),
+            None => bail!("The signature field is required."),
+        };
+
+        self.check_header_parameters(&merged_map)?;
+        let merged = JwsHeader::from_map(merged_map)?;
+
+        Ok(JsonSignature {
+            protected_b64,
+            protected_map,
+            unprotected_map,
+            merged,
+            signature,
+            b64,
+        })
+    }
+
+    fn verify_json_signature(
+        &self,
+        payload_b64: &str,
+        sig: &JsonSignature,
+        verifiers: &[&dyn JwsVerifier],
+    ) -> bool {
+        let alg = match sig.merged.algorithm() {
+            Some(val) => val,
+            None => return false,
+        };
+
+        verifiers.iter().any(|verifier| {
+            if verifier.algorithm().name() != alg {
+                return false;
+            }
+
+            if let Some(expected) = verifier.key_id() {
+                if sig.merged.key_id() != Some(expected) {
+                    return false;
+                }
+            }
+
+            if self.check_algorithm(alg).is_err() {
+                return false;
+            }
+
+            verifier
+                .verify_parts(
+                    &[sig.protected_b64.as_bytes(), b".", payload_b64.as_bytes()],
+                    &sig.signature,
+                )
+                .is_ok()
+        })
+    }
+}
+
+/// A signature entry of a JWS that is formatted by json serialization.
+struct JsonSignature {
+    protected_b64: String,
+    protected_map: Map<String, Value>,
+    unprotected_map: Map<String, Value>,
+    merged: JwsHeader,
+    signature: Vec<u8>,
+    b64: bool,
 }
@@ -258,18 +258,26 @@ impl JwsHeader {
 
     /// Set a value for content type header claim (cty).
     ///
+    /// A media type of the form "application/foo" is stored in the short form "foo",
+    /// as RFC 7515 recommends. Any other value, such as "JWT" or "text/plain", is
+    /// stored as is.
+    ///
     /// # Arguments
     ///
     /// * `value` - a content type (e.g. "JWT")
     pub fn set_content_type(&mut self, value: impl Into<String>) {
         let value: String = value.into();
+        let value = util::compact_media_type(&value).to_string();
         self.claims.insert("cty".to_string(), Value::String(value));
     }
 
-    /// Return the value for content type header claim (cty).
+    /// Return the value for content type header claim (cty) in the short form.
+    ///
+    /// A received "application/foo" is returned as "foo", the same as a value set by
+    /// `set_content_type`. The value as received is available by `claim("cty")`.
     pub fn content_type(&self) -> Option<&str> {
         match self.claims.get("cty") {
-            Some(Value::String(val)) => Some(val),
+            Some(Value::String(val)) => Some(util::compact_media_type(val)),
             _ => None,
         }
     }
@@ -375,14 +383,17 @@ impl JwsHeader {
                 self.claims.insert(key.to_string(), val);
             }
             None => {
-                self.claims.remove(key);
+                self.claims.shift_remove(key);
             }
         }
 
         Ok(())
     }
 
-    /// Return values for header claims set
+    /// Return values for header claims set.
+    ///
+    /// The claims are kept in the order they were set, and the header is serialized
+    /// in this order, so the encoded header bytes can be reproduced by the caller.
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
     }
@@ -547,4 +558,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_content_type() -> Result<()> {
+        let mut header = JwsHeader::new();
+        for (value, expected) in vec![
+            ("JWT", "JWT"),
+            ("application/cbor", "cbor"),
+            ("Application/cbor", "cbor"),
+            ("application/vnd.example+json", "vnd.example+json"),
+            ("application/foo/bar", "application/foo/bar"),
+            ("text/plain", "text/plain"),
+        ] {
+            header.set_content_type(value);
+            assert_eq!(header.content_type(), Some(expected));
+            assert_eq!(header.claim("cty"), Some(&json!(expected)));
+        }
+
+        // A received value is kept as is and read in the short form.
+        let header = JwsHeader::from_bytes(br#"{"cty":"application/cbor"}"#)?;
+        assert_eq!(header.content_type(), Some("cbor"));
+        assert_eq!(header.claim("cty"), Some(&json!("application/cbor")));
+
+        Ok(())
+    }
 }
@@ -1,8 +1,11 @@
 use std::fmt::{Debug, Display};
 use std::ops::Deref;
 
+use anyhow::bail;
+
 use crate::jwk::Jwk;
 use crate::jws::JwsHeader;
+use crate::util;
 use crate::{JoseError, JoseHeader, Map, Value};
 
 /// Represent JWS protected and unprotected header claims
@@ -21,6 +24,16 @@ impl JwsHeaderSet {
         }
     }
 
+    pub(crate) fn from_maps(
+        protected: Map<String, Value>,
+        unprotected: Map<String, Value>,
+    ) -> Self {
+        Self {
+            protected,
+            unprotected,
+        }
+    }
+
     /// Set a value for algorithm header claim (alg).
     ///
     /// # Arguments
@@ -31,10 +44,10 @@ impl JwsHeaderSet {
         let key = "alg";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -57,10 +70,10 @@ impl JwsHeaderSet {
         let key = "jku";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -83,10 +96,10 @@ impl JwsHeaderSet {
         let key = "jwk";
         let value: Map<String, Value> = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::Object(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::Object(value));
         }
@@ -112,10 +125,10 @@ impl JwsHeaderSet {
         let key = "x5u";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -141,10 +154,10 @@ impl JwsHeaderSet {
             .map(|v| Value::String(base64::encode_config(v.as_ref(), base64::URL_SAFE_NO_PAD)))
             .collect();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::Array(vec));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected.insert(key.to_string(), Value::Array(vec));
         }
     }
@@ -184,10 +197,10 @@ impl JwsHeaderSet {
         let key = "x5t";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -217,10 +230,10 @@ impl JwsHeaderSet {
         let key = "x5t#S256";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -246,10 +259,10 @@ impl JwsHeaderSet {
         let key = "kid";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -272,10 +285,10 @@ impl JwsHeaderSet {
         let key = "typ";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -291,26 +304,34 @@ impl JwsHeaderSet {
 
     /// Set a value for content type header claim (cty).
     ///
+    /// A media type of the form "application/foo" is stored in the short form "foo",
+    /// as RFC 7515 recommends. Any other value, such as "JWT" or "text/plain", is
+    /// stored as is.
+    ///
     /// # Arguments
     ///
     /// * `value` - a content type (e.g. "JWT")
     pub fn set_content_type(&mut self, value: impl Into<String>, protection: bool) {
         let key = "cty";
         let value: String = value.into();
+        let value = util::compact_media_type(&value).to_string();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
     }
 
-    /// Return the value for content type header claim (cty).
+    /// Return the value for content type header claim (cty) in the short form.
+    ///
+    /// A received "application/foo" is returned as "foo", the same as a value set by
+    /// `set_content_type`. The value as received is available by `claim("cty")`.
     pub fn content_type(&self) -> Option<&str> {
         match self.claim("cty") {
-            Some(Value::String(val)) => Some(val),
+            Some(Value::String(val)) => Some(util::compact_media_type(val)),
             _ => None,
         }
     }
@@ -324,9 +345,9 @@ impl JwsHeaderSet {
         let key = "crit";
         let vec = values
             .iter()
-            .map(|v| Value::String(base64::encode_config(v.as_ref(), base64::URL_SAFE_NO_PAD)))
+            .map(|v| Value::String(v.as_ref().to_string()))
             .collect();
-        self.unprotected.remove(key);
+        self.unprotected.shift_remove(key);
         self.protected.insert(key.to_string(), Value::Array(vec));
     }
 
@@ -354,7 +375,7 @@ impl JwsHeaderSet {
     /// * `value` - is base64url-encode payload
     pub fn set_base64url_encode_payload(&mut self, value: bool) {
         let key = "b64";
-        self.unprotected.remove(key);
+        self.unprotected.shift_remove(key);
         self.protected.insert(key.to_string(), Value::Bool(value));
     }
 
@@ -375,10 +396,10 @@ impl JwsHeaderSet {
         let key = "url";
         let value: String = value.into();
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -401,10 +422,10 @@ impl JwsHeaderSet {
         let key = "nonce";
         let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
         if protection {
-            self.unprotected.remove(key);
+            self.unprotected.shift_remove(key);
             self.protected.insert(key.to_string(), Value::String(value));
         } else {
-            self.protected.remove(key);
+            self.protected.shift_remove(key);
             self.unprotected
                 .insert(key.to_string(), Value::String(value));
         }
@@ -431,16 +452,16 @@ impl JwsHeaderSet {
             Some(val) => {
                 JwsHeader::check_claim(key, &val)?;
                 if protection {
-                    self.unprotected.remove(key);
+                    self.unprotected.shift_remove(key);
                     self.protected.insert(key.to_string(), val);
                 } else {
-                    self.protected.remove(key);
+                    self.protected.shift_remove(key);
                     self.unprotected.insert(key.to_string(), val);
                 }
             }
             None => {
-                self.protected.remove(key);
-                self.unprotected.remove(key);
+                self.protected.shift_remove(key);
+                self.unprotected.shift_remove(key);
             }
         }
 
@@ -463,6 +484,25 @@ impl JwsHeaderSet {
         }
         map
     }
+
+    /// Check that no header claim appears in both the protected and unprotected header.
+    pub fn validate_disjoint(&self) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            for key in self.unprotected.keys() {
+                if self.protected.contains_key(key) {
+                    bail!("A duplicate key exists: {}", key);
+                }
+            }
+            Ok(())
+        })()
+        .map_err(|err| JoseError::InvalidJwsFormat(err))
+    }
+
+    /// Return the combined header of the protected and unprotected header claims.
+    pub fn merge(&self) -> Result<JwsHeader, JoseError> {
+        self.validate_disjoint()?;
+        JwsHeader::from_map(self.to_map())
+    }
 }
 
 impl JoseHeader for JwsHeaderSet {
@@ -504,3 +544,41 @@ impl Deref for JwsHeaderSet {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use crate::jws::JwsHeaderSet;
+    use crate::{JoseError, Map};
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let mut header = JwsHeaderSet::new();
+        header.set_algorithm("HS256", true);
+        header.set_key_id("kid", false);
+        header.validate_disjoint()?;
+
+        let merged = header.merge()?;
+        assert_eq!(merged.algorithm(), Some("HS256"));
+        assert_eq!(merged.key_id(), Some("kid"));
+
+        let mut protected = Map::new();
+        protected.insert("alg".to_string(), json!("HS256"));
+        protected.insert("kid".to_string(), json!("kid"));
+        let mut unprotected = Map::new();
+        unprotected.insert("kid".to_string(), json!("other"));
+        let header = JwsHeaderSet::from_maps(protected, unprotected);
+        assert!(matches!(
+            header.validate_disjoint(),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            header.merge(),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, bail};
+use openssl::hash::MessageDigest;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreRef;
+use openssl::x509::{X509Ref, X509StoreContext, X509};
+
+use crate::jws::{
+    EdDSA, JwsAlgorithm, JwsHeader, JwsVerifier, ES256, ES256K, ES384, ES512, PS256, PS384, PS512,
+    RS256, RS384, RS512,
+};
+use crate::JoseError;
+
+/// Return the certificates of the X.509 certificate chain header claim (x5c).
+///
+/// The first certificate contains the public key of the signing key. The chain
+/// is not validated; use `verify_x509_certificate_chain` for it.
+///
+/// # Arguments
+///
+/// * `header` - The JWS header claims.
+pub fn x509_certificate_chain(header: &JwsHeader) -> Result<Vec<X509>, JoseError> {
+    (|| -> anyhow::Result<Vec<X509>> {
+        let chain = match header.x509_certificate_chain() {
+            Some(vals) if vals.len() > 0 => vals,
+            Some(_) => bail!("The JWS x5c header claim must not be empty."),
+            None => bail!("The JWS x5c header claim is required."),
+        };
+
+        let mut vec = Vec::with_capacity(chain.len());
+        for der in chain {
+            vec.push(X509::from_der(&der)?);
+        }
+        Ok(vec)
+    })()
+    .map_err(|err| JoseError::InvalidJwsFormat(err))
+}
+
+/// Validate the X.509 certificate chain header claim (x5c) against the trust anchors
+/// and return the first certificate, which contains the public key of the signing key.
+///
+/// The certificates following the first one are used as untrusted intermediates.
+/// If the X.509 certificate SHA-256 thumbprint header claim (x5t#S256) is present,
+/// it must match the first certificate.
+///
+/// # Arguments
+///
+/// * `header` - The JWS header claims.
+/// * `trust_anchors` - The trusted root certificates.
+pub fn verify_x509_certificate_chain(
+    header: &JwsHeader,
+    trust_anchors: &X509StoreRef,
+) -> Result<X509, JoseError> {
+    let mut chain = x509_certificate_chain(header)?;
+    (|| -> anyhow::Result<X509> {
+        let leaf = chain.remove(0);
+        let mut intermediates = Stack::new()?;
+        for cert in chain {
+            intermediates.push(cert)?;
+        }
+
+        let mut context = X509StoreContext::new()?;
+        let result = context.init(trust_anchors, &leaf, &intermediates, |context| {
+            if context.verify_cert()? {
+                Ok(None)
+            } else {
+                Ok(Some(context.error()))
+            }
+        })?;
+        if let Some(err) = result {
+            bail!("The X.509 certificate chain is not trusted: {}", err);
+        }
+
+        Ok(leaf)
+    })()
+    .map_err(|err| JoseError::InvalidJwsFormat(err))
+    .and_then(|leaf| {
+        if header.claim("x5t#S256").is_some() {
+            verify_x509_certificate_sha256_thumbprint(header, &leaf)?;
+        }
+        Ok(leaf)
+    })
+}
+
+/// Return the SHA-256 thumbprint of a X.509 certificate, that is the SHA-256 digest
+/// of its DER encoding. It is the value of the x5t#S256 header claim before base64url
+/// encoding, so it can be passed to `JwsHeader::set_x509_certificate_sha256_thumbprint`.
+///
+/// # Arguments
+///
+/// * `certificate` - A X.509 certificate.
+pub fn x509_certificate_sha256_thumbprint(certificate: &X509Ref) -> Result<Vec<u8>, JoseError> {
+    (|| -> anyhow::Result<Vec<u8>> {
+        let digest = certificate.digest(MessageDigest::sha256())?;
+        Ok(digest.to_vec())
+    })()
+    .map_err(|err| JoseError::InvalidKeyFormat(err))
+}
+
+/// Check that the X.509 certificate SHA-256 thumbprint header claim (x5t#S256)
+/// matches a X.509 certificate, e.g. one that is selected by the thumbprint.
+///
+/// A mismatch means that the header was tampered with or the wrong certificate
+/// was selected.
+///
+/// # Arguments
+///
+/// * `header` - The JWS header claims.
+/// * `certificate` - A X.509 certificate.
+pub fn verify_x509_certificate_sha256_thumbprint(
+    header: &JwsHeader,
+    certificate: &X509Ref,
+) -> Result<(), JoseError> {
+    let thumbprint = x509_certificate_sha256_thumbprint(certificate)?;
+    (|| -> anyhow::Result<()> {
+        match header.x509_certificate_sha256_thumbprint() {
+            Some(val) if val == thumbprint => {}
+            Some(_) => bail!("The JWS x5t#S256 header claim is mismatched."),
+            None => bail!("The JWS x5t#S256 header claim is required."),
+        }
+        Ok(())
+    })()
+    .map_err(|err| JoseError::InvalidJwsFormat(err))
+}
+
+/// Return a verifier from the public key of a X.509 certificate.
+///
+/// # Arguments
+///
+/// * `algorithm` - The expected signing algorithm.
+/// * `certificate` - A X.509 certificate.
+pub fn verifier_from_x509_certificate(
+    algorithm: &dyn JwsAlgorithm,
+    certificate: &X509Ref,
+) -> Result<Box<dyn JwsVerifier>, JoseError> {
+    let public_key = (|| -> anyhow::Result<Vec<u8>> {
+        let public_key = certificate.public_key()?;
+        Ok(public_key.public_key_to_der()?)
+    })()
+    .map_err(|err| JoseError::InvalidKeyFormat(err))?;
+
+    let verifier: Box<dyn JwsVerifier> = match algorithm.name() {
+        "RS256" => Box::new(RS256.verifier_from_der(&public_key)?),
+        "RS384" => Box::new(RS384.verifier_from_der(&public_key)?),
+        "RS512" => Box::new(RS512.verifier_from_der(&public_key)?),
+        "PS256" => Box::new(PS256.verifier_from_der(&public_key)?),
+        "PS384" => Box::new(PS384.verifier_from_der(&public_key)?),
+        "PS512" => Box::new(PS512.verifier_from_der(&public_key)?),
+        "ES256" => Box::new(ES256.verifier_from_der(&public_key)?),
+        "ES256K" => Box::new(ES256K.verifier_from_der(&public_key)?),
+        "ES384" => Box::new(ES384.verifier_from_der(&public_key)?),
+        "ES512" => Box::new(ES512.verifier_from_der(&public_key)?),
+        "EdDSA" => Box::new(EdDSA.verifier_from_der(&public_key)?),
+        val => {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                "The algorithm cannot be used with a X.509 certificate: {}",
+                val
+            )))
+        }
+    };
+    Ok(verifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, PKeyRef, Private};
+    use openssl::x509::extension::{BasicConstraints, KeyUsage};
+    use openssl::x509::store::X509StoreBuilder;
+    use openssl::x509::{X509Name, X509Ref, X509};
+
+    use crate::jwk::KeyPair;
+    use crate::jws::{self, JwsHeader, ES256, HS256, RS256};
+    use crate::{JoseError, Value};
+
+    #[test]
+    fn verify_jws_with_x509_certificate_chain() -> Result<()> {
+        let root_key = PKey::private_key_from_pem(&load_file("pem/EC_P-256_private.pem")?)?;
+        let root = issue_certificate("root", &root_key, None, true)?;
+
+        let leaf_pem = load_file("pem/RSA_2048bit_private.pem")?;
+        let leaf_key = PKey::private_key_from_pem(&leaf_pem)?;
+        let leaf = issue_certificate("leaf", &leaf_key, Some((&root, &root_key)), false)?;
+
+        let mut header = JwsHeader::new();
+        header.set_x509_certificate_chain(&vec![leaf.to_der()?]);
+        let signer = RS256.signer_from_pem(&leaf_pem)?;
+        let jws = jws::serialize_compact(b"test payload!", &header, &signer)?;
+
+        let mut store = X509StoreBuilder::new()?;
+        store.add_cert(root)?;
+        let store = store.build();
+
+        let header = jws::extract_header(&jws)?;
+        let certificate = jws::verify_x509_certificate_chain(&header, &store)?;
+        let verifier = jws::verifier_from_x509_certificate(&RS256, &certificate)?;
+        let (payload, _) = jws::deserialize_compact(&jws, &*verifier)?;
+        assert_eq!(payload, b"test payload!");
+
+        // The algorithm must suit the key of the certificate.
+        assert!(matches!(
+            jws::verifier_from_x509_certificate(&ES256, &certificate),
+            Err(JoseError::InvalidKeyFormat(_))
+        ));
+        assert!(matches!(
+            jws::verifier_from_x509_certificate(&HS256, &certificate),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        // A chain issued by another root is not trusted.
+        let other_store = X509StoreBuilder::new()?.build();
+        assert!(matches!(
+            jws::verify_x509_certificate_chain(&header, &other_store),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        // A token signed by a key other than the one of the certificate is rejected.
+        let other_key = RS256.generate_key_pair(2048)?;
+        let signer = RS256.signer_from_der(&other_key.to_der_private_key())?;
+        let mut header = JwsHeader::new();
+        header.set_x509_certificate_chain(&vec![leaf.to_der()?]);
+        let jws = jws::serialize_compact(b"test payload!", &header, &signer)?;
+
+        let header = jws::extract_header(&jws)?;
+        let certificate = jws::verify_x509_certificate_chain(&header, &store)?;
+        let verifier = jws::verifier_from_x509_certificate(&RS256, &certificate)?;
+        assert!(matches!(
+            jws::deserialize_compact(&jws, &*verifier),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        // The x5c header claim is required.
+        assert!(matches!(
+            jws::x509_certificate_chain(&JwsHeader::new()),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_jws_with_x509_certificate_sha256_thumbprint() -> Result<()> {
+        let key = PKey::private_key_from_pem(&load_file("pem/EC_P-256_private.pem")?)?;
+        let certificate = issue_certificate("self", &key, None, true)?;
+        let other_certificate = issue_certificate("other", &key, None, true)?;
+
+        let thumbprint = jws::x509_certificate_sha256_thumbprint(&certificate)?;
+        assert_eq!(
+            thumbprint,
+            openssl::sha::sha256(&certificate.to_der()?).to_vec()
+        );
+
+        let mut header = JwsHeader::new();
+        header.set_x509_certificate_sha256_thumbprint(&thumbprint);
+        let header = jws::extract_header(&jws::serialize_compact(
+            b"test payload!",
+            &header,
+            &ES256.signer_from_pem(&load_file("pem/EC_P-256_private.pem")?)?,
+        )?)?;
+        assert_eq!(
+            header.claim("x5t#S256"),
+            Some(&Value::String(base64::encode_config(
+                &thumbprint,
+                base64::URL_SAFE_NO_PAD
+            )))
+        );
+
+        jws::verify_x509_certificate_sha256_thumbprint(&header, &certificate)?;
+        assert!(matches!(
+            jws::verify_x509_certificate_sha256_thumbprint(&header, &other_certificate),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            jws::verify_x509_certificate_sha256_thumbprint(&JwsHeader::new(), &certificate),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        // The thumbprint is checked with the x5c header claim.
+        let mut store = X509StoreBuilder::new()?;
+        store.add_cert(certificate.clone())?;
+        store.add_cert(other_certificate.clone())?;
+        let store = store.build();
+
+        let mut header = JwsHeader::new();
+        header.set_x509_certificate_chain(&vec![certificate.to_der()?]);
+        header.set_x509_certificate_sha256_thumbprint(&thumbprint);
+        jws::verify_x509_certificate_chain(&header, &store)?;
+
+        header.set_x509_certificate_chain(&vec![other_certificate.to_der()?]);
+        assert!(matches!(
+            jws::verify_x509_certificate_chain(&header, &store),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    fn issue_certificate(
+        name: &str,
+        key: &PKeyRef<Private>,
+        issuer: Option<(&X509Ref, &PKeyRef<Private>)>,
+        ca: bool,
+    ) -> Result<X509> {
+        let mut subject = X509Name::builder()?;
+        subject.append_entry_by_text("CN", name)?;
+        let subject = subject.build();
+
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        let serial_number = BigNum::from_u32(1)?.to_asn1_integer()?;
+        builder.set_serial_number(&serial_number)?;
+        builder.set_subject_name(&subject)?;
+        builder.set_pubkey(key)?;
+        let not_before = Asn1Time::days_from_now(0)?;
+        builder.set_not_before(&not_before)?;
+        let not_after = Asn1Time::days_from_now(1)?;
+        builder.set_not_after(&not_after)?;
+        if ca {
+            builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+            builder.append_extension(KeyUsage::new().critical().key_cert_sign().build()?)?;
+        }
+        match issuer {
+            Some((issuer, issuer_key)) => {
+                builder.set_issuer_name(issuer.subject_name())?;
+                builder.sign(issuer_key, MessageDigest::sha256())?;
+            }
+            None => {
+                builder.set_issuer_name(&subject)?;
+                builder.sign(key, MessageDigest::sha256())?;
+            }
+        }
+        Ok(builder.build())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let data = fs::read(&pb)?;
+        Ok(data)
+    }
+}
@@ -12,10 +12,11 @@ pub use crate::jwt::jwt_payload_validator::JwtPayloadValidator;
 pub use crate::jwt::alg::unsecured::UnsecuredJwsAlgorithm::None;
 
 use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
 
 use crate::jwe::{JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::{Jwk, JwkSet};
-use crate::jws::{JwsHeader, JwsSigner, JwsVerifier};
+use crate::jws::{JwsAlgorithm, JwsHeader, JwsSigner, JwsVerifier};
 use crate::{JoseError, JoseHeader};
 
 static DEFAULT_CONTEXT: Lazy<JwtContext> = Lazy::new(|| JwtContext::new());
@@ -60,6 +61,28 @@ pub fn encode_with_encrypter(
     DEFAULT_CONTEXT.encode_with_encrypter(payload, header, encrypter)
 }
 
+/// Return the string repsentation of the nested JWT that is signed with the
+/// siginig algorithm and then encrypted with the encrypting algorithm.
+/// The cty header claim of the JWE header is set to "JWT".
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `jws_header` - The JWS heaser claims of the inner JWT.
+/// * `signer` - a signer object.
+/// * `jwe_header` - The JWE heaser claims of the outer JWT.
+/// * `encrypter` - a encrypter object.
+pub fn encode_with_signer_and_encrypter(
+    payload: &JwtPayload,
+    jws_header: &JwsHeader,
+    signer: &dyn JwsSigner,
+    jwe_header: &JweHeader,
+    encrypter: &dyn JweEncrypter,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT
+        .encode_with_signer_and_encrypter(payload, jws_header, signer, jwe_header, encrypter)
+}
+
 /// Return the Jose header decoded from JWT.
 ///
 /// # Arguments
@@ -107,6 +130,38 @@ where
     DEFAULT_CONTEXT.decode_with_verifier_selector(input, selector)
 }
 
+/// Return the payload of the JWT decoded by the verifier as a user defined type.
+///
+/// The payload claims are not validated; use `decode_typed_with_validator`
+/// to validate them before deserializing.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier` - a verifier of the signing algorithm.
+pub fn decode_typed<T: DeserializeOwned>(
+    input: impl AsRef<[u8]>,
+    verifier: &dyn JwsVerifier,
+) -> Result<(T, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_typed(input, verifier)
+}
+
+/// Return the payload of the JWT decoded by the verifier as a user defined type,
+/// after validating the payload claims by the validator.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier` - a verifier of the signing algorithm.
+/// * `validator` - a validator of the payload claims.
+pub fn decode_typed_with_validator<T: DeserializeOwned>(
+    input: impl AsRef<[u8]>,
+    verifier: &dyn JwsVerifier,
+    validator: &JwtPayloadValidator,
+) -> Result<(T, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_typed_with_validator(input, verifier, validator)
+}
+
 /// Return the JWT object decoded by using a JWK set.
 ///
 /// # Arguments
@@ -125,6 +180,46 @@ where
     DEFAULT_CONTEXT.decode_with_verifier_in_jwk_set(input, jwk_set, selector)
 }
 
+/// Return the JWT object verified by a key in a JWK set.
+///
+/// The alg header claim must be one of the allowed algorithms. If the kid header
+/// claim is present, the keys with the same kid are tried. Otherwise every key
+/// suitable for verifying the algorithm is tried. The payload claims are not
+/// validated; use `JwtPayloadValidator` for them.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `jwk_set` - a JWK set.
+/// * `algorithms` - the allowed signing algorithms.
+pub fn decode_with_jwk_set(
+    input: impl AsRef<[u8]>,
+    jwk_set: &JwkSet,
+    algorithms: &[&dyn JwsAlgorithm],
+) -> Result<(JwtPayload, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_with_jwk_set(input, jwk_set, algorithms)
+}
+
+/// Return the nested JWT object that is decrypted by the decrypter and then
+/// verified by the verifier. The cty header claim of the JWE header must be "JWT".
+///
+/// The JWE is fully authenticated before the inner JWT is parsed, so
+/// unauthenticated plaintext never reaches the JWS verifier. If the cty header
+/// claim is not "JWT", the error kind is `JoseErrorKind::InvalidContentType`.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `decrypter` - a decrypter of the decrypting algorithm.
+/// * `verifier` - a verifier of the signing algorithm.
+pub fn decode_with_decrypter_and_verifier(
+    input: impl AsRef<[u8]>,
+    decrypter: &dyn JweDecrypter,
+    verifier: &dyn JwsVerifier,
+) -> Result<(JwtPayload, JweHeader, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_with_decrypter_and_verifier(input, decrypter, verifier)
+}
+
 /// Return the JWT object decoded by the selected decrypter.
 ///
 /// # Arguments
@@ -179,22 +274,23 @@ mod tests {
     use std::time::{Duration, SystemTime};
 
     use anyhow::Result;
+    use serde::Deserialize;
     use serde_json::json;
 
     #[allow(deprecated)]
     use crate::jwe::{
-        Dir, A128GCMKW, A128KW, A192GCMKW, A192KW, A256GCMKW, A256KW, ECDH_ES, ECDH_ES_A128KW,
-        ECDH_ES_A192KW, ECDH_ES_A256KW, PBES2_HS256_A128KW, PBES2_HS384_A192KW, PBES2_HS512_A256KW,
-        RSA1_5, RSA_OAEP, RSA_OAEP_256,
+        self, Dir, JweHeader, A128GCMKW, A128KW, A192GCMKW, A192KW, A256GCMKW, A256KW, ECDH_ES,
+        ECDH_ES_A128KW, ECDH_ES_A192KW, ECDH_ES_A256KW, PBES2_HS256_A128KW, PBES2_HS384_A192KW,
+        PBES2_HS512_A256KW, RSA1_5, RSA_OAEP, RSA_OAEP_256, RSA_OAEP_384, RSA_OAEP_512,
     };
-    use crate::jwk::Jwk;
+    use crate::jwk::{Jwk, JwkSet};
     use crate::jws::{
-        EdDSA, JwsHeader, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384, PS512,
-        RS256, RS384, RS512,
+        self, EdDSA, JwsHeader, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384,
+        PS512, RS256, RS384, RS512,
     };
-    use crate::jwt::{self, JwtPayload};
+    use crate::jwt::{self, JwtContext, JwtPayload, JwtPayloadValidator};
     use crate::util;
-    use crate::Value;
+    use crate::{JoseError, JoseErrorKind, Map, Value};
 
     #[test]
     fn test_decode_header() -> Result<()> {
@@ -243,6 +339,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_none_with_hmac_verifier() -> Result<()> {
+        let private_key = util::random_bytes(64);
+        let verifier = HS256.verifier_from_bytes(&private_key)?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("admin");
+        let jwt_string = jwt::encode_unsecured(&src_payload, &src_header)?;
+
+        assert!(matches!(
+            jwt::decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            jws::deserialize_compact(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        let signer = HS256.signer_from_bytes(&private_key)?;
+        let signed_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+        let parts: Vec<&str> = signed_string.split('.').collect();
+        let jwt_string = format!(
+            "{}.{}.{}",
+            jwt_string.split('.').next().unwrap(),
+            parts[1],
+            parts[2]
+        );
+        assert!(matches!(
+            jwt::decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwt_with_hmac() -> Result<()> {
         for alg in &[HS256, HS384, HS512] {
@@ -265,6 +398,181 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_with_padded_base64() -> Result<()> {
+        let key = util::random_bytes(32);
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+        let jwt_string = format!("{}=", jwt_string);
+
+        let mut context = JwtContext::new();
+        assert!(matches!(
+            context.decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        context.set_lenient_base64(true);
+        let (dst_payload, _) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(dst_payload, JwtPayload::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_max_token_size() -> Result<()> {
+        let key = util::random_bytes(32);
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(HS256.to_jwk(&key));
+
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+
+        let mut context = JwtContext::new();
+        context.set_max_token_size(jwt_string.len() - 1);
+        assert!(matches!(
+            context.decode_header(&jwt_string),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+        assert!(matches!(
+            context.decode_with_verifier(&jwt_string, &verifier),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+        assert!(matches!(
+            context.decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS256]),
+            Err(JoseError::InvalidJwsFormat(_))
+        ));
+
+        context.set_max_token_size(jwt_string.len());
+        let (dst_payload, _) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(dst_payload, JwtPayload::new());
+        let (dst_payload, _) = context.decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS256])?;
+        assert_eq!(dst_payload, JwtPayload::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_jwt() -> Result<()> {
+        let sign_key = util::random_bytes(64);
+        let enc_key = util::random_bytes(32);
+
+        let mut src_jws_header = JwsHeader::new();
+        src_jws_header.set_token_type("JWT");
+        let mut src_jwe_header = JweHeader::new();
+        src_jwe_header.set_content_encryption("A128CBC-HS256");
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("sub");
+
+        let signer = HS256.signer_from_bytes(&sign_key)?;
+        let encrypter = Dir.encrypter_from_bytes(&enc_key)?;
+        let jwt_string = jwt::encode_with_signer_and_encrypter(
+            &src_payload,
+            &src_jws_header,
+            &signer,
+            &src_jwe_header,
+            &encrypter,
+        )?;
+
+        let verifier = HS256.verifier_from_bytes(&sign_key)?;
+        let decrypter = Dir.decrypter_from_bytes(&enc_key)?;
+        let (dst_payload, dst_jwe_header, dst_jws_header) =
+            jwt::decode_with_decrypter_and_verifier(&jwt_string, &decrypter, &verifier)?;
+
+        src_jws_header.set_claim("alg", Some(json!(HS256.name())))?;
+        src_jwe_header.set_claim("alg", Some(json!(Dir.name())))?;
+        src_jwe_header.set_content_type("JWT");
+        assert_eq!(src_jws_header, dst_jws_header);
+        assert_eq!(src_jwe_header, dst_jwe_header);
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_jwt_with_invalid_signature() -> Result<()> {
+        let sign_key = util::random_bytes(64);
+        let enc_key = util::random_bytes(32);
+
+        let src_payload = JwtPayload::new();
+        let signer = HS256.signer_from_bytes(&sign_key)?;
+        let inner_jwt = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        let pos = inner_jwt.len() - 10;
+        let modified = match &inner_jwt[pos..(pos + 1)] {
+            "A" => "B",
+            _ => "A",
+        };
+        let inner_jwt = format!(
+            "{}{}{}",
+            &inner_jwt[..pos],
+            modified,
+            &inner_jwt[(pos + 1)..]
+        );
+
+        let mut jwe_header = JweHeader::new();
+        jwe_header.set_content_encryption("A128CBC-HS256");
+        let encrypter = Dir.encrypter_from_bytes(&enc_key)?;
+        let verifier = HS256.verifier_from_bytes(&sign_key)?;
+        let decrypter = Dir.decrypter_from_bytes(&enc_key)?;
+
+        jwe_header.set_content_type("JWT");
+        let jwt_string = jwe::serialize_compact(inner_jwt.as_bytes(), &jwe_header, &encrypter)?;
+        assert!(matches!(
+            jwt::decode_with_decrypter_and_verifier(&jwt_string, &decrypter, &verifier),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        jwe_header.set_content_type("JOSE");
+        let jwt_string = jwe::serialize_compact(inner_jwt.as_bytes(), &jwe_header, &encrypter)?;
+        let err = jwt::decode_with_decrypter_and_verifier(&jwt_string, &decrypter, &verifier)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJwtFormat(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidContentType));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_jwt_with_invalid_tag() -> Result<()> {
+        let sign_key = util::random_bytes(64);
+        let enc_key = util::random_bytes(32);
+
+        // The inner JWT has an invalid signature, so an error of the JWS layer
+        // would show that the plaintext was handed over before authentication.
+        let signer = HS256.signer_from_bytes(&sign_key)?;
+        let inner_jwt = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+        let inner_jwt = format!("{}A", inner_jwt);
+
+        let mut jwe_header = JweHeader::new();
+        jwe_header.set_content_encryption("A128CBC-HS256");
+        jwe_header.set_content_type("JWT");
+        let encrypter = Dir.encrypter_from_bytes(&enc_key)?;
+        let jwt_string = jwe::serialize_compact(inner_jwt.as_bytes(), &jwe_header, &encrypter)?;
+
+        let pos = jwt_string.len() - 10;
+        let modified = match &jwt_string[pos..(pos + 1)] {
+            "A" => "B",
+            _ => "A",
+        };
+        let jwt_string = format!(
+            "{}{}{}",
+            &jwt_string[..pos],
+            modified,
+            &jwt_string[(pos + 1)..]
+        );
+
+        let verifier = HS256.verifier_from_bytes(&sign_key)?;
+        let decrypter = Dir.decrypter_from_bytes(&enc_key)?;
+        let err = jwt::decode_with_decrypter_and_verifier(&jwt_string, &decrypter, &verifier)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidSignature(_)));
+        assert_eq!(err.kind(), Some(JoseErrorKind::TagMismatch));
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwt_with_rsa_pem() -> Result<()> {
         for alg in &[RS256, RS384, RS512] {
@@ -639,6 +947,7 @@ mod tests {
                         A128KW => "jwk/oct_128bit_private.jwk",
                         A192KW => "jwk/oct_192bit_private.jwk",
                         A256KW => "jwk/oct_256bit_private.jwk",
+                        _ => unreachable!(),
                     })?;
                     let external_jwt = load_file(
                         &(match zip {
@@ -757,6 +1066,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_with_rsaes_oaep_sha2() -> Result<()> {
+        for alg in vec![RSA_OAEP_256, RSA_OAEP_384, RSA_OAEP_512] {
+            let public_key = Jwk::from_bytes(&load_file("jwk/RSA_public.jwk")?)?;
+            let private_key = Jwk::from_bytes(&load_file("jwk/RSA_private.jwk")?)?;
+
+            let mut src_header = JweHeader::new();
+            src_header.set_content_encryption("A128GCM");
+            let mut src_payload = JwtPayload::new();
+            src_payload.set_issuer("joe");
+
+            let encrypter = alg.encrypter_from_jwk(&public_key)?;
+            let jwt_string = jwt::encode_with_encrypter(&src_payload, &src_header, &encrypter)?;
+
+            let decrypter = alg.decrypter_from_jwk(&private_key)?;
+            let (dst_payload, dst_header) = jwt::decode_with_decrypter(&jwt_string, &decrypter)?;
+
+            assert_eq!(dst_header.algorithm(), Some(alg.name()));
+            assert_eq!(src_payload, dst_payload);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_external_jwt_decrypt_with_rsaes() -> Result<()> {
         #[allow(deprecated)]
@@ -797,6 +1130,266 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_decode_with_jwk_set() -> Result<()> {
+        let mut jwk_a = HS256.to_jwk(&HS256.generate_key());
+        jwk_a.set_key_id("a");
+        let mut jwk_b = HS256.to_jwk(&HS256.generate_key());
+        jwk_b.set_key_id("b");
+        let jwk_set = JwkSet::from_map({
+            let mut map = Map::new();
+            map.insert(
+                "keys".to_string(),
+                Value::Array(vec![
+                    Value::Object(jwk_a.as_ref().clone()),
+                    Value::Object(jwk_b.as_ref().clone()),
+                ]),
+            );
+            map
+        })?;
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("sub");
+
+        // The kid header claim selects the key.
+        let signer = HS256.signer_from_jwk(&jwk_b)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        let (dst_payload, dst_header) =
+            jwt::decode_with_jwk_set(&jwt_string, &jwk_set, &[&RS256, &HS256])?;
+        assert_eq!(dst_header.key_id(), Some("b"));
+        assert_eq!(dst_payload, src_payload);
+
+        // Without the kid header claim, every key is tried.
+        let mut signer = HS256.signer_from_jwk(&jwk_b)?;
+        signer.remove_key_id();
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        let (dst_payload, dst_header) = jwt::decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS256])?;
+        assert_eq!(dst_header.key_id(), None);
+        assert_eq!(dst_payload, src_payload);
+
+        // The alg header claim must be allowed.
+        assert!(matches!(
+            jwt::decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS384, &RS256]),
+            Err(JoseError::UnsupportedSignatureAlgorithm(_))
+        ));
+
+        // A key with the kid must exist.
+        let mut signer = HS256.signer_from_bytes(&HS256.generate_key())?;
+        signer.set_key_id("c");
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jwt::decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS256]),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        // No key in the set verifies the signature.
+        signer.remove_key_id();
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jwt::decode_with_jwk_set(&jwt_string, &jwk_set, &[&HS256]),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_reissue() -> Result<()> {
+        let now = SystemTime::now();
+        let old_iat = now - Duration::from_secs(3600);
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("sub");
+        src_payload.set_claim("role", Some(json!("admin")))?;
+        src_payload.set_issued_at(&old_iat);
+        src_payload.set_expires_at(&(now + Duration::from_secs(60)));
+
+        let old_key = util::random_bytes(32);
+        let old_signer = HS256.signer_from_bytes(&old_key)?;
+        let old_verifier = HS256.verifier_from_bytes(&old_key)?;
+        let mut src_header = JwsHeader::new();
+        src_header.set_key_id("inbound");
+        src_header.set_claim("inbound", Some(json!(true)))?;
+        let inbound = jwt::encode_with_signer(&src_payload, &src_header, &old_signer)?;
+
+        let (mut payload, _) = jwt::decode_with_verifier(&inbound, &old_verifier)?;
+        let exp = now + Duration::from_secs(3600);
+        payload.set_expires_at(&exp);
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let new_signer = ES256.signer_from_pem(&private_key)?;
+        let new_verifier = ES256.verifier_from_pem(&public_key)?;
+        let outbound = payload.reissue(&new_signer)?;
+
+        let (dst_payload, dst_header) = jwt::decode_with_verifier(&outbound, &new_verifier)?;
+        assert_eq!(dst_header.algorithm(), Some("ES256"));
+        assert_eq!(dst_header.token_type(), Some("JWT"));
+        assert_eq!(dst_header.key_id(), None);
+        assert_eq!(dst_header.claim("inbound"), None);
+        assert_eq!(dst_payload.subject(), Some("sub"));
+        assert_eq!(dst_payload.claim("role"), Some(&json!("admin")));
+        assert_eq!(dst_payload.expires_at(), payload.expires_at());
+        assert!(dst_payload.issued_at().unwrap() > old_iat);
+        assert!(jwt::decode_with_verifier(&outbound, &old_verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_with_jku() -> Result<()> {
+        let mut jwk = HS256.to_jwk(&HS256.generate_key());
+        jwk.set_key_id("a");
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(jwk.clone());
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("sub");
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_jwk_set_url("https://example.com/jwks.json");
+        let signer = HS256.signer_from_jwk(&jwk)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+
+        let mut context = JwtContext::new();
+        assert!(matches!(
+            context.decode_with_jku(&jwt_string, &[&HS256]),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        context.set_jku_resolver(move |url| {
+            if url == "https://example.com/jwks.json" {
+                Ok(jwk_set.clone())
+            } else {
+                Err(JoseError::InvalidJwtFormat(anyhow::anyhow!(
+                    "The jku is not allowed: {}",
+                    url
+                )))
+            }
+        });
+        let (dst_payload, dst_header) = context.decode_with_jku(&jwt_string, &[&HS256])?;
+        assert_eq!(dst_header.key_id(), Some("a"));
+        assert_eq!(dst_payload, src_payload);
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_jwk_set_url("https://attacker.example/jwks.json");
+        let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+        assert!(matches!(
+            context.decode_with_jku(&jwt_string, &[&HS256]),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            context.decode_with_jku(&jwt_string, &[&HS256]),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_expected_type() -> Result<()> {
+        let key = HS256.generate_key();
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+
+        let mut context = JwtContext::new();
+        context.set_expected_type("at+jwt");
+        assert_eq!(context.expected_type(), Some("at+jwt"));
+
+        for typ in &["at+jwt", "AT+JWT", "application/at+jwt"] {
+            let mut src_header = JwsHeader::new();
+            src_header.set_token_type(*typ);
+            let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &src_header, &signer)?;
+
+            let (_, dst_header) = context.decode_with_verifier(&jwt_string, &verifier)?;
+            assert_eq!(dst_header.token_type(), Some(*typ));
+        }
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &src_header, &signer)?;
+        let err = context
+            .decode_with_verifier(&jwt_string, &verifier)
+            .expect_err("A mismatching typ was accepted");
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidType));
+
+        let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+        let err = context
+            .decode_with_verifier(&jwt_string, &verifier)
+            .expect_err("An absent typ was accepted");
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidType));
+
+        context.remove_expected_type();
+        context.decode_with_verifier(&jwt_string, &verifier)?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Eq, PartialEq, Deserialize)]
+    struct Claims {
+        sub: String,
+        #[serde(rename = "https://example.com/roles")]
+        roles: Vec<String>,
+        #[serde(rename = "tid")]
+        tenant_id: Option<u64>,
+    }
+
+    #[test]
+    fn test_jwt_decode_typed() -> Result<()> {
+        let key = HS256.generate_key();
+        let signer = HS256.signer_from_bytes(&key)?;
+        let verifier = HS256.verifier_from_bytes(&key)?;
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("user-1");
+        src_payload.set_claim("https://example.com/roles", Some(json!(["admin", "dev"])))?;
+        src_payload.set_expires_at(&(SystemTime::now() + Duration::from_secs(60)));
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+
+        let (claims, header): (Claims, JwsHeader) = jwt::decode_typed(&jwt_string, &verifier)?;
+        assert_eq!(header.algorithm(), Some("HS256"));
+        assert_eq!(
+            claims,
+            Claims {
+                sub: "user-1".to_string(),
+                roles: vec!["admin".to_string(), "dev".to_string()],
+                tenant_id: None,
+            }
+        );
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_subject("user-1");
+        let (claims, _) =
+            jwt::decode_typed_with_validator::<Claims>(&jwt_string, &verifier, &validator)?;
+        assert_eq!(claims.sub, "user-1");
+
+        validator.set_subject("user-2");
+        assert!(matches!(
+            jwt::decode_typed_with_validator::<Claims>(&jwt_string, &verifier, &validator),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        // The signature is verified before deserializing.
+        let other_verifier = HS256.verifier_from_bytes(&HS256.generate_key())?;
+        assert!(matches!(
+            jwt::decode_typed::<Claims>(&jwt_string, &other_verifier),
+            Err(JoseError::InvalidSignature(_))
+        ));
+
+        // A required field is missing.
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("user-1");
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(matches!(
+            jwt::decode_typed::<Claims>(&jwt_string, &verifier),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -5,6 +5,11 @@ use anyhow::bail;
 use crate::jws::{JwsAlgorithm, JwsSigner, JwsVerifier};
 use crate::JoseError;
 
+/// The "none" algorithm for unsecured JWTs (RFC 7519 Section 6).
+///
+/// Only the verifier of this algorithm accepts a JWT with the "none" alg header claim.
+/// Every key-based verifier requires the alg header claim to match its own algorithm, so
+/// a "none" JWT is rejected unless this verifier is selected explicitly.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum UnsecuredJwsAlgorithm {
     None,
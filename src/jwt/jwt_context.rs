@@ -1,15 +1,29 @@
-use anyhow::bail;
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
 
+use anyhow::{anyhow, bail};
+use serde::de::DeserializeOwned;
+
+use crate::jose_error::JoseErrorDetail;
 use crate::jwe::{JweContext, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::{Jwk, JwkSet};
-use crate::jws::{JwsContext, JwsHeader, JwsSigner, JwsVerifier};
-use crate::jwt::{self, JwtPayload};
-use crate::{JoseError, JoseHeader, Map, Value};
+use crate::jws::{
+    EdDSA, JwsAlgorithm, JwsContext, JwsHeader, JwsSigner, JwsVerifier, ES256, ES256K, ES384,
+    ES512, HS256, HS384, HS512, PS256, PS384, PS512, RS256, RS384, RS512,
+};
+#[cfg(feature = "sha3")]
+use crate::jws::{HS3_256, HS3_384, HS3_512};
+use crate::jwt::{self, JwtPayload, JwtPayloadValidator};
+use crate::util;
+use crate::{AlgorithmPolicy, JoseError, JoseErrorKind, JoseHeader, JsonType, Map, Value};
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JwtContext {
     jws_context: JwsContext,
     jwe_context: JweContext,
+    jku_resolver: Option<JkuResolver>,
+    expected_type: Option<String>,
 }
 
 impl JwtContext {
@@ -17,6 +31,8 @@ impl JwtContext {
         Self {
             jws_context: JwsContext::new(),
             jwe_context: JweContext::new(),
+            jku_resolver: None,
+            expected_type: None,
         }
     }
 
@@ -49,6 +65,155 @@ impl JwtContext {
         self.jwe_context.remove_acceptable_critical(name);
     }
 
+    /// Register a private header parameter with the expected type of its value.
+    ///
+    /// A registered header parameter is acceptable in the crit header claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    /// * `json_type` - the expected type of the value
+    pub fn register_header_parameter(&mut self, name: &str, json_type: JsonType) {
+        self.jws_context.register_header_parameter(name, json_type);
+        self.jwe_context.register_header_parameter(name, json_type);
+    }
+
+    /// Unregister a private header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn unregister_header_parameter(&mut self, name: &str) {
+        self.jws_context.unregister_header_parameter(name);
+        self.jwe_context.unregister_header_parameter(name);
+    }
+
+    /// Return the expected type of a registered header parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a header parameter name
+    pub fn header_parameter_type(&self, name: &str) -> Option<JsonType> {
+        self.jws_context.header_parameter_type(name)
+    }
+
+    /// Set whether the values of registered header parameters of a received JWT are
+    /// validated against their expected types. The default is false.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to validate
+    pub fn set_strict_header_parameters(&mut self, value: bool) {
+        self.jws_context.set_strict_header_parameters(value);
+        self.jwe_context.set_strict_header_parameters(value);
+    }
+
+    /// Test the values of registered header parameters are validated.
+    pub fn is_strict_header_parameters(&self) -> bool {
+        self.jws_context.is_strict_header_parameters()
+    }
+
+    /// Set whether base64url segments of a received JWT may contain `=` padding
+    /// or the `+` and `/` characters of standard base64. The default is strict.
+    ///
+    /// In lenient mode, ASCII whitespace in a signed JWT is also ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to decode leniently
+    pub fn set_lenient_base64(&mut self, value: bool) {
+        self.jws_context.set_lenient_base64(value);
+        self.jwe_context.set_lenient_base64(value);
+    }
+
+    /// Test base64url segments of a received JWT are decoded leniently.
+    pub fn is_lenient_base64(&self) -> bool {
+        self.jws_context.is_lenient_base64()
+    }
+
+    /// Set the maximum size in bytes of a received JWT.
+    ///
+    /// A larger input is rejected before any part of it is decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a maximum byte size
+    pub fn set_max_token_size(&mut self, value: usize) {
+        self.jws_context.set_max_token_size(value);
+        self.jwe_context.set_max_token_size(value);
+    }
+
+    /// Return the maximum size in bytes of a received JWT.
+    pub fn max_token_size(&self) -> usize {
+        self.jws_context.max_token_size()
+    }
+
+    /// Set a policy that restricts the algorithms used to sign, verify, encrypt
+    /// and decrypt a JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - a algorithm policy
+    pub fn set_algorithm_policy(&mut self, policy: AlgorithmPolicy) {
+        self.jws_context.set_algorithm_policy(policy.clone());
+        self.jwe_context.set_algorithm_policy(policy);
+    }
+
+    /// Return the policy that restricts the algorithms.
+    pub fn algorithm_policy(&self) -> Option<&AlgorithmPolicy> {
+        self.jws_context.algorithm_policy()
+    }
+
+    /// Remove the policy that restricts the algorithms.
+    pub fn remove_algorithm_policy(&mut self) {
+        self.jws_context.remove_algorithm_policy();
+        self.jwe_context.remove_algorithm_policy();
+    }
+
+    /// Set a function that returns the JWK set of a JWK set URL header claim (jku).
+    ///
+    /// This library doesn't fetch the URL itself. The resolver is responsible for
+    /// fetching it and must accept only trusted URLs, or a token can make the
+    /// application send requests to any URL and trust any key.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - a function that returns the JWK set of a URL
+    pub fn set_jku_resolver<F>(&mut self, resolver: F)
+    where
+        F: Fn(&str) -> Result<JwkSet, JoseError> + Send + Sync + 'static,
+    {
+        self.jku_resolver = Some(JkuResolver(Arc::new(resolver)));
+    }
+
+    /// Remove the function that returns the JWK set of a JWK set URL header claim (jku).
+    pub fn remove_jku_resolver(&mut self) {
+        self.jku_resolver = None;
+    }
+
+    /// Set the type that the typ header claim of a decoded JWT must indicate,
+    /// e.g. "JWT" or "at+jwt" (RFC 8725 Section 3.11).
+    ///
+    /// The media types are compared case-insensitively, and a value without "/" is treated as
+    /// if "application/" were prepended. A JWT without the typ header claim is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - an expected typ header claim value
+    pub fn set_expected_type(&mut self, value: &str) {
+        self.expected_type = Some(value.to_string());
+    }
+
+    /// Return the type that the typ header claim of a decoded JWT must indicate.
+    pub fn expected_type(&self) -> Option<&str> {
+        self.expected_type.as_deref()
+    }
+
+    /// Remove the type that the typ header claim of a decoded JWT must indicate.
+    pub fn remove_expected_type(&mut self) {
+        self.expected_type = None;
+    }
+
     /// Return the string repsentation of the JWT with a "none" algorithm.
     ///
     /// # Arguments
@@ -115,6 +280,36 @@ impl JwtContext {
         Ok(jwt)
     }
 
+    /// Return the string repsentation of the nested JWT that is signed with the
+    /// siginig algorithm and then encrypted with the encrypting algorithm.
+    /// The cty header claim of the JWE header is set to "JWT".
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `jws_header` - The JWS heaser claims of the inner JWT.
+    /// * `signer` - a signer object.
+    /// * `jwe_header` - The JWE heaser claims of the outer JWT.
+    /// * `encrypter` - a encrypter object.
+    pub fn encode_with_signer_and_encrypter(
+        &self,
+        payload: &JwtPayload,
+        jws_header: &JwsHeader,
+        signer: &dyn JwsSigner,
+        jwe_header: &JweHeader,
+        encrypter: &dyn JweEncrypter,
+    ) -> Result<String, JoseError> {
+        let inner_jwt = self.encode_with_signer(payload, jws_header, signer)?;
+
+        let mut jwe_header = jwe_header.clone();
+        jwe_header.set_content_type("JWT");
+
+        let jwt =
+            self.jwe_context
+                .serialize_compact(inner_jwt.as_bytes(), &jwe_header, encrypter)?;
+        Ok(jwt)
+    }
+
     /// Return the Jose header decoded from JWT.
     ///
     /// # Arguments
@@ -123,16 +318,26 @@ impl JwtContext {
     pub fn decode_header(&self, input: impl AsRef<[u8]>) -> Result<Box<dyn JoseHeader>, JoseError> {
         (|| -> anyhow::Result<Box<dyn JoseHeader>> {
             let input = input.as_ref();
+            if input.len() > self.max_token_size() {
+                bail!(
+                    "The JWT size {} exceeds the maximum token size {}.",
+                    input.len(),
+                    self.max_token_size()
+                );
+            }
+
             let parts: Vec<&[u8]> = input.split(|b| *b == '.' as u8).collect();
             if parts.len() == 3 {
                 // JWS
-                let header = base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD)?;
+                let header =
+                    util::decode_base64_url(parts[0], self.jws_context.is_lenient_base64())?;
                 let header: Map<String, Value> = serde_json::from_slice(&header)?;
                 let header = JwsHeader::from_map(header)?;
                 Ok(Box::new(header))
             } else if parts.len() == 5 {
                 // JWE
-                let header = base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD)?;
+                let header =
+                    util::decode_base64_url(parts[0], self.jwe_context.is_lenient_base64())?;
                 let header: Map<String, Value> = serde_json::from_slice(&header)?;
                 let header = JweHeader::from_map(header)?;
                 Ok(Box::new(header))
@@ -210,6 +415,8 @@ impl JwtContext {
                         })
                     })?;
 
+            self.check_token_type(header.token_type())?;
+
             let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
@@ -221,6 +428,52 @@ impl JwtContext {
         })
     }
 
+    /// Return the payload of the JWT decoded by the verifier as a user defined type.
+    ///
+    /// The payload claims are not validated; use `decode_typed_with_validator`
+    /// to validate them before deserializing.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    pub fn decode_typed<T: DeserializeOwned>(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier: &dyn JwsVerifier,
+    ) -> Result<(T, JwsHeader), JoseError> {
+        let (payload, header) = self.decode_with_verifier(input, verifier)?;
+        let payload = Self::deserialize_payload(payload)?;
+        Ok((payload, header))
+    }
+
+    /// Return the payload of the JWT decoded by the verifier as a user defined type,
+    /// after validating the payload claims by the validator.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    /// * `validator` - a validator of the payload claims.
+    pub fn decode_typed_with_validator<T: DeserializeOwned>(
+        &self,
+        input: impl AsRef<[u8]>,
+        verifier: &dyn JwsVerifier,
+        validator: &JwtPayloadValidator,
+    ) -> Result<(T, JwsHeader), JoseError> {
+        let (payload, header) = self.decode_with_verifier(input, verifier)?;
+        validator.validate(&payload)?;
+        let payload = Self::deserialize_payload(payload)?;
+        Ok((payload, header))
+    }
+
+    fn deserialize_payload<T: DeserializeOwned>(payload: JwtPayload) -> Result<T, JoseError> {
+        let map: Map<String, Value> = payload.into();
+        serde_json::from_value(Value::Object(map)).map_err(|err| {
+            JoseError::InvalidClaim(anyhow!("The JWT payload cannot be deserialized: {}", err))
+        })
+    }
+
     /// Return the JWT object decoded by using a JWK set.
     ///
     /// # Arguments
@@ -252,6 +505,163 @@ impl JwtContext {
         })
     }
 
+    /// Return the JWT object verified by a key in a JWK set.
+    ///
+    /// The alg header claim must be one of the allowed algorithms. If the kid header
+    /// claim is present, the keys with the same kid are tried. Otherwise every key
+    /// suitable for verifying the algorithm is tried. The payload claims are not
+    /// validated; use `JwtPayloadValidator` for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `jwk_set` - a JWK set.
+    /// * `algorithms` - the allowed signing algorithms.
+    pub fn decode_with_jwk_set(
+        &self,
+        input: impl AsRef<[u8]>,
+        jwk_set: &JwkSet,
+        algorithms: &[&dyn JwsAlgorithm],
+    ) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        let input = input.as_ref();
+        let header = self.decode_jws_header(input)?;
+
+        let algorithm = match header.algorithm() {
+            Some(name) => match algorithms.iter().find(|val| val.name() == name) {
+                Some(val) => *val,
+                None => {
+                    return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                        "The JWT alg header claim is not allowed: {}",
+                        name
+                    )))
+                }
+            },
+            None => {
+                return Err(JoseError::InvalidJwtFormat(anyhow!(
+                    "The JWT alg header claim is required."
+                )))
+            }
+        };
+
+        let jwks = jwk_set.select_for_verification(algorithm, header.key_id());
+        if jwks.is_empty() {
+            return Err(JoseError::InvalidJwtFormat(anyhow!(
+                "A key for {} is not found in the JWK set.",
+                algorithm.name()
+            )));
+        }
+
+        // Without a kid header claim, the kid of a key must not be required by the verifier.
+        let mut last_err = None;
+        for jwk in jwks {
+            let result = match header.key_id() {
+                Some(_) => verifier_from_jwk(algorithm, jwk),
+                None => {
+                    let mut jwk = jwk.clone();
+                    jwk.set_parameter("kid", None)?;
+                    verifier_from_jwk(algorithm, &jwk)
+                }
+            };
+
+            let result = match result {
+                Ok(verifier) => self.decode_with_verifier(input, &*verifier),
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(val) => return Ok(val),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap())
+    }
+
+    /// Return the JWT object verified by a key in the JWK set of the JWK set URL
+    /// header claim (jku).
+    ///
+    /// The JWK set is returned by the resolver set by `set_jku_resolver`, and the key is
+    /// selected in the same way as `decode_with_jwk_set`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `algorithms` - the allowed signing algorithms.
+    pub fn decode_with_jku(
+        &self,
+        input: impl AsRef<[u8]>,
+        algorithms: &[&dyn JwsAlgorithm],
+    ) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        let input = input.as_ref();
+        let header = self.decode_jws_header(input)?;
+
+        let jku = match header.jwk_set_url() {
+            Some(val) => val,
+            None => {
+                return Err(JoseError::InvalidJwtFormat(anyhow!(
+                    "The JWT jku header claim is required."
+                )))
+            }
+        };
+
+        let resolver = match &self.jku_resolver {
+            Some(val) => val,
+            None => {
+                return Err(JoseError::InvalidJwtFormat(anyhow!(
+                    "A jku resolver is not set to resolve the JWT jku header claim: {}",
+                    jku
+                )))
+            }
+        };
+
+        let jwk_set = (resolver.0)(jku)?;
+        self.decode_with_jwk_set(input, &jwk_set, algorithms)
+    }
+
+    /// Return the nested JWT object that is decrypted by the decrypter and then
+    /// verified by the verifier. The cty header claim of the JWE header must be "JWT".
+    ///
+    /// The JWE is fully authenticated before the inner JWT is parsed, so
+    /// unauthenticated plaintext never reaches the JWS verifier. If the cty header
+    /// claim is not "JWT", the error kind is `JoseErrorKind::InvalidContentType`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `decrypter` - a decrypter of the decrypting algorithm.
+    /// * `verifier` - a verifier of the signing algorithm.
+    pub fn decode_with_decrypter_and_verifier(
+        &self,
+        input: impl AsRef<[u8]>,
+        decrypter: &dyn JweDecrypter,
+        verifier: &dyn JwsVerifier,
+    ) -> Result<(JwtPayload, JweHeader, JwsHeader), JoseError> {
+        (|| -> anyhow::Result<(JwtPayload, JweHeader, JwsHeader)> {
+            // The JWE context checks the authentication tag before returning the plaintext.
+            let (inner_jwt, jwe_header) = self.jwe_context.deserialize_compact(input, decrypter)?;
+
+            match jwe_header.content_type() {
+                Some(val) if val.eq_ignore_ascii_case("JWT") => {}
+                Some(val) => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidContentType,
+                    format!("The JWE cty header claim must be JWT: {}", val)
+                )),
+                None => bail!(JoseErrorDetail::new(
+                    JoseErrorKind::InvalidContentType,
+                    "The JWE cty header claim is required."
+                )),
+            }
+
+            let (payload, jws_header) = self.decode_with_verifier(&inner_jwt, verifier)?;
+
+            Ok((payload, jwe_header, jws_header))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
     /// Return the JWT object decoded by the selected decrypter.
     ///
     /// # Arguments
@@ -292,6 +702,8 @@ impl JwtContext {
                         Ok(Some(decrypter))
                     })?;
 
+            self.check_token_type(header.token_type())?;
+
             let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
@@ -333,4 +745,109 @@ impl JwtContext {
             Ok(None)
         })
     }
+
+    fn check_token_type(&self, token_type: Option<&str>) -> anyhow::Result<()> {
+        let expected_type = match &self.expected_type {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+
+        let expected = util::compact_media_type(expected_type);
+        match token_type {
+            Some(val) if util::compact_media_type(val).eq_ignore_ascii_case(expected) => Ok(()),
+            Some(val) => bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidType,
+                format!(
+                    "The JWT typ header claim must be {}: {}",
+                    expected_type, val
+                )
+            )),
+            None => bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidType,
+                format!("The JWT typ header claim is required: {}", expected_type)
+            )),
+        }
+    }
+
+    fn decode_jws_header(&self, input: &[u8]) -> Result<JwsHeader, JoseError> {
+        self.jws_context
+            .check_token_size(input)
+            .map_err(|err| JoseError::InvalidJwsFormat(err))?;
+
+        (|| -> anyhow::Result<JwsHeader> {
+            let lenient = self.jws_context.is_lenient_base64();
+            let input = if lenient {
+                util::strip_ascii_whitespace(input)
+            } else {
+                Cow::Borrowed(input)
+            };
+
+            let parts: Vec<&[u8]> = input.split(|b| *b == '.' as u8).collect();
+            if parts.len() != 3 {
+                bail!("The input cannot be recognized as a signed JWT.");
+            }
+
+            let header = util::decode_base64_url(parts[0], lenient)?;
+            let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = JwsHeader::from_map(header)?;
+            Ok(header)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+}
+
+/// A function that returns the JWK set of a JWK set URL.
+#[derive(Clone)]
+struct JkuResolver(Arc<dyn Fn(&str) -> Result<JwkSet, JoseError> + Send + Sync>);
+
+impl Debug for JkuResolver {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("JkuResolver")
+    }
+}
+
+impl PartialEq for JkuResolver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
+
+impl Eq for JkuResolver {}
+
+fn verifier_from_jwk(
+    algorithm: &dyn JwsAlgorithm,
+    jwk: &Jwk,
+) -> Result<Box<dyn JwsVerifier>, JoseError> {
+    let verifier: Box<dyn JwsVerifier> = match algorithm.name() {
+        "HS256" => Box::new(HS256.verifier_from_jwk(jwk)?),
+        "HS384" => Box::new(HS384.verifier_from_jwk(jwk)?),
+        "HS512" => Box::new(HS512.verifier_from_jwk(jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-256" => Box::new(HS3_256.verifier_from_jwk(jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-384" => Box::new(HS3_384.verifier_from_jwk(jwk)?),
+        #[cfg(feature = "sha3")]
+        "josekit:HS3-512" => Box::new(HS3_512.verifier_from_jwk(jwk)?),
+        "RS256" => Box::new(RS256.verifier_from_jwk(jwk)?),
+        "RS384" => Box::new(RS384.verifier_from_jwk(jwk)?),
+        "RS512" => Box::new(RS512.verifier_from_jwk(jwk)?),
+        "PS256" => Box::new(PS256.verifier_from_jwk(jwk)?),
+        "PS384" => Box::new(PS384.verifier_from_jwk(jwk)?),
+        "PS512" => Box::new(PS512.verifier_from_jwk(jwk)?),
+        "ES256" => Box::new(ES256.verifier_from_jwk(jwk)?),
+        "ES256K" => Box::new(ES256K.verifier_from_jwk(jwk)?),
+        "ES384" => Box::new(ES384.verifier_from_jwk(jwk)?),
+        "ES512" => Box::new(ES512.verifier_from_jwk(jwk)?),
+        "EdDSA" => Box::new(EdDSA.verifier_from_jwk(jwk)?),
+        val => {
+            return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+                "The algorithm cannot be used with a JWK set: {}",
+                val
+            )))
+        }
+    };
+    Ok(verifier)
 }
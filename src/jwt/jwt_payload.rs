@@ -2,8 +2,15 @@ use std::convert::Into;
 use std::fmt::Display;
 use std::time::{Duration, SystemTime};
 
+use crate::jws::{JwsHeader, JwsSigner};
+use crate::jwt;
 use crate::{JoseError, Map, Number, Value};
-use anyhow::bail;
+use anyhow::{anyhow, bail};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 9999-12-31T23:59:59Z
+const MAX_NUMERIC_DATE: u64 = 253402300799;
 
 #[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub struct JwtPayload {
@@ -30,6 +37,26 @@ impl JwtPayload {
         Ok(Self { claims: map })
     }
 
+    /// Return the JWT payload from a value that is serialized into a JSON object.
+    ///
+    /// Registered claims such as iss and exp are checked as in `from_map`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a value that serializes to JWT payload claims.
+    pub fn from_serialize<T: Serialize>(value: &T) -> Result<Self, JoseError> {
+        let claims = (|| -> anyhow::Result<Map<String, Value>> {
+            match serde_json::to_value(value)? {
+                Value::Object(val) => Ok(val),
+                _ => bail!("The JWT payload must be a JSON object."),
+            }
+        })()
+        .map_err(|err| JoseError::InvalidJson(err))?;
+
+        let payload = Self::from_map(claims)?;
+        Ok(payload)
+    }
+
     /// Set a value for issuer payload claim (iss).
     ///
     /// # Arguments
@@ -68,26 +95,28 @@ impl JwtPayload {
 
     /// Set values for audience payload claim (aud).
     ///
+    /// A single audience is set as a string and several audiences as an array.
+    /// An empty list removes the claim.
+    ///
     /// # Arguments
     ///
     /// * `values` - a list of audiences
     pub fn set_audience(&mut self, values: Vec<impl Into<String>>) {
         let key = "aud".to_string();
-        if values.len() == 1 {
-            for val in values {
-                let val: String = val.into();
-                self.claims.insert(key, Value::String(val));
-                break;
+        let mut values: Vec<Value> = values
+            .into_iter()
+            .map(|val| Value::String(val.into()))
+            .collect();
+        match values.len() {
+            0 => {
+                self.claims.remove(&key);
             }
-        } else if values.len() > 1 {
-            let mut vec1 = Vec::with_capacity(values.len());
-            let mut vec2 = Vec::with_capacity(values.len());
-            for val in values {
-                let val: String = val.into();
-                vec1.push(Value::String(val.clone()));
-                vec2.push(val);
+            1 => {
+                self.claims.insert(key, values.remove(0));
+            }
+            _ => {
+                self.claims.insert(key, Value::Array(values));
             }
-            self.claims.insert(key.clone(), Value::Array(vec1));
         }
     }
 
@@ -117,25 +146,26 @@ impl JwtPayload {
     ///
     /// * `value` - A expiration time on or after which the JWT must not be accepted for processing.
     pub fn set_expires_at(&mut self, value: &SystemTime) {
-        let key = "exp".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        self.set_numeric_date("exp", value);
     }
 
     /// Return the system time for expires at payload claim (exp).
     pub fn expires_at(&self) -> Option<SystemTime> {
-        match self.claims.get("exp") {
-            Some(Value::Number(val)) => match val.as_u64() {
-                Some(val) => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(val)),
-                None => None,
-            },
-            _ => None,
-        }
+        self.numeric_date("exp")
+    }
+
+    /// Return the duration from the specified time until the expires at payload claim (exp),
+    /// or None if the claim is not present. An already expired JWT returns a zero duration.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - A time from which the duration is measured.
+    pub fn time_until_expiry(&self, now: &SystemTime) -> Option<Duration> {
+        self.expires_at().map(|expires_at| {
+            expires_at
+                .duration_since(*now)
+                .unwrap_or_else(|_| Duration::from_secs(0))
+        })
     }
 
     /// Set a system time for not before payload claim (nbf).
@@ -144,25 +174,12 @@ impl JwtPayload {
     ///
     /// * `value` - A time before which the JWT must not be accepted for processing.
     pub fn set_not_before(&mut self, value: &SystemTime) {
-        let key = "nbf".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        self.set_numeric_date("nbf", value);
     }
 
     /// Return the system time for not before payload claim (nbf).
     pub fn not_before(&self) -> Option<SystemTime> {
-        match self.claims.get("nbf") {
-            Some(Value::Number(val)) => match val.as_u64() {
-                Some(val) => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(val)),
-                None => None,
-            },
-            _ => None,
-        }
+        self.numeric_date("nbf")
     }
 
     /// Set a time for issued at payload claim (iat).
@@ -171,25 +188,12 @@ impl JwtPayload {
     ///
     /// * `value` - a time at which the JWT was issued.
     pub fn set_issued_at(&mut self, value: &SystemTime) {
-        let key = "iat".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        self.set_numeric_date("iat", value);
     }
 
     /// Return the time for a issued at payload claim (iat).
     pub fn issued_at(&self) -> Option<SystemTime> {
-        match self.claims.get("iat") {
-            Some(Value::Number(val)) => match val.as_u64() {
-                Some(val) => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(val)),
-                None => None,
-            },
-            _ => None,
-        }
+        self.numeric_date("iat")
     }
 
     /// Set a value for JWT ID payload claim (jti).
@@ -242,11 +246,108 @@ impl JwtPayload {
         self.claims.get(key)
     }
 
+    /// Return a value for payload claim of a specified key that is deserialized into a type.
+    ///
+    /// Return `Ok(None)` if the claim is absent, or `JoseError::InvalidClaim` if the claim
+    /// cannot be deserialized into the type.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of payload claim
+    pub fn claim_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, JoseError> {
+        match self.claims.get(key) {
+            Some(val) => match serde_json::from_value(val.clone()) {
+                Ok(val) => Ok(Some(val)),
+                Err(err) => Err(JoseError::InvalidClaim(anyhow!(
+                    "The JWT {} payload claim cannot be deserialized: {}",
+                    key,
+                    err
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
     /// Return values for payload claims set
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
     }
 
+    /// Return an iterator over every payload claim, including the registered ones,
+    /// in insertion order.
+    pub fn claims(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.claims.iter()
+    }
+
+    /// Return the names of every payload claim in insertion order.
+    pub fn claim_names(&self) -> impl Iterator<Item = &str> {
+        self.claims.keys().map(|key| key.as_str())
+    }
+
+    /// Return a new JWT of the claims of this payload that is signed with the signer.
+    ///
+    /// The issued at payload claim (iat) is set to the current time and the other claims
+    /// are kept as they are. The JWS header is a new one whose typ is "JWT", so neither
+    /// the header nor the signature of the JWT from which this payload was decoded is
+    /// carried over. To change claims, modify the payload (or a clone of it) beforehand.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - a signer object.
+    pub fn reissue(&self, signer: &dyn JwsSigner) -> Result<String, JoseError> {
+        let mut payload = self.clone();
+        payload.set_issued_at(&SystemTime::now());
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        jwt::encode_with_signer(&payload, &header, signer)
+    }
+
+    /// Set a time as a NumericDate, the number of seconds from 1970-01-01T00:00:00Z UTC.
+    ///
+    /// Fractions of a second are truncated, and a time out of the range of
+    /// `parse_numeric_date` is set as the nearest end of the range.
+    fn set_numeric_date(&mut self, key: &str, value: &SystemTime) {
+        let secs = match value.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(val) => val.as_secs().min(MAX_NUMERIC_DATE),
+            Err(_) => 0,
+        };
+        self.claims
+            .insert(key.to_string(), Value::Number(Number::from(secs)));
+    }
+
+    fn numeric_date(&self, key: &str) -> Option<SystemTime> {
+        match self.claims.get(key) {
+            Some(val) => Self::parse_numeric_date(key, val).ok(),
+            None => None,
+        }
+    }
+
+    /// Parse a NumericDate payload claim (exp, nbf, iat).
+    ///
+    /// RFC 7519 permits a fractional NumericDate, but this library only accepts
+    /// an integer of seconds from 1970-01-01T00:00:00Z to 9999-12-31T23:59:59Z.
+    /// Fractions, strings, negative and out of range values are rejected.
+    pub(crate) fn parse_numeric_date(key: &str, value: &Value) -> anyhow::Result<SystemTime> {
+        let secs = match value {
+            Value::Number(val) => match val.as_u64() {
+                Some(val) if val <= MAX_NUMERIC_DATE => val,
+                Some(_) => bail!("The JWT {} payload claim is out of range: {}", key, val),
+                None if val.is_f64() => {
+                    bail!("The JWT {} payload claim must be an integer: {}", key, val)
+                }
+                None => bail!(
+                    "The JWT {} payload claim must not be negative: {}",
+                    key,
+                    val
+                ),
+            },
+            _ => bail!("The JWT {} payload claim must be a number.", key),
+        };
+
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
     fn check_claim(key: &str, value: &Value) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             match key {
@@ -269,16 +370,9 @@ impl JwtPayload {
                     }
                     _ => bail!("The JWT {} payload claim must be a string or array.", key),
                 },
-                "exp" | "nbf" | "iat" => match &value {
-                    Value::Number(val) => match val.as_u64() {
-                        Some(_) => {}
-                        None => bail!(
-                            "The JWT {} payload claim must be a positive integer within 64bit.",
-                            key
-                        ),
-                    },
-                    _ => bail!("The JWT {} header claim must be a string.", key),
-                },
+                "exp" | "nbf" | "iat" => {
+                    Self::parse_numeric_date(key, value)?;
+                }
                 _ => {}
             }
 
@@ -309,12 +403,14 @@ impl Display for JwtPayload {
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime};
 
     use anyhow::Result;
+    use serde::{Deserialize, Serialize};
     use serde_json::json;
 
     use super::JwtPayload;
+    use crate::{JoseError, Map, Value};
 
     #[test]
     fn test_new_payload() -> Result<()> {
@@ -343,4 +439,200 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_numeric_date_round_trip() -> Result<()> {
+        let exp = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+
+        let mut payload = JwtPayload::new();
+        payload.set_expires_at(&exp);
+        payload.set_audience(vec!["aud0"]);
+
+        let json = payload.to_string();
+        assert_eq!(json, r#"{"exp":1700000000,"aud":"aud0"}"#);
+
+        let map: Map<String, Value> = serde_json::from_str(&json)?;
+        let payload = JwtPayload::from_map(map)?;
+        assert_eq!(
+            payload.expires_at(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(payload.audience(), Some(vec!["aud0"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_until_expiry() -> Result<()> {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut payload = JwtPayload::new();
+        assert_eq!(payload.time_until_expiry(&now), None);
+
+        payload.set_expires_at(&(now + Duration::from_secs(300)));
+        assert_eq!(
+            payload.time_until_expiry(&now),
+            Some(Duration::from_secs(300))
+        );
+
+        payload.set_expires_at(&now);
+        assert_eq!(
+            payload.time_until_expiry(&now),
+            Some(Duration::from_secs(0))
+        );
+
+        payload.set_expires_at(&(now - Duration::from_secs(300)));
+        assert_eq!(
+            payload.time_until_expiry(&now),
+            Some(Duration::from_secs(0))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_audience() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["aud0", "aud1"]);
+        assert_eq!(payload.claim("aud"), Some(&json!(["aud0", "aud1"])));
+
+        payload.set_audience(Vec::<String>::new());
+        assert_eq!(payload.claim("aud"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_claims() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("iss");
+        payload.set_expires_at(&SystemTime::UNIX_EPOCH);
+        payload.set_claim("payload_claim", Some(json!("payload_claim")))?;
+        payload.set_subject("sub");
+
+        let claims: Vec<(&String, &Value)> = payload.claims().collect();
+        assert_eq!(
+            claims,
+            vec![
+                (&"iss".to_string(), &json!("iss")),
+                (&"exp".to_string(), &json!(0)),
+                (&"payload_claim".to_string(), &json!("payload_claim")),
+                (&"sub".to_string(), &json!("sub")),
+            ]
+        );
+
+        let names: Vec<&str> = payload.claim_names().collect();
+        assert_eq!(names, vec!["iss", "exp", "payload_claim", "sub"]);
+
+        let mut copy = JwtPayload::new();
+        for (key, value) in payload.claims() {
+            copy.set_claim(key, Some(value.clone()))?;
+        }
+        assert_eq!(copy, payload);
+
+        Ok(())
+    }
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Claims {
+        iss: String,
+        exp: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sub: Option<String>,
+        profile: Profile,
+    }
+
+    #[test]
+    fn test_from_serialize() -> Result<()> {
+        let claims = Claims {
+            iss: "iss".to_string(),
+            exp: 1_700_000_000,
+            sub: None,
+            profile: Profile {
+                name: "Alice".to_string(),
+                roles: vec!["admin".to_string()],
+                address: Address {
+                    country: "JP".to_string(),
+                    postal_code: "100-0001".to_string(),
+                },
+            },
+        };
+
+        let payload = JwtPayload::from_serialize(&claims)?;
+        assert_eq!(payload.issuer(), Some("iss"));
+        assert_eq!(
+            payload.expires_at(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(payload.subject(), None);
+        assert_eq!(
+            payload.claim_as::<Profile>("profile")?,
+            Some(claims.profile)
+        );
+
+        assert!(matches!(
+            JwtPayload::from_serialize(&vec!["iss"]),
+            Err(JoseError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            JwtPayload::from_serialize(&json!({ "exp": "tomorrow" })),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        country: String,
+        postal_code: String,
+    }
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    struct Profile {
+        name: String,
+        roles: Vec<String>,
+        address: Address,
+    }
+
+    #[test]
+    fn test_claim_as() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_claim(
+            "profile",
+            Some(json!({
+                "name": "Alice",
+                "roles": ["admin", "user"],
+                "address": { "country": "JP", "postal_code": "100-0001" }
+            })),
+        )?;
+        payload.set_claim("roles", Some(json!("admin")))?;
+
+        let profile: Option<Profile> = payload.claim_as("profile")?;
+        assert_eq!(
+            profile,
+            Some(Profile {
+                name: "Alice".to_string(),
+                roles: vec!["admin".to_string(), "user".to_string()],
+                address: Address {
+                    country: "JP".to_string(),
+                    postal_code: "100-0001".to_string(),
+                },
+            })
+        );
+
+        let missing: Option<Profile> = payload.claim_as("missing")?;
+        assert_eq!(missing, None);
+
+        assert!(matches!(
+            payload.claim_as::<Vec<String>>("roles"),
+            Err(JoseError::InvalidClaim(_))
+        ));
+        assert!(matches!(
+            payload.claim_as::<Profile>("roles"),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use std::convert::Into;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use anyhow::bail;
 
@@ -12,7 +12,10 @@ pub struct JwtPayloadValidator {
     base_time: Option<SystemTime>,
     min_issued_time: Option<SystemTime>,
     max_issued_time: Option<SystemTime>,
-    audience: Option<String>,
+    leeway: Duration,
+    issuers: Vec<String>,
+    issuer_trailing_slash_normalized: bool,
+    audiences: Vec<String>,
     claims: Map<String, Value>,
 }
 
@@ -23,7 +26,10 @@ impl JwtPayloadValidator {
             base_time: None,
             min_issued_time: None,
             max_issued_time: None,
-            audience: None,
+            leeway: Duration::from_secs(0),
+            issuers: Vec::new(),
+            issuer_trailing_slash_normalized: false,
+            audiences: Vec::new(),
             claims: Map::new(),
         }
     }
@@ -70,6 +76,25 @@ impl JwtPayloadValidator {
         self.max_issued_time.as_ref()
     }
 
+    /// Set a leeway for time related claims (exp, nbf, iat) validation to allow
+    /// a clock skew between systems. The default value is zero.
+    ///
+    /// A token is valid until the expiration time plus the leeway, it is valid
+    /// from the not before time minus the leeway, and the issued time can be
+    /// after the maximum issued time up to the leeway.
+    ///
+    /// # Arguments
+    ///
+    /// * `leeway` - a leeway
+    pub fn set_leeway(&mut self, leeway: Duration) {
+        self.leeway = leeway;
+    }
+
+    /// Return the leeway for time related claims (exp, nbf, iat) validation.
+    pub fn leeway(&self) -> Duration {
+        self.leeway
+    }
+
     /// Set a value for issuer payload claim (iss) validation.
     ///
     /// # Arguments
@@ -77,15 +102,44 @@ impl JwtPayloadValidator {
     /// * `value` - a issuer
     pub fn set_issuer(&mut self, value: impl Into<String>) {
         let value: String = value.into();
-        self.claims.insert("iss".to_string(), Value::String(value));
+        self.set_issuers(&[value.as_str()]);
     }
 
     /// Return the value for issuer payload claim (iss) validation.
+    /// If multiple issuers are acceptable, the first one is returned.
     pub fn issuer(&self) -> Option<&str> {
-        match self.claims.get("iss") {
-            Some(Value::String(val)) => Some(val),
-            _ => None,
-        }
+        self.issuers.first().map(|val| val.as_str())
+    }
+
+    /// Set acceptable values for issuer payload claim (iss) validation.
+    /// The validation passes if the iss claim equals any of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - acceptable issuers
+    pub fn set_issuers(&mut self, values: &[&str]) {
+        self.issuers = values.iter().map(|val| val.to_string()).collect();
+    }
+
+    /// Return the acceptable values for issuer payload claim (iss) validation.
+    pub fn issuers(&self) -> Vec<&str> {
+        self.issuers.iter().map(|val| val.as_str()).collect()
+    }
+
+    /// Set whether issuer payload claim (iss) validation ignores a single trailing
+    /// slash, so that `https://example.com/` matches `https://example.com`.
+    /// The default is false.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to ignore a trailing slash
+    pub fn normalize_issuer_trailing_slash(&mut self, value: bool) {
+        self.issuer_trailing_slash_normalized = value;
+    }
+
+    /// Test issuer payload claim (iss) validation ignores a single trailing slash.
+    pub fn is_issuer_trailing_slash_normalized(&self) -> bool {
+        self.issuer_trailing_slash_normalized
     }
 
     /// Set a value for subject payload claim (sub) validation.
@@ -113,15 +167,28 @@ impl JwtPayloadValidator {
     /// * `value` - a audience
     pub fn set_audience(&mut self, value: impl Into<String>) {
         let value: String = value.into();
-        self.audience = Some(value);
+        self.set_audiences(&[value.as_str()]);
     }
 
     /// Return the value for audience payload claim (aud) validation.
+    /// If multiple audiences are acceptable, the first one is returned.
     pub fn audience(&self) -> Option<&str> {
-        match self.audience {
-            Some(ref val) => Some(val),
-            _ => None,
-        }
+        self.audiences.first().map(|val| val.as_str())
+    }
+
+    /// Set acceptable values for audience payload claim (aud) validation.
+    /// The validation passes if the aud claim contains any of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - acceptable audiences
+    pub fn set_audiences(&mut self, values: &[&str]) {
+        self.audiences = values.iter().map(|val| val.to_string()).collect();
+    }
+
+    /// Return the acceptable values for audience payload claim (aud) validation.
+    pub fn audiences(&self) -> Vec<&str> {
+        self.audiences.iter().map(|val| val.as_str()).collect()
     }
 
     /// Set a value for JWT ID payload claim (jti) validation.
@@ -163,6 +230,10 @@ impl JwtPayloadValidator {
 
     /// Validate a decoded JWT payload.
     ///
+    /// The time related claims (exp, nbf, iat) must be integers of seconds up to the year 9999.
+    /// A fractional, non-numeric or out of range value is rejected with
+    /// `JoseError::InvalidJwtFormat`.
+    ///
     /// # Arguments
     ///
     /// * `payload` - a decoded JWT payload.
@@ -173,25 +244,39 @@ impl JwtPayloadValidator {
             let min_issued_time = self.min_issued_time().unwrap_or(&SystemTime::UNIX_EPOCH);
             let max_issued_time = self.max_issued_time().unwrap_or(&now);
 
-            if let Some(not_before) = payload.not_before() {
-                if &not_before > current_time {
-                    bail!(
-                        "The token is not yet valid: {}",
-                        time::OffsetDateTime::from(not_before),
-                    );
+            let numeric_date = |key: &str| -> Result<Option<SystemTime>, JoseError> {
+                match payload.claim(key) {
+                    Some(val) => match JwtPayload::parse_numeric_date(key, val) {
+                        Ok(val) => Ok(Some(val)),
+                        Err(err) => Err(JoseError::InvalidJwtFormat(err)),
+                    },
+                    None => Ok(None),
+                }
+            };
+
+            if let Some(not_before) = numeric_date("nbf")? {
+                if let Some(limit) = current_time.checked_add(self.leeway) {
+                    if not_before > limit {
+                        bail!(
+                            "The token is not yet valid: {}",
+                            time::OffsetDateTime::from(not_before),
+                        );
+                    }
                 }
             }
 
-            if let Some(expires_at) = payload.expires_at() {
-                if &expires_at <= current_time {
-                    bail!(
-                        "The token has expired: {}",
-                        time::OffsetDateTime::from(expires_at),
-                    );
+            if let Some(expires_at) = numeric_date("exp")? {
+                if let Some(limit) = expires_at.checked_add(self.leeway) {
+                    if limit <= *current_time {
+                        bail!(
+                            "The token has expired: {}",
+                            time::OffsetDateTime::from(expires_at),
+                        );
+                    }
                 }
             }
 
-            if let Some(issued_at) = payload.issued_at() {
+            if let Some(issued_at) = numeric_date("iat")? {
                 if &issued_at < min_issued_time {
                     bail!(
                         "The issued time is too old: {}",
@@ -199,17 +284,42 @@ impl JwtPayloadValidator {
                     );
                 }
 
-                if &issued_at > max_issued_time {
-                    bail!(
-                        "The issued time is too new: {}",
-                        time::OffsetDateTime::from(issued_at),
-                    );
+                if let Some(limit) = max_issued_time.checked_add(self.leeway) {
+                    if issued_at > limit {
+                        bail!(
+                            "The issued time is too new: {}",
+                            time::OffsetDateTime::from(issued_at),
+                        );
+                    }
+                }
+            }
+
+            if !self.issuers.is_empty() {
+                let issuer = match payload.issuer() {
+                    Some(val) => val,
+                    None => bail!("Key iss is missing."),
+                };
+
+                let normalize = |val: &str| -> String {
+                    if self.issuer_trailing_slash_normalized {
+                        val.strip_suffix('/').unwrap_or(val).to_string()
+                    } else {
+                        val.to_string()
+                    }
+                };
+                let actual = normalize(issuer);
+                if !self.issuers.iter().any(|val| normalize(val) == actual) {
+                    bail!("Key iss is invalid: {}", issuer);
                 }
             }
 
-            if let Some(audience) = &self.audience {
+            if !self.audiences.is_empty() {
                 if let Some(audiences) = payload.audience() {
-                    if !audiences.contains(&audience.as_str()) {
+                    if !self
+                        .audiences
+                        .iter()
+                        .any(|val| audiences.contains(&val.as_str()))
+                    {
                         bail!("Key aud is invalid: {}", audiences.join(", "));
                     }
                 }
@@ -242,6 +352,7 @@ mod tests {
     use serde_json::json;
 
     use crate::jwt::{JwtPayload, JwtPayloadValidator};
+    use crate::{JoseError, Map};
 
     #[test]
     fn test_jwt_payload_validate() -> Result<()> {
@@ -264,4 +375,195 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_jwt_payload_validate_with_leeway() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_expires_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_leeway(Duration::from_secs(60));
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(130));
+        validator.validate(&payload)?;
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(190));
+        assert!(validator.validate(&payload).is_err());
+
+        let mut payload = JwtPayload::new();
+        payload.set_not_before(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(70));
+        validator.validate(&payload)?;
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(10));
+        assert!(validator.validate(&payload).is_err());
+
+        let mut payload = JwtPayload::new();
+        payload.set_issued_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+
+        validator.set_max_issued_time(SystemTime::UNIX_EPOCH + Duration::from_secs(70));
+        validator.validate(&payload)?;
+
+        validator.set_max_issued_time(SystemTime::UNIX_EPOCH + Duration::from_secs(10));
+        assert!(validator.validate(&payload).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_without_leeway() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_expires_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+
+        let mut validator = JwtPayloadValidator::new();
+        assert_eq!(validator.leeway(), Duration::from_secs(0));
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(99));
+        validator.validate(&payload)?;
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(100));
+        assert!(validator.validate(&payload).is_err());
+
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(130));
+        assert!(validator.validate(&payload).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_with_max_leeway() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_expires_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+        payload.set_not_before(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+        payload.set_issued_at(&(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_leeway(Duration::MAX);
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(10));
+        validator.set_max_issued_time(SystemTime::UNIX_EPOCH + Duration::from_secs(10));
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_with_audiences() -> Result<()> {
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_audiences(&["https://example.com", "example"]);
+        assert_eq!(validator.audience(), Some("https://example.com"));
+        assert_eq!(
+            validator.audiences(),
+            vec!["https://example.com", "example"]
+        );
+
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["example"]);
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_claim("aud", Some(json!("https://example.com")))?;
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["other", "example"]);
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["other0", "other1"]);
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_with_issuers() -> Result<()> {
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuers(&["https://example.com/", "https://example.org"]);
+        assert_eq!(validator.issuer(), Some("https://example.com/"));
+        assert_eq!(
+            validator.issuers(),
+            vec!["https://example.com/", "https://example.org"]
+        );
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://example.org");
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://example.com");
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        assert!(!validator.is_issuer_trailing_slash_normalized());
+        validator.normalize_issuer_trailing_slash(true);
+        assert!(validator.is_issuer_trailing_slash_normalized());
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://example.org/");
+        validator.validate(&payload)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://example.org//");
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("https://example.net");
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        let payload = JwtPayload::new();
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_reject_invalid_numeric_date() -> Result<()> {
+        for exp in &[json!("1700000000"), json!(1.5), json!(1e30), json!(-1)] {
+            let mut map = Map::new();
+            map.insert("exp".to_string(), exp.clone());
+            assert!(matches!(
+                JwtPayload::from_map(map),
+                Err(JoseError::InvalidJwtFormat(_))
+            ));
+
+            let mut payload = JwtPayload::new();
+            assert!(matches!(
+                payload.set_claim("exp", Some(exp.clone())),
+                Err(JoseError::InvalidJwtFormat(_))
+            ));
+        }
+
+        let mut map = Map::new();
+        map.insert("nbf".to_string(), json!(253402300800u64));
+        assert!(matches!(
+            JwtPayload::from_map(map),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        let mut payload = JwtPayload::new();
+        payload.set_claim("nbf", Some(json!(253402300799u64)))?;
+        let validator = JwtPayloadValidator::new();
+        assert!(matches!(
+            validator.validate(&payload),
+            Err(JoseError::InvalidClaim(_))
+        ));
+
+        Ok(())
+    }
 }
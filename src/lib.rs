@@ -8,11 +8,15 @@ pub mod jws;
 pub mod jwt;
 pub mod util;
 
+mod algorithm_policy;
 mod jose_error;
 mod jose_header;
+mod json_type;
 
-pub use crate::jose_error::JoseError;
+pub use crate::algorithm_policy::AlgorithmPolicy;
+pub use crate::jose_error::{JoseError, JoseErrorKind};
 pub use crate::jose_header::JoseHeader;
+pub use crate::json_type::JsonType;
 
 pub use serde_json::{Map, Number, Value};
 
@@ -1,20 +1,29 @@
+pub mod concat_kdf;
 pub mod der;
 pub mod hash_algorithm;
+pub mod hkdf;
 pub mod oid;
+pub mod random_source;
+
+use std::borrow::Cow;
 
 use anyhow::bail;
 use once_cell::sync::Lazy;
 use openssl::bn::BigNumRef;
-use openssl::rand;
+use openssl::{memcmp, rand};
 use regex::{self, bytes};
 
 pub use crate::util::hash_algorithm::HashAlgorithm;
+pub use crate::util::random_source::{OpensslRandomSource, RandomSource};
 
 pub use HashAlgorithm::Sha1 as SHA_1;
 pub use HashAlgorithm::Sha256 as SHA_256;
 pub use HashAlgorithm::Sha384 as SHA_384;
 pub use HashAlgorithm::Sha512 as SHA_512;
 
+/// The placeholder that `Debug` implementations print instead of secret key material.
+pub(crate) const REDACTED: &str = "<redacted>";
+
 pub fn random_bytes(len: usize) -> Vec<u8> {
     let mut vec = vec![0; len];
     rand::rand_bytes(&mut vec).unwrap();
@@ -25,6 +34,90 @@ pub(crate) fn ceiling(len: usize, div: usize) -> usize {
     (len + (div - 1)) / div
 }
 
+/// Compare two byte strings in constant time.
+///
+/// The running time depends only on the lengths of the inputs, not on the position of
+/// the first differing byte. Use it to check MACs, authentication tags and HMAC signatures.
+///
+/// # Arguments
+///
+/// * `a` - a byte string.
+/// * `b` - an another byte string.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    memcmp::eq(a, b)
+}
+
+/// Overwrite the sensitive data with zeros when the zeroize feature is enabled.
+#[cfg(feature = "zeroize")]
+pub(crate) fn zeroize(buf: &mut [u8]) {
+    zeroize::Zeroize::zeroize(buf);
+}
+
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn zeroize(_buf: &mut [u8]) {}
+
+/// Decode a hex string of test vectors.
+#[cfg(test)]
+pub(crate) fn decode_hex(input: &str) -> Vec<u8> {
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Decode a base64url segment of a JOSE serialization.
+///
+/// In lenient mode, trailing `=` padding and the `+` and `/` characters of standard
+/// base64 are also accepted. Any other character is still rejected.
+pub(crate) fn decode_base64_url(
+    input: impl AsRef<[u8]>,
+    lenient: bool,
+) -> Result<Vec<u8>, base64::DecodeError> {
+    let input = input.as_ref();
+    if !lenient {
+        // The decoder of base64 0.13 accepts padding even if the config has no padding.
+        if let Some(pos) = input.iter().position(|b| *b == b'=') {
+            return Err(base64::DecodeError::InvalidByte(pos, b'='));
+        }
+        return base64::decode_config(input, base64::URL_SAFE_NO_PAD);
+    }
+
+    let mut end = input.len();
+    while end > 0 && input.len() - end < 2 && input[end - 1] == b'=' {
+        end -= 1;
+    }
+    let input: Vec<u8> = input[..end]
+        .iter()
+        .map(|b| match b {
+            b'+' => b'-',
+            b'/' => b'_',
+            _ => *b,
+        })
+        .collect();
+    base64::decode_config(&input, base64::URL_SAFE_NO_PAD)
+}
+
+/// Remove ASCII whitespace, e.g. the line breaks of a wrapped token, from the input.
+///
+/// The input is borrowed as is when it contains no whitespace.
+pub(crate) fn strip_ascii_whitespace(input: &[u8]) -> Cow<[u8]> {
+    if input.iter().any(|b| b.is_ascii_whitespace()) {
+        Cow::Owned(
+            input
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .cloned()
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
 pub(crate) fn is_base64_url_safe_nopad(input: &str) -> bool {
     static RE_BASE64: Lazy<regex::Regex> = Lazy::new(|| {
         regex::Regex::new(
@@ -36,6 +129,27 @@ pub(crate) fn is_base64_url_safe_nopad(input: &str) -> bool {
     RE_BASE64.is_match(input)
 }
 
+/// Return the short form of a media type for the typ and cty header claims (RFC 7515 4.1.10).
+///
+/// The "application/" prefix is omitted, ignoring case, when no other '/' follows it,
+/// so "application/foo" becomes "foo". Any other value, e.g. "JWT", "text/plain" or
+/// "application/foo/bar", is returned as is. A recipient treats a value without '/'
+/// as if "application/" were prepended, so both forms denote the same media type.
+pub(crate) fn compact_media_type(value: &str) -> &str {
+    const PREFIX: &str = "application/";
+    match value.get(..PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(PREFIX) => {
+            let rest = &value[PREFIX.len()..];
+            if rest.is_empty() || rest.contains('/') {
+                value
+            } else {
+                rest
+            }
+        }
+        _ => value,
+    }
+}
+
 pub(crate) fn parse_pem(input: &[u8]) -> anyhow::Result<(String, Vec<u8>)> {
     static RE_PEM: Lazy<bytes::Regex> = Lazy::new(|| {
         bytes::Regex::new(concat!(
@@ -67,6 +181,21 @@ pub(crate) fn parse_pem(input: &[u8]) -> anyhow::Result<(String, Vec<u8>)> {
     Ok(result)
 }
 
+/// Return the PEM blocks in the input with their labels.
+pub(crate) fn split_pem(input: &[u8]) -> Vec<(String, &[u8])> {
+    static RE_PEM_BLOCK: Lazy<bytes::Regex> = Lazy::new(|| {
+        bytes::Regex::new(r"(?s)-----BEGIN ([A-Z0-9 -]+)-----.*?-----END [A-Z0-9 -]+-----").unwrap()
+    });
+
+    RE_PEM_BLOCK
+        .captures_iter(input)
+        .map(|caps| {
+            let label = String::from_utf8_lossy(caps.get(1).unwrap().as_bytes()).into_owned();
+            (label, caps.get(0).unwrap().as_bytes())
+        })
+        .collect()
+}
+
 pub(crate) fn num_to_vec(num: &BigNumRef, len: usize) -> Vec<u8> {
     let vec = num.to_vec();
     if vec.len() < len {
@@ -83,7 +212,94 @@ pub(crate) fn num_to_vec(num: &BigNumRef, len: usize) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use super::is_base64_url_safe_nopad;
+    use std::time::{Duration, Instant};
+
+    use super::{
+        compact_media_type, constant_time_eq, is_base64_url_safe_nopad, strip_ascii_whitespace,
+    };
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abcdef", b"abcdef"));
+        assert!(!constant_time_eq(b"abcdef", b"abcdeg"));
+        assert!(!constant_time_eq(b"abcdef", b"Abcdef"));
+        assert!(!constant_time_eq(b"abcdef", b"abcde"));
+        assert!(!constant_time_eq(b"abcde", b"abcdef"));
+        assert!(!constant_time_eq(b"", b"a"));
+    }
+
+    // Timing is noisy, so run this explicitly with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_constant_time_eq_timing() {
+        let a = vec![0u8; 64 * 1024];
+        let mut first = a.clone();
+        first[0] = 1;
+        let mut last = a.clone();
+        *last.last_mut().unwrap() = 1;
+
+        let measure = |b: &[u8]| -> Duration {
+            (0..7)
+                .map(|_| {
+                    let start = Instant::now();
+                    for _ in 0..1000 {
+                        assert!(!constant_time_eq(&a, b));
+                    }
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        let first_time = measure(&first);
+        let last_time = measure(&last);
+        // An early exit would make the first-byte mismatch thousands of times faster.
+        assert!(
+            first_time * 2 > last_time,
+            "first: {:?}, last: {:?}",
+            first_time,
+            last_time
+        );
+    }
+
+    #[test]
+    fn test_strip_ascii_whitespace() {
+        assert_eq!(&*strip_ascii_whitespace(b"abc.def"), b"abc.def");
+        assert_eq!(&*strip_ascii_whitespace(b" ab\r\nc.d\tef\n"), b"abc.def");
+        assert_eq!(&*strip_ascii_whitespace(b""), b"");
+    }
+
+    #[test]
+    fn test_compact_media_type() {
+        assert_eq!(compact_media_type("JWT"), "JWT");
+        assert_eq!(compact_media_type("jwt"), "jwt");
+        assert_eq!(compact_media_type("application/jwt"), "jwt");
+        assert_eq!(compact_media_type("application/JWT"), "JWT");
+        assert_eq!(compact_media_type("Application/cbor"), "cbor");
+        assert_eq!(
+            compact_media_type("application/vnd.foo+json"),
+            "vnd.foo+json"
+        );
+        assert_eq!(
+            compact_media_type("application/foo/bar"),
+            "application/foo/bar"
+        );
+        assert_eq!(compact_media_type("application/"), "application/");
+        assert_eq!(compact_media_type("application"), "application");
+        assert_eq!(compact_media_type("text/plain"), "text/plain");
+        assert_eq!(compact_media_type("x-application/foo"), "x-application/foo");
+        assert_eq!(compact_media_type("applicatioñ/foo"), "applicatioñ/foo");
+        assert_eq!(compact_media_type(""), "");
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        let mut buf = vec![0xFFu8; 32];
+        super::zeroize(&mut buf);
+        assert_eq!(buf, vec![0u8; 32]);
+    }
 
     #[test]
     fn test_is_base64_url_safe_nopad() {
@@ -0,0 +1,110 @@
+use anyhow::bail;
+use openssl::hash::Hasher;
+
+use crate::jose_error::JoseErrorDetail;
+use crate::util::{self, HashAlgorithm};
+use crate::{JoseError, JoseErrorKind};
+
+/// Derive a key with the single-step Concatenation Key Derivation Function
+/// (Concat KDF) of NIST SP 800-56A, as used by ECDH-ES (RFC 7518 Section 4.6.2).
+///
+/// The OtherInfo is the concatenation of the algorithm ID, the party U info and
+/// the party V info, each prefixed by its 32-bit big-endian byte length, followed
+/// by the 32-bit big-endian bit length of the derived key.
+///
+/// # Arguments
+///
+/// * `z` - a shared secret.
+/// * `alg_id` - an algorithm ID.
+/// * `party_u_info` - an information about the producer (empty if absent).
+/// * `party_v_info` - an information about the recipient (empty if absent).
+/// * `key_len` - a byte size of the derived key.
+/// * `digest` - a hash algorithm.
+pub fn derive(
+    z: &[u8],
+    alg_id: &[u8],
+    party_u_info: &[u8],
+    party_v_info: &[u8],
+    key_len: usize,
+    digest: HashAlgorithm,
+) -> Result<Vec<u8>, JoseError> {
+    (|| -> anyhow::Result<Vec<u8>> {
+        if key_len > (u32::MAX / 8) as usize {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of Concat KDF output must be {} or less: {}",
+                    u32::MAX / 8,
+                    key_len
+                )
+            ));
+        }
+
+        let key_len_bytes = ((key_len * 8) as u32).to_be_bytes();
+        let alg_id_len_bytes = (alg_id.len() as u32).to_be_bytes();
+        let party_u_info_len_bytes = (party_u_info.len() as u32).to_be_bytes();
+        let party_v_info_len_bytes = (party_v_info.len() as u32).to_be_bytes();
+
+        let md = digest.message_digest();
+        let count = util::ceiling(key_len, digest.output_len());
+        let mut derived_key = Vec::with_capacity(count * digest.output_len());
+        for i in 0..count {
+            let mut hasher = Hasher::new(md)?;
+            hasher.update(&((i + 1) as u32).to_be_bytes())?;
+            hasher.update(z)?;
+            hasher.update(&alg_id_len_bytes)?;
+            hasher.update(alg_id)?;
+            hasher.update(&party_u_info_len_bytes)?;
+            hasher.update(party_u_info)?;
+            hasher.update(&party_v_info_len_bytes)?;
+            hasher.update(party_v_info)?;
+            hasher.update(&key_len_bytes)?;
+
+            let block = hasher.finish()?;
+            derived_key.extend_from_slice(&block);
+        }
+        derived_key.truncate(key_len);
+
+        Ok(derived_key)
+    })()
+    .map_err(|err| JoseError::InvalidKeyFormat(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::derive;
+    use crate::util::{decode_hex, SHA_256};
+
+    const RFC7518_Z: [u8; 32] = [
+        158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49, 110,
+        163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+    ];
+
+    #[test]
+    fn derive_rfc7518_ecdh_es() -> Result<()> {
+        // RFC 7518 Appendix C. Example ECDH-ES Key Agreement Computation
+        let key = derive(&RFC7518_Z, b"A128GCM", b"Alice", b"Bob", 16, SHA_256)?;
+        assert_eq!(
+            base64::encode_config(&key, base64::URL_SAFE_NO_PAD),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn derive_multiple_blocks() -> Result<()> {
+        let key = derive(&RFC7518_Z, b"A256CBC-HS512", b"Alice", b"Bob", 64, SHA_256)?;
+        assert_eq!(
+            key,
+            decode_hex(concat!(
+                "3986aa79f6396420e580e5d3890f623fee5d4522307929eb99ee3425a001ecc1",
+                "75b1754e3fb644ce825034b562523e9a8806bca8d76afa861e9b79515803225d",
+            ))
+        );
+
+        Ok(())
+    }
+}
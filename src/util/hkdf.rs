@@ -0,0 +1,192 @@
+use anyhow::bail;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use crate::jose_error::JoseErrorDetail;
+use crate::util::HashAlgorithm;
+use crate::{JoseError, JoseErrorKind};
+
+/// Derive a key with the HMAC-based Extract-and-Expand Key Derivation Function
+/// (HKDF) of RFC 5869.
+///
+/// # Arguments
+///
+/// * `salt` - a salt value. An empty salt is treated as zeros of the hash length.
+/// * `ikm` - an input keying material.
+/// * `info` - an application specific information.
+/// * `length` - a byte size of the output keying material (at most 255 times the hash length).
+/// * `digest` - a hash algorithm of HMAC.
+pub fn derive(
+    salt: &[u8],
+    ikm: &[u8],
+    info: &[u8],
+    length: usize,
+    digest: HashAlgorithm,
+) -> Result<Vec<u8>, JoseError> {
+    let prk = extract(salt, ikm, digest)?;
+    expand(&prk, info, length, digest)
+}
+
+/// Compute a pseudorandom key with the extract step of HKDF (RFC 5869 Section 2.2).
+///
+/// # Arguments
+///
+/// * `salt` - a salt value. An empty salt is treated as zeros of the hash length.
+/// * `ikm` - an input keying material.
+/// * `digest` - a hash algorithm of HMAC.
+pub fn extract(salt: &[u8], ikm: &[u8], digest: HashAlgorithm) -> Result<Vec<u8>, JoseError> {
+    (|| -> anyhow::Result<Vec<u8>> {
+        let zero_salt;
+        let salt = if salt.is_empty() {
+            zero_salt = vec![0; digest.output_len()];
+            &zero_salt
+        } else {
+            salt
+        };
+        let salt_key = PKey::hmac(salt)?;
+        let mut signer = Signer::new(digest.message_digest(), &salt_key)?;
+        signer.update(ikm)?;
+        let prk = signer.sign_to_vec()?;
+
+        Ok(prk)
+    })()
+    .map_err(|err| JoseError::InvalidKeyFormat(err))
+}
+
+/// Compute an output keying material with the expand step of HKDF (RFC 5869 Section 2.3).
+///
+/// # Arguments
+///
+/// * `prk` - a pseudorandom key, usually the output of `extract`.
+/// * `info` - an application specific information.
+/// * `length` - a byte size of the output keying material (at most 255 times the hash length).
+/// * `digest` - a hash algorithm of HMAC.
+pub fn expand(
+    prk: &[u8],
+    info: &[u8],
+    length: usize,
+    digest: HashAlgorithm,
+) -> Result<Vec<u8>, JoseError> {
+    (|| -> anyhow::Result<Vec<u8>> {
+        let hash_len = digest.output_len();
+        if length > 255 * hash_len {
+            bail!(JoseErrorDetail::new(
+                JoseErrorKind::InvalidKeyLength,
+                format!(
+                    "The length of HKDF output must be {} or less: {}",
+                    255 * hash_len,
+                    length
+                )
+            ));
+        }
+
+        let md = digest.message_digest();
+        let prk_key = PKey::hmac(prk)?;
+        let mut okm = Vec::with_capacity(length + hash_len);
+        let mut block = Vec::new();
+        let mut counter = 1u8;
+        while okm.len() < length {
+            let mut signer = Signer::new(md, &prk_key)?;
+            signer.update(&block)?;
+            signer.update(info)?;
+            signer.update(&[counter])?;
+            block = signer.sign_to_vec()?;
+            okm.extend_from_slice(&block);
+            counter = counter.wrapping_add(1);
+        }
+        okm.truncate(length);
+
+        Ok(okm)
+    })()
+    .map_err(|err| JoseError::InvalidKeyFormat(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{derive, expand, extract};
+    use crate::util::{decode_hex, SHA_256, SHA_512};
+    use crate::JoseErrorKind;
+
+    #[test]
+    fn derive_rfc5869_sha256() -> Result<()> {
+        // RFC 5869 A.1. Test Case 1
+        let okm = derive(
+            &decode_hex("000102030405060708090a0b0c"),
+            &[0x0b; 22],
+            &decode_hex("f0f1f2f3f4f5f6f7f8f9"),
+            42,
+            SHA_256,
+        )?;
+        assert_eq!(
+            okm,
+            decode_hex(
+                "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+            )
+        );
+
+        // RFC 5869 A.2. Test Case 2
+        let okm = derive(
+            &(0x60..=0xaf).collect::<Vec<u8>>(),
+            &(0x00..=0x4f).collect::<Vec<u8>>(),
+            &(0xb0..=0xff).collect::<Vec<u8>>(),
+            82,
+            SHA_256,
+        )?;
+        assert_eq!(
+            okm,
+            decode_hex(concat!(
+                "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c",
+                "59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71",
+                "cc30c58179ec3e87c14c01d5c1f3434f1d87",
+            ))
+        );
+
+        // RFC 5869 A.3. Test Case 3
+        let okm = derive(b"", &[0x0b; 22], b"", 42, SHA_256)?;
+        assert_eq!(
+            okm,
+            decode_hex(
+                "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn extract_and_expand_rfc5869_sha256() -> Result<()> {
+        // RFC 5869 A.1. Test Case 1
+        let prk = extract(
+            &decode_hex("000102030405060708090a0b0c"),
+            &[0x0b; 22],
+            SHA_256,
+        )?;
+        assert_eq!(
+            prk,
+            decode_hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5")
+        );
+
+        let okm = expand(&prk, &decode_hex("f0f1f2f3f4f5f6f7f8f9"), 42, SHA_256)?;
+        assert_eq!(
+            okm,
+            decode_hex(
+                "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn derive_max_length() -> Result<()> {
+        let okm = derive(b"salt", b"ikm", b"info", 255 * 64, SHA_512)?;
+        assert_eq!(okm.len(), 255 * 64);
+
+        let err = derive(b"salt", b"ikm", b"info", 255 * 32 + 1, SHA_256).unwrap_err();
+        assert_eq!(err.kind(), Some(JoseErrorKind::InvalidKeyLength));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::Debug;
+
+use openssl::rand;
+
+/// Represent a source of random bytes that is used to generate keys and initialization vectors.
+pub trait RandomSource: Debug + Send + Sync {
+    /// Fill the buffer with random bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - a buffer to fill
+    fn fill_bytes(&self, buf: &mut [u8]);
+
+    /// Return a vector of random bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - a byte size of the vector
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let mut vec = vec![0; len];
+        self.fill_bytes(&mut vec);
+        vec
+    }
+
+    fn box_clone(&self) -> Box<dyn RandomSource>;
+}
+
+impl PartialEq for dyn RandomSource {
+    fn eq(&self, other: &Self) -> bool {
+        format!("{:?}", self) == format!("{:?}", other)
+    }
+}
+
+impl Eq for dyn RandomSource {}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// A random source that uses the cryptographically secure generator of OpenSSL.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct OpensslRandomSource;
+
+impl RandomSource for OpensslRandomSource {
+    fn fill_bytes(&self, buf: &mut [u8]) {
+        rand::rand_bytes(buf).unwrap();
+    }
+
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}